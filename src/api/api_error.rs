@@ -1,3 +1,4 @@
+use super::network_error::{self, NetworkErrorKind};
 use crate::cache::CacheError;
 use std::error::Error;
 use std::fmt;
@@ -14,11 +15,14 @@ pub enum ApiError {
     CacheError { source: CacheError },
     Expired,
     IOError { source: io::Error },
+    InvalidNxmUrl { reason: &'static str },
     IsUnitTest,
     JoinError { source: JoinError },
     ParseError { source: ParseError },
     ParseIntError { source: ParseIntError },
+    ProxyError { proxy: String, source: reqwest::Error },
     SerializationError { source: serde_json::Error },
+    Timeout,
     WebsocketError { source: tungstenite::Error },
 }
 
@@ -32,25 +36,51 @@ impl Error for ApiError {
             ApiError::JoinError { ref source } => Some(source),
             ApiError::ParseError { ref source } => Some(source),
             ApiError::ParseIntError { ref source } => Some(source),
+            ApiError::ProxyError { ref source, .. } => Some(source),
             ApiError::SerializationError { ref source } => Some(source),
             _ => None,
         }
     }
 }
 
+impl ApiError {
+    pub fn network_error_kind(&self) -> Option<NetworkErrorKind> {
+        match self {
+            ApiError::ConnectionError { source } | ApiError::ProxyError { source, .. } => {
+                Some(NetworkErrorKind::of(source))
+            }
+            ApiError::Timeout => Some(NetworkErrorKind::Timeout),
+            _ => None,
+        }
+    }
+
+    // Network errors that might go away by themselves, meaning that the request can be retried later
+    pub fn is_transient(&self) -> bool {
+        self.network_error_kind().is_some_and(|kind| kind.is_transient())
+    }
+}
+
 impl fmt::Display for ApiError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             ApiError::ApiKeyMissing => f.write_str("No apikey configured. API connections are disabled."),
             ApiError::CacheError { source } => source.fmt(f),
-            ApiError::ConnectionError { source } => source.fmt(f),
+            ApiError::ConnectionError { source } => match NetworkErrorKind::of(source) {
+                NetworkErrorKind::Status(_) => source.fmt(f),
+                kind => write!(f, "{kind} ({})", network_error::root_cause(source)),
+            },
             ApiError::Expired => f.write_str("Download link is expired."),
+            ApiError::InvalidNxmUrl { reason } => write!(f, "Invalid nxm:// URL: {reason}."),
             ApiError::IOError { source } => source.fmt(f),
             ApiError::JoinError { source } => source.fmt(f),
             ApiError::SerializationError { source } => source.fmt(f),
             ApiError::IsUnitTest => f.write_str("Unit tests aren't allowed to make network connections."),
+            ApiError::Timeout => f.write_str("connection timed out"),
             ApiError::ParseError { source } => source.fmt(f),
             ApiError::ParseIntError { source } => source.fmt(f),
+            ApiError::ProxyError { proxy, source } => {
+                write!(f, "Unable to connect through proxy {proxy} ({})", network_error::root_cause(source))
+            }
             ApiError::WebsocketError { source } => source.fmt(f),
         }
     }
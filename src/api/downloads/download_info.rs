@@ -3,14 +3,18 @@ use super::FileInfo;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::sync::atomic::{AtomicU8, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use url::Url;
 
 const DL_STATE_DONE: u8 = 0;
 const DL_STATE_DOWNLOADING: u8 = 1;
 const DL_STATE_ERROR: u8 = 2;
 const DL_STATE_EXPIRED: u8 = 3;
-const DL_STATE_PAUSED: u8 = 4;
+const DL_STATE_PAUSED: u8 = 4; // paused by the user, kept as is for compatibility with older .part.json files
+const DL_STATE_PAUSED_NETWORK_ERROR: u8 = 5;
+const DL_STATE_PAUSED_DISK_FULL: u8 = 6;
+const DL_STATE_PAUSED_RATE_LIMITED: u8 = 7;
+const DL_STATE_PAUSED_FILE_EXISTS: u8 = 8;
 
 /* Serde can't serialize tokio's Rwlock.
  * We'll just use an AtomicU8 and convert it to an enum in the few places where it's needed.
@@ -21,7 +25,17 @@ pub enum DownloadState {
     Downloading,
     Error,
     Expired,
-    Paused,
+    Paused(PauseReason),
+}
+
+// Only downloads paused due to network errors are resumed automatically on startup.
+#[derive(Debug, Deserialize, Serialize)]
+pub enum PauseReason {
+    UserRequested,
+    NetworkError,
+    DiskFull,
+    RateLimited,
+    FileExists, // a different file with the same name is in the way, and the user is asked what to do
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -30,6 +44,29 @@ pub struct DownloadInfo {
     pub url: Url,
     state: Arc<AtomicU8>,
     pub progress: DownloadProgress,
+    // Why the download failed, kept so that it can be looked at before retrying. Missing from older metadata.
+    #[serde(default)]
+    error: Arc<Mutex<Option<String>>>,
+    // The nxm:// URL the download was queued with, so that the queue can be exported. Missing from older metadata.
+    #[serde(default)]
+    pub nxm_url: Option<String>,
+}
+
+// A download that hasn't finished, as saved by Downloads::export_queue()
+#[derive(Debug, Deserialize, Serialize)]
+pub struct QueuedDownload {
+    // The key in the URL expires after a while, after which only premium users can download the file without a new one
+    pub nxm_url: Option<String>,
+    pub file_info: FileInfo,
+}
+
+impl From<&DownloadInfo> for QueuedDownload {
+    fn from(dl_info: &DownloadInfo) -> Self {
+        Self {
+            nxm_url: dl_info.nxm_url.clone(),
+            file_info: dl_info.file_info.clone(),
+        }
+    }
 }
 
 impl DownloadInfo {
@@ -39,9 +76,24 @@ impl DownloadInfo {
             url,
             state: Arc::new(DL_STATE_DOWNLOADING.into()),
             progress: DownloadProgress::default(),
+            error: Arc::new(Mutex::new(None)),
+            nxm_url: None,
         }
     }
 
+    pub fn set_error(&self, msg: String) {
+        *self.error.lock().unwrap() = Some(msg);
+        self.set_state(DownloadState::Error);
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+
+    pub fn clear_error(&self) {
+        self.error.lock().unwrap().take();
+    }
+
     pub fn set_state(&self, state_enum: DownloadState) {
         self.state.store(
             match state_enum {
@@ -49,7 +101,11 @@ impl DownloadInfo {
                 DownloadState::Downloading => DL_STATE_DOWNLOADING,
                 DownloadState::Error => DL_STATE_ERROR,
                 DownloadState::Expired => DL_STATE_EXPIRED,
-                DownloadState::Paused => DL_STATE_PAUSED,
+                DownloadState::Paused(PauseReason::UserRequested) => DL_STATE_PAUSED,
+                DownloadState::Paused(PauseReason::NetworkError) => DL_STATE_PAUSED_NETWORK_ERROR,
+                DownloadState::Paused(PauseReason::DiskFull) => DL_STATE_PAUSED_DISK_FULL,
+                DownloadState::Paused(PauseReason::RateLimited) => DL_STATE_PAUSED_RATE_LIMITED,
+                DownloadState::Paused(PauseReason::FileExists) => DL_STATE_PAUSED_FILE_EXISTS,
             },
             Ordering::Relaxed,
         );
@@ -60,7 +116,11 @@ impl DownloadInfo {
             DL_STATE_DONE => DownloadState::Done,
             DL_STATE_DOWNLOADING => DownloadState::Downloading,
             DL_STATE_ERROR => DownloadState::Error,
-            DL_STATE_PAUSED => DownloadState::Paused,
+            DL_STATE_PAUSED => DownloadState::Paused(PauseReason::UserRequested),
+            DL_STATE_PAUSED_NETWORK_ERROR => DownloadState::Paused(PauseReason::NetworkError),
+            DL_STATE_PAUSED_DISK_FULL => DownloadState::Paused(PauseReason::DiskFull),
+            DL_STATE_PAUSED_RATE_LIMITED => DownloadState::Paused(PauseReason::RateLimited),
+            DL_STATE_PAUSED_FILE_EXISTS => DownloadState::Paused(PauseReason::FileExists),
             // Treat any other value as expired because the user has to restart the download anyway.
             _ => DownloadState::Expired,
         }
@@ -74,7 +134,47 @@ impl fmt::Display for DownloadState {
             DownloadState::Error => write!(f, "Error"),
             DownloadState::Expired => write!(f, "Expired"),
             DownloadState::Downloading => write!(f, "Downloading"),
-            DownloadState::Paused => write!(f, "Paused"),
+            DownloadState::Paused(PauseReason::UserRequested) => write!(f, "Paused"),
+            DownloadState::Paused(PauseReason::NetworkError) => write!(f, "Paused (network error)"),
+            DownloadState::Paused(PauseReason::DiskFull) => write!(f, "Paused (disk full)"),
+            DownloadState::Paused(PauseReason::RateLimited) => write!(f, "Paused (rate limited)"),
+            DownloadState::Paused(PauseReason::FileExists) => write!(f, "Paused (file exists)"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{DownloadInfo, DownloadState, QueuedDownload};
+    use crate::api::downloads::FileInfo;
+    use url::Url;
+
+    #[test]
+    fn saved_error() {
+        let fi = FileInfo::new("morrowind".to_string(), 46599, 1000014198, "Mod.7z".to_string());
+        let dl_info = DownloadInfo::new(fi, Url::parse("https://example.com/Mod.7z").unwrap());
+        dl_info.set_error("Download Mod.7z failed with error: 403 Forbidden".to_string());
+
+        let loaded: DownloadInfo = serde_json::from_str(&serde_json::to_string(&dl_info).unwrap()).unwrap();
+        assert!(matches!(loaded.get_state(), DownloadState::Error));
+        assert_eq!(loaded.error().as_deref(), Some("Download Mod.7z failed with error: 403 Forbidden"));
+
+        loaded.clear_error();
+        assert_eq!(loaded.error(), None);
+    }
+
+    #[test]
+    fn queued_download() {
+        let fi = FileInfo::new("morrowind".to_string(), 46599, 1000014198, "Mod.7z".to_string());
+        let mut dl_info = DownloadInfo::new(fi, Url::parse("https://example.com/Mod.7z").unwrap());
+        let json = serde_json::to_string(&QueuedDownload::from(&dl_info)).unwrap();
+        assert_eq!(serde_json::from_str::<QueuedDownload>(&json).unwrap().nxm_url, None);
+
+        let nxm_url = "nxm://morrowind/mods/46599/files/1000014198?key=abc&expires=1&user_id=2";
+        dl_info.nxm_url = Some(nxm_url.to_string());
+        let json = serde_json::to_string(&vec![QueuedDownload::from(&dl_info)]).unwrap();
+        let queue: Vec<QueuedDownload> = serde_json::from_str(&json).unwrap();
+        assert_eq!(queue[0].nxm_url.as_deref(), Some(nxm_url));
+        assert_eq!(queue[0].file_info.file_id, 1000014198);
+    }
+}
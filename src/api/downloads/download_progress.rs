@@ -9,26 +9,75 @@ pub struct DownloadProgress {
     pub bytes_read: Arc<AtomicU64>,
     pub size: String,
     size_unit: usize,
+    // Missing from metadata saved by older versions
+    #[serde(default)]
+    content_length: Option<u64>,
 }
 
 impl DownloadProgress {
     pub fn new(bytes_read: Arc<AtomicU64>, content_length: Option<u64>) -> Self {
         let size = match content_length {
             Some(total) => format::human_readable(total),
-            None => ("?".to_string(), 3), // fall back to formatting size as mebibytes
+            None => ("?".to_string(), 0), // the progress is shown with its own unit
         };
         Self {
             bytes_read,
             size: size.0,
             size_unit: size.1,
+            content_length,
         }
     }
+
+    pub fn content_length(&self) -> Option<u64> {
+        self.content_length
+    }
+
+    // How much of the file has been downloaded, between 0 and 1. None if the server didn't tell the file size.
+    pub fn fraction(&self) -> Option<f64> {
+        match self.content_length {
+            Some(total) if total > 0 => Some((self.bytes_read.load(Ordering::Relaxed) as f64 / total as f64).min(1.0)),
+            _ => None,
+        }
+    }
+}
+
+/* The size of the whole file from a 206 response, whose Content-Length is only what's left after the start of the
+ * requested range. Content-Range is "bytes <first>-<last>/<size>", where the size can be "*" if it isn't known. */
+pub fn partial_content_total(
+    content_range: Option<&str>,
+    range_start: u64,
+    content_length: Option<u64>,
+) -> Option<u64> {
+    let from_range = content_range.and_then(|range| range.rsplit_once('/')).and_then(|(_, size)| size.parse().ok());
+    from_range.or(content_length.map(|len| range_start + len))
 }
 
 impl fmt::Display for DownloadProgress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let print =
-            format!("{}/{}", format::bytes_as_unit(self.bytes_read.load(Ordering::Relaxed), self.size_unit), self.size);
-        write!(f, "{}", print)
+        let bytes_read = self.bytes_read.load(Ordering::Relaxed);
+        match self.content_length {
+            Some(_) => write!(f, "{}/{}", format::bytes_as_unit(bytes_read, self.size_unit), self.size),
+            None => write!(f, "{}/?", format::human_size(bytes_read)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{partial_content_total, DownloadProgress};
+    use std::sync::atomic::AtomicU64;
+    use std::sync::Arc;
+
+    #[test]
+    fn resume_percentage() {
+        // 600 of 1000 bytes on disk, resumed with 100 bytes of overlap, so the server sends 500 bytes from byte 500
+        let total = partial_content_total(None, 500, Some(500));
+        assert_eq!(total, Some(1000));
+        assert_eq!(partial_content_total(Some("bytes 500-999/1000"), 500, Some(500)), Some(1000));
+        assert_eq!(partial_content_total(Some("bytes 500-999/*"), 500, Some(500)), Some(1000));
+        assert_eq!(partial_content_total(None, 500, None), None);
+
+        let progress = DownloadProgress::new(Arc::new(AtomicU64::new(600)), total);
+        assert_eq!(progress.fraction(), Some(0.6));
     }
 }
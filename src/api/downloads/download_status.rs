@@ -0,0 +1,66 @@
+use super::DownloadInfo;
+use crate::util::format;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::atomic::Ordering;
+
+// A snapshot of a download, sent to `dmodman --list-downloads` through the socket
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DownloadStatus {
+    pub file_name: String,
+    pub game: String,
+    pub mod_id: u32,
+    pub file_id: u64,
+    pub state: String,
+    pub bytes_read: u64,
+    pub size: Option<u64>,
+}
+
+impl From<&DownloadInfo> for DownloadStatus {
+    fn from(dl_info: &DownloadInfo) -> Self {
+        let fi = &dl_info.file_info;
+        Self {
+            file_name: fi.file_name.clone(),
+            game: fi.game.clone(),
+            mod_id: fi.mod_id,
+            file_id: fi.file_id,
+            state: dl_info.get_state().to_string(),
+            bytes_read: dl_info.progress.bytes_read.load(Ordering::Relaxed),
+            size: dl_info.progress.content_length(),
+        }
+    }
+}
+
+/* The state of the running instance, served by the status endpoint of the socket for dashboards and status bars.
+ * Downloads include the queued and finished ones, like in the UI. */
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StatusSnapshot {
+    pub downloads: Vec<DownloadStatus>,
+    pub updates: Vec<AvailableUpdate>,
+}
+
+// A downloaded file that has an update, or whose mod has a new file that might be one
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AvailableUpdate {
+    pub file_name: String,
+    pub game: String,
+    pub mod_id: u32,
+    pub file_id: u64,
+    // Either "OutOfDate" or "HasNewFile", as in the update status of the file
+    pub status: String,
+}
+
+impl fmt::Display for DownloadStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let progress = match self.size {
+            Some(size) if size > 0 => format!("{:.1}%", self.bytes_read as f64 / size as f64 * 100.0),
+            _ => "?".to_string(),
+        };
+        let sizes = format!(
+            "{}/{}",
+            format::human_size(self.bytes_read),
+            self.size.map_or("?".to_string(), format::human_size)
+        );
+        write!(f, "{:<24}{:>8}{:>22}  {}", self.state, progress, sizes, self.file_name)
+    }
+}
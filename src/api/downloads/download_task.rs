@@ -1,22 +1,33 @@
-use super::DownloadState;
-use super::{Client, DownloadInfo, DownloadProgress, Downloads};
+use super::{partial_content_total, ApiError, Client, DownloadInfo, DownloadProgress, Downloads};
+use super::{DownloadState, PauseReason};
 use crate::cache::{Cache, Cacheable};
-use crate::config::{Config, PathType};
+use crate::config::Config;
+use crate::util;
+use crate::util::format;
 use crate::Logger;
 
 use std::fmt::{Debug, Display};
+use std::io::ErrorKind;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc,
 };
+use std::time::Duration;
 
-use reqwest::header::RANGE;
+use reqwest::header::{CONTENT_RANGE, RANGE};
 use reqwest::{Response, StatusCode};
 use tokio::fs::OpenOptions;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt, BufWriter};
+use tokio::sync::oneshot;
+use tokio::time;
 use tokio::{fs, fs::File};
 use tokio::{task, task::JoinHandle};
-use tokio_stream::StreamExt;
+use tokio_stream::{Stream, StreamExt};
+
+// How much of the end of a .part file is downloaded again to check it before resuming, when verify_resume is set
+const VERIFY_RESUME_BYTES: u64 = 64 * 1024;
 
 pub struct DownloadTask {
     cache: Cache,
@@ -25,6 +36,11 @@ pub struct DownloadTask {
     logger: Logger,
     downloads: Downloads,
     join_handle: Option<JoinHandle<()>>,
+    // Tells the spawned task to flush its data and exit. Dropping it does the same.
+    cancel: Option<oneshot::Sender<()>>,
+    /* Where the .part file is written, kept once the download has started so that it's continued from the same file
+     * if download_dir is changed in the meantime. The finished file is moved to the current download directory. */
+    part_path: Option<PathBuf>,
     pub dl_info: DownloadInfo,
 }
 
@@ -45,31 +61,57 @@ impl DownloadTask {
             dl_info,
             downloads,
             join_handle: None,
+            cancel: None,
+            part_path: None,
+        }
+    }
+
+    pub async fn part_path(&self) -> PathBuf {
+        match &self.part_path {
+            Some(path) => path.clone(),
+            None => {
+                let fi = &self.dl_info.file_info;
+                self.config.part_path(&fi.game, &fi.file_name).await
+            }
+        }
+    }
+
+    // The .part.json file is kept next to the .part file
+    pub async fn dl_info_path(&self) -> PathBuf {
+        let file_name = &self.dl_info.file_info.file_name;
+        self.part_path().await.with_file_name(format!("{file_name}.part.json"))
+    }
+
+    /* Stops the transfer and waits until the received data has been written to disk and the download state saved, so
+     * that the download can be resumed from exactly where it left off. The state itself is left unchanged. */
+    pub async fn stop(&mut self) {
+        if let Some(handle) = self.request_stop() {
+            let _ = handle.await;
         }
     }
 
-    pub fn stop(&mut self) {
-        if let Some(handle) = &self.join_handle {
-            handle.abort();
+    // Tells the transfer to stop without waiting for it. Returns the handle of the task, if it was running.
+    pub fn request_stop(&mut self) -> Option<JoinHandle<()>> {
+        if let Some(cancel) = self.cancel.take() {
+            let _ = cancel.send(());
         }
+        self.join_handle.take()
     }
 
     pub async fn toggle_pause(&mut self) {
         match self.dl_info.get_state() {
             DownloadState::Downloading => {
-                if let Some(handle) = &self.join_handle {
-                    handle.abort();
-                }
-                self.dl_info.set_state(DownloadState::Paused);
+                self.stop().await;
+                self.dl_info.set_state(DownloadState::Paused(PauseReason::UserRequested));
             }
-            DownloadState::Paused | DownloadState::Error => {
+            DownloadState::Paused(_) | DownloadState::Error => {
                 self.dl_info.set_state(DownloadState::Downloading);
                 let _ = self.start().await;
             }
             // TODO premium users could get a new download link through the API, without having to visit Nexusmods
             DownloadState::Expired => {
                 self.dl_info.set_state(DownloadState::Expired);
-                self.logger.log(format!(
+                self.logger.warn(format!(
                     "Download link for {} expired, please download again.",
                     self.dl_info.file_info.file_name
                 ));
@@ -79,30 +121,64 @@ impl DownloadTask {
         self.save_dl_info().await;
     }
 
-    // helper function to reduce repetition in start()
+    /* Helper function to reduce repetition in start(). The error is saved along with the download, so that the reason
+     * is still shown for the download after the message has scrolled out of view or the program has been restarted. */
     async fn log_and_set_error<S: Into<String> + Debug + Display>(&self, msg: S) {
-        self.logger.log(msg);
-        self.dl_info.set_state(DownloadState::Error);
-        self.downloads.has_changed.store(true, Ordering::Relaxed);
+        let msg: String = msg.into();
+        self.logger.error(msg.clone());
+        self.dl_info.set_error(msg);
+        self.downloads.has_changed.set();
+        self.save_dl_info().await;
     }
 
-    pub async fn file_exists(&mut self) -> bool {
-        let file_name = &self.dl_info.file_info.file_name;
+    // Like log_and_set_error(), but the reason is saved so that it's known when the program is restarted
+    async fn log_and_pause<S: Into<String> + Debug + Display>(&self, msg: S, reason: PauseReason) {
+        self.logger.warn(msg);
+        self.dl_info.set_state(DownloadState::Paused(reason));
+        self.downloads.has_changed.set();
+        self.save_dl_info().await;
+    }
 
-        let mut path = self.config.download_dir();
-        path.push(file_name);
+    /* Returns true if the file is already in the download directory. Its size is compared to the one the Nexus reports,
+     * if the file list of the mod is cached. A file of a different size is probably corrupted, or a different file with
+     * the same name, so the download is paused until the user decides whether to replace it. */
+    pub async fn file_exists(&mut self) -> bool {
+        let fi = &self.dl_info.file_info;
+        let file_name = &fi.file_name;
+        let path = self.config.file_path(&fi.game, file_name).await;
 
-        if path.exists() {
-            if self.cache.file_index.file_id_map.read().await.get(&self.dl_info.file_info.file_id).is_none() {
-                self.logger.log(format!("{} already exists but was missing its metadata.", file_name));
-                let _ = self.downloads.update_metadata(&self.dl_info.file_info).await;
-            } else {
-                self.logger.log(format!("{} already exists and won't be downloaded.", file_name));
+        let size = match fs::metadata(&path).await {
+            Ok(md) => md.len(),
+            Err(_) => return false,
+        };
+        if let Some(expected_kb) = self.expected_size_kb().await {
+            if !size_matches(size, expected_kb) {
+                self.logger.warn(format!(
+                    "{} already exists, but its size ({}) differs from that of the file on the Nexus ({}).",
+                    file_name,
+                    format::human_size(size),
+                    format::human_size(expected_kb * 1024)
+                ));
+                self.dl_info.set_state(DownloadState::Paused(PauseReason::FileExists));
+                self.downloads.file_conflicts.lock().unwrap().push_back(self.dl_info.file_info.file_id);
+                self.downloads.has_changed.set();
+                return true;
             }
-            return true;
         }
 
-        false
+        if self.cache.file_index.file_id_map.read().await.get(&self.dl_info.file_info.file_id).is_none() {
+            self.logger.log(format!("{} already exists but was missing its metadata.", file_name));
+            let _ = self.downloads.update_metadata(&self.dl_info.file_info).await;
+        } else {
+            self.logger.log(format!("{} already exists and won't be downloaded.", file_name));
+        }
+        true
+    }
+
+    async fn expected_size_kb(&self) -> Option<u64> {
+        let fi = &self.dl_info.file_info;
+        let file_list = self.cache.file_lists.get((&fi.game, fi.mod_id)).await?;
+        file_list.files.iter().find(|fd| fd.file_id == fi.file_id).map(|fd| fd.size_kb)
     }
 
     pub async fn start(&mut self) -> Result<(), ()> {
@@ -110,20 +186,23 @@ impl DownloadTask {
             return Err(());
         }
 
-        let mut path = self.config.download_dir();
+        let game = self.dl_info.file_info.game.clone();
+        let file_name = self.dl_info.file_info.file_name.clone();
+        let part_path = self.part_path().await;
+        self.part_path = Some(part_path.clone());
 
-        if let Err(e) = fs::create_dir_all(&path).await {
-            self.log_and_set_error(format!("Error when creating download directory: {}", e)).await;
-            return Err(());
+        for dir in [
+            self.config.download_dir_for_game(&game),
+            part_path.parent().unwrap().to_path_buf(),
+        ] {
+            if let Err(e) = fs::create_dir_all(&dir).await {
+                self.log_and_set_error(format!("Error when creating download directory {:?}: {}", dir, e)).await;
+                return Err(());
+            }
         }
 
         self.dl_info.set_state(DownloadState::Downloading);
 
-        let file_name = self.dl_info.file_info.file_name.clone();
-        path.push(&file_name);
-        let mut part_path = self.config.download_dir();
-        part_path.push(format!("{}.part", file_name));
-
         let mut builder = self.client.build_request(self.dl_info.url.clone()).unwrap();
 
         /* The HTTP Range header is used to resume downloads.
@@ -131,69 +210,188 @@ impl DownloadTask {
         let bytes_read = Arc::new(AtomicU64::new(0));
 
         let resuming_download = part_path.exists();
+        let mut overlap = 0;
         if resuming_download {
-            bytes_read.store(fs::metadata(&part_path).await.unwrap().len(), Ordering::Relaxed);
-            builder = builder.header(RANGE, format!("bytes={:?}-", bytes_read));
+            let part_len = fs::metadata(&part_path).await.unwrap().len();
+            // The .part file can't be resumed if it's larger than the whole file, which means it's corrupted
+            if self.dl_info.progress.content_length().is_some_and(|len| part_len > len) {
+                return self.restart_from_scratch(&part_path, "it's larger than the file on the server").await;
+            }
+            bytes_read.store(part_len, Ordering::Relaxed);
+            /* Asking for some of the already downloaded data again lets us check that the end of the .part file is
+             * intact before appending to it. */
+            if self.downloads.verify_resume.load(Ordering::Relaxed) {
+                overlap = part_len.min(VERIFY_RESUME_BYTES);
+            }
+            builder = builder.header(RANGE, format!("bytes={}-", part_len - overlap));
         }
 
-        let resp = builder.send().await;
-        if resp.is_err() {
-            self.log_and_set_error("Unable to contact nexus server to start download.").await;
-            return Err(());
-        }
-        let resp = resp.unwrap();
+        let resp = match self.client.send(builder).await {
+            // The server can't send anything past the end of the file, so the .part file is too large
+            Ok(resp) if resuming_download && resp.status() == StatusCode::RANGE_NOT_SATISFIABLE => {
+                return self.restart_from_scratch(&part_path, "the server can't continue from its size").await;
+            }
+            Ok(resp) => resp,
+            Err(e) => {
+                let msg = format!("Unable to start download of {file_name}: {e}");
+                // Only failures that might go away by themselves are continued automatically
+                if e.is_transient() {
+                    self.log_and_pause(msg, PauseReason::NetworkError).await;
+                } else {
+                    self.log_and_set_error(msg).await;
+                }
+                return Err(());
+            }
+        };
 
+        let bytes_on_disk = bytes_read.load(Ordering::Relaxed);
         let file;
-        match self.get_open_opts(&resp, resuming_download, &bytes_read).await {
-            Some(open_opts) => match open_opts.open(&part_path).await {
-                Ok(f) => file = f,
+        let range_start = bytes_on_disk - overlap;
+        match self.get_open_opts(&resp, resuming_download, &bytes_read, range_start).await {
+            Some(open_opts) => {
+                if let Some(len) = resp.content_length() {
+                    // A 206 response only contains the rest of the file, while a whole file overwrites the partial one
+                    let needed = match resp.status() {
+                        StatusCode::PARTIAL_CONTENT => len,
+                        _ => len.saturating_sub(bytes_on_disk),
+                    };
+                    if let Err(msg) = self.check_free_space(needed).await {
+                        self.log_and_set_error(msg).await;
+                        return Err(());
+                    }
+                }
+                match open_opts.open(&part_path).await {
+                    Ok(f) => file = f,
+                    Err(e) => {
+                        self.log_and_set_error(format!("Unable to open {} for writing: {}", file_name, e)).await;
+                        return Err(());
+                    }
+                }
+            }
+            None => return Err(()),
+        }
+
+        let partial_content = resp.status() == StatusCode::PARTIAL_CONTENT;
+        let mut stream = resp.bytes_stream();
+        if overlap > 0 && partial_content {
+            let read_timeout = self.client.read_timeout;
+            match verify_overlap(&part_path, overlap, &mut stream, read_timeout).await {
+                Ok(Some(appended)) => {
+                    bytes_read.fetch_add(appended, Ordering::Relaxed);
+                }
+                Ok(None) => {
+                    drop(file);
+                    return self.restart_from_scratch(&part_path, "its end doesn't match the file on the server").await;
+                }
                 Err(e) => {
-                    self.log_and_set_error(format!("Unable to open {} for writing: {}", file_name, e)).await;
+                    self.log_and_pause(
+                        format!("Unable to verify {file_name} before resuming: {e}"),
+                        PauseReason::NetworkError,
+                    )
+                    .await;
                     return Err(());
                 }
-            },
-            None => return Err(()),
+            }
         }
 
         let downloads = self.downloads.clone();
         let dl_info = self.dl_info.clone();
+        let dl_info_path = self.dl_info_path().await;
+        let config = self.config.clone();
         let logger = self.logger.clone();
         let file_name = file_name.clone();
+        let read_timeout = self.client.read_timeout;
+        let (cancel, cancel_rx) = oneshot::channel();
         let handle: JoinHandle<()> = task::spawn(async move {
             // The actual downloading is done here
-            if let Err(()) = transfer_data(file, resp, &logger, &downloads, &dl_info).await {
+            if let Err(()) = transfer_data(file, stream, &logger, &downloads, &dl_info, read_timeout, cancel_rx).await {
+                // transfer_data() sets the state, which needs to be saved so the download can be resumed correctly
+                if let Err(e) = dl_info.save(dl_info_path).await {
+                    logger.error(format!("Error when saving download state for {}: {}", file_name, e));
+                }
                 return;
             }
 
-            if fs::rename(part_path.clone(), path).await.is_err() {
-                logger.log(format!("Download of {} complete, but unable to remove .part extension.", file_name));
+            // The download directory may have been changed in the config while the file was being downloaded
+            let path = config.file_path(&game, &file_name).await;
+            let _ = fs::create_dir_all(config.download_dir_for_game(&game)).await;
+            // The .part file may be in temp_download_dir, on a different filesystem than the download directory
+            if let Err(e) = util::move_file(&part_path, &path).await {
+                logger.error(format!(
+                    "Download of {} complete, but unable to move it from {:?}: {}",
+                    file_name, part_path, e
+                ));
             }
 
-            part_path.pop();
-            part_path.push(format!("{}.part.json", file_name));
-            if fs::remove_file(&part_path).await.is_err() {
-                logger.log(format!("Unable to remove .part.json file after download is complete: {:?}", part_path));
+            if fs::remove_file(&dl_info_path).await.is_err() {
+                logger
+                    .error(format!("Unable to remove .part.json file after download is complete: {:?}", dl_info_path));
             }
 
+            dl_info.clear_error();
             dl_info.set_state(DownloadState::Done);
-            downloads.has_changed.store(true, Ordering::Relaxed);
+            downloads.has_changed.set();
+            if downloads.desktop_notifications.load(Ordering::Relaxed) {
+                util::notify_desktop("dmodman: Download Complete", &file_name).await;
+            }
 
             if let Err(e) = downloads.update_metadata(&dl_info.file_info).await {
-                logger.log(format!("Unable to update metadata for downloaded file {}: {}", file_name, e));
+                logger.error(format!("Unable to update metadata for downloaded file {}: {}", file_name, e));
             }
+            downloads.run_post_download_command(&dl_info.file_info).await;
+            downloads.apply_download_action(&dl_info.file_info).await;
         });
         self.join_handle = Some(handle);
+        self.cancel = Some(cancel);
         Ok(())
     }
 
+    /* Starts a failed download again, after the user has fixed whatever went wrong. The .part file is kept, so the
+     * download continues from where it failed. */
+    pub async fn retry(&mut self) {
+        if !matches!(self.dl_info.get_state(), DownloadState::Error) {
+            return;
+        }
+        self.logger.log(format!("Retrying download of {}.", self.dl_info.file_info.file_name));
+        if self.start().await.is_ok() {
+            self.dl_info.clear_error();
+        }
+        self.save_dl_info().await;
+    }
+
+    // Replaces the existing file with a new download, after the user has chosen to do so
+    pub async fn overwrite_existing(&mut self) -> Result<(), ()> {
+        let fi = &self.dl_info.file_info;
+        let path = self.config.file_path(&fi.game, &fi.file_name).await;
+        if let Err(e) = fs::remove_file(&path).await {
+            self.log_and_set_error(format!("Unable to remove {}: {e}", self.dl_info.file_info.file_name)).await;
+            return Err(());
+        }
+        self.start().await
+    }
+
+    // Deletes the .part file and starts the download again, for when the partial download can't be trusted
+    async fn restart_from_scratch(&mut self, part_path: &Path, reason: &str) -> Result<(), ()> {
+        let file_name = &self.dl_info.file_info.file_name;
+        self.logger
+            .warn(format!("Restarting download of {file_name}, as the partial file can't be resumed: {reason}."));
+        if let Err(e) = fs::remove_file(part_path).await {
+            self.log_and_set_error(format!("Unable to remove the partial file of {file_name}: {e}")).await;
+            return Err(());
+        }
+        self.dl_info.progress.bytes_read.store(0, Ordering::Relaxed);
+        // The .part file no longer exists, so this can't recurse again
+        Box::pin(self.start()).await
+    }
+
     /* Sets OpenOptions depending on whether the download is new (200 OK) or resumed (206 PARTIAL_CONTENT).
-     * Updates download progress and
-     * */
+     * Updates the download progress and saves it, so that the size of the whole file is known when resuming later. */
     async fn get_open_opts(
         &mut self,
         resp: &Response,
         resuming_download: bool,
         bytes_read: &Arc<AtomicU64>,
+        range_start: u64,
     ) -> Option<OpenOptions> {
         let file_name = &self.dl_info.file_info.file_name;
         let mut open_opts = OpenOptions::new();
@@ -205,15 +403,21 @@ impl DownloadTask {
                         open_opts.write(true).create(true)
                     }
                     StatusCode::PARTIAL_CONTENT => {
-                        if resuming_download {
-                            self.dl_info.progress.bytes_read = bytes_read.clone();
-                        } else {
-                            self.logger.log(
-                                "Server unexpectedly responded with 206 PARTIAL CONTENT \
-                                           when starting download for {file_name}",
-                            );
-                            self.dl_info.progress = DownloadProgress::new(bytes_read.clone(), resp.content_length());
+                        if !resuming_download {
+                            self.logger.warn(format!(
+                                "Server unexpectedly responded with 206 PARTIAL CONTENT when starting download for \
+                                 {file_name}"
+                            ));
                         }
+                        // The size saved when the download started is authoritative, otherwise it's worked out
+                        let total = match self.dl_info.progress.content_length() {
+                            Some(total) if resuming_download => Some(total),
+                            _ => {
+                                let content_range = resp.headers().get(CONTENT_RANGE).and_then(|v| v.to_str().ok());
+                                partial_content_total(content_range, range_start, resp.content_length())
+                            }
+                        };
+                        self.dl_info.progress = DownloadProgress::new(bytes_read.clone(), total);
                         open_opts.append(true)
                     }
                     // Running into some other non-error status code shouldn't happen.
@@ -229,7 +433,13 @@ impl DownloadTask {
             Err(e) => {
                 if resp.status() == StatusCode::GONE {
                     self.dl_info.set_state(DownloadState::Expired);
-                    self.downloads.has_changed.store(true, Ordering::Relaxed);
+                    self.downloads.has_changed.set();
+                } else if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                    self.log_and_pause(
+                        format!("Download {file_name} was rate limited by the server."),
+                        PauseReason::RateLimited,
+                    )
+                    .await;
                 } else {
                     self.log_and_set_error(format!("Download {file_name} failed with error: {}", e.status().unwrap()))
                         .await;
@@ -241,48 +451,221 @@ impl DownloadTask {
         Some(open_opts)
     }
 
-    async fn save_dl_info(&self) {
-        if let Err(e) = self.dl_info.save(self.config.path_for(PathType::DownloadInfo(&self.dl_info))).await {
+    /* Fails if downloading the rest of the file would leave less free space than the config reserves, so that the
+     * download doesn't run out of space halfway through. The space is checked where the .part file is written. */
+    async fn check_free_space(&self, needed: u64) -> Result<(), String> {
+        let reserved = self.downloads.reserved_disk_space.load(Ordering::Relaxed);
+        let part_dir = self.part_path().await.parent().unwrap().to_path_buf();
+        let available = match util::available_space(part_dir).await {
+            Ok(available) => available,
+            // Not knowing the free space shouldn't prevent downloading
+            Err(e) => {
+                self.logger.warn(format!("Unable to check free disk space: {e}"));
+                return Ok(());
+            }
+        };
+        match space_shortfall(needed, available, reserved) {
+            Some(shortfall) => Err(format!(
+                "Not enough disk space for {}: it needs {} more than the {} available{}.",
+                self.dl_info.file_info.file_name,
+                format::human_size(shortfall),
+                format::human_size(available),
+                if reserved > 0 {
+                    format!(" when reserving {}", format::human_size(reserved))
+                } else {
+                    String::new()
+                }
+            )),
+            None => Ok(()),
+        }
+    }
+
+    pub async fn save_dl_info(&self) {
+        if let Err(e) = self.dl_info.save(self.dl_info_path().await).await {
             self.logger
-                .log(format!("Error when saving download state for {}: {}", self.dl_info.file_info.file_name, e));
+                .error(format!("Error when saving download state for {}: {}", self.dl_info.file_info.file_name, e));
         }
     }
 }
 
-async fn transfer_data(
+// How many bytes are missing for the download to fit while leaving the reserved space free
+fn space_shortfall(needed: u64, available: u64, reserved: u64) -> Option<u64> {
+    needed.saturating_add(reserved).checked_sub(available).filter(|shortfall| *shortfall > 0)
+}
+
+/* Reads the start of a response that begins `overlap` bytes before the end of the .part file, and compares it to the
+ * end of the file. Returns None if they differ. Otherwise, any new data received along with the overlap is appended to
+ * the file and its length returned, while the rest of the response is left in the stream. */
+async fn verify_overlap<S, B>(
+    part_path: &Path,
+    overlap: u64,
+    stream: &mut S,
+    read_timeout: Duration,
+) -> Result<Option<u64>, ApiError>
+where
+    S: Stream<Item = reqwest::Result<B>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    let mut file = File::open(part_path).await?;
+    file.seek(SeekFrom::End(-(overlap as i64))).await?;
+    let mut on_disk = vec![];
+    file.read_to_end(&mut on_disk).await?;
+
+    let mut received: Vec<u8> = vec![];
+    while (received.len() as u64) < overlap {
+        match time::timeout(read_timeout, stream.next()).await {
+            Ok(Some(bytes)) => received.extend_from_slice(bytes?.as_ref()),
+            Ok(None) => break,
+            Err(_) => return Err(ApiError::Timeout),
+        }
+    }
+    let (overlapping, rest) = received.split_at(received.len().min(overlap as usize));
+    if overlapping != on_disk.as_slice() {
+        return Ok(None);
+    }
+    // The last chunk can contain new data after the overlap
+    if !rest.is_empty() {
+        let mut file = OpenOptions::new().append(true).open(part_path).await?;
+        file.write_all(rest).await?;
+        file.flush().await?;
+    }
+    Ok(Some(rest.len() as u64))
+}
+
+async fn transfer_data<S, B>(
     file: File,
-    resp: Response,
+    mut stream: S,
     logger: &Logger,
     downloads: &Downloads,
     dl_info: &DownloadInfo,
-) -> Result<(), ()> {
+    read_timeout: Duration,
+    mut cancel: oneshot::Receiver<()>,
+) -> Result<(), ()>
+where
+    S: Stream<Item = reqwest::Result<B>> + Unpin,
+    B: AsRef<[u8]>,
+{
+    let file_name = &dl_info.file_info.file_name;
     let mut bufwriter = BufWriter::new(file);
-    let mut stream = resp.bytes_stream();
 
-    while let Some(item) = stream.next().await {
+    loop {
+        let item = tokio::select! {
+            item = time::timeout(read_timeout, stream.next()) => match item {
+                Ok(item) => item,
+                Err(_) => {
+                    logger.warn(format!(
+                        "Download of {file_name} stalled: no data received in {} seconds.",
+                        read_timeout.as_secs()
+                    ));
+                    if let Err(e) = bufwriter.flush().await {
+                        logger.error(format!("IO error when flushing bytes to disk: {}", e));
+                    }
+                    dl_info.set_state(DownloadState::Paused(PauseReason::NetworkError));
+                    downloads.has_changed.set();
+                    return Err(());
+                }
+            },
+            _ = &mut cancel => {
+                // Stopped by pausing, deleting or quitting. The caller decides what the state should be.
+                if let Err(e) = bufwriter.flush().await {
+                    logger.error(format!("IO error when flushing bytes to disk: {}", e));
+                }
+                return Err(());
+            }
+        };
+        let item = match item {
+            Some(item) => item,
+            None => break,
+        };
         match item {
             Ok(bytes) => {
-                if let Err(e) = bufwriter.write_all(&bytes).await {
-                    logger.log(format!("IO error when writing bytes to disk: {}", e));
+                let bytes = bytes.as_ref();
+                if let Err(e) = bufwriter.write_all(bytes).await {
+                    let msg = format!("IO error when writing bytes to disk: {}", e);
+                    logger.error(msg.clone());
+                    if e.kind() == ErrorKind::StorageFull {
+                        dl_info.set_state(DownloadState::Paused(PauseReason::DiskFull));
+                    } else {
+                        dl_info.set_error(msg);
+                    }
+                    downloads.has_changed.set();
                     return Err(());
                 }
                 dl_info.progress.bytes_read.fetch_add(bytes.len() as u64, Ordering::Relaxed);
-                downloads.has_changed.store(true, Ordering::Relaxed);
+                downloads.has_changed.set();
             }
             Err(e) => {
-                logger.log(format!("Error during download: {}", e));
+                let e = ApiError::from(e);
+                let msg = format!("Error during download of {file_name}: {e}");
+                logger.error(msg.clone());
                 /* The download could fail for network-related reasons. Flush the data we got so that we can
                  * continue it at some later point. */
                 if let Err(e) = bufwriter.flush().await {
-                    logger.log(format!("IO error when flushing bytes to disk: {}", e));
-                    return Err(());
+                    logger.error(format!("IO error when flushing bytes to disk: {}", e));
+                }
+                if e.is_transient() {
+                    dl_info.set_state(DownloadState::Paused(PauseReason::NetworkError));
+                } else {
+                    dl_info.set_error(msg);
                 }
+                downloads.has_changed.set();
+                return Err(());
             }
         }
     }
     if let Err(e) = bufwriter.flush().await {
-        logger.log(format!("IO error when flushing bytes to disk: {}", e));
+        let msg = format!("IO error when flushing bytes to disk: {}", e);
+        logger.error(msg.clone());
+        dl_info.set_error(msg);
+        downloads.has_changed.set();
         return Err(());
     }
     Ok(())
 }
+
+// The Nexus only reports sizes in KiB, and its rounding isn't known
+fn size_matches(size: u64, expected_kb: u64) -> bool {
+    size.div_ceil(1024).abs_diff(expected_kb) <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{size_matches, space_shortfall, verify_overlap};
+    use std::time::Duration;
+
+    #[test]
+    fn existing_size() {
+        assert!(size_matches(1024 * 1024, 1024));
+        assert!(size_matches(1024 * 1024 + 1, 1024));
+        assert!(size_matches(1024 * 1023 + 1, 1024));
+        assert!(!size_matches(512 * 1024, 1024));
+        assert!(!size_matches(0, 1024));
+    }
+
+    #[test]
+    fn shortfall() {
+        assert_eq!(space_shortfall(100, 1000, 0), None);
+        assert_eq!(space_shortfall(1000, 1000, 0), None);
+        assert_eq!(space_shortfall(1000, 100, 0), Some(900));
+        assert_eq!(space_shortfall(100, 1000, 950), Some(50));
+    }
+
+    #[tokio::test]
+    async fn overlap() {
+        let part_path = std::env::temp_dir().join(format!("dmodman-overlap-{}.part", std::process::id()));
+        let timeout = Duration::from_secs(1);
+        let chunks = |chunks: &[&[u8]]| {
+            let chunks: Vec<reqwest::Result<Vec<u8>>> = chunks.iter().map(|c| Ok(c.to_vec())).collect();
+            tokio_stream::iter(chunks)
+        };
+
+        tokio::fs::write(&part_path, b"abcdef").await.unwrap();
+        let mut stream = chunks(&[b"de", b"fgh", b"ij"]);
+        assert_eq!(verify_overlap(&part_path, 3, &mut stream, timeout).await.unwrap(), Some(2));
+        assert_eq!(tokio::fs::read(&part_path).await.unwrap(), b"abcdefgh");
+
+        let mut stream = chunks(&[b"xyz"]);
+        assert_eq!(verify_overlap(&part_path, 3, &mut stream, timeout).await.unwrap(), None);
+        tokio::fs::remove_file(&part_path).await.unwrap();
+    }
+}
@@ -1,38 +1,78 @@
 pub mod download_info;
 pub mod download_progress;
+mod download_status;
 mod download_task;
 pub mod file_info;
 pub mod nxm_url;
 
 pub use self::download_info::*;
 pub use self::download_progress::*;
+pub use self::download_status::{AvailableUpdate, DownloadStatus, StatusSnapshot};
 use self::download_task::*;
 pub use self::file_info::*;
 pub use self::nxm_url::*;
 
 use crate::api::query::{md5_search::*, DownloadLink, FileList, Queriable};
 use crate::api::{ApiError, Client};
-use crate::cache::{Cache, Cacheable, LocalFile, UpdateStatus};
-use crate::config::{Config, PathType};
+use crate::archives::Archives;
+use crate::cache::{Cache, Cacheable, LocalFile, UpdateStatus, VerificationState};
+use crate::config::{Config, DownloadAction, PathType};
+use crate::util::changes::ChangeFlag;
 use crate::{util, Logger};
 
+use std::collections::{HashSet, VecDeque};
 use std::ffi::OsStr;
 use std::io::ErrorKind;
+use std::path::Path;
+use std::process::{Command, Stdio};
 use std::str::FromStr;
 use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
+    atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    Arc, Mutex,
 };
+use std::time::Duration;
 
 use indexmap::IndexMap;
+use reqwest::StatusCode;
 use tokio::fs;
 use tokio::sync::RwLock;
+use tokio::{task, time};
 use url::Url;
 
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Whether a download was started, or skipped because the file is already downloaded or being downloaded
+#[derive(Debug, PartialEq)]
+pub enum QueueOutcome {
+    Queued,
+    Skipped,
+}
+
+// Combined progress of the downloads that are in progress, and counts of the others
+#[derive(Default)]
+pub struct DownloadTotals {
+    pub active: usize,
+    pub queued: usize, // paused, waiting to be resumed
+    pub done: usize,
+    pub bytes_read: u64,
+    // Across the downloads whose size is known. None if there are none.
+    pub fraction: Option<f64>,
+}
+
 #[derive(Clone)]
 pub struct Downloads {
     pub tasks: Arc<RwLock<IndexMap<u64, DownloadTask>>>,
-    pub has_changed: Arc<AtomicBool>,
+    pub has_changed: ChangeFlag,
+    // Shared with the download tasks, so that reloading the config takes effect for running downloads
+    pub desktop_notifications: Arc<AtomicBool>,
+    pub reserved_disk_space: Arc<AtomicU64>,        // in bytes
+    pub max_concurrent_downloads: Arc<AtomicUsize>, // 0 means no limit
+    pub verify_resume: Arc<AtomicBool>,
+    pub post_download_command: Arc<Mutex<Option<String>>>,
+    // Downloads whose file name is taken by a different file, waiting for the user to decide what to do
+    pub file_conflicts: Arc<Mutex<VecDeque<u64>>>,
+    // Paused downloads that resume_all() hasn't resumed yet, since max_concurrent_downloads were already running
+    resume_queue: Arc<Mutex<HashSet<u64>>>,
     logger: Logger,
     cache: Cache,
     client: Client,
@@ -43,7 +83,14 @@ impl Downloads {
     pub async fn new(cache: &Cache, client: &Client, config: &Config, logger: &Logger) -> Self {
         Self {
             tasks: Arc::new(RwLock::new(IndexMap::new())),
-            has_changed: Arc::new(AtomicBool::new(true)),
+            has_changed: ChangeFlag::new(true),
+            desktop_notifications: Arc::new(AtomicBool::new(config.desktop_notifications)),
+            reserved_disk_space: Arc::new(AtomicU64::new(config.reserved_disk_space * 1024 * 1024)),
+            max_concurrent_downloads: Arc::new(AtomicUsize::new(config.max_concurrent_downloads)),
+            verify_resume: Arc::new(AtomicBool::new(config.verify_resume)),
+            post_download_command: Arc::new(Mutex::new(config.post_download_command.clone())),
+            file_conflicts: Arc::new(Mutex::new(VecDeque::new())),
+            resume_queue: Arc::new(Mutex::new(HashSet::new())),
             cache: cache.clone(),
             client: client.clone(),
             config: config.clone(),
@@ -51,40 +98,324 @@ impl Downloads {
         }
     }
 
+    // Applies the settings that can be changed at runtime
+    pub fn apply_config(&self, config: &Config) {
+        self.desktop_notifications.store(config.desktop_notifications, Ordering::Relaxed);
+        self.reserved_disk_space.store(config.reserved_disk_space * 1024 * 1024, Ordering::Relaxed);
+        self.max_concurrent_downloads.store(config.max_concurrent_downloads, Ordering::Relaxed);
+        self.verify_resume.store(config.verify_resume, Ordering::Relaxed);
+        *self.post_download_command.lock().unwrap() = config.post_download_command.clone();
+    }
+
+    /* Runs post_download_command from the config once a download has finished. The placeholders {path}, {game},
+     * {mod_id} and {file_name} are replaced in each argument, and the same values are passed in the DMODMAN_PATH,
+     * DMODMAN_GAME, DMODMAN_MOD_ID and DMODMAN_FILE_NAME environment variables. A failing command is only logged, along
+     * with what it wrote to stderr, since the download itself succeeded. Its output would otherwise end up in the UI. */
+    pub async fn run_post_download_command(&self, fi: &FileInfo) {
+        let template = match &*self.post_download_command.lock().unwrap() {
+            Some(cmd) => cmd.clone(),
+            None => return,
+        };
+        let path = self.config.file_path(&fi.game, &fi.file_name).await;
+        let vars = [
+            ("path", path.to_string_lossy().to_string()),
+            ("game", fi.game.clone()),
+            ("mod_id", fi.mod_id.to_string()),
+            ("file_name", fi.file_name.clone()),
+        ];
+        let mut args = expand_command(&template, &vars);
+        if args.is_empty() {
+            self.logger.warn("The post_download_command setting in the config is empty.");
+            return;
+        }
+        let program = args.remove(0);
+        let envs: Vec<(String, String)> =
+            vars.iter().map(|(name, value)| (format!("DMODMAN_{}", name.to_uppercase()), value.clone())).collect();
+        let cmdline = format!("{program} {}", args.join(" "));
+        let output = task::spawn_blocking(move || {
+            Command::new(program)
+                .args(args)
+                .envs(envs)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .output()
+        })
+        .await;
+        match output {
+            Ok(Ok(output)) if output.status.success() => {}
+            Ok(Ok(output)) => {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if stderr.trim().is_empty() {
+                    self.logger.warn(format!("\"{cmdline}\" exited with {}.", output.status));
+                } else {
+                    self.logger.warn(format!("\"{cmdline}\" exited with {}: {}", output.status, stderr.trim()));
+                }
+            }
+            Ok(Err(e)) => self.logger.error(format!("Unable to run \"{cmdline}\": {e}")),
+            Err(e) => self.logger.error(format!("Unable to run \"{cmdline}\": {e}")),
+        }
+    }
+
+    /* Keeps, extracts or stages a finished download, depending on the download_actions for its game and category in the
+     * config. A staged file is moved with its metadata to <staging_dir>/<game>, which removes it from the file list. */
+    pub async fn apply_download_action(&self, fi: &FileInfo) {
+        let category = self
+            .cache
+            .file_lists
+            .get((&fi.game, fi.mod_id))
+            .await
+            .and_then(|fl| fl.files.into_iter().find(|fd| fd.file_id == fi.file_id))
+            .and_then(|fd| fd.category_name);
+        let path = self.config.file_path(&fi.game, &fi.file_name).await;
+        match self.config.download_action(&fi.game, category.as_deref()) {
+            DownloadAction::Keep => {}
+            DownloadAction::Extract => {
+                /* A corrupt or encrypted archive has already been reported, and can be downloaded again or extracted with
+                 * a password from the archives tab */
+                let _ = Archives::test_and_extract(&self.config, &self.cache, &self.logger, &path, None).await;
+            }
+            DownloadAction::Stage => {
+                let staging_dir = match &self.config.staging_dir {
+                    Some(dir) => Path::new(dir).join(&fi.game),
+                    None => {
+                        self.logger.warn(format!("Keeping {}, since staging_dir isn't set.", fi.file_name));
+                        return;
+                    }
+                };
+                if let Err(e) = self.stage(fi, &path, &staging_dir).await {
+                    self.logger.error(format!("Unable to move {} to {:?}: {}", fi.file_name, staging_dir, e));
+                    return;
+                }
+                self.logger.log(format!("Moved {} to {:?}.", fi.file_name, staging_dir));
+            }
+        }
+    }
+
+    async fn stage(&self, fi: &FileInfo, path: &Path, staging_dir: &Path) -> Result<(), std::io::Error> {
+        fs::create_dir_all(staging_dir).await?;
+        util::move_file(path, &staging_dir.join(&fi.file_name)).await?;
+        let metadata_name = format!("{}.json", fi.file_name);
+        util::move_file(&path.with_file_name(&metadata_name), &staging_dir.join(metadata_name)).await?;
+        self.cache.file_index.remove(fi.file_id).await?;
+        Ok(())
+    }
+
+    pub async fn active_count(&self) -> usize {
+        let lock = self.tasks.read().await;
+        lock.values().filter(|task| matches!(task.dl_info.get_state(), DownloadState::Downloading)).count()
+    }
+
+    pub async fn totals(&self) -> DownloadTotals {
+        let mut totals = DownloadTotals::default();
+        let (mut sized_read, mut sized_total) = (0, 0);
+        for task in self.tasks.read().await.values() {
+            match task.dl_info.get_state() {
+                DownloadState::Downloading => {}
+                DownloadState::Paused(_) => {
+                    totals.queued += 1;
+                    continue;
+                }
+                DownloadState::Done => {
+                    totals.done += 1;
+                    continue;
+                }
+                _ => continue,
+            }
+            let bytes_read = task.dl_info.progress.bytes_read.load(Ordering::Relaxed);
+            totals.active += 1;
+            totals.bytes_read += bytes_read;
+            if let Some(len) = task.dl_info.progress.content_length() {
+                sized_read += bytes_read.min(len);
+                sized_total += len;
+            }
+        }
+        if sized_total > 0 {
+            totals.fraction = Some(sized_read as f64 / sized_total as f64);
+        }
+        totals
+    }
+
+    /* Used when queuing many downloads at once, like when updating all mods. Downloads started from nxm:// URLs begin
+     * immediately, since the user asked for them one at a time. */
+    pub async fn wait_for_free_slot(&self) {
+        loop {
+            let limit = self.max_concurrent_downloads.load(Ordering::Relaxed);
+            if limit == 0 || self.active_count().await < limit {
+                return;
+            }
+            time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
     pub async fn toggle_pause_for(&self, i: usize) {
         let mut lock = self.tasks.write().await;
         let (_, task) = lock.get_index_mut(i).unwrap();
         task.toggle_pause().await;
-        self.has_changed.store(true, Ordering::Relaxed);
+        self.has_changed.set();
     }
 
-    pub async fn try_queue(&self, nxm_str: &str) {
-        let nxm;
-        match NxmUrl::from_str(nxm_str) {
-            Ok(n) => nxm = n,
-            Err(e) => {
-                if let ApiError::Expired = e {
-                    self.logger.log(format!("nxm url has expired: {nxm_str}"));
-                    return;
-                } else {
-                    self.logger.log(format!("Unable to parse string \"{nxm_str}\" as nxm url: {e}"));
-                    return;
+    // The download is looked up by id, since the list may have changed while the user was asked to confirm
+    pub async fn retry(&self, file_id: u64) {
+        if let Some(task) = self.tasks.write().await.get_mut(&file_id) {
+            task.retry().await;
+        }
+        self.has_changed.set();
+    }
+
+    /* Moves a download one row up or down in the queue, which is the order that resume_all() resumes them in. The order
+     * is saved, so that it's kept after a restart. Running downloads have to be paused first. Returns whether the
+     * download was moved. */
+    pub async fn move_up(&self, i: usize) -> bool {
+        if i == 0 {
+            return false;
+        }
+        self.swap(i, i - 1).await
+    }
+
+    pub async fn move_down(&self, i: usize) -> bool {
+        self.swap(i, i + 1).await
+    }
+
+    async fn swap(&self, i: usize, j: usize) -> bool {
+        let mut lock = self.tasks.write().await;
+        if j >= lock.len() {
+            return false;
+        }
+        let (_, task) = lock.get_index(i).unwrap();
+        if let DownloadState::Downloading = task.dl_info.get_state() {
+            let file_name = task.dl_info.file_info.file_name.clone();
+            drop(lock);
+            self.logger.log(format!("{file_name} is being downloaded, pause it before moving it in the queue."));
+            return false;
+        }
+        lock.swap_indices(i, j);
+        let order: Vec<u64> = lock.keys().copied().collect();
+        drop(lock);
+        self.has_changed.set();
+        if let Err(e) = self.save_order(&order).await {
+            self.logger.warn(format!("Unable to save the order of the downloads: {e}"));
+        }
+        true
+    }
+
+    async fn save_order(&self, order: &[u64]) -> Result<(), std::io::Error> {
+        let path = self.config.path_for(PathType::DownloadOrder).await;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        fs::write(path, serde_json::to_vec(order)?).await
+    }
+
+    // Puts the downloads in the order they were moved into, with those that weren't saved at the end
+    async fn restore_order(&self) {
+        let path = self.config.path_for(PathType::DownloadOrder).await;
+        let order: Vec<u64> = match fs::read(path).await {
+            Ok(contents) => serde_json::from_slice(&contents).unwrap_or_default(),
+            Err(_) => return,
+        };
+        let position = |file_id: &u64| order.iter().position(|id| id == file_id).unwrap_or(usize::MAX);
+        self.tasks.write().await.sort_by(|a, _, b, _| position(a).cmp(&position(b)));
+        self.has_changed.set();
+    }
+
+    // Pausing saves the download state, so the downloads stay paused even if the program doesn't exit cleanly
+    pub async fn pause_all(&self) {
+        self.resume_queue.lock().unwrap().clear();
+        let mut lock = self.tasks.write().await;
+        for task in lock.values_mut() {
+            if let DownloadState::Downloading = task.dl_info.get_state() {
+                task.toggle_pause().await;
+            }
+        }
+        self.has_changed.set();
+    }
+
+    /* Resumes the paused downloads in the order of the list, as many at a time as max_concurrent_downloads allows. The
+     * rest wait in the background, and the next one in the list is resumed whenever a download stops. */
+    pub async fn resume_all(&self) {
+        let paused: Vec<u64> = self
+            .tasks
+            .read()
+            .await
+            .iter()
+            .filter(|(_, task)| matches!(task.dl_info.get_state(), DownloadState::Paused(_)))
+            .map(|(file_id, _)| *file_id)
+            .collect();
+        let is_running = {
+            let mut queue = self.resume_queue.lock().unwrap();
+            let is_running = !queue.is_empty();
+            queue.extend(paused);
+            is_running
+        };
+        if !is_running {
+            let me = self.clone();
+            task::spawn(async move { me.resume_queued().await });
+        }
+    }
+
+    async fn resume_queued(&self) {
+        loop {
+            self.wait_for_free_slot().await;
+            // The list is looked at again each time, since downloads may have been moved or deleted in the meantime
+            let order: Vec<u64> = self.tasks.read().await.keys().copied().collect();
+            let next = {
+                let mut queue = self.resume_queue.lock().unwrap();
+                queue.retain(|file_id| order.contains(file_id));
+                match order.into_iter().find(|file_id| queue.contains(file_id)) {
+                    Some(file_id) => {
+                        queue.remove(&file_id);
+                        file_id
+                    }
+                    None => return,
+                }
+            };
+            if let Some(task) = self.tasks.write().await.get_mut(&next) {
+                if let DownloadState::Paused(_) = task.dl_info.get_state() {
+                    task.toggle_pause().await;
                 }
             }
+            self.has_changed.set();
         }
+    }
 
-        let url;
-        match self.request_download_link(&nxm).await {
-            Ok(u) => url = u,
-            Err(_e) => return,
+    /* Errors are logged, and also returned so that they can be reported to whoever sent the nxm:// URL through the
+     * socket. */
+    pub async fn try_queue(&self, nxm: &NxmUrl) -> Result<QueueOutcome, String> {
+        /* A stale link can't be used to request a download link, so there's no point in trying. If the file's download
+         * has failed, it's marked as expired to show that it needs a new link from the Nexus. Paused downloads keep
+         * their state, since their old download link may still work. */
+        if let Err(ApiError::Expired) = nxm.check_expiration() {
+            if let Some(task) = self.tasks.write().await.get_mut(&nxm.file_id) {
+                if let DownloadState::Error = task.dl_info.get_state() {
+                    task.dl_info.set_state(DownloadState::Expired);
+                    task.save_dl_info().await;
+                    self.has_changed.set();
+                }
+            }
+            let msg = format!("nxm url has expired: {}", nxm.url);
+            self.logger.error(&msg);
+            return Err(msg);
         }
+
+        self.queue_file(&nxm.domain_name, nxm.mod_id, nxm.file_id, &nxm.query).await
+    }
+
+    /* Requests a download link for the file and starts downloading it. The query contains the key and expiry time from
+     * a nxm:// URL, and can be left empty by premium users. */
+    pub async fn queue_file(&self, game: &str, mod_id: u32, file_id: u64, query: &str) -> Result<QueueOutcome, String> {
+        let url = match self.request_download_link(game, mod_id, file_id, query).await {
+            Ok(u) => u,
+            // request_download_link() logs the error
+            Err(e) => return Err(format!("Failed to query download links from Nexus: {}", e)),
+        };
         let file_name = util::file_name_from_url(&url);
 
-        if let Some(task) = self.tasks.write().await.get_mut(&nxm.file_id) {
+        if let Some(task) = self.tasks.write().await.get_mut(&file_id) {
             match task.dl_info.get_state() {
                 DownloadState::Downloading => {
                     self.logger.log(format!("Download of {} is already in progress.", file_name));
-                    return;
+                    return Ok(QueueOutcome::Skipped);
                 }
                 DownloadState::Done => {
                     self.logger.log(format!(
@@ -92,58 +423,72 @@ impl Downloads {
                         file_name
                     ));
                     let _ = task.start().await;
-                    self.has_changed.store(true, Ordering::Relaxed);
-                    return;
+                    self.has_changed.set();
+                    return Ok(QueueOutcome::Queued);
                 }
                 // Restart the download using the new download link.
                 _ => {
                     task.dl_info.url = url.clone();
-                    if let Err(()) = task.start().await {
-                        self.logger.log(format!("Failed to restart download for {}", &file_name));
+                    task.dl_info.nxm_url = Some(nxm_url(game, mod_id, file_id, query));
+                    let res = task
+                        .start()
+                        .await
+                        .map(|()| QueueOutcome::Queued)
+                        .map_err(|()| format!("Failed to restart download for {}", &file_name));
+                    if let Err(msg) = &res {
+                        self.logger.log(msg);
                     }
-                    if let Err(e) = task.dl_info.save(self.config.path_for(PathType::DownloadInfo(&task.dl_info))).await
-                    {
-                        self.logger.log(format!("Couldn't store new download url for {}: {}", &file_name, e));
+                    if let Err(e) = task.dl_info.save(task.dl_info_path().await).await {
+                        self.logger.error(format!("Couldn't store new download url for {}: {}", &file_name, e));
                     }
-                    return;
+                    return res;
                 }
             }
         } // Important to drop the lock here or self.add() deadlocks
-        let f_info = FileInfo::new(nxm.domain_name, nxm.mod_id, nxm.file_id, file_name);
-        self.add(DownloadInfo::new(f_info, url)).await;
+        let f_info = FileInfo::new(game.to_string(), mod_id, file_id, file_name);
+        let mut dl_info = DownloadInfo::new(f_info, url);
+        dl_info.nxm_url = Some(nxm_url(game, mod_id, file_id, query));
+        match self.add(dl_info).await {
+            true => Ok(QueueOutcome::Queued),
+            false => Ok(QueueOutcome::Skipped),
+        }
     }
 
-    pub async fn add(&self, dl_info: DownloadInfo) {
+    // Returns false if the file has already been downloaded
+    pub async fn add(&self, dl_info: DownloadInfo) -> bool {
         let mut task =
             DownloadTask::new(&self.cache, &self.client, &self.config, &self.logger, dl_info.clone(), self.clone());
 
         if task.file_exists().await {
-            return;
+            // Kept in the list until the user decides whether to replace the existing file
+            if let DownloadState::Paused(PauseReason::FileExists) = task.dl_info.get_state() {
+                self.tasks.write().await.insert(dl_info.file_info.file_id, task);
+                return true;
+            }
+            return false;
         }
 
+        // Downloads interrupted by network errors are resumed, but the other pause reasons need the user's input
         match dl_info.get_state() {
-            DownloadState::Paused => {}
+            DownloadState::Paused(PauseReason::NetworkError) => if let Ok(()) = task.start().await {},
+            DownloadState::Paused(_) => {}
             _ => if let Ok(()) = task.start().await {},
         }
         self.tasks.write().await.insert(dl_info.file_info.file_id, task);
-        self.has_changed.store(true, Ordering::Relaxed);
+        self.has_changed.set();
+        true
     }
 
-    async fn request_download_link(&self, nxm: &NxmUrl) -> Result<Url, ApiError> {
+    async fn request_download_link(&self, game: &str, mod_id: u32, file_id: u64, query: &str) -> Result<Url, ApiError> {
         match DownloadLink::request(
             &self.client,
             // TODO get rid of passing a vec as argument
-            vec![
-                &nxm.domain_name,
-                &nxm.mod_id.to_string(),
-                &nxm.file_id.to_string(),
-                &nxm.query,
-            ],
+            vec![game, &mod_id.to_string(), &file_id.to_string(), query],
         )
         .await
         {
             Ok(dl_links) => {
-                self.cache.save_download_links(&dl_links, &nxm.domain_name, &nxm.mod_id, &nxm.file_id).await?;
+                self.cache.save_download_links(&dl_links, game, &mod_id, &file_id).await?;
                 /* The API returns multiple locations for Premium users. The first option is by default the Premium-only
                  * global CDN, unless the user has selected a preferred download location.
                  * For small files the download URL is the same regardless of location choice.
@@ -153,7 +498,7 @@ impl Downloads {
                 match Url::parse(&location.URI) {
                     Ok(url) => Ok(url),
                     Err(e) => {
-                        self.logger.log(format!(
+                        self.logger.error(format!(
                             "Failed to parse URI in response from Nexus: {}. \
                                                 Please file a bug about this.",
                             &location.URI
@@ -163,7 +508,7 @@ impl Downloads {
                 }
             }
             Err(e) => {
-                self.logger.log(format!("Failed to query download links from Nexus: {}", e));
+                self.logger.error(format!("Failed to query download links from Nexus: {}", e));
                 Err(e)
             }
         }
@@ -184,12 +529,12 @@ impl Downloads {
             match FileList::request(&self.client, vec![game, &mod_id.to_string()]).await {
                 Ok(fl) => {
                     if let Err(e) = self.cache.save_file_list(&fl, game, mod_id).await {
-                        self.logger.log(format!("Unable to save file list for {} mod {}: {}", game, mod_id, e));
+                        self.logger.error(format!("Unable to save file list for {} mod {}: {}", game, mod_id, e));
                     }
                     Some(fl)
                 }
                 Err(e) => {
-                    self.logger.log(format!("Unable to query file list for {} mod {}: {}", game, mod_id, e));
+                    self.logger.error(format!("Unable to query file list for {} mod {}: {}", game, mod_id, e));
                     None
                 }
             }
@@ -204,9 +549,9 @@ impl Downloads {
                     match lf.update_status {
                         UpdateStatus::UpToDate(_) | UpdateStatus::HasNewFile(_) => {
                             lf.update_status = UpdateStatus::UpToDate(latest_timestamp);
-                            let path = self.config.path_for(PathType::LocalFile(&lf));
+                            let path = self.config.path_for(PathType::LocalFile(&lf)).await;
                             if let Err(e) = lf.save(path).await {
-                                self.logger.log(format!("Couldn't set UpdateStatus for {}: {}", lf.file_name, e));
+                                self.logger.error(format!("Couldn't set UpdateStatus for {}: {}", lf.file_name, e));
                             }
                         }
                         // Probably doesn't make sense to do anything in the other cases..?
@@ -216,25 +561,25 @@ impl Downloads {
             }
         }
 
-        let lf = LocalFile::new(fi.clone(), UpdateStatus::UpToDate(latest_timestamp));
-        self.verify_hash(&lf).await;
+        let mut lf = LocalFile::new(fi.clone(), UpdateStatus::UpToDate(latest_timestamp));
+        lf.verification = self.verify_hash(&lf).await;
         self.cache.save_local_file(lf.clone()).await?;
         Ok(())
     }
 
-    async fn verify_hash(&self, local_file: &LocalFile) {
-        let mut path = self.config.download_dir();
-        path.push(&local_file.file_name);
+    async fn verify_hash(&self, local_file: &LocalFile) -> VerificationState {
+        let path = self.config.file_path(&local_file.game, &local_file.file_name).await;
         match util::md5sum(path).await {
             Ok(md5) => {
-                if let Ok(query_res) = Md5Search::request(&self.client, vec![&local_file.game, &md5]).await {
+                let res = Md5Search::request(&self.client, vec![&local_file.game, &md5]).await;
+                if let Ok(query_res) = &res {
                     // Uncomment to save API response
                     //let _ = query_res
                     //    .save(self.config.path_for(PathType::Md5Search(
                     //        &local_file.game,
                     //        &local_file.mod_id,
                     //        &local_file.file_id,
-                    //    )))
+                    //    )).await)
                     //    .await;
 
                     if let Some(md5result) =
@@ -243,25 +588,35 @@ impl Downloads {
                         if !(md5.eq(&md5result.file_details.md5)
                             && local_file.file_name.eq(&md5result.file_details.file_name))
                         {
-                            self.logger.log(format!(
+                            self.logger.warn(format!(
                                 "Warning: API returned unexpected file when checking hash for {}",
                                 &local_file.file_name
                             ));
                             let mi = &md5result.r#mod;
                             let fd = &md5result.file_details;
-                            self.logger.log(format!("Found {:?}: {} ({})", mi.name, fd.name, fd.file_name));
-                            self.logger.log("This should be reported as a Nexus bug. See README for details.");
+                            self.logger.warn(format!("Found {:?}: {} ({})", mi.name, fd.name, fd.file_name));
+                            self.logger.warn("This should be reported as a Nexus bug. See README for details.");
                         }
                         // Early return if success, else fall through to error reporting.
-                        return;
+                        return VerificationState::Verified;
                     }
                 }
-                self.logger.log(format!("Unable to verify integrity of: {}", &local_file.file_name));
-                self.logger.log("This could mean the download got corrupted. See README for details.");
+                // The API responds with 404 if no file has this md5 sum
+                let state = match res {
+                    Ok(_) => VerificationState::Mismatch,
+                    Err(ApiError::ConnectionError { source }) if source.status() == Some(StatusCode::NOT_FOUND) => {
+                        VerificationState::Mismatch
+                    }
+                    Err(_) => VerificationState::Unverified,
+                };
+                self.logger.error(format!("Unable to verify integrity of: {}", &local_file.file_name));
+                self.logger.warn("This could mean the download got corrupted. See README for details.");
+                state
             }
             Err(e) => {
-                self.logger.log(format!("Error when checking hash for: {}", local_file.file_name));
-                self.logger.log(format!("{}", e));
+                self.logger.error(format!("Error when checking hash for: {}", local_file.file_name));
+                self.logger.error(format!("{}", e));
+                VerificationState::Unverified
             }
         }
     }
@@ -270,25 +625,137 @@ impl Downloads {
         let mut tasks_lock = self.tasks.write().await;
         let (_, mut task) = tasks_lock.shift_remove_index(i).unwrap();
         if let DownloadState::Done = task.dl_info.get_state() {
-            self.has_changed.store(true, Ordering::Relaxed);
+            self.has_changed.set();
             return;
         }
-        task.stop();
-        let mut path = self.config.download_dir();
-        path.push(format!("{}.part", &task.dl_info.file_info.file_name));
-        if fs::remove_file(path.clone()).await.is_err() {
-            self.logger.log(format!("Unable to delete {:?}.", &path));
+        task.stop().await;
+        for path in [task.part_path().await, task.dl_info_path().await] {
+            if fs::remove_file(&path).await.is_err() {
+                self.logger.error(format!("Unable to delete {:?}.", &path));
+            }
         }
-        path.pop();
-        path.push(format!("{}.part.json", &task.dl_info.file_info.file_name));
-        if fs::remove_file(path.clone()).await.is_err() {
-            self.logger.log(format!("Unable to delete {:?}.", &path));
+        self.has_changed.set();
+    }
+
+    // The file id of a download that is waiting for the user to decide whether to replace an existing file
+    pub fn next_file_conflict(&self) -> Option<u64> {
+        self.file_conflicts.lock().unwrap().pop_front()
+    }
+
+    /* Either downloads the file again, replacing the existing one, or keeps the existing file and removes the download
+     * from the list. */
+    pub async fn resolve_file_conflict(&self, file_id: u64, overwrite: bool) {
+        let mut lock = self.tasks.write().await;
+        if overwrite {
+            if let Some(task) = lock.get_mut(&file_id) {
+                let _ = task.overwrite_existing().await;
+            }
+        } else if let Some(task) = lock.shift_remove(&file_id) {
+            drop(lock);
+            let fi = &task.dl_info.file_info;
+            let _ = fs::remove_file(task.dl_info_path().await).await;
+            if !self.cache.file_index.file_id_map.read().await.contains_key(&file_id) {
+                let _ = self.update_metadata(fi).await;
+            }
+            self.logger.log(format!("Kept the existing {}.", fi.file_name));
+        }
+        self.has_changed.set();
+    }
+
+    pub async fn status_list(&self) -> Vec<DownloadStatus> {
+        self.tasks.read().await.values().map(|task| DownloadStatus::from(&task.dl_info)).collect()
+    }
+
+    pub async fn status_snapshot(&self) -> StatusSnapshot {
+        let mut updates = vec![];
+        for fdata in self.cache.file_index.files_sorted.read().await.iter() {
+            let lf = fdata.local_file.read().await;
+            let status = match lf.update_status {
+                UpdateStatus::OutOfDate(_) => "OutOfDate",
+                UpdateStatus::HasNewFile(_) => "HasNewFile",
+                UpdateStatus::UpToDate(_) | UpdateStatus::IgnoredUntil(_) => continue,
+            };
+            updates.push(AvailableUpdate {
+                file_name: lf.file_name.clone(),
+                game: lf.game.clone(),
+                mod_id: lf.mod_id,
+                file_id: lf.file_id,
+                status: status.to_string(),
+            });
+        }
+        StatusSnapshot {
+            downloads: self.status_list().await,
+            updates,
+        }
+    }
+
+    /* Saves the downloads that haven't finished, whether they're in progress, paused or failed, to the file as JSON, so
+     * that they can be queued on another machine with import_queue(). Returns how many were saved. */
+    pub async fn export_queue(&self, path: &Path) -> Result<usize, std::io::Error> {
+        let queue: Vec<QueuedDownload> = self
+            .tasks
+            .read()
+            .await
+            .values()
+            .filter(|task| !matches!(task.dl_info.get_state(), DownloadState::Done))
+            .map(|task| QueuedDownload::from(&task.dl_info))
+            .collect();
+        fs::write(path, serde_json::to_string_pretty(&queue)?).await?;
+        self.logger.log(format!("Saved {} downloads to {}.", queue.len(), path.display()));
+        Ok(queue.len())
+    }
+
+    /* Queues the downloads saved by export_queue(). Their nxm:// URLs are used as long as they haven't expired, otherwise
+     * a new download link is requested, which only works for premium users. Downloads that fail are logged, and the
+     * others are still queued. Returns how many were queued. */
+    pub async fn import_queue(&self, path: &Path) -> Result<usize, String> {
+        let json = fs::read_to_string(path).await.map_err(|e| format!("Unable to read {}: {e}", path.display()))?;
+        let queue: Vec<QueuedDownload> =
+            serde_json::from_str(&json).map_err(|e| format!("Unable to parse {}: {e}", path.display()))?;
+        let mut queued = 0;
+        for qd in &queue {
+            let nxm = qd.nxm_url.as_deref().and_then(|url| NxmUrl::from_str(url).ok());
+            let fi = &qd.file_info;
+            let outcome = match nxm {
+                Some(nxm) if nxm.check_expiration().is_ok() => self.try_queue(&nxm).await,
+                _ => self.queue_file(&fi.game, fi.mod_id, fi.file_id, "").await,
+            };
+            if let Ok(QueueOutcome::Queued) = outcome {
+                queued += 1;
+            }
+        }
+        self.logger.log(format!("Queued {queued} of {} downloads from {}.", queue.len(), path.display()));
+        Ok(queued)
+    }
+
+    /* Stops the running downloads so that their data is flushed to disk and their state saved. They're left in the
+     * Downloading state, so they continue when the program is started again. All of them are stopped at once, and any
+     * that haven't finished writing within SHUTDOWN_TIMEOUT, e.g. due to a hung network filesystem, are aborted. An
+     * aborted download is resumed from its last saved state. */
+    pub async fn shutdown(&self) {
+        let mut handles: Vec<_> =
+            self.tasks.write().await.values_mut().filter_map(|task| task.request_stop()).collect();
+        let all_stopped = time::timeout(SHUTDOWN_TIMEOUT, async {
+            for handle in handles.iter_mut() {
+                let _ = handle.await;
+            }
+        })
+        .await;
+        if all_stopped.is_err() {
+            self.logger.warn("Some downloads didn't stop in time and were aborted.");
+            for handle in &handles {
+                handle.abort();
+            }
         }
-        self.has_changed.store(true, Ordering::Relaxed);
     }
 
     pub async fn resume_on_startup(&self) {
-        if let Ok(mut file_stream) = fs::read_dir(&self.config.download_dir()).await {
+        for dir in self.config.part_dirs() {
+            let mut file_stream = if let Ok(file_stream) = fs::read_dir(&dir).await {
+                file_stream
+            } else {
+                continue;
+            };
             while let Some(f) = file_stream.next_entry().await.unwrap() {
                 // Resume incomplete downloads
                 if f.path().is_file() && f.path().extension().and_then(OsStr::to_str) == Some("part") {
@@ -299,13 +766,13 @@ impl Downloads {
                         }
                         Err(ref e) => {
                             if e.kind() == ErrorKind::NotFound {
-                                self.logger.log(format!(
+                                self.logger.warn(format!(
                                     "Metadata for partially downloaded file {:?} is missing.\n
                                          The download needs to be restarted through the Nexus.",
                                     f.file_name()
                                 ));
                             } else {
-                                self.logger.log(format!(
+                                self.logger.error(format!(
                                     "Unable to deserialize metadata from {:?}:\n
                                         {}",
                                     f.file_name(),
@@ -317,5 +784,47 @@ impl Downloads {
                 }
             }
         }
+        self.restore_order().await;
+    }
+}
+
+// The nxm:// URL that the file would be downloaded with from the Nexus, with the query that has the key if there is one
+fn nxm_url(game: &str, mod_id: u32, file_id: u64, query: &str) -> String {
+    let url = format!("nxm://{game}/mods/{mod_id}/files/{file_id}");
+    if query.is_empty() {
+        url
+    } else {
+        format!("{url}?{query}")
+    }
+}
+
+// Splits the command into arguments and replaces the {name} placeholders in each, so values with spaces stay whole
+fn expand_command(template: &str, vars: &[(&str, String)]) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|arg| vars.iter().fold(arg.to_string(), |arg, (name, value)| arg.replace(&format!("{{{name}}}"), value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::expand_command;
+
+    #[test]
+    fn post_download_command() {
+        let vars = [
+            ("path", "/home/user/Downloads/dmodman/Some Mod-123.7z".to_string()),
+            ("game", "morrowind".to_string()),
+            ("mod_id", "46599".to_string()),
+        ];
+        assert_eq!(
+            expand_command("mv {path} /mods/{game}/{mod_id}/", &vars),
+            vec![
+                "mv",
+                "/home/user/Downloads/dmodman/Some Mod-123.7z",
+                "/mods/morrowind/46599/"
+            ]
+        );
+        assert!(expand_command("  ", &vars).is_empty());
     }
 }
@@ -5,51 +5,81 @@ use std::str::FromStr;
 use std::time::{SystemTime, UNIX_EPOCH};
 use url::Url;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub struct NxmUrl {
     pub url: Url,
     pub query: String,
     pub domain_name: String, // this is the game name
     pub mod_id: u32,
     pub file_id: u64,
-    pub key: String,
-    pub expires: u64,
-    pub user_id: u32,
+    // Links for free users are only valid with these. Premium users can download without them.
+    pub key: Option<String>,
+    pub expires: Option<u64>,
+    pub user_id: Option<u32>,
 }
 
+/* Parsing doesn't check whether the link has expired, so that a stale link can still be matched with its download.
+ * Use check_expiration() before requesting a download link with it. */
 impl FromStr for NxmUrl {
     type Err = ApiError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let url = Url::parse(s)?;
+        if url.scheme() != "nxm" {
+            return Err(ApiError::InvalidNxmUrl {
+                reason: "not a nxm:// URL",
+            });
+        }
+
+        let game = match url.host_str() {
+            Some(host) => host.to_string(),
+            None => return Err(ApiError::InvalidNxmUrl { reason: "missing game" }),
+        };
+        // The path is /mods/<mod_id>/files/<file_id>
+        let segments: Vec<&str> = url.path_segments().map(|s| s.collect()).unwrap_or_default();
+        let (mod_id, file_id) = match segments.as_slice() {
+            ["mods", mod_id, "files", file_id] => (mod_id.parse()?, file_id.parse()?),
+            _ => {
+                return Err(ApiError::InvalidNxmUrl {
+                    reason: "expected /mods/<mod id>/files/<file id>",
+                })
+            }
+        };
 
-        let mut path_segments = url.path_segments().unwrap();
-        let game = url.host().unwrap().to_string();
-        let _mods = path_segments.next();
-        let mod_id: u32 = path_segments.next().unwrap().parse()?;
-        let _files = path_segments.next();
-        let file_id: u64 = path_segments.next().unwrap().parse()?;
-        let q = url.clone();
-        let query: String = q.query().unwrap().to_string();
-        let mut query_pairs = url.query_pairs();
-        let key: String = query_pairs.next().unwrap().1.to_string();
-        let expires: u64 = query_pairs.next().unwrap().1.parse()?;
-        let user_id: u32 = query_pairs.next().unwrap().1.parse()?;
+        let (mut key, mut expires, mut user_id) = (None, None, None);
+        for (name, value) in url.query_pairs() {
+            match name.as_ref() {
+                "key" => key = Some(value.to_string()),
+                "expires" => expires = Some(value.parse()?),
+                "user_id" => user_id = Some(value.parse()?),
+                _ => {}
+            }
+        }
+        if key.is_some() != expires.is_some() {
+            return Err(ApiError::InvalidNxmUrl {
+                reason: "key and expires must be given together",
+            });
+        }
 
-        let ret: NxmUrl = NxmUrl {
+        Ok(NxmUrl {
+            query: url.query().unwrap_or_default().to_string(),
             url,
-            query,
             domain_name: check_game_special_case(game),
             mod_id,
             file_id,
             key,
             expires,
             user_id,
-        };
-
-        check_expiration(&expires)?;
+        })
+    }
+}
 
-        Ok(ret)
+impl NxmUrl {
+    pub fn check_expiration(&self) -> Result<(), ApiError> {
+        match self.expires {
+            Some(expires) => check_expiration(&expires),
+            None => Ok(()),
+        }
     }
 }
 
@@ -82,9 +112,49 @@ mod tests {
     fn expired_nxm() -> Result<(), ApiError> {
         let nxm_str =
             "nxm://SkyrimSE/mods/8850/files/27772?key=XnbXtdAspojLzUAn7x-Grw&expires=1583065790&user_id=1234321";
-        if let Err(ApiError::Expired) = NxmUrl::from_str(nxm_str) {
+        if let Err(ApiError::Expired) = NxmUrl::from_str(nxm_str)?.check_expiration() {
             return Ok(());
         }
         panic!("Nxm link should have expired");
     }
+
+    #[test]
+    fn free_user_nxm() -> Result<(), ApiError> {
+        let nxm_str =
+            "nxm://SkyrimSE/mods/8850/files/27772?key=XnbXtdAspojLzUAn7x-Grw&expires=4102444800&user_id=1234321";
+        let nxm = NxmUrl::from_str(nxm_str)?;
+        assert_eq!(nxm.domain_name, "skyrimspecialedition");
+        assert_eq!((nxm.mod_id, nxm.file_id), (8850, 27772));
+        assert_eq!(nxm.key.as_deref(), Some("XnbXtdAspojLzUAn7x-Grw"));
+        assert_eq!(nxm.expires, Some(4102444800));
+        assert_eq!(nxm.user_id, Some(1234321));
+        assert_eq!(nxm.query, "key=XnbXtdAspojLzUAn7x-Grw&expires=4102444800&user_id=1234321");
+        nxm.check_expiration()
+    }
+
+    #[test]
+    fn premium_nxm() -> Result<(), ApiError> {
+        let nxm = NxmUrl::from_str("nxm://morrowind/mods/46599/files/1000014198")?;
+        assert_eq!(nxm.domain_name, "morrowind");
+        assert_eq!((nxm.mod_id, nxm.file_id), (46599, 1000014198));
+        assert!(nxm.key.is_none() && nxm.expires.is_none());
+        assert_eq!(nxm.query, "");
+        nxm.check_expiration()
+    }
+
+    #[test]
+    fn malformed_nxm() {
+        for nxm_str in [
+            "nxm://SkyrimSE/mods/8850",
+            "nxm://SkyrimSE/mods/8850/files/",
+            "nxm://SkyrimSE/mods/abc/files/27772",
+            "nxm://SkyrimSE/files/27772/mods/8850",
+            "nxm://SkyrimSE/mods/8850/files/27772?key=XnbXtdAspojLzUAn7x-Grw",
+            "nxm://SkyrimSE/mods/8850/files/27772?key=XnbXtdAspojLzUAn7x-Grw&expires=soon",
+            "https://www.nexusmods.com/skyrimspecialedition/mods/8850",
+            "not a url",
+        ] {
+            assert!(NxmUrl::from_str(nxm_str).is_err(), "{nxm_str} should be invalid");
+        }
+    }
 }
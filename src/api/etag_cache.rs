@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/* The latest responses to API requests along with their ETags, by endpoint. Requests that have a cached response are
+ * sent with If-None-Match, and if the response hasn't changed, the Nexus answers with 304 Not Modified instead of
+ * sending it again. This mostly helps the update checker, which requests the same file lists over and over. The cache
+ * only lives as long as the program, since the responses that matter are already saved in the cache directory. */
+#[derive(Clone, Default)]
+pub struct EtagCache {
+    responses: Arc<RwLock<HashMap<String, CachedResponse>>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachedResponse {
+    pub etag: String,
+    pub body: String,
+}
+
+impl EtagCache {
+    pub fn get(&self, endpoint: &str) -> Option<CachedResponse> {
+        self.responses.read().unwrap().get(endpoint).cloned()
+    }
+
+    pub fn insert(&self, endpoint: &str, etag: String, body: String) {
+        self.responses.write().unwrap().insert(endpoint.to_string(), CachedResponse { etag, body });
+    }
+
+    // The responses depend on the API key and the game slug, so they're forgotten when those change
+    pub fn clear(&self) {
+        self.responses.write().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CachedResponse, EtagCache};
+
+    #[test]
+    fn cached_responses() {
+        let cache = EtagCache::default();
+        let endpoint = "games/morrowind/mods/46599/files.json";
+        assert_eq!(cache.get(endpoint), None);
+
+        cache.insert(endpoint, "W/\"abc\"".to_string(), "{}".to_string());
+        cache.clone().insert(endpoint, "W/\"def\"".to_string(), "{\"files\":[]}".to_string());
+        assert_eq!(
+            cache.get(endpoint),
+            Some(CachedResponse {
+                etag: "W/\"def\"".to_string(),
+                body: "{\"files\":[]}".to_string()
+            })
+        );
+
+        cache.clear();
+        assert_eq!(cache.get(endpoint), None);
+    }
+}
@@ -0,0 +1,97 @@
+use super::{ApiError, Client, FileList, Queriable};
+use crate::cache::{ArchiveState, Cache, LocalFile, UpdateStatus, VerificationState};
+use crate::util;
+
+use std::path::Path;
+
+use reqwest::StatusCode;
+
+/* Identifies mod files that were downloaded without dmodman, so that they can be managed like any other download.
+ * Each file is hashed and looked up with the Nexus md5 search, after which its metadata is written next to it the same
+ * way as for files downloaded through dmodman. */
+pub struct Importer {
+    cache: Cache,
+    client: Client,
+    game: String,
+}
+
+impl Importer {
+    pub fn new(cache: Cache, client: Client, game: &str) -> Self {
+        Self {
+            cache,
+            client,
+            game: game.to_string(),
+        }
+    }
+
+    // On success returns a description of the identified file, otherwise the reason it couldn't be imported
+    pub async fn import(&self, path: &Path) -> Result<String, String> {
+        self.import_hashed(path, &Self::hash(path).await?).await
+    }
+
+    // Hashing is done in a blocking thread, as it can take a while for large files
+    pub async fn hash(path: &Path) -> Result<String, String> {
+        util::md5sum(path.to_path_buf()).await.map_err(|e| format!("Unable to hash file: {e}"))
+    }
+
+    // For looking up the same file in several games without hashing it again
+    pub async fn import_hashed(&self, path: &Path, md5: &str) -> Result<String, String> {
+        let file_name = path.file_name().unwrap().to_string_lossy().into_owned();
+
+        let search = match self.client.md5_search(&self.game, md5).await {
+            Ok(search) => search,
+            Err(ApiError::ConnectionError { source }) if source.status() == Some(StatusCode::NOT_FOUND) => {
+                return Err(format!("Not found on the Nexus for {}", self.game));
+            }
+            Err(e) => return Err(format!("md5 search failed: {e}")),
+        };
+        // Prefer the result with the same file name in case several uploads have the same contents
+        let result = search
+            .results
+            .iter()
+            .filter(|res| res.file_details.md5 == md5)
+            .max_by_key(|res| res.file_details.file_name == file_name)
+            .ok_or_else(|| format!("Not found on the Nexus for {}", self.game))?;
+        let (mi, fd) = (&result.r#mod, &result.file_details);
+
+        if self.cache.file_index.file_id_map.read().await.contains_key(&fd.file_id) {
+            return Err(format!("Same file as the already managed {}", fd.file_name));
+        }
+
+        // The file index needs the file list to find the file's details
+        let has_file_details = self
+            .cache
+            .file_lists
+            .get((&self.game, mi.mod_id))
+            .await
+            .is_some_and(|fl| fl.files.iter().any(|f| f.file_id == fd.file_id));
+        if !has_file_details {
+            let file_list = FileList::request(&self.client, vec![&self.game, &mi.mod_id.to_string()])
+                .await
+                .map_err(|e| format!("Unable to query file list: {e}"))?;
+            self.cache
+                .save_file_list(&file_list, &self.game, mi.mod_id)
+                .await
+                .map_err(|e| format!("Unable to save file list: {e}"))?;
+        }
+        // Not needed for importing, but saves a request when showing the file's details
+        let _ = self.cache.save_mod_info(mi, &self.game, mi.mod_id).await;
+
+        // The update checker takes care of finding out whether there are newer files
+        let lf = LocalFile {
+            game: self.game.clone(),
+            file_name,
+            mod_id: mi.mod_id,
+            file_id: fd.file_id,
+            update_status: UpdateStatus::UpToDate(fd.uploaded_timestamp),
+            // Identified by its md5 sum
+            verification: VerificationState::Verified,
+            archive_state: ArchiveState::Untested,
+            installed_at: None,
+            deployed_files: vec![],
+        };
+        self.cache.save_local_file(lf).await.map_err(|e| format!("Unable to save metadata: {e}"))?;
+
+        Ok(format!("{} - {} {}", mi.name.clone().unwrap_or_default(), fd.name, fd.version.clone().unwrap_or_default()))
+    }
+}
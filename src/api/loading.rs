@@ -0,0 +1,54 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::util::changes;
+
+/* Counts the API operations in flight, so that the UI can show that something is loading. Each operation holds a
+ * LoadingGuard for as long as it runs. */
+#[derive(Clone, Default)]
+pub struct LoadingCounter {
+    count: Arc<AtomicUsize>,
+}
+
+impl LoadingCounter {
+    pub fn guard(&self) -> LoadingGuard {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        changes::notify();
+        LoadingGuard {
+            count: self.count.clone(),
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.count.load(Ordering::Relaxed) > 0
+    }
+}
+
+// Decrements the count when dropped, also when the operation fails or its task is aborted
+pub struct LoadingGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for LoadingGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::Relaxed);
+        changes::notify();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoadingCounter;
+
+    #[test]
+    fn loading_guards() {
+        let loading = LoadingCounter::default();
+        assert!(!loading.is_loading());
+        let first = loading.guard();
+        let second = loading.clone().guard();
+        drop(first);
+        assert!(loading.is_loading());
+        drop(second);
+        assert!(!loading.is_loading());
+    }
+}
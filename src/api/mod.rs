@@ -1,14 +1,23 @@
 pub mod api_error;
 pub mod client;
 pub mod downloads;
+pub mod etag_cache;
+pub mod importer;
+pub mod loading;
+pub mod network_error;
 pub mod query;
 pub mod request_counter;
 pub mod sso;
 pub mod update_checker;
+pub mod user_mod_sync;
 
 pub use api_error::*;
 pub use client::*;
 pub use downloads::*;
+pub use etag_cache::EtagCache;
+pub use importer::Importer;
+pub use loading::LoadingCounter;
 pub use query::*;
 pub use request_counter::RequestCounter;
 pub use update_checker::*;
+pub use user_mod_sync::UserModSync;
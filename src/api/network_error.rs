@@ -0,0 +1,83 @@
+use std::error::Error;
+use std::fmt;
+
+/* Tells apart the reasons why a request failed, so that the user can see whether the problem is on their end or the
+ * Nexus's, and so that only failures that might go away by themselves are retried.
+ * reqwest only recognizes some of these, so DNS and TLS errors are detected from the messages of the underlying
+ * errors. */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NetworkErrorKind {
+    Timeout,
+    Dns,
+    Tls,
+    Connect,
+    Status(u16),
+    Other,
+}
+
+impl NetworkErrorKind {
+    pub fn of(e: &reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return Self::Timeout;
+        }
+        if let Some(status) = e.status() {
+            return Self::Status(status.as_u16());
+        }
+        let cause = root_cause(e).to_lowercase();
+        if cause.contains("dns error") || cause.contains("failed to lookup address") {
+            Self::Dns
+        } else if cause.contains("certificate") || cause.contains("tls") || cause.contains("ssl") {
+            Self::Tls
+        } else if e.is_connect() {
+            Self::Connect
+        } else {
+            Self::Other
+        }
+    }
+
+    // Whether trying again later might succeed
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Timeout | Self::Dns | Self::Connect | Self::Other => true,
+            Self::Tls => false,
+            Self::Status(code) => *code == 429 || *code >= 500,
+        }
+    }
+}
+
+impl fmt::Display for NetworkErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("connection timed out"),
+            Self::Dns => f.write_str("DNS lookup failed"),
+            Self::Tls => f.write_str("TLS error"),
+            Self::Connect => f.write_str("unable to connect"),
+            Self::Status(code) => write!(f, "server responded with {code}"),
+            Self::Other => f.write_str("network error"),
+        }
+    }
+}
+
+// reqwest's own message only says which request failed, while the reason is in the innermost error
+pub fn root_cause(e: &reqwest::Error) -> String {
+    let mut cause: &dyn Error = e;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkErrorKind;
+
+    #[tokio::test]
+    async fn connection_refused() {
+        // Nothing listens on port 1, so the connection is refused right away
+        let e = reqwest::get("http://127.0.0.1:1/").await.unwrap_err();
+        assert_eq!(NetworkErrorKind::of(&e), NetworkErrorKind::Connect);
+        assert!(NetworkErrorKind::of(&e).is_transient());
+        assert!(!NetworkErrorKind::Status(404).is_transient());
+        assert!(NetworkErrorKind::Status(503).is_transient());
+    }
+}
@@ -0,0 +1,31 @@
+use super::Queriable;
+use serde::{Deserialize, Serialize};
+
+// The account that the API key belongs to. The response also has the key itself and the email, which aren't kept.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub user_id: u64,
+    pub name: String,
+    pub is_premium: bool,
+    pub is_supporter: bool,
+}
+
+impl Queriable for Account {
+    const FORMAT_STRING: &'static str = "users/validate.json";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Account;
+
+    #[test]
+    fn deserialize_account() {
+        let json = r#"{"user_id":1234,"key":"abc","name":"dmodman","is_premium?":false,"is_supporter?":true,
+            "email":"user@example.com","profile_url":"https://www.nexusmods.com/users/1234","is_supporter":true,
+            "is_premium":false}"#;
+        let account: Account = serde_json::from_str(json).unwrap();
+        assert_eq!((account.user_id, account.name.as_str()), (1234, "dmodman"));
+        assert!(!account.is_premium);
+        assert!(account.is_supporter);
+    }
+}
@@ -36,7 +36,7 @@ mod tests {
         let config = ConfigBuilder::default().profile(game).build().unwrap();
         let mod_id: u32 = 74484;
         let file_id: u64 = 1662417060;
-        let path = config.path_for(PathType::DownloadLink(game, &mod_id, &file_id));
+        let path = config.path_for(PathType::DownloadLink(game, &mod_id, &file_id)).await;
         let links = DownloadLink::load(path).await.unwrap();
         assert_eq!(links.locations.get(1).unwrap().short_name, "Amsterdam");
 
@@ -49,7 +49,7 @@ mod tests {
         let config = ConfigBuilder::default().profile(game).build().unwrap();
         let mod_id: u32 = 343;
         let file_id: u64 = 5801;
-        let path = config.path_for(PathType::DownloadLink(game, &mod_id, &file_id));
+        let path = config.path_for(PathType::DownloadLink(game, &mod_id, &file_id)).await;
         let links = DownloadLink::load(path).await.unwrap();
         assert_eq!(links.locations.first().unwrap().short_name, "Nexus CDN");
 
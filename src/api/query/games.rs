@@ -1,3 +1,4 @@
+use super::Queriable;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
@@ -5,27 +6,47 @@ pub struct Games {
     games: Vec<GameInfo>,
 }
 
-#[derive(Serialize, Deserialize)]
+// Only the name is used, to show games by their name instead of their domain name
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GameInfo {
-    id: u64,
-    name: String,
-    forum_url: String,
-    nexusmods_url: String,
-    genre: String,
-    file_count: u64,
-    downloads: u64,
-    domain_name: String,
-    approved_date: u64,
-    file_views: u64,
-    authors: u64,
-    file_endorsements: u64,
-    mods: u64,
-    categories: Vec<Category>,
+    pub id: u64,
+    pub name: String,
+    pub forum_url: String,
+    pub nexusmods_url: String,
+    pub genre: String,
+    pub file_count: u64,
+    pub downloads: u64,
+    pub domain_name: String,
+    pub approved_date: u64,
+    pub file_views: u64,
+    pub authors: u64,
+    pub file_endorsements: u64,
+    pub mods: u64,
+    pub categories: Vec<Category>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct Category {
-    category_id: u64,
-    name: String,
-    parent_category: bool,
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Category {
+    pub category_id: u64,
+    pub name: String,
+    pub parent_category: bool,
+}
+
+impl Queriable for GameInfo {
+    const FORMAT_STRING: &'static str = "games/{}.json";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GameInfo;
+
+    #[test]
+    fn deserialize_game_info() {
+        let json = r#"{"id": 100, "name": "Morrowind", "domain_name": "morrowind", "categories": [
+            {"category_id": 1, "name": "Morrowind", "parent_category": false}]}"#;
+        let gi: GameInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(gi.name, "Morrowind");
+        assert_eq!(gi.categories.len(), 1);
+    }
 }
@@ -1,3 +1,4 @@
+pub mod account;
 pub mod download_link;
 pub mod file_details;
 pub mod file_list;
@@ -6,7 +7,9 @@ pub mod md5_search;
 pub mod mod_info;
 pub mod queriable;
 pub mod search;
+pub mod user_mods;
 
+pub use self::account::*;
 pub use self::download_link::*;
 pub use self::file_details::*;
 pub use self::file_list::*;
@@ -15,3 +18,4 @@ pub use self::md5_search::*;
 pub use self::mod_info::*;
 pub use self::queriable::*;
 pub use self::search::*;
+pub use self::user_mods::*;
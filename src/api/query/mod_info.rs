@@ -2,7 +2,9 @@ use super::Queriable;
 use serde::{Deserialize, Serialize};
 
 // TODO several of these should probably be Options
-#[derive(Debug, Serialize, Deserialize)]
+// Hidden and removed mods lack most fields, which are then left to their default values
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ModInfo {
     pub name: Option<String>,
     pub summary: Option<String>,
@@ -12,6 +14,7 @@ pub struct ModInfo {
     pub game_id: u32,
     pub domain_name: String,
     pub category_id: u32,
+    pub endorsement_count: u32,
     pub version: String,
     pub created_timestamp: u64,
     pub created_time: String,
@@ -27,20 +30,27 @@ pub struct ModInfo {
     pub endorsement: Option<Endorsement>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Endorsement {
     pub endorse_status: String,
     pub timestamp: Option<u32>,
     pub version: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserInfo {
     pub member_group_id: u32,
     pub member_id: u32,
     pub name: String,
 }
 
+impl ModInfo {
+    // Mods can be hidden or removed by their authors or moderators, in which case most of the details are missing
+    pub fn is_available(&self) -> bool {
+        self.available && self.status == "published"
+    }
+}
+
 impl Queriable for ModInfo {
     const FORMAT_STRING: &'static str = "games/{}/mods/{}.json";
 }
@@ -3,24 +3,15 @@ use crate::api::Client;
 use crate::util::format;
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
-use tokio::task;
 
 #[async_trait]
 pub trait Queriable: DeserializeOwned {
     const FORMAT_STRING: &'static str;
 
-    /* TODO don't crash if server returns unexpected response, log response instead.
-     * Currently unimplemented because the UI is unable to wrap long messages. */
+    // Responses that don't match the expected format are returned as a SerializationError
     async fn request(client: &Client, params: Vec<&str>) -> Result<Self, ApiError> {
         let endpoint = format::vec_with_format_string(Self::FORMAT_STRING, params);
-        let resp = client.send_api_request(&endpoint).await?.error_for_status()?;
-        client.request_counter.push(resp.headers()).await;
-
-        task::spawn_blocking(
-            move || async move { Ok(serde_json::from_value::<Self>(resp.json().await.unwrap()).unwrap()) },
-        )
-        .await
-        .unwrap()
-        .await
+        let body = client.send_cached_api_request(&endpoint).await?;
+        Ok(serde_json::from_str(&body)?)
     }
 }
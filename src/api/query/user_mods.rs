@@ -0,0 +1,40 @@
+use super::Queriable;
+use serde::{Deserialize, Serialize};
+
+/* The user's endorsements and tracked mods across all games. These are requested once for the whole account, which is
+ * far cheaper than querying each mod when the user has thousands of them. */
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Endorsements {
+    pub results: Vec<UserEndorsement>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserEndorsement {
+    pub mod_id: u32,
+    pub domain_name: String,
+    pub date: Option<u64>,
+    pub version: Option<String>,
+    pub status: String, // "Endorsed", "Abstained" or "Undecided"
+}
+
+impl Queriable for Endorsements {
+    const FORMAT_STRING: &'static str = "user/endorsements.json";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TrackedMods {
+    pub results: Vec<TrackedMod>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackedMod {
+    pub mod_id: u32,
+    pub domain_name: String,
+}
+
+impl Queriable for TrackedMods {
+    const FORMAT_STRING: &'static str = "user/tracked_mods.json";
+}
@@ -1,8 +1,9 @@
 use reqwest::header::HeaderMap;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use crate::util::changes::ChangeFlag;
+
 #[derive(Debug, Default)]
 struct Counter {
     hourly_remaining: Option<u16>,
@@ -12,14 +13,14 @@ struct Counter {
 #[derive(Clone)]
 pub struct RequestCounter {
     counter: Arc<RwLock<Counter>>,
-    pub has_changed: Arc<AtomicBool>,
+    pub has_changed: ChangeFlag,
 }
 
 impl RequestCounter {
     pub fn new() -> Self {
         Self {
             counter: Arc::new(RwLock::new(Counter::default())),
-            has_changed: Arc::new(AtomicBool::from(false)),
+            has_changed: ChangeFlag::new(false),
         }
     }
 
@@ -32,15 +33,41 @@ impl RequestCounter {
         if let Some(value) = headers.get("x-rl-hourly-remaining") {
             counter.hourly_remaining = value.to_str().map_or(None, |v| str::parse::<u16>(v).ok());
         }
-        self.has_changed.store(true, Ordering::Relaxed);
+        self.has_changed.set();
+    }
+
+    /* Whether the remaining quota allows making this many requests. The hourly quota is only used once the daily one
+     * runs out, so either is enough. An unknown quota is assumed to be enough. */
+    pub async fn allows(&self, requests: usize) -> bool {
+        let counter = self.counter.read().await;
+        match (counter.hourly_remaining, counter.daily_remaining) {
+            (Some(hourly), Some(daily)) => usize::from(hourly.max(daily)) >= requests,
+            _ => true,
+        }
     }
 
-    pub async fn format(&self) -> String {
+    // The remaining (hourly, daily) API requests, as reported by the latest response
+    pub async fn remaining(&self) -> (Option<u16>, Option<u16>) {
         let counter = self.counter.read().await;
-        format!(
-            "Remaining | hourly: {} | daily: {}",
-            counter.hourly_remaining.map_or_else(|| "NA".to_string(), |i| i.to_string()),
-            counter.daily_remaining.map_or_else(|| "NA".to_string(), |i| i.to_string())
-        )
+        (counter.hourly_remaining, counter.daily_remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestCounter;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    #[tokio::test]
+    async fn quota_allows() {
+        let counter = RequestCounter::new();
+        assert!(counter.allows(1000).await);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rl-daily-remaining", HeaderValue::from_static("0"));
+        headers.insert("x-rl-hourly-remaining", HeaderValue::from_static("42"));
+        counter.push(&headers).await;
+        assert!(counter.allows(42).await);
+        assert!(!counter.allows(43).await);
     }
 }
@@ -1,15 +1,18 @@
 use super::ApiError;
-use super::{Client, FileList, FileUpdate, Queriable};
+use super::{Client, Downloads, FileList, FileUpdate, Queriable, QueueOutcome};
 use crate::cache::{Cache, Cacheable, FileData, UpdateStatus};
 use crate::config::PathType;
 use crate::Config;
 use crate::Logger;
 
 use std::collections::BinaryHeap;
-use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures_util::future::join_all;
+use tokio::sync::Mutex;
 use tokio::task;
+use tokio::time::{self, MissedTickBehavior};
 
 #[derive(Clone)]
 pub struct UpdateChecker {
@@ -17,6 +20,8 @@ pub struct UpdateChecker {
     client: Client,
     config: Config,
     logger: Logger,
+    // Held while checking all mods, so that the timer and the manual checks don't overlap
+    checking_all: Arc<Mutex<()>>,
 }
 
 impl UpdateChecker {
@@ -26,82 +31,241 @@ impl UpdateChecker {
             client,
             config,
             logger,
+            checking_all: Arc::new(Mutex::new(())),
         }
     }
 
-    pub async fn ignore_file(&self, i: usize) {
-        let f_lock = self.cache.file_index.files_sorted.read().await;
-        let fd = f_lock.get(i).unwrap();
-        let mut lf_lock = fd.local_file.write().await;
-        if let Some(latest_remote_file) =
-            self.cache.file_lists.get((&lf_lock.game, lf_lock.mod_id)).await.unwrap().file_updates.peek()
-        {
-            lf_lock.update_status = UpdateStatus::IgnoredUntil(latest_remote_file.uploaded_timestamp);
-
-            if let Err(e) = lf_lock.save(self.config.path_for(PathType::LocalFile(&lf_lock))).await {
-                self.logger.log(format!("Unable save ignore status for: {e}."));
+    /* Ignores updates to the file until the mod is updated again, or stops ignoring them if they already are. When
+     * updates are no longer ignored, the status is reset to the file's own upload time and checked again. */
+    pub async fn toggle_ignored(&self, i: usize) {
+        let (game, mod_id) = {
+            let f_lock = self.cache.file_index.files_sorted.read().await;
+            let fd = f_lock.get(i).unwrap();
+            let mut lf_lock = fd.local_file.write().await;
+            if let UpdateStatus::IgnoredUntil(_) = lf_lock.update_status {
+                lf_lock.update_status = UpdateStatus::UpToDate(fd.file_details.uploaded_timestamp);
+            } else if let Some(latest_remote_file) =
+                self.cache.file_lists.get((&lf_lock.game, lf_lock.mod_id)).await.unwrap().file_updates.peek()
+            {
+                lf_lock.update_status = UpdateStatus::IgnoredUntil(latest_remote_file.uploaded_timestamp);
+            } else {
+                return;
             }
-            self.cache.file_index.has_changed.store(true, Ordering::Relaxed);
-        }
+
+            if let Err(e) = lf_lock.save(self.config.path_for(PathType::LocalFile(&lf_lock)).await).await {
+                self.logger.error(format!("Unable save ignore status for: {e}."));
+            }
+            self.cache.file_index.has_changed.set();
+            match lf_lock.update_status {
+                UpdateStatus::IgnoredUntil(_) => return,
+                _ => (lf_lock.game.clone(), lf_lock.mod_id),
+            }
+        };
+        self.update_mod(game, mod_id).await;
+    }
+
+    // The version of the newest file in the update chain of the given file, based on the cached file list
+    pub async fn newest_version(&self, game: &str, mod_id: u32, file_id: u64) -> Option<String> {
+        let file_list = self.cache.file_lists.get((game, mod_id)).await?;
+        let newest = newest_in_chain(file_id, &file_list.file_updates)?;
+        file_list.files.iter().find(|f| f.file_id == newest.new_file_id).and_then(|f| f.version.clone())
+    }
+
+    // The game, mod id and the newest file in the update chain of the file at index i of files_sorted
+    pub async fn newest_file_for(&self, i: usize) -> Option<(String, u32, FileUpdate)> {
+        let fdata = self.cache.file_index.files_sorted.read().await.get(i)?.clone();
+        let (game, mod_id) = {
+            let lf = fdata.local_file.read().await;
+            (lf.game.clone(), lf.mod_id)
+        };
+        let file_list = self.cache.file_lists.get((&game, mod_id)).await?;
+        let newest = newest_in_chain(fdata.file_id, &file_list.file_updates)?;
+        Some((game, mod_id, newest))
     }
 
     pub async fn update_all(&self) {
-        let mods;
-        {
-            let lock = self.cache.file_index.mod_file_map.read().await;
-            mods = lock.clone().into_keys();
-        }
-        for (game, mod_id) in mods {
-            self.update_mod(game, mod_id).await;
-        }
-        self.logger.log("Finished checking updates.");
+        let me = self.clone();
+        task::spawn(async move {
+            let _guard = me.checking_all.lock().await;
+            let mods: Vec<(String, u32)> = me.cache.file_index.mod_file_map.read().await.keys().cloned().collect();
+            me.check_mods(&mods).await;
+            me.logger.log("Finished checking updates.");
+        });
     }
 
-    pub async fn update_mod(&self, game: String, mod_id: u32) {
+    /* Checks all mods for updates every interval while the program runs, starting one interval from now. Mods whose
+     * files all have their updates ignored are skipped. So is a whole round if another check is already running, or if
+     * the API request quota is running low. */
+    pub fn start_timer(&self, interval: Duration) {
         let me = self.clone();
         task::spawn(async move {
-            let lock = me.cache.file_index.mod_file_map.read().await;
-            let files = lock.get(&(game.to_owned(), mod_id)).unwrap();
-
-            let mut needs_refresh = false;
-            let mut checked: Vec<(Arc<FileData>, UpdateStatus)> = vec![];
-            /* First try to check updates with cached values.
-             * If the UpdateStatus is already OutOfDate or HasNewFile, there's no reason to query the API.
-             * Only query the API if a file is still reported as UpToDate.
-             */
-            if let Some(fl) = me.cache.file_lists.get((&game, mod_id)).await {
-                checked = me.check_mod(files, &fl).await;
-                for (_fdata, status) in &checked {
-                    if let UpdateStatus::UpToDate(_) = status {
-                        needs_refresh = true;
+            let mut timer = time::interval(interval);
+            timer.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            // The first tick completes immediately
+            timer.tick().await;
+            loop {
+                timer.tick().await;
+                let _guard = match me.checking_all.try_lock() {
+                    Ok(guard) => guard,
+                    Err(_) => {
+                        me.logger.debug("Skipping scheduled update check, another check is in progress.");
+                        continue;
                     }
+                };
+                let mods = me.mods_not_ignored().await;
+                if !me.client.request_counter.allows(mods.len()).await {
+                    me.logger.warn("Skipping scheduled update check, the API request quota is running low.");
+                    continue;
+                }
+                let found = me.check_mods(&mods).await;
+                if found > 0 {
+                    me.logger.log(format!("Scheduled update check found updates for {found} file(s)."));
                 }
-            } else {
-                me.logger.log(format!("Strange, no file list in cache for {mod_id}. Fetching."));
-                needs_refresh = true;
             }
-            if needs_refresh {
-                /* We only need to make one API request per mod, since the response contains info about all files in
-                 * that mod. */
-                match me.refresh_filelist(&game, mod_id).await {
-                    Ok(fl) => {
-                        checked = me.check_mod(files, &fl).await;
+        });
+    }
+
+    async fn mods_not_ignored(&self) -> Vec<(String, u32)> {
+        let lock = self.cache.file_index.mod_file_map.read().await;
+        let mut mods = vec![];
+        for (key, files) in lock.iter() {
+            for fdata in files {
+                if !matches!(fdata.local_file.read().await.update_status, UpdateStatus::IgnoredUntil(_)) {
+                    mods.push(key.clone());
+                    break;
+                }
+            }
+        }
+        mods
+    }
+
+    // Returns the number of files that were found to have updates
+    async fn check_mods(&self, mods: &[(String, u32)]) -> usize {
+        // Also covers the time between the requests, such as saving the file lists
+        let _loading = self.client.loading.guard();
+        join_all(mods.iter().map(|(game, mod_id)| self.check_updates(game, *mod_id))).await.into_iter().sum()
+    }
+
+    pub async fn update_mod(&self, game: String, mod_id: u32) {
+        let me = self.clone();
+        task::spawn(async move {
+            me.check_updates(&game, mod_id).await;
+        });
+    }
+
+    /* Checks updates for all mods and queues downloads of the newest versions of out of date files. Download links can
+     * only be generated through the API by premium users, others have to use the nxm:// links on the Nexus. */
+    pub async fn update_all_with_downloads(&self, downloads: Downloads) {
+        let me = self.clone();
+        task::spawn(async move {
+            let _guard = me.checking_all.lock().await;
+            let mods: Vec<(String, u32)> = me.cache.file_index.mod_file_map.read().await.keys().cloned().collect();
+            me.check_mods(&mods).await;
+
+            let files: Vec<Arc<FileData>> = me.cache.file_index.files_sorted.read().await.clone();
+            let mut queued = 0;
+            for fdata in files {
+                let (game, mod_id) = {
+                    let lf = fdata.local_file.read().await;
+                    match lf.update_status {
+                        UpdateStatus::OutOfDate(_) => (lf.game.clone(), lf.mod_id),
+                        _ => continue,
+                    }
+                };
+                let fd = &fdata.file_details;
+                let file_list = match me.cache.file_lists.get((&game, mod_id)).await {
+                    Some(fl) => fl,
+                    None => continue,
+                };
+                let newest = match newest_in_chain(fdata.file_id, &file_list.file_updates) {
+                    Some(upd) => upd,
+                    None => {
+                        me.logger.log(format!("No newer version of {} found in its update chain.", fd.name));
+                        continue;
                     }
+                };
+                if me.cache.file_index.file_id_map.read().await.contains_key(&newest.new_file_id) {
+                    continue;
+                }
+                let new_version = file_list
+                    .files
+                    .iter()
+                    .find(|f| f.file_id == newest.new_file_id)
+                    .and_then(|f| f.version.clone())
+                    .unwrap_or_default();
+
+                downloads.wait_for_free_slot().await;
+                match downloads.queue_file(&game, mod_id, newest.new_file_id, "").await {
+                    Ok(QueueOutcome::Queued) => {}
+                    Ok(QueueOutcome::Skipped) => continue,
                     Err(e) => {
-                        me.logger.log(format!("Error when refresh filelist for {mod_id}: {}", e));
+                        me.logger.error(format!(
+                            "Unable to queue update for {}: {e}. Downloading without a nxm:// link requires a premium \
+                             account.",
+                            fd.name
+                        ));
+                        break;
                     }
                 }
+                me.logger.log(format!(
+                    "Queued update for {}: v{} → v{new_version}",
+                    fd.name,
+                    fd.version.clone().unwrap_or_default()
+                ));
+                queued += 1;
+            }
+            me.logger.log(format!("Finished updating, queued {queued} download(s)."));
+        });
+    }
+
+    // Returns the number of files whose status changed to having updates
+    async fn check_updates(&self, game: &str, mod_id: u32) -> usize {
+        let lock = self.cache.file_index.mod_file_map.read().await;
+        let files = lock.get(&(game.to_owned(), mod_id)).unwrap();
+
+        let mut needs_refresh = false;
+        let mut checked: Vec<(Arc<FileData>, UpdateStatus)> = vec![];
+        /* First try to check updates with cached values.
+         * If the UpdateStatus is already OutOfDate or HasNewFile, there's no reason to query the API.
+         * Only query the API if a file is still reported as UpToDate.
+         */
+        if let Some(fl) = self.cache.file_lists.get((game, mod_id)).await {
+            checked = self.check_mod(files, &fl).await;
+            for (_fdata, status) in &checked {
+                if let UpdateStatus::UpToDate(_) = status {
+                    needs_refresh = true;
+                }
             }
-            for (file, new_status) in checked {
-                let mut lf = file.local_file.write().await;
-                if lf.update_status != new_status {
-                    me.logger.log(format!("Setting {} status to {:?}", file.file_details.name, new_status));
-                    lf.update_status = new_status;
-                    lf.save(me.config.path_for(PathType::LocalFile(&lf))).await.unwrap();
+        } else {
+            self.logger.log(format!("Strange, no file list in cache for {mod_id}. Fetching."));
+            needs_refresh = true;
+        }
+        if needs_refresh {
+            /* We only need to make one API request per mod, since the response contains info about all files in
+             * that mod. */
+            match self.refresh_filelist(game, mod_id).await {
+                Ok(fl) => {
+                    checked = self.check_mod(files, &fl).await;
+                }
+                Err(e) => {
+                    self.logger.error(format!("Error when refresh filelist for {mod_id}: {}", e));
                 }
             }
-            me.cache.file_index.has_changed.store(true, Ordering::Relaxed);
-        });
+        }
+        let mut found = 0;
+        for (file, new_status) in checked {
+            let mut lf = file.local_file.write().await;
+            if lf.update_status != new_status {
+                if let UpdateStatus::OutOfDate(_) | UpdateStatus::HasNewFile(_) = new_status {
+                    found += 1;
+                }
+                self.logger.debug(format!("Setting {} status to {:?}", file.file_details.name, new_status));
+                lf.update_status = new_status;
+                lf.save(self.config.path_for(PathType::LocalFile(&lf)).await).await.unwrap();
+            }
+        }
+        self.cache.file_index.has_changed.set();
+        found
     }
 
     async fn refresh_filelist(&self, game: &str, mod_id: u32) -> Result<FileList, ApiError> {
@@ -236,8 +400,24 @@ impl UpdateChecker {
     }
 }
 
+// Follows the update chain starting from file_id, returning the update to the newest version of the file
+fn newest_in_chain(file_id: u64, file_updates: &BinaryHeap<FileUpdate>) -> Option<FileUpdate> {
+    let updates = file_updates.clone().into_sorted_vec();
+    let mut newest: Option<&FileUpdate> = None;
+    let mut current = file_id;
+    // Each update is newer than the previous one, so the chain can't loop back on itself
+    for upd in &updates {
+        if upd.old_file_id == current {
+            current = upd.new_file_id;
+            newest = Some(upd);
+        }
+    }
+    newest.cloned()
+}
+
 #[cfg(test)]
 mod tests {
+    use super::newest_in_chain;
     use crate::api::{ApiError, Client, UpdateChecker};
     use crate::cache::Cache;
     use crate::cache::UpdateStatus;
@@ -251,7 +431,7 @@ mod tests {
         let config = ConfigBuilder::default().profile(game).build().unwrap();
 
         let cache = Cache::new(&config).await.unwrap();
-        let client = Client::new(&config).await;
+        let client = Client::new(&config).await?;
         let logger = Logger::default();
         let updater = UpdateChecker::new(cache.clone(), client, config, logger);
 
@@ -276,7 +456,7 @@ mod tests {
         let config = ConfigBuilder::default().profile(game).build().unwrap();
         let cache = Cache::new(&config).await?;
         let msgs = Logger::default();
-        let client = Client::new(&config).await;
+        let client = Client::new(&config).await?;
         let update = UpdateChecker::new(cache.clone(), client, config, msgs);
 
         let lock = cache.file_index.mod_file_map.read().await;
@@ -311,7 +491,7 @@ mod tests {
         let config = ConfigBuilder::default().profile(game).build().unwrap();
         let cache = Cache::new(&config).await?;
         let msgs = Logger::default();
-        let client = Client::new(&config).await;
+        let client = Client::new(&config).await?;
         let update = UpdateChecker::new(cache.clone(), client, config, msgs);
 
         let lock = cache.file_index.mod_file_map.read().await;
@@ -333,4 +513,20 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn update_chain() -> Result<(), ApiError> {
+        let game = "morrowind";
+        let mod_id = 46599;
+        let config = ConfigBuilder::default().profile(game).build().unwrap();
+        let cache = Cache::new(&config).await?;
+        let file_list = cache.file_lists.get((game, mod_id)).await.unwrap();
+
+        // 1000014198 -> 1000014225 -> 1000014314 -> 1000014601
+        let newest = newest_in_chain(1000014198, &file_list.file_updates).unwrap();
+        assert_eq!(newest.new_file_id, 1000014601);
+        assert_eq!(newest.old_file_id, 1000014314);
+        assert!(newest_in_chain(1000014601, &file_list.file_updates).is_none());
+        Ok(())
+    }
 }
@@ -0,0 +1,78 @@
+use super::Client;
+use crate::cache::{Cache, UserModLists};
+use crate::Logger;
+
+use tokio::task;
+
+/* Keeps the cached lists of endorsed and tracked mods in sync with the Nexus. Requests are made in the background so
+ * that they don't block the UI, and the results are reported in the log. */
+#[derive(Clone)]
+pub struct UserModSync {
+    cache: Cache,
+    client: Client,
+    logger: Logger,
+}
+
+impl UserModSync {
+    pub fn new(cache: Cache, client: Client, logger: Logger) -> Self {
+        Self { cache, client, logger }
+    }
+
+    pub fn refresh(&self) {
+        let me = self.clone();
+        task::spawn(async move {
+            let res = tokio::try_join!(me.client.endorsements(), me.client.tracked_mods());
+            match res {
+                Ok((endorsements, tracked_mods)) => {
+                    let lists = UserModLists::from_responses(endorsements, tracked_mods);
+                    me.logger.log(format!(
+                        "Found {} endorsed and {} tracked mods on the Nexus.",
+                        lists.endorsed.len(),
+                        lists.tracked.len()
+                    ));
+                    if let Err(e) = me.cache.user_mods.replace(lists).await {
+                        me.logger.error(format!("Unable to save endorsements and tracked mods: {e}"));
+                    }
+                }
+                Err(e) => me.logger.error(format!("Unable to fetch endorsements and tracked mods: {e}")),
+            }
+        });
+    }
+
+    // The version is that of the file the mod is endorsed from, which the Nexus shows next to the endorsement
+    pub fn toggle_endorsed(&self, game: String, mod_id: u32, version: String) {
+        let me = self.clone();
+        task::spawn(async move {
+            let endorse = !me.cache.user_mods.read().await.endorsed.contains(&(game.clone(), mod_id));
+            if let Err(e) = me.client.endorse(&game, mod_id, &version, endorse).await {
+                me.logger.error(format!("Unable to change endorsement of {game} mod {mod_id}: {e}"));
+                return;
+            }
+            let action = if endorse {
+                "Endorsed"
+            } else {
+                "Abstained from endorsing"
+            };
+            me.logger.log(format!("{action} {game} mod {mod_id}."));
+            if let Err(e) = me.cache.user_mods.set_endorsed((&game, mod_id), endorse).await {
+                me.logger.error(format!("Unable to save endorsements and tracked mods: {e}"));
+            }
+        });
+    }
+
+    pub fn toggle_tracked(&self, game: String, mod_id: u32) {
+        let me = self.clone();
+        task::spawn(async move {
+            let track = !me.cache.user_mods.read().await.tracked.contains(&(game.clone(), mod_id));
+            if let Err(e) = me.client.track(&game, mod_id, track).await {
+                me.logger.error(format!("Unable to change tracking of {game} mod {mod_id}: {e}"));
+                return;
+            }
+            let action = if track { "Tracking" } else { "Stopped tracking" };
+            me.logger.log(format!("{action} {game} mod {mod_id}."));
+            if let Err(e) = me.cache.user_mods.set_tracked((&game, mod_id), track).await {
+                me.logger.error(format!("Unable to save endorsements and tracked mods: {e}"));
+            }
+        });
+    }
+}
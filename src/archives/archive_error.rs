@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt;
+use std::process::ExitStatus;
+use tokio::io;
+use tokio::task::JoinError;
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    IOError {
+        source: io::Error,
+    },
+    JoinError {
+        source: JoinError,
+    },
+    // None of the programs that can extract this kind of archive are installed
+    NoExtractor {
+        programs: Vec<&'static str>,
+    },
+    ExtractorFailed {
+        program: &'static str,
+        status: ExitStatus,
+        stderr: String,
+    },
+    // The archive is encrypted, and no password or the wrong one was given
+    WrongPassword {
+        program: &'static str,
+    },
+}
+
+impl Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ArchiveError::IOError { ref source } => Some(source),
+            ArchiveError::JoinError { ref source } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveError::IOError { source } => source.fmt(f),
+            ArchiveError::JoinError { source } => source.fmt(f),
+            ArchiveError::NoExtractor { programs } if programs.is_empty() => f.write_str("Unsupported archive type."),
+            ArchiveError::NoExtractor { programs } => {
+                write!(f, "Extracting this archive requires one of: {}.", programs.join(", "))
+            }
+            ArchiveError::ExtractorFailed {
+                program,
+                status,
+                stderr,
+            } => {
+                write!(f, "{program} exited with {status}: {}", stderr.trim())
+            }
+            ArchiveError::WrongPassword { program } => {
+                write!(f, "{program} is unable to open the archive without the right password.")
+            }
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(error: io::Error) -> Self {
+        ArchiveError::IOError { source: error }
+    }
+}
+
+impl From<JoinError> for ArchiveError {
+    fn from(error: JoinError) -> Self {
+        ArchiveError::JoinError { source: error }
+    }
+}
@@ -0,0 +1,666 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use roxmltree::{Document, Node};
+
+/* FOMOD is the de facto standard format for mod installers. An archive with a fomod/ModuleConfig.xml file describes
+ * the files that are always installed, a number of install steps in which the user picks options (plugins) from
+ * groups, and files that are installed depending on the flags set by the chosen options.
+ * Schema: https://github.com/fomod-lang/fomod/blob/master/ModuleConfig.xsd
+ *
+ * Only flag dependencies are evaluated. File and game version dependencies can't be checked without knowing about the
+ * game installation, and are treated as satisfied. */
+
+pub const MODULE_CONFIG: &str = "fomod/moduleconfig.xml";
+
+#[derive(Debug)]
+pub struct ModuleConfig {
+    pub module_name: String,
+    pub required_files: Vec<FileItem>,
+    pub steps: Vec<InstallStep>,
+    pub conditional_installs: Vec<ConditionalInstall>,
+}
+
+#[derive(Debug)]
+pub struct InstallStep {
+    pub name: String,
+    pub visible: Option<Dependencies>,
+    pub groups: Vec<Group>,
+}
+
+#[derive(Debug)]
+pub struct Group {
+    pub name: String,
+    pub group_type: GroupType,
+    pub plugins: Vec<Plugin>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupType {
+    AtLeastOne,
+    AtMostOne,
+    ExactlyOne,
+    All,
+    Any,
+}
+
+#[derive(Debug)]
+pub struct Plugin {
+    pub name: String,
+    pub description: String,
+    pub files: Vec<FileItem>,
+    pub condition_flags: Vec<(String, String)>,
+    pub plugin_type: PluginType,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PluginType {
+    Required,
+    Optional,
+    Recommended,
+    NotUsable,
+    CouldBeUsable,
+}
+
+#[derive(Clone, Debug)]
+pub struct FileItem {
+    pub source: String,
+    pub destination: Option<String>,
+    pub is_folder: bool,
+    pub priority: i32,
+}
+
+#[derive(Debug)]
+pub struct Dependencies {
+    pub operator: Operator,
+    pub flags: Vec<(String, String)>,
+    pub nested: Vec<Dependencies>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Operator {
+    And,
+    Or,
+}
+
+#[derive(Debug)]
+pub struct ConditionalInstall {
+    pub dependencies: Dependencies,
+    pub files: Vec<FileItem>,
+}
+
+impl ModuleConfig {
+    pub fn parse(xml: &str) -> Result<Self, roxmltree::Error> {
+        let doc = Document::parse(xml)?;
+        let root = doc.root_element();
+
+        let module_name = child(root, "moduleName").and_then(|n| n.text()).unwrap_or_default().trim().to_string();
+        let required_files = child(root, "requiredInstallFiles").map(parse_files).unwrap_or_default();
+
+        let mut steps = vec![];
+        if let Some(steps_node) = child(root, "installSteps") {
+            for step in children(steps_node, "installStep") {
+                let mut groups = vec![];
+                if let Some(groups_node) = child(step, "optionalFileGroups") {
+                    for group in children(groups_node, "group") {
+                        let plugins = match child(group, "plugins") {
+                            Some(plugins_node) => {
+                                let mut plugins: Vec<Plugin> =
+                                    children(plugins_node, "plugin").map(parse_plugin).collect();
+                                sort_by_order(plugins_node, &mut plugins, |p| &p.name);
+                                plugins
+                            }
+                            None => vec![],
+                        };
+                        groups.push(Group {
+                            name: group.attribute("name").unwrap_or_default().to_string(),
+                            group_type: parse_group_type(group.attribute("type").unwrap_or_default()),
+                            plugins,
+                        });
+                    }
+                    sort_by_order(groups_node, &mut groups, |g| &g.name);
+                }
+                steps.push(InstallStep {
+                    name: step.attribute("name").unwrap_or_default().to_string(),
+                    visible: child(step, "visible").map(parse_dependencies),
+                    groups,
+                });
+            }
+            sort_by_order(steps_node, &mut steps, |s| &s.name);
+        }
+
+        let mut conditional_installs = vec![];
+        if let Some(patterns) = child(root, "conditionalFileInstalls").and_then(|n| child(n, "patterns")) {
+            for pattern in children(patterns, "pattern") {
+                if let Some(dependencies) = child(pattern, "dependencies") {
+                    conditional_installs.push(ConditionalInstall {
+                        dependencies: parse_dependencies(dependencies),
+                        files: child(pattern, "files").map(parse_files).unwrap_or_default(),
+                    });
+                }
+            }
+        }
+
+        Ok(Self {
+            module_name,
+            required_files,
+            steps,
+            conditional_installs,
+        })
+    }
+}
+
+impl Dependencies {
+    pub fn is_satisfied(&self, flags: &HashMap<String, String>) -> bool {
+        let mut results = self
+            .flags
+            .iter()
+            .map(|(flag, value)| flags.get(flag).map_or(value.is_empty(), |v| v == value))
+            .chain(self.nested.iter().map(|deps| deps.is_satisfied(flags)));
+        match self.operator {
+            Operator::And => results.all(|b| b),
+            Operator::Or => {
+                // An Or without any checkable dependencies shouldn't block the installation
+                if self.flags.is_empty() && self.nested.is_empty() {
+                    return true;
+                }
+                results.any(|b| b)
+            }
+        }
+    }
+}
+
+/* ModuleConfig.xml is usually UTF-8 or UTF-16 with a byte order mark, regardless of what the XML declaration says. */
+pub fn decode_xml(bytes: &[u8]) -> String {
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.is_element() && n.tag_name().name() == name)
+}
+
+fn children<'a, 'input: 'a>(node: Node<'a, 'input>, name: &'a str) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children().filter(move |n| n.is_element() && n.tag_name().name() == name)
+}
+
+// Lists of steps, groups and plugins are sorted alphabetically unless the order attribute says otherwise
+fn sort_by_order<T>(list_node: Node, items: &mut [T], name: fn(&T) -> &String) {
+    match list_node.attribute("order").unwrap_or("Ascending") {
+        "Explicit" => {}
+        "Descending" => items.sort_by(|a, b| cmp_names(name(b), name(a))),
+        _ => items.sort_by(|a, b| cmp_names(name(a), name(b))),
+    }
+}
+
+fn cmp_names(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+fn parse_files(node: Node) -> Vec<FileItem> {
+    node.children()
+        .filter(|n| n.is_element() && matches!(n.tag_name().name(), "file" | "folder"))
+        .map(|n| FileItem {
+            source: n.attribute("source").unwrap_or_default().to_string(),
+            destination: n.attribute("destination").map(|d| d.to_string()),
+            is_folder: n.tag_name().name() == "folder",
+            priority: n.attribute("priority").and_then(|p| p.parse().ok()).unwrap_or(0),
+        })
+        .collect()
+}
+
+fn parse_plugin(node: Node) -> Plugin {
+    let condition_flags = child(node, "conditionFlags")
+        .map(|flags| {
+            children(flags, "flag")
+                .map(|f| {
+                    let value = f.text().unwrap_or_default().trim().to_string();
+                    (f.attribute("name").unwrap_or_default().to_string(), value)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    /* The type is either given directly, or as a default type with patterns that depend on other mods being
+     * installed. Since those can't be checked, the default type is used. */
+    let plugin_type = child(node, "typeDescriptor")
+        .and_then(|td| {
+            child(td, "type").or_else(|| child(td, "dependencyType").and_then(|dt| child(dt, "defaultType")))
+        })
+        .and_then(|t| t.attribute("name"))
+        .map_or(PluginType::Optional, parse_plugin_type);
+
+    Plugin {
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        description: child(node, "description").and_then(|n| n.text()).unwrap_or_default().trim().to_string(),
+        files: child(node, "files").map(parse_files).unwrap_or_default(),
+        condition_flags,
+        plugin_type,
+    }
+}
+
+fn parse_dependencies(node: Node) -> Dependencies {
+    // <visible> can either contain the dependencies directly or wrap them in a <dependencies> element
+    let node = if node.tag_name().name() == "visible" {
+        child(node, "dependencies").unwrap_or(node)
+    } else {
+        node
+    };
+    let operator = match node.attribute("operator") {
+        Some("Or") => Operator::Or,
+        _ => Operator::And,
+    };
+    let flags = children(node, "flagDependency")
+        .map(|f| {
+            (f.attribute("flag").unwrap_or_default().to_string(), f.attribute("value").unwrap_or_default().to_string())
+        })
+        .collect();
+    let nested = children(node, "dependencies").map(parse_dependencies).collect();
+    Dependencies {
+        operator,
+        flags,
+        nested,
+    }
+}
+
+fn parse_group_type(group_type: &str) -> GroupType {
+    match group_type {
+        "SelectAtLeastOne" => GroupType::AtLeastOne,
+        "SelectAtMostOne" => GroupType::AtMostOne,
+        "SelectExactlyOne" => GroupType::ExactlyOne,
+        "SelectAll" => GroupType::All,
+        _ => GroupType::Any,
+    }
+}
+
+fn parse_plugin_type(plugin_type: &str) -> PluginType {
+    match plugin_type {
+        "Required" => PluginType::Required,
+        "Recommended" => PluginType::Recommended,
+        "NotUsable" => PluginType::NotUsable,
+        "CouldBeUsable" => PluginType::CouldBeUsable,
+        _ => PluginType::Optional,
+    }
+}
+
+/* Keeps track of the user's choices while going through the install steps. */
+pub struct FomodInstaller {
+    pub config: ModuleConfig,
+    pub step: usize,
+    // selected[step][group][plugin]
+    selected: Vec<Vec<Vec<bool>>>,
+}
+
+impl FomodInstaller {
+    pub fn new(config: ModuleConfig) -> Self {
+        let selected = config.steps.iter().map(|step| step.groups.iter().map(default_selection).collect()).collect();
+        let mut installer = Self {
+            config,
+            step: 0,
+            selected,
+        };
+        if !installer.config.steps.is_empty() && !installer.is_visible(0) {
+            installer.step = installer.next_visible(0).unwrap_or(installer.config.steps.len());
+        }
+        installer
+    }
+
+    pub fn has_steps(&self) -> bool {
+        self.step < self.config.steps.len()
+    }
+
+    pub fn current_step(&self) -> Option<&InstallStep> {
+        self.config.steps.get(self.step)
+    }
+
+    pub fn is_selected(&self, group: usize, plugin: usize) -> bool {
+        self.selected[self.step][group][plugin]
+    }
+
+    pub fn toggle(&mut self, group: usize, plugin: usize) {
+        let group_def = &self.config.steps[self.step].groups[group];
+        match group_def.plugins[plugin].plugin_type {
+            PluginType::Required | PluginType::NotUsable => return,
+            _ => {}
+        }
+        let selection = &mut self.selected[self.step][group];
+        match group_def.group_type {
+            GroupType::All => {}
+            GroupType::ExactlyOne => {
+                selection.iter_mut().for_each(|s| *s = false);
+                selection[plugin] = true;
+            }
+            GroupType::AtMostOne => {
+                let was_selected = selection[plugin];
+                selection.iter_mut().for_each(|s| *s = false);
+                selection[plugin] = !was_selected;
+            }
+            GroupType::AtLeastOne => {
+                if !selection[plugin] || selection.iter().filter(|s| **s).count() > 1 {
+                    selection[plugin] = !selection[plugin];
+                }
+            }
+            GroupType::Any => selection[plugin] = !selection[plugin],
+        }
+    }
+
+    /* Moves to the next visible step. Returns false when the current step was the last one, i.e. the installer is
+     * ready to install. */
+    pub fn next(&mut self) -> bool {
+        match self.next_visible(self.step) {
+            Some(step) => {
+                self.step = step;
+                true
+            }
+            None => {
+                self.step = self.config.steps.len();
+                false
+            }
+        }
+    }
+
+    pub fn previous(&mut self) -> bool {
+        match (0..self.step.min(self.config.steps.len())).rev().find(|i| self.is_visible(*i)) {
+            Some(step) => {
+                self.step = step;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // The flags set by the options selected up to, but not including, the given step
+    pub fn flags(&self, until_step: usize) -> HashMap<String, String> {
+        let mut flags = HashMap::new();
+        for (s, step) in self.config.steps.iter().enumerate().take(until_step) {
+            if !self.is_visible_with(s, &flags) {
+                continue;
+            }
+            for (g, group) in step.groups.iter().enumerate() {
+                for (p, plugin) in group.plugins.iter().enumerate() {
+                    if self.selected[s][g][p] {
+                        for (flag, value) in &plugin.condition_flags {
+                            flags.insert(flag.clone(), value.clone());
+                        }
+                    }
+                }
+            }
+        }
+        flags
+    }
+
+    // All files to be installed, in the order they should be copied. Files with a higher priority overwrite others.
+    pub fn files(&self) -> Vec<FileItem> {
+        let mut files = self.config.required_files.clone();
+        for (s, step) in self.config.steps.iter().enumerate() {
+            if !self.is_visible(s) {
+                continue;
+            }
+            for (g, group) in step.groups.iter().enumerate() {
+                for (p, plugin) in group.plugins.iter().enumerate() {
+                    if self.selected[s][g][p] {
+                        files.extend(plugin.files.iter().cloned());
+                    }
+                }
+            }
+        }
+        let flags = self.flags(self.config.steps.len());
+        for conditional in &self.config.conditional_installs {
+            if conditional.dependencies.is_satisfied(&flags) {
+                files.extend(conditional.files.iter().cloned());
+            }
+        }
+        // sort_by_key is stable, so files with equal priority keep their order
+        files.sort_by_key(|f| f.priority);
+        files
+    }
+
+    fn next_visible(&self, from: usize) -> Option<usize> {
+        (from + 1..self.config.steps.len()).find(|i| self.is_visible(*i))
+    }
+
+    fn is_visible(&self, step: usize) -> bool {
+        self.is_visible_with(step, &self.flags(step))
+    }
+
+    fn is_visible_with(&self, step: usize, flags: &HashMap<String, String>) -> bool {
+        match &self.config.steps[step].visible {
+            Some(deps) => deps.is_satisfied(flags),
+            None => true,
+        }
+    }
+}
+
+fn default_selection(group: &Group) -> Vec<bool> {
+    let mut selection: Vec<bool> = group
+        .plugins
+        .iter()
+        .map(|p| {
+            group.group_type == GroupType::All
+                || matches!(p.plugin_type, PluginType::Required | PluginType::Recommended)
+        })
+        .collect();
+    if matches!(group.group_type, GroupType::ExactlyOne | GroupType::AtMostOne) {
+        // Only keep the first preselected option
+        if let Some(first) = selection.iter().position(|s| *s) {
+            selection.iter_mut().skip(first + 1).for_each(|s| *s = false);
+        }
+    }
+    if matches!(group.group_type, GroupType::ExactlyOne | GroupType::AtLeastOne) && !selection.iter().any(|s| *s) {
+        if let Some(first) = group.plugins.iter().position(|p| p.plugin_type != PluginType::NotUsable) {
+            selection[first] = true;
+        }
+    }
+    selection
+}
+
+/* Copies the files chosen in the installer from the extracted archive to the destination directory.
+ * Paths in ModuleConfig.xml are relative to the directory containing the fomod directory, use backslashes and often
+ * don't match the case of the actual files. Entries with a path that leads outside of its directory are skipped, and
+ * returned along with the number of files that were copied. */
+pub fn install_files(src_root: &Path, dest_root: &Path, files: &[FileItem]) -> Result<(usize, Vec<String>), io::Error> {
+    let mut count = 0;
+    let mut skipped = vec![];
+    for item in files {
+        let destination = item.destination.as_deref().unwrap_or(&item.source);
+        let dest = match relative_path(destination) {
+            Some(components) if relative_path(&item.source).is_some() => {
+                dest_root.join(components.iter().collect::<PathBuf>())
+            }
+            _ => {
+                skipped.push(format!("{} -> {destination}", item.source));
+                continue;
+            }
+        };
+        let src = resolve_path(src_root, &item.source).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} doesn't exist in the archive", item.source))
+        })?;
+        if item.is_folder {
+            count += copy_dir(&src, &dest)?;
+        } else {
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&src, &dest)?;
+            count += 1;
+        }
+    }
+    Ok((count, skipped))
+}
+
+/* The components of a relative path from ModuleConfig.xml. Paths that could lead outside of the directory they're
+ * relative to, i.e. ones with a .. component, a leading separator or a drive prefix, are rejected, since a crafted
+ * installer could otherwise overwrite any file that the user can write to. */
+fn relative_path(path: &str) -> Option<Vec<&str>> {
+    if path.starts_with(['\\', '/']) {
+        return None;
+    }
+    let components: Vec<&str> = path.split(['\\', '/']).filter(|c| !c.is_empty() && *c != ".").collect();
+    let is_normal = |c: &&str| {
+        !c.contains(':') && matches!(Path::new(c).components().collect::<Vec<_>>().as_slice(), [Component::Normal(_)])
+    };
+    components.iter().all(is_normal).then_some(components)
+}
+
+fn resolve_path(root: &Path, rel_path: &str) -> Option<PathBuf> {
+    let mut path = root.to_path_buf();
+    for component in relative_path(rel_path)? {
+        let exact = path.join(component);
+        if exact.exists() {
+            path = exact;
+            continue;
+        }
+        let entry = fs::read_dir(&path)
+            .ok()?
+            .flatten()
+            .find(|entry| entry.file_name().to_string_lossy().eq_ignore_ascii_case(component))?;
+        path = entry.path();
+    }
+    Some(path)
+}
+
+fn copy_dir(src: &Path, dest: &Path) -> Result<usize, io::Error> {
+    let mut count = 0;
+    fs::create_dir_all(dest)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let dest_path = dest.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            count += copy_dir(&entry.path(), &dest_path)?;
+        } else {
+            fs::copy(entry.path(), dest_path)?;
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const XML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<config xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+    <moduleName>Test Mod</moduleName>
+    <requiredInstallFiles>
+        <folder source="Core" destination="" />
+    </requiredInstallFiles>
+    <installSteps order="Explicit">
+        <installStep name="Textures">
+            <optionalFileGroups>
+                <group name="Resolution" type="SelectExactlyOne">
+                    <plugins order="Explicit">
+                        <plugin name="2K">
+                            <description>Smaller textures</description>
+                            <files><folder source="Textures\2K" destination="textures" /></files>
+                            <conditionFlags><flag name="res">2k</flag></conditionFlags>
+                            <typeDescriptor><type name="Optional" /></typeDescriptor>
+                        </plugin>
+                        <plugin name="4K">
+                            <description>Bigger textures</description>
+                            <files><folder source="Textures\4K" destination="textures" /></files>
+                            <conditionFlags><flag name="res">4k</flag></conditionFlags>
+                            <typeDescriptor><type name="Recommended" /></typeDescriptor>
+                        </plugin>
+                    </plugins>
+                </group>
+            </optionalFileGroups>
+        </installStep>
+        <installStep name="4K extras">
+            <visible><flagDependency flag="res" value="4k" /></visible>
+            <optionalFileGroups>
+                <group name="Extras" type="SelectAny">
+                    <plugins>
+                        <plugin name="Normal maps">
+                            <description />
+                            <files><file source="extra\normals.dds" /></files>
+                            <typeDescriptor><type name="Optional" /></typeDescriptor>
+                        </plugin>
+                    </plugins>
+                </group>
+            </optionalFileGroups>
+        </installStep>
+    </installSteps>
+    <conditionalFileInstalls>
+        <patterns>
+            <pattern>
+                <dependencies operator="And"><flagDependency flag="res" value="2k" /></dependencies>
+                <files><file source="patch\2k.esp" destination="2k.esp" priority="1" /></files>
+            </pattern>
+        </patterns>
+    </conditionalFileInstalls>
+</config>"#;
+
+    #[test]
+    fn parse_and_select() {
+        let config = ModuleConfig::parse(XML).unwrap();
+        assert_eq!("Test Mod", config.module_name);
+        assert_eq!(2, config.steps.len());
+        assert_eq!(GroupType::ExactlyOne, config.steps[0].groups[0].group_type);
+
+        let mut installer = FomodInstaller::new(config);
+        // The recommended option is selected by default
+        assert!(!installer.is_selected(0, 0));
+        assert!(installer.is_selected(0, 1));
+        assert!(installer.next());
+        assert_eq!("4K extras", installer.current_step().unwrap().name);
+
+        // Selecting 2K hides the second step and enables the conditional install
+        installer.previous();
+        installer.toggle(0, 0);
+        assert!(!installer.is_selected(0, 1));
+        assert!(!installer.next());
+        let sources: Vec<String> = installer.files().into_iter().map(|f| f.source).collect();
+        assert_eq!(vec!["Core", "Textures\\2K", "patch\\2k.esp"], sources);
+    }
+
+    #[test]
+    fn reject_paths_outside_destination() {
+        let dir = std::env::temp_dir().join(format!("dmodman-fomod-{}", std::process::id()));
+        let (src, dest) = (dir.join("src"), dir.join("dest"));
+        std::fs::create_dir_all(src.join("Core")).unwrap();
+        std::fs::write(src.join("Core").join("Mod.esp"), b"").unwrap();
+        let item = |source: &str, destination: &str| FileItem {
+            source: source.to_string(),
+            destination: Some(destination.to_string()),
+            is_folder: false,
+            priority: 0,
+        };
+        let files = vec![
+            item("core\\mod.esp", "Mod.esp"),
+            item("Core\\Mod.esp", "../evil.esp"),
+            item("Core\\Mod.esp", "textures\\..\\..\\evil.esp"),
+            item("Core\\Mod.esp", "/tmp/evil.esp"),
+            item("..\\src\\Core\\Mod.esp", "Copy.esp"),
+        ];
+        let res = install_files(&src, &dest, &files);
+        let escaped = dir.join("evil.esp").exists();
+        let installed: Vec<_> = std::fs::read_dir(&dest).unwrap().flatten().map(|e| e.file_name()).collect();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (count, skipped) = res.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(skipped.len(), 4);
+        assert!(!escaped);
+        assert_eq!(installed, vec!["Mod.esp"]);
+    }
+
+    #[test]
+    fn decode_utf16_with_bom() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("<config/>".encode_utf16().flat_map(|c| c.to_le_bytes()));
+        assert_eq!("<config/>", decode_xml(&bytes));
+    }
+}
@@ -1,5 +1,19 @@
-use std::ffi::OsStr;
-use std::path::PathBuf;
+mod archive_error;
+mod fomod;
+mod mod_type;
+mod passwords;
+
+pub use archive_error::ArchiveError;
+pub use fomod::*;
+pub use mod_type::ModStructureType;
+use passwords::PasswordStore;
+
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
 
 use compress_tools::*;
 // This module mixes std and tokio fs, be mindful which one we're using
@@ -7,26 +21,70 @@ use std::fs::File;
 use tokio::fs;
 use tokio::fs::DirEntry;
 
-use crate::config::Config;
+use crate::api::{ApiError, Client};
+use crate::cache::{ArchiveState, Cache, Cacheable, FileData, VerificationState};
+use crate::config::{self, Config, PathType};
 use crate::logger::Logger;
+use crate::util;
+
+use reqwest::StatusCode;
+
+// A file or directory in an archive, as listed by Archives::list_contents()
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub is_dir: bool,
+    pub crc: Option<u32>, // computed while listing, None for directories
+    // The password of the archive, if it's encrypted and has been extracted with the password this session
+    pub password: Option<String>,
+}
+
+// Why test_and_extract() didn't extract an archive
+#[derive(Debug, PartialEq)]
+pub enum ExtractFailure {
+    Corrupt(Option<u64>), // the file id, if the archive has metadata
+    NeedsPassword,        // the archive is encrypted, and no password was saved for it or the given one is wrong
+}
 
 pub struct Archives {
     config: Config,
+    cache: Cache,
+    client: Client,
     logger: Logger,
     has_changed: bool,
     pub files: Vec<DirEntry>,
+    // The file ids of archives that failed their integrity test, until the user decides whether to download them again
+    corrupt_archives: Arc<Mutex<VecDeque<u64>>>,
+    // Encrypted archives that the user tried to extract, waiting for them to enter the password
+    password_requests: Arc<Mutex<VecDeque<PathBuf>>>,
+    // The passwords of the encrypted archives that have been extracted, by path
+    passwords: Arc<Mutex<HashMap<PathBuf, String>>>,
 }
 
 impl Archives {
-    pub fn new(config: Config, logger: Logger) -> Self {
+    pub fn new(config: Config, cache: Cache, client: Client, logger: Logger) -> Self {
         Self {
             config,
+            cache,
+            client,
             logger,
             has_changed: true,
             files: vec![],
+            corrupt_archives: Arc::new(Mutex::new(VecDeque::new())),
+            password_requests: Arc::new(Mutex::new(VecDeque::new())),
+            passwords: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    pub fn next_corrupt_archive(&self) -> Option<u64> {
+        self.corrupt_archives.lock().unwrap().pop_front()
+    }
+
+    pub fn next_password_request(&self) -> Option<PathBuf> {
+        self.password_requests.lock().unwrap().pop_front()
+    }
+
     pub fn swap_has_changed(&mut self) -> bool {
         let ret = self.has_changed;
         self.has_changed = false;
@@ -35,8 +93,13 @@ impl Archives {
 
     pub async fn list(&mut self) -> &Vec<DirEntry> {
         let mut ret: Vec<DirEntry> = vec![];
-        if let Ok(mut dir_entries) = fs::read_dir(self.config.download_dir()).await {
+        for dir in self.config.download_dirs() {
             // TODO log errors since this shouldn't fail
+            let mut dir_entries = if let Ok(dir_entries) = fs::read_dir(dir).await {
+                dir_entries
+            } else {
+                continue;
+            };
             while let Ok(Some(f)) = dir_entries.next_entry().await {
                 if f.path().is_file() {
                     let path = f.path();
@@ -52,17 +115,47 @@ impl Archives {
         &self.files
     }
 
-    pub async fn list_contents(&self, path: PathBuf) -> Result<Vec<String>> {
+    /* Lists the entries of the archive in the order they're stored. Paths use forward slashes, even if the archive was
+     * made on Windows. Listing reads through the whole archive, so the checksums of the files come at no extra cost. */
+    pub async fn list_contents(&self, path: PathBuf) -> Result<Vec<ArchiveEntry>> {
+        let password = self.passwords.lock().unwrap().get(&path).cloned();
         tokio::task::spawn_blocking(move || {
-            let mut file = File::open(path).unwrap();
-            list_archive_files(&mut file)
+            let mut entries: Vec<ArchiveEntry> = vec![];
+            let mut crc = 0;
+            for contents in ArchiveIterator::from_read(File::open(path)?)? {
+                match contents {
+                    ArchiveContents::StartOfEntry(name, stat) => {
+                        let name = name.replace('\\', "/");
+                        // S_IFMT and S_IFDIR, which some archivers set without a trailing slash on the name
+                        let is_dir = name.ends_with('/') || stat.st_mode & 0o170000 == 0o040000;
+                        entries.push(ArchiveEntry {
+                            path: PathBuf::from(name),
+                            size: stat.st_size.max(0) as u64,
+                            is_dir,
+                            crc: None,
+                            password: password.clone(),
+                        });
+                        crc = 0;
+                    }
+                    ArchiveContents::DataChunk(data) => crc = util::crc32_update(crc, &data),
+                    ArchiveContents::EndOfEntry => {
+                        if let Some(entry) = entries.last_mut().filter(|entry| !entry.is_dir) {
+                            entry.crc = Some(crc);
+                        }
+                    }
+                    ArchiveContents::Err(e) => return Err(e),
+                }
+            }
+            Ok(entries)
         })
         .await?
     }
 
-    pub async fn extract(&self, selected_index: usize, dest_dir_name: String) {
+    // Extracts the archive with libarchive. Used when installing archives that don't have a FOMOD installer.
+    pub async fn uncompress(&self, selected_index: usize, dest_dir_name: String) {
         let src_path = self.files.get(selected_index).unwrap().path();
-        let mut dest_path = self.config.download_dir();
+        // Extracted next to the archive, which may be in a per-game directory
+        let mut dest_path = src_path.parent().map(Path::to_path_buf).unwrap_or_else(|| self.config.download_dir());
 
         let logger = self.logger.clone();
         std::thread::spawn(move || match File::open(&src_path) {
@@ -74,13 +167,609 @@ impl Archives {
                         logger.log(format!("Finished extracting: {:?}", src_path.file_name().unwrap()));
                     }
                     Err(e) => {
-                        logger.log(format!("Extract failed with error: {:?}", e));
+                        logger.error(format!("Extract failed with error: {:?}", e));
                     }
                 }
             }
             Err(e) => {
-                logger.log(format!("Unable to extract: {src_path:?} {:?}", e));
+                logger.error(format!("Unable to extract: {src_path:?} {:?}", e));
             }
         });
     }
+
+    /* Extracts the selected archive with an external program, to where test_and_extract() puts it. The archive is first
+     * verified against the md5 sum on the Nexus, if it was downloaded from there, and then tested with the archiver. A
+     * corrupt archive isn't extracted, and if it's from the Nexus, the user is asked whether to download it again.
+     * Progress is reported in the log. The user is asked for the password of an encrypted archive, unless it has been
+     * saved. */
+    pub fn extract_selected(&self, selected_index: usize) {
+        let src_path = self.files.get(selected_index).unwrap().path();
+
+        let config = self.config.clone();
+        let cache = self.cache.clone();
+        let client = self.client.clone();
+        let logger = self.logger.clone();
+        let corrupt_archives = self.corrupt_archives.clone();
+        let password_requests = self.password_requests.clone();
+        let passwords = self.passwords.clone();
+        tokio::task::spawn(async move {
+            let file_name = src_path.file_name().unwrap().to_string_lossy().into_owned();
+            verify(&config, &cache, &client, &logger, &src_path, &file_name).await;
+            let res = Archives::test_and_extract(&config, &cache, &logger, &src_path, None).await;
+            handle_extract_result(res, src_path, &corrupt_archives, &password_requests, &passwords);
+        });
+    }
+
+    /* Extracts an encrypted archive with the password that the user entered, which is saved if it's the right one. If
+     * it's wrong, the user is asked again. */
+    pub fn extract_with_password(&self, src_path: PathBuf, password: String) {
+        let config = self.config.clone();
+        let cache = self.cache.clone();
+        let logger = self.logger.clone();
+        let corrupt_archives = self.corrupt_archives.clone();
+        let password_requests = self.password_requests.clone();
+        let passwords = self.passwords.clone();
+        tokio::task::spawn(async move {
+            let res = Archives::test_and_extract(&config, &cache, &logger, &src_path, Some(&password)).await;
+            handle_extract_result(res, src_path, &corrupt_archives, &password_requests, &passwords);
+        });
+    }
+
+    /* Tests the archive and extracts it into a directory named after it, next to it, or into the game's data directory if
+     * one is set in game_data_dirs. A corrupt archive isn't extracted, and the error has its file id if it has metadata,
+     * so that it can be downloaded again. Progress is reported in the log. Also used for downloads that are set to be
+     * extracted once they're finished.
+     * Encrypted archives are extracted with the given password, which is saved once the archive passes its test with it.
+     * Without one, the password saved for the archive is used, and if there's none, NeedsPassword is returned. Returns
+     * the password that the archive was extracted with, if it's encrypted. */
+    pub async fn test_and_extract(
+        config: &Config,
+        cache: &Cache,
+        logger: &Logger,
+        src_path: &Path,
+        password: Option<&str>,
+    ) -> std::result::Result<Option<String>, ExtractFailure> {
+        let file_name = src_path.file_name().unwrap().to_string_lossy().into_owned();
+        let dest_path = src_path.with_extension("");
+        let mut password = password.map(str::to_string);
+        let mut tested = Archives::test(src_path, password.as_deref()).await;
+        if password.is_none() && matches!(tested, Err(ArchiveError::WrongPassword { .. })) {
+            password = saved_password(config, logger, src_path).await;
+            if password.is_some() {
+                tested = Archives::test(src_path, password.as_deref()).await;
+            }
+        }
+        match tested {
+            Ok(program) => {
+                logger.log(format!("{file_name} passed the integrity test of {program}."));
+                set_archive_state(config, cache, logger, &file_name, ArchiveState::Intact).await;
+                if let Some(password) = &password {
+                    save_password(config, logger, src_path, password).await;
+                }
+            }
+            Err(ArchiveError::WrongPassword { .. }) if password.is_some() => {
+                logger.warn(format!("The password for {file_name} is wrong, not extracting it."));
+                return Err(ExtractFailure::NeedsPassword);
+            }
+            Err(ArchiveError::WrongPassword { .. }) => {
+                logger.warn(format!(
+                    "{file_name} is password protected. Extract it in the archives tab to enter the password."
+                ));
+                return Err(ExtractFailure::NeedsPassword);
+            }
+            Err(e @ ArchiveError::ExtractorFailed { .. }) => {
+                logger.error(format!("{file_name} is corrupt, not extracting it: {e}"));
+                let file_id = set_archive_state(config, cache, logger, &file_name, ArchiveState::Corrupt).await;
+                return Err(ExtractFailure::Corrupt(file_id));
+            }
+            // Extracting may still work, and reports its own errors if it doesn't
+            Err(e) => logger.warn(format!("Unable to test {file_name} before extracting it: {e}")),
+        }
+        if let Some(fdata) = cache.file_index.get_by_filename(&file_name).await {
+            let game = fdata.local_file.read().await.game.clone();
+            if let Some(data_dir) = config.data_dir_for_game(&game) {
+                deploy(config, cache, logger, &fdata, src_path, &data_dir, password.as_deref()).await;
+                return Ok(password);
+            }
+        }
+        logger.log(format!("Begin extracting {file_name} to {:?}", dest_path));
+        match Archives::extract(src_path, &dest_path, password.as_deref()).await {
+            Ok(program) => logger.log(format!("Finished extracting {file_name} with {program}.")),
+            Err(e) => logger.error(format!("Unable to extract {file_name}: {e}")),
+        }
+        Ok(password)
+    }
+
+    /* Extracts an archive with whichever suitable program is installed, trying them in the order returned by
+     * extractors_for(). These tend to support more formats and compression methods than libarchive, which is used when
+     * installing archives. Returns the name of the program that was used. */
+    pub async fn extract(
+        path: &Path,
+        dest: &Path,
+        password: Option<&str>,
+    ) -> std::result::Result<&'static str, ArchiveError> {
+        fs::create_dir_all(dest).await?;
+        run_first_installed(extractors_for(path, dest, password.is_some()), password).await
+    }
+
+    /* Runs the integrity test of the first installed program that can extract the archive, which checks the CRCs of the
+     * files in it. ExtractorFailed means that the archive is corrupt, and WrongPassword that it's encrypted and the
+     * password is missing or wrong. Returns the name of the program that was used. */
+    pub async fn test(path: &Path, password: Option<&str>) -> std::result::Result<&'static str, ArchiveError> {
+        run_first_installed(testers_for(path, password.is_some()), password).await
+    }
+
+    /* Looks for a fomod/ModuleConfig.xml in the archive. Returns the path of the directory containing the fomod
+     * directory, which the paths in the config are relative to, along with the parsed config.
+     * Errors are logged and None is returned, so that the archive can still be extracted as is. */
+    pub async fn read_fomod(&self, selected_index: usize) -> Option<(String, ModuleConfig)> {
+        let path = self.files.get(selected_index).unwrap().path();
+        let res = tokio::task::spawn_blocking(move || -> Result<Option<(String, Vec<u8>)>> {
+            let mut file = File::open(&path)?;
+            let config_path = list_archive_files(&mut file)?.into_iter().find(|f| {
+                let f = f.replace('\\', "/").to_lowercase();
+                f == MODULE_CONFIG || f.ends_with(&format!("/{MODULE_CONFIG}"))
+            });
+            match config_path {
+                Some(config_path) => {
+                    let root = config_path[..config_path.len() - MODULE_CONFIG.len()].to_string();
+                    let mut xml = vec![];
+                    uncompress_archive_file(File::open(&path)?, &mut xml, &config_path)?;
+                    Ok(Some((root, xml)))
+                }
+                None => Ok(None),
+            }
+        })
+        .await;
+
+        match res {
+            Ok(Ok(Some((root, xml)))) => match ModuleConfig::parse(&decode_xml(&xml)) {
+                Ok(config) => Some((root, config)),
+                Err(e) => {
+                    self.logger.warn(format!("Unable to parse ModuleConfig.xml, extracting as is: {e}"));
+                    None
+                }
+            },
+            Ok(Ok(None)) => None,
+            Ok(Err(e)) => {
+                self.logger.error(format!("Unable to read archive: {e:?}"));
+                None
+            }
+            Err(e) => {
+                self.logger.error(format!("Unable to read archive: {e:?}"));
+                None
+            }
+        }
+    }
+
+    /* Extracts the archive to a temporary directory and copies only the files selected in the FOMOD installer to the
+     * target directory. */
+    pub async fn install_fomod(
+        &self,
+        selected_index: usize,
+        dest_dir_name: String,
+        root: String,
+        files: Vec<FileItem>,
+    ) {
+        let src_path = self.files.get(selected_index).unwrap().path();
+        let dest_base = src_path.parent().map(Path::to_path_buf).unwrap_or_else(|| self.config.download_dir());
+        let dest_path = dest_base.join(&dest_dir_name);
+        let staging_path = dest_base.join(format!(".{dest_dir_name}.fomod"));
+
+        let logger = self.logger.clone();
+        std::thread::spawn(move || {
+            let file_name = src_path.file_name().unwrap().to_owned();
+            logger.log(format!("Begin installing: {:?}", file_name));
+            let res = File::open(&src_path)
+                .map_err(Error::from)
+                .and_then(|src_file| uncompress_archive(src_file, &staging_path, Ownership::Ignore))
+                .and_then(|()| install_files(&staging_path.join(&root), &dest_path, &files).map_err(Error::from));
+            match res {
+                Ok((count, skipped)) => {
+                    for entry in skipped {
+                        logger
+                            .warn(format!("Skipped {entry} in {file_name:?}, since it leads outside of {dest_path:?}"));
+                    }
+                    logger.log(format!("Finished installing {count} file(s) from {:?}", file_name));
+                }
+                Err(e) => {
+                    logger.error(format!("Install failed with error: {:?}", e));
+                }
+            }
+            if let Err(e) = std::fs::remove_dir_all(&staging_path) {
+                logger.warn(format!("Unable to remove temporary directory {staging_path:?}: {e}"));
+            }
+        });
+    }
+}
+
+// Queues the questions to the user about an archive that test_and_extract() didn't extract
+fn handle_extract_result(
+    res: std::result::Result<Option<String>, ExtractFailure>,
+    src_path: PathBuf,
+    corrupt_archives: &Mutex<VecDeque<u64>>,
+    password_requests: &Mutex<VecDeque<PathBuf>>,
+    passwords: &Mutex<HashMap<PathBuf, String>>,
+) {
+    match res {
+        Ok(Some(password)) => {
+            passwords.lock().unwrap().insert(src_path, password);
+            return;
+        }
+        Err(ExtractFailure::Corrupt(Some(file_id))) => corrupt_archives.lock().unwrap().push_back(file_id),
+        Err(ExtractFailure::NeedsPassword) => password_requests.lock().unwrap().push_back(src_path),
+        _ => return,
+    }
+    util::changes::notify();
+}
+
+/* Runs the programs in order until one of them is installed, and returns the name of the one that succeeded. Blocks
+ * in a separate thread, since archives can take a long time to process. The password is given to the programs on
+ * stdin, and archives that turn out to be encrypted get an empty one if there's none, which fails instead of waiting
+ * for input. */
+async fn run_first_installed(
+    commands: Vec<(&'static str, Vec<OsString>)>,
+    password: Option<&str>,
+) -> std::result::Result<&'static str, ArchiveError> {
+    let programs: Vec<&'static str> = commands.iter().map(|(program, _)| *program).collect();
+    let input = format!("{}\n", password.unwrap_or_default());
+    tokio::task::spawn_blocking(move || {
+        for (program, args) in commands {
+            match run_with_input(program, &args, &input) {
+                Ok(output) if output.status.success() => return Ok(program),
+                Ok(output) if asks_for_password(&output.stdout) || asks_for_password(&output.stderr) => {
+                    return Err(ArchiveError::WrongPassword { program })
+                }
+                Ok(output) => {
+                    return Err(ArchiveError::ExtractorFailed {
+                        program,
+                        status: output.status,
+                        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                    })
+                }
+                // Only try the next program if this one isn't installed
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(ArchiveError::NoExtractor { programs })
+    })
+    .await?
+}
+
+/* Runs the program with the input written to its stdin, which is how it's given the password of an encrypted archive,
+ * since the command line of a process can be read by other users. The program runs in a new session without a
+ * controlling terminal, so that it reads the password from stdin instead of the terminal that the UI is drawn on. */
+fn run_with_input(program: &str, args: &[OsString], input: &str) -> std::io::Result<Output> {
+    let mut command = Command::new(program);
+    command.args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        extern "C" {
+            fn setsid() -> i32;
+        }
+        // setsid() is async-signal-safe, so it can be called between fork and exec
+        unsafe {
+            command.pre_exec(|| {
+                setsid();
+                Ok(())
+            });
+        }
+    }
+    let mut child = command.spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Programs that don't need a password may exit without reading it
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    child.wait_with_output()
+}
+
+// Whether the output of an extractor says that the password of the archive is missing or wrong
+fn asks_for_password(output: &[u8]) -> bool {
+    let output = String::from_utf8_lossy(output).to_lowercase();
+    ["wrong password", "incorrect password", "password incorrect"].iter().any(|message| output.contains(message))
+}
+
+// The password saved for the archive when it was last extracted with one
+async fn saved_password(config: &Config, logger: &Logger, path: &Path) -> Option<String> {
+    let md5 = match util::md5sum(path.to_path_buf()).await {
+        Ok(md5) => md5,
+        Err(e) => {
+            logger.error(format!("Unable to compute checksum of {path:?}: {e}"));
+            return None;
+        }
+    };
+    match PasswordStore::new(&config::config_dir(), &config.cache_dir()).get(&md5) {
+        Ok(password) => password,
+        Err(e) => {
+            logger.error(format!("Unable to read the saved archive passwords: {e}"));
+            None
+        }
+    }
+}
+
+async fn save_password(config: &Config, logger: &Logger, path: &Path, password: &str) {
+    let md5 = match util::md5sum(path.to_path_buf()).await {
+        Ok(md5) => md5,
+        Err(e) => {
+            logger.error(format!("Unable to compute checksum of {path:?}: {e}"));
+            return;
+        }
+    };
+    if let Err(e) = PasswordStore::new(&config::config_dir(), &config.cache_dir()).insert(&md5, password) {
+        logger.error(format!("Unable to save the password of {path:?}: {e}"));
+    }
+}
+
+// Stores the result of the integrity test in the archive's metadata. Returns the file id if the archive has metadata.
+async fn set_archive_state(
+    config: &Config,
+    cache: &Cache,
+    logger: &Logger,
+    file_name: &str,
+    state: ArchiveState,
+) -> Option<u64> {
+    let fdata = cache.file_index.get_by_filename(file_name).await?;
+    let mut lf = fdata.local_file.write().await;
+    lf.archive_state = state;
+    if let Err(e) = lf.save(config.path_for(PathType::LocalFile(&lf)).await).await {
+        logger.error(format!("Unable to save the integrity test result of {file_name}: {e}"));
+    }
+    Some(fdata.file_id)
+}
+
+/* Extracts the archive into the game's data directory, unless that would overwrite files that aren't from another version
+ * of the same mod. The extracted files are stored in the archive's metadata, which is how the files in the data directory
+ * are known to belong to a mod. */
+async fn deploy(
+    config: &Config,
+    cache: &Cache,
+    logger: &Logger,
+    fdata: &FileData,
+    src_path: &Path,
+    data_dir: &Path,
+    password: Option<&str>,
+) {
+    let file_name = src_path.file_name().unwrap().to_string_lossy().into_owned();
+    let src = src_path.to_path_buf();
+    let files = match tokio::task::spawn_blocking(move || archive_files(&src)).await {
+        Ok(Ok(files)) => files,
+        Ok(Err(e)) => {
+            logger.error(format!("Unable to list the files in {file_name}: {e}"));
+            return;
+        }
+        Err(e) => {
+            logger.error(format!("Unable to list the files in {file_name}: {e}"));
+            return;
+        }
+    };
+    let (game, mod_id) = {
+        let lf = fdata.local_file.read().await;
+        (lf.game.clone(), lf.mod_id)
+    };
+    let mut owners: HashMap<String, (u32, String)> = HashMap::new();
+    for other in cache.file_index.files_sorted.read().await.iter().filter(|other| other.file_id != fdata.file_id) {
+        let lf = other.local_file.read().await;
+        if lf.game == game {
+            for path in &lf.deployed_files {
+                owners.insert(path.clone(), (lf.mod_id, lf.file_name.clone()));
+            }
+        }
+    }
+    let conflicts = find_conflicts(&files, data_dir, &owners, mod_id);
+    if !conflicts.is_empty() {
+        let shown = conflicts.iter().take(5).cloned().collect::<Vec<String>>().join(", ");
+        let more = if conflicts.len() > 5 {
+            format!(" and {} more", conflicts.len() - 5)
+        } else {
+            String::new()
+        };
+        logger.warn(format!(
+            "Not extracting {file_name} into {data_dir:?}, since it would overwrite {shown}{more}. Move the files away \
+            or remove the other mod first."
+        ));
+        return;
+    }
+
+    logger.log(format!("Begin extracting {file_name} to {:?}", data_dir));
+    match Archives::extract(src_path, data_dir, password).await {
+        Ok(program) => logger.log(format!("Finished extracting {file_name} with {program}.")),
+        Err(e) => {
+            logger.error(format!("Unable to extract {file_name}: {e}"));
+            return;
+        }
+    }
+    let mut lf = fdata.local_file.write().await;
+    lf.deployed_files = files;
+    if let Err(e) = lf.save(config.path_for(PathType::LocalFile(&lf)).await).await {
+        logger.error(format!("Unable to save the extracted files of {file_name}: {e}"));
+    }
+}
+
+// The paths of the files in the archive, without its directories, with forward slashes
+fn archive_files(path: &Path) -> Result<Vec<String>> {
+    let names = list_archive_files(File::open(path)?)?;
+    Ok(names
+        .into_iter()
+        .filter(|name| !name.ends_with('/') && !name.ends_with('\\'))
+        .map(|name| name.replace('\\', "/"))
+        .collect())
+}
+
+/* The files that extracting would overwrite in the data directory, unless they were extracted there from another file
+ * of the same mod, which is how updates replace the old version. Each is described along with where it came from. */
+fn find_conflicts(
+    files: &[String],
+    data_dir: &Path,
+    owners: &HashMap<String, (u32, String)>,
+    mod_id: u32,
+) -> Vec<String> {
+    files
+        .iter()
+        .filter(|path| data_dir.join(path).is_file())
+        .filter_map(|path| match owners.get(path) {
+            Some((owner_mod_id, _)) if *owner_mod_id == mod_id => None,
+            Some((_, owner)) => Some(format!("{path} (from {owner})")),
+            None => Some(format!("{path} (not extracted by dmodman)")),
+        })
+        .collect()
+}
+
+/* Checks the archive against the md5 sum that the Nexus has for it, and stores the result in its metadata. Archives that
+ * weren't downloaded from the Nexus are skipped. The hash is computed in a blocking task, so this doesn't stall the UI.
+ * A mismatch is only reported, since the archive may still be extractable. */
+async fn verify(config: &Config, cache: &Cache, client: &Client, logger: &Logger, path: &Path, file_name: &str) {
+    let fdata = match cache.file_index.get_by_filename(file_name).await {
+        Some(fdata) => fdata,
+        None => return,
+    };
+    let (game, file_id) = {
+        let lf = fdata.local_file.read().await;
+        (lf.game.clone(), lf.file_id)
+    };
+    let md5 = match util::md5sum(path.to_path_buf()).await {
+        Ok(md5) => md5,
+        Err(e) => {
+            logger.error(format!("Unable to compute checksum of {file_name}: {e}"));
+            return;
+        }
+    };
+    // The API responds with 404 if no file has this md5 sum
+    let state = match client.md5_search(&game, &md5).await {
+        Ok(search) if search.results.iter().any(|res| res.file_details.file_id == file_id) => {
+            VerificationState::Verified
+        }
+        Ok(_) => VerificationState::Mismatch,
+        Err(ApiError::ConnectionError { source }) if source.status() == Some(StatusCode::NOT_FOUND) => {
+            VerificationState::Mismatch
+        }
+        Err(e) => {
+            logger.warn(format!("Unable to verify {file_name}: {e}"));
+            return;
+        }
+    };
+    match state {
+        VerificationState::Verified => logger.log(format!("{file_name} matches its checksum on the Nexus.")),
+        _ => logger.error(format!("{file_name} doesn't match its checksum on the Nexus, it may be corrupted.")),
+    }
+
+    let mut lf = fdata.local_file.write().await;
+    lf.verification = state;
+    if let Err(e) = lf.save(config.path_for(PathType::LocalFile(&lf)).await).await {
+        logger.error(format!("Unable to save verification state of {file_name}: {e}"));
+    }
+}
+
+/* The programs that can extract the archive, in order of preference, along with their arguments. 7z handles all the
+ * formats, while unzip is a common fallback for zip files. Compressed tarballs are left to tar, since 7z would only
+ * decompress the outer layer. Encrypted archives need 7z, since unzip only reads passwords from a terminal. */
+fn extractors_for(path: &Path, dest: &Path, encrypted: bool) -> Vec<(&'static str, Vec<OsString>)> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    let is_zip = name.ends_with(".zip");
+    let is_tar = [".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.xz", ".tar.zst"].iter().any(|ext| name.ends_with(ext));
+    if !(is_zip || is_tar || name.ends_with(".7z") || name.ends_with(".rar")) {
+        return vec![];
+    }
+
+    let mut output_arg = OsString::from("-o");
+    output_arg.push(dest);
+    let mut extractors = vec![];
+    if is_tar {
+        extractors.push(("tar", vec!["-xf".into(), path.into(), "-C".into(), dest.into()]));
+    }
+    extractors.push(("7z", vec!["x".into(), "-y".into(), output_arg, path.into()]));
+    if is_zip && !encrypted {
+        extractors.push(("unzip", vec!["-o".into(), path.into(), "-d".into(), dest.into()]));
+    }
+    extractors
+}
+
+// The same programs as extractors_for(), in the same order, with the arguments that test the archive instead
+fn testers_for(path: &Path, encrypted: bool) -> Vec<(&'static str, Vec<OsString>)> {
+    extractors_for(path, Path::new(""), encrypted)
+        .into_iter()
+        .map(|(program, _)| {
+            let arg = match program {
+                "tar" => "-tf",
+                "7z" => "t",
+                _ => "-tq",
+            };
+            (program, vec![arg.into(), path.into()])
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{asks_for_password, extractors_for, find_conflicts, testers_for};
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    #[test]
+    fn deploy_conflicts() {
+        let data_dir = std::env::temp_dir().join(format!("dmodman-deploy-{}", std::process::id()));
+        std::fs::create_dir_all(data_dir.join("textures")).unwrap();
+        for file in ["old.esp", "other.esp", "vanilla.bsa"] {
+            std::fs::write(data_dir.join(file), b"").unwrap();
+        }
+        let owners = HashMap::from([
+            ("old.esp".to_string(), (1, "Mod-1-0.7z".to_string())),
+            ("other.esp".to_string(), (2, "Other-2-1-0.7z".to_string())),
+        ]);
+        let files: Vec<String> =
+            ["old.esp", "other.esp", "vanilla.bsa", "textures", "new.esp"].iter().map(|f| f.to_string()).collect();
+        let conflicts = find_conflicts(&files, &data_dir, &owners, 1);
+        std::fs::remove_dir_all(&data_dir).unwrap();
+
+        // Files from another version of the same mod are replaced, and directories are merged
+        assert_eq!(
+            conflicts,
+            vec![
+                "other.esp (from Other-2-1-0.7z)",
+                "vanilla.bsa (not extracted by dmodman)"
+            ]
+        );
+    }
+
+    #[test]
+    fn choose_extractors() {
+        let dest = Path::new("/tmp/dest");
+        let programs = |name: &str| -> Vec<&str> {
+            extractors_for(Path::new(name), dest, false).into_iter().map(|(program, _)| program).collect()
+        };
+        assert_eq!(programs("Mod-1-0.7z"), vec!["7z"]);
+        assert_eq!(programs("Mod-1-0.ZIP"), vec!["7z", "unzip"]);
+        assert_eq!(programs("Mod-1-0.tar.gz"), vec!["tar", "7z"]);
+        assert!(programs("Mod-1-0.exe").is_empty());
+
+        let (_, args) = extractors_for(Path::new("/dl/Mod.zip"), dest, false).remove(0);
+        assert_eq!(args, vec!["x", "-y", "-o/tmp/dest", "/dl/Mod.zip"]);
+
+        let testers = testers_for(Path::new("/dl/Mod.tar.gz"), false);
+        assert_eq!(
+            testers,
+            vec![
+                ("tar", vec!["-tf".into(), "/dl/Mod.tar.gz".into()]),
+                ("7z", vec!["t".into(), "/dl/Mod.tar.gz".into()])
+            ]
+        );
+        assert_eq!(
+            testers_for(Path::new("/dl/Mod.zip"), false)[1],
+            ("unzip", vec!["-tq".into(), "/dl/Mod.zip".into()])
+        );
+    }
+
+    #[test]
+    fn archive_passwords() {
+        // The password isn't on the command line, and unzip can't read it from stdin
+        let extractors = extractors_for(Path::new("/dl/Mod.zip"), Path::new("/tmp/dest"), true);
+        assert_eq!(extractors, vec![("7z", vec!["x".into(), "-y".into(), "-o/tmp/dest".into(), "/dl/Mod.zip".into()])]);
+        let testers = testers_for(Path::new("/dl/Mod.zip"), true);
+        assert_eq!(testers, vec![("7z", vec!["t".into(), "/dl/Mod.zip".into()])]);
+
+        assert!(asks_for_password(b"ERROR: Data Error in encrypted file. Wrong password? : Mod.esp"));
+        assert!(asks_for_password(b"   skipping: Mod.esp                incorrect password"));
+        assert!(!asks_for_password(b"ERROR: CRC Failed : Mod.esp"));
+    }
 }
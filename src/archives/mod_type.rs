@@ -0,0 +1,78 @@
+use std::fs::File;
+use std::path::Path;
+
+use compress_tools::list_archive_files;
+
+use super::Archives;
+
+// How the files in a mod archive are laid out, which decides where they need to go when the mod is installed
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ModStructureType {
+    BethesdaData, // has a Data or Data Files directory, so it's installed into the game directory
+    RootLevel,    // the files are at the top of the archive, so it's installed into the data directory as is
+    Unknown,      // empty, wrapped in a single directory, or laid out by a FOMOD installer
+}
+
+impl ModStructureType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::BethesdaData => "Data dir",
+            Self::RootLevel => "Root level",
+            Self::Unknown => "Unknown",
+        }
+    }
+}
+
+impl Archives {
+    /* Guesses the structure of the mod from the names at the top level of the archive. Only the list of files is read,
+     * which is fast even for large archives. Archives that can't be read are Unknown. */
+    pub fn detect_mod_type(path: &Path) -> ModStructureType {
+        match File::open(path).map(list_archive_files) {
+            Ok(Ok(names)) => mod_type_of(&names),
+            _ => ModStructureType::Unknown,
+        }
+    }
+}
+
+fn mod_type_of(names: &[String]) -> ModStructureType {
+    // The first component of each path, and whether it's a directory, i.e. has something below it
+    let mut top_level: Vec<(String, bool)> = vec![];
+    for name in names {
+        let name = name.replace('\\', "/");
+        let (first, rest) = name.split_once('/').unwrap_or((&name, ""));
+        if first.is_empty() {
+            continue;
+        }
+        let is_dir = !rest.is_empty() || name.ends_with('/');
+        match top_level.iter_mut().find(|(n, _)| n.eq_ignore_ascii_case(first)) {
+            Some((_, dir)) => *dir |= is_dir,
+            None => top_level.push((first.to_string(), is_dir)),
+        }
+    }
+    let has_dir = |wanted: &str| top_level.iter().any(|(name, is_dir)| *is_dir && name.eq_ignore_ascii_case(wanted));
+    if has_dir("data") || has_dir("data files") {
+        ModStructureType::BethesdaData
+    } else if has_dir("fomod") || top_level.is_empty() || matches!(top_level.as_slice(), [(_, true)]) {
+        ModStructureType::Unknown
+    } else {
+        ModStructureType::RootLevel
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{mod_type_of, ModStructureType};
+
+    #[test]
+    fn mod_types() {
+        let names = |names: &[&str]| -> Vec<String> { names.iter().map(|n| n.to_string()).collect() };
+        assert_eq!(mod_type_of(&names(&["Data/", "Data/Mod.esp", "readme.txt"])), ModStructureType::BethesdaData);
+        assert_eq!(mod_type_of(&names(&["Data Files\\meshes\\a.nif"])), ModStructureType::BethesdaData);
+        assert_eq!(mod_type_of(&names(&["Mod.esp", "textures/a.dds"])), ModStructureType::RootLevel);
+        assert_eq!(mod_type_of(&names(&["Mod 1.0/", "Mod 1.0/Mod.esp"])), ModStructureType::Unknown);
+        assert_eq!(mod_type_of(&names(&["fomod/ModuleConfig.xml", "00 Core/Mod.esp"])), ModStructureType::Unknown);
+        assert_eq!(mod_type_of(&[]), ModStructureType::Unknown);
+        // A file named like the data directory isn't one
+        assert_eq!(mod_type_of(&names(&["data", "Mod.esp"])), ModStructureType::RootLevel);
+    }
+}
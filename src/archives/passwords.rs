@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use age::secrecy::ExposeSecret;
+use age::x25519::Identity;
+
+const KEY_FILE: &str = "archive_passwords.key";
+const STORE_FILE: &str = "archive_passwords.age";
+
+/* The passwords of encrypted archives, by the md5 sum of the archive, so that a password only has to be entered once,
+ * even if the archive is renamed or downloaded again. The passwords are encrypted with age, using a key that's generated
+ * the first time a password is saved. The key is kept in the config directory, apart from the passwords in the data
+ * directory, and is only readable by the user. This keeps the passwords out of copies of the data directory, but not
+ * from anyone who can read the user's files. */
+pub struct PasswordStore {
+    key_path: PathBuf,
+    store_path: PathBuf,
+}
+
+impl PasswordStore {
+    pub fn new(key_dir: &Path, store_dir: &Path) -> Self {
+        Self {
+            key_path: key_dir.join(KEY_FILE),
+            store_path: store_dir.join(STORE_FILE),
+        }
+    }
+
+    pub fn get(&self, md5: &str) -> io::Result<Option<String>> {
+        Ok(self.read()?.remove(md5))
+    }
+
+    pub fn insert(&self, md5: &str, password: &str) -> io::Result<()> {
+        let mut passwords = self.read()?;
+        passwords.insert(md5.to_string(), password.to_string());
+        let identity = match self.identity()? {
+            Some(identity) => identity,
+            None => self.create_identity()?,
+        };
+        let json = serde_json::to_vec(&passwords).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        let encrypted =
+            age::encrypt(&identity.to_public(), &json).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        write_private(&self.store_path, &encrypted)
+    }
+
+    // Nothing has been saved yet if either the key or the store doesn't exist
+    fn read(&self) -> io::Result<HashMap<String, String>> {
+        let identity = match self.identity()? {
+            Some(identity) => identity,
+            None => return Ok(HashMap::new()),
+        };
+        let encrypted = match fs::read(&self.store_path) {
+            Ok(encrypted) => encrypted,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e),
+        };
+        let json = age::decrypt(&identity, &encrypted).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))?;
+        serde_json::from_slice(&json).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))
+    }
+
+    fn identity(&self) -> io::Result<Option<Identity>> {
+        match fs::read_to_string(&self.key_path) {
+            Ok(key) => Identity::from_str(key.trim()).map(Some).map_err(|e| io::Error::new(ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn create_identity(&self) -> io::Result<Identity> {
+        let identity = Identity::generate();
+        write_private(&self.key_path, identity.to_string().expose_secret().as_bytes())?;
+        Ok(identity)
+    }
+}
+
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    io::Write::write_all(&mut options.open(path)?, contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PasswordStore;
+
+    #[test]
+    fn store_passwords() {
+        let dir = std::env::temp_dir().join(format!("dmodman-passwords-{}", std::process::id()));
+        let (key_dir, store_dir) = (dir.join("config"), dir.join("data"));
+        let store = PasswordStore::new(&key_dir, &store_dir);
+        assert_eq!(store.get("abc").unwrap(), None);
+        store.insert("abc", "hunter2").unwrap();
+        store.insert("def", "correct horse").unwrap();
+        // The passwords aren't stored in plain text
+        let encrypted = std::fs::read(store_dir.join(super::STORE_FILE)).unwrap();
+        let found = encrypted.windows(7).any(|w| w == b"hunter2");
+        let key_is_apart = key_dir.join(super::KEY_FILE).exists() && !store_dir.join(super::KEY_FILE).exists();
+
+        let store = PasswordStore::new(&key_dir, &store_dir);
+        let (abc, def) = (store.get("abc").unwrap(), store.get("def").unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!found);
+        assert!(key_is_apart);
+        assert_eq!(abc.as_deref(), Some("hunter2"));
+        assert_eq!(def.as_deref(), Some("correct horse"));
+    }
+}
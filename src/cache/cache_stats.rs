@@ -0,0 +1,28 @@
+use std::time::{Duration, SystemTime};
+
+/* A summary of the downloaded files, shown in the bottom bar. Mods are counted per (game, mod_id), and the size is what
+ * the files take on disk. */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub files: usize,
+    pub mods: usize,
+    pub games: usize,
+    pub total_size: u64,
+    // When the newest file was written to the download directory
+    pub latest_install: Option<SystemTime>,
+}
+
+/* Disk usage of the downloaded files of one game. Superseded files are those the Nexus has marked as old versions or
+ * has newer versions of, which are the first to go when pruning. */
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GameUsage {
+    pub game: String,
+    pub files: usize,
+    pub size: u64,
+    pub superseded_size: u64,
+}
+
+impl CacheStats {
+    // How long the stats are reused before being computed again
+    pub const MAX_AGE: Duration = Duration::from_secs(5);
+}
@@ -1,6 +1,6 @@
 use crate::api::downloads::DownloadInfo;
 use crate::api::query::{DownloadLink, FileDetails, FileList, GameInfo, Md5Search, ModInfo};
-use crate::cache::LocalFile;
+use crate::cache::{LocalFile, UserModLists};
 use async_trait::async_trait;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -8,17 +8,33 @@ use tokio::io::{AsyncWriteExt, Error};
 use tokio::{fs, fs::File};
 
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Keeps the temporary files of saves to the same path apart
+static SAVE_COUNTER: AtomicU64 = AtomicU64::new(0);
 
 #[async_trait]
 pub trait Cacheable: Serialize + DeserializeOwned {
+    /* Writes to a temporary file next to the target, which is then renamed over it. Renaming is atomic, so a crash
+     * during the save leaves either the old or the new data, never a partial file. */
     async fn save(&self, path: PathBuf) -> Result<(), Error> {
         fs::create_dir_all(path.parent().unwrap().to_str().unwrap()).await?;
         let data = serde_json::to_string_pretty(&self)?;
-        let mut file = File::create(&path).await?;
-        file.write_all(data.as_bytes()).await?;
-        Ok(())
+        let tmp_path = tmp_path_for(&path);
+        let result = async {
+            let mut file = File::create(&tmp_path).await?;
+            file.write_all(data.as_bytes()).await?;
+            file.sync_all().await?;
+            fs::rename(&tmp_path, &path).await
+        }
+        .await;
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path).await;
+        }
+        result
     }
 
+    // Malformed JSON is reported as ErrorKind::InvalidData, or as UnexpectedEof if the file is cut short
     async fn load(path: PathBuf) -> Result<Self, Error> {
         tokio::task::spawn_blocking(move || async move { Ok(serde_json::from_str(&fs::read_to_string(&path).await?)?) })
             .await
@@ -27,6 +43,12 @@ pub trait Cacheable: Serialize + DeserializeOwned {
     }
 }
 
+// <file>.<n>.tmp, which Cache::clean_orphans() removes if a crash leaves it behind
+fn tmp_path_for(path: &std::path::Path) -> PathBuf {
+    let n = SAVE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!("{}.{n}.tmp", path.file_name().unwrap().to_string_lossy()))
+}
+
 impl Cacheable for DownloadInfo {}
 impl Cacheable for DownloadLink {}
 impl Cacheable for FileDetails {}
@@ -35,6 +57,7 @@ impl Cacheable for GameInfo {}
 impl Cacheable for LocalFile {}
 impl Cacheable for Md5Search {}
 impl Cacheable for ModInfo {}
+impl Cacheable for UserModLists {}
 
 #[cfg(test)]
 mod tests {
@@ -42,6 +65,7 @@ mod tests {
     use crate::cache::cacheable::Cacheable;
     use crate::config::ConfigBuilder;
     use crate::config::PathType;
+    use std::io::ErrorKind;
 
     #[tokio::test]
     async fn read_cached_mod_info() -> Result<(), ApiError> {
@@ -49,7 +73,7 @@ mod tests {
         let mod_id = 46599;
 
         let config = ConfigBuilder::load().unwrap().profile(game).build().unwrap();
-        let path = config.path_for(PathType::ModInfo(game, &mod_id));
+        let path = config.path_for(PathType::ModInfo(game, &mod_id)).await;
         println!("{:?}", path);
 
         let mi: ModInfo = ModInfo::load(path).await?;
@@ -63,7 +87,7 @@ mod tests {
         let mod_id = 46599;
 
         let config = ConfigBuilder::default().profile(game).build().unwrap();
-        let path = config.path_for(PathType::FileList(game, &mod_id));
+        let path = config.path_for(PathType::FileList(game, &mod_id)).await;
 
         let fl = FileList::load(path).await?;
         let mut upds = fl.file_updates.clone();
@@ -75,4 +99,24 @@ mod tests {
         assert_eq!(fl.file_updates.peek().unwrap().old_file_name, "GH TR - PT Meshes-46599-1-01-1556986716.7z");
         Ok(())
     }
+
+    #[tokio::test]
+    async fn save_and_load() -> Result<(), ApiError> {
+        let game = "morrowind";
+        let mod_id = 46599;
+        let config = ConfigBuilder::default().profile(game).build().unwrap();
+        let fl = FileList::load(config.path_for(PathType::FileList(game, &mod_id)).await).await?;
+
+        let dir = std::env::temp_dir().join(format!("dmodman-cacheable-{}", std::process::id()));
+        let path = dir.join("file_list.json");
+        fl.save(path.clone()).await?;
+        assert_eq!(FileList::load(path.clone()).await?.files.len(), fl.files.len());
+        // Only the saved file is left, without temporary files
+        assert_eq!(std::fs::read_dir(&dir)?.count(), 1);
+
+        std::fs::write(&path, "{\"files\": [")?;
+        assert_eq!(FileList::load(path).await.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
 }
@@ -1,17 +1,19 @@
-use super::{CacheError, Cacheable, FileData, FileLists, LocalFile};
-use crate::config::Config;
+use super::{push_load_error, remove_if_exists, CacheError, FileData, FileLists, LocalFile};
+use crate::config::{Config, PathType};
+use crate::logger::Logger;
+use crate::util::changes::ChangeFlag;
 
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
 use std::ffi::OsStr;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc,
-};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::UNIX_EPOCH;
 
 use std::fs;
-use tokio::sync::RwLock;
+use tokio::sync::{watch, RwLock};
 
 // Contains various data structures to efficiently look up FileData
 #[derive(Clone)]
@@ -24,65 +26,165 @@ pub struct FileIndex {
     // used by the UI
     pub files_sorted: Arc<RwLock<Vec<Arc<FileData>>>>,
     // should the list be re-rendered
-    pub has_changed: Arc<AtomicBool>,
+    pub has_changed: ChangeFlag,
     // reference to FileLists (which uses Arc internally)
     file_lists: FileLists,
+    // (loaded, total) while the metadata is being loaded in the background
+    load_progress: Arc<Mutex<Option<(usize, usize)>>>,
+    loaded: Arc<watch::Sender<bool>>,
+    /* Counts the reloads, so that a load that was still running when the index was reloaded stops adding the files of
+     * the old download directory. It's only changed while holding the locks of the maps and the progress. */
+    generation: Arc<AtomicUsize>,
+    // for the paths of the metadata files
+    config: Config,
 }
 
+// Enough files to keep a blocking thread busy for a moment, while the table still fills in quickly
+const LOAD_BATCH_SIZE: usize = 100;
+
 impl FileIndex {
-    pub async fn new(config: &Config, file_lists: FileLists) -> Result<Self, CacheError> {
-        // It's unexpected but possible that FileDetails is missing
-        let mut file_index: HashMap<u64, Arc<FileData>> = HashMap::new();
-        let mut mod_files: HashMap<(String, u32), BinaryHeap<Arc<FileData>>> = HashMap::new();
-        let mut files_sorted: Vec<Arc<FileData>> = vec![];
-
-        /* 1. Iterates through all <mod_file>.json files in the download directory for the current game, skipping those
-         *    where the corresponding <mod_file> is missing.
-         * 2. Serialize the json files into LocalFile's.
-         * 3. Use the file id to map each LocalFile to a FileDetails, stored in the FileData struct.
-         * 4. Store the FileData's in a timestamp-sorted binary heap because the update algorithm depends on it. */
-
-        // Sort files by creation time
-        let mut dir_entries: Vec<_> = match fs::read_dir(config.download_dir()) {
-            Ok(rd) => rd.map(|f| f.unwrap()).collect(),
-            Err(_) => vec![],
+    /* 1. Iterates through all <mod_file>.json files in the download directory and in the per-game directories,
+     *    skipping those where the corresponding <mod_file> is missing.
+     * 2. Serialize the json files into LocalFile's.
+     * 3. Use the file id to map each LocalFile to a FileDetails, stored in the FileData struct.
+     * 4. Store the FileData's in a timestamp-sorted binary heap because the update algorithm depends on it. */
+    pub async fn new(
+        config: &Config,
+        file_lists: FileLists,
+        load_errors: &mut Vec<String>,
+    ) -> Result<Self, CacheError> {
+        let file_index = Self::empty(config, file_lists);
+        for batch in files_with_metadata(config).chunks(LOAD_BATCH_SIZE) {
+            file_index.load_batch(batch.to_vec(), load_errors, 0).await;
+        }
+        file_index.loaded.send_replace(true);
+        Ok(file_index)
+    }
+
+    /* Returns an empty index right away, and loads the metadata in the background so that the UI can be shown while
+     * thousands of files are still being read. The files are parsed in batches on blocking threads, and each batch is
+     * added to the index as soon as it's done, in the same order as new() would. The progress is available from
+     * load_progress(), and errors are logged once all the files have been read. */
+    pub fn new_in_background(config: &Config, file_lists: FileLists, logger: Logger) -> Self {
+        let file_index = Self::empty(config, file_lists);
+        *file_index.load_progress.lock().unwrap() = Some((0, 0));
+        file_index.load_in_background(logger, 0);
+        file_index
+    }
+
+    /* Empties the index and loads it again in the background, for when download_dir is changed in the config. A load
+     * that's still running stops at its next batch. */
+    pub async fn reload(&self, logger: Logger) {
+        let generation = {
+            let mut files_lock = self.file_id_map.write().await;
+            let mut mf_lock = self.mod_file_map.write().await;
+            let mut fs_lock = self.files_sorted.write().await;
+            let mut progress = self.load_progress.lock().unwrap();
+            let generation = self.generation.fetch_add(1, Ordering::Relaxed) + 1;
+            files_lock.clear();
+            mf_lock.clear();
+            fs_lock.clear();
+            *progress = Some((0, 0));
+            self.loaded.send_replace(false);
+            generation
         };
-        dir_entries.sort_by_key(|f| match f.metadata() {
-            Ok(md) => md.created().unwrap(),
-            Err(_) => UNIX_EPOCH,
-        });
+        self.has_changed.set();
+        self.load_in_background(logger, generation);
+    }
 
-        for f in dir_entries {
-            if f.path().is_file() && f.path().extension().and_then(OsStr::to_str) != Some("json") {
-                let json_file = f.path().with_file_name(format!("{}.json", f.file_name().to_string_lossy()));
-                if let Ok(lf) = LocalFile::load(json_file).await {
-                    if let Some(file_list) = file_lists.get((&lf.game, lf.mod_id)).await {
-                        let file_details = file_list.files.iter().find(|fd| fd.file_id == lf.file_id).unwrap();
-                        let file_data = Arc::new(FileData::new(lf.clone(), file_details.clone()));
-                        file_index.insert(lf.file_id, file_data.clone());
-                        files_sorted.push(file_data.clone());
-                        match mod_files.get_mut(&(lf.game.to_string(), lf.mod_id)) {
-                            Some(heap) => {
-                                heap.push(file_data);
-                            }
-                            None => {
-                                let mut heap = BinaryHeap::new();
-                                heap.push(file_data);
-                                mod_files.insert((lf.game.to_string(), lf.mod_id), heap);
-                            }
-                        }
-                    }
+    fn load_in_background(&self, logger: Logger, generation: usize) {
+        let me = self.clone();
+        let config = self.config.clone();
+        tokio::task::spawn(async move {
+            let files = tokio::task::spawn_blocking(move || files_with_metadata(&config)).await.unwrap_or_default();
+            let mut load_errors = vec![];
+            let mut loaded = 0;
+            for batch in files.chunks(LOAD_BATCH_SIZE) {
+                if !me.load_batch(batch.to_vec(), &mut load_errors, generation).await {
+                    return;
+                }
+                loaded += batch.len();
+                let mut progress = me.load_progress.lock().unwrap();
+                if me.generation.load(Ordering::Relaxed) == generation {
+                    *progress = Some((loaded, files.len()));
                 }
             }
-        }
+            // Checked while holding the lock, since a reload may have started after the last batch
+            let _files_lock = me.file_id_map.write().await;
+            if me.generation.load(Ordering::Relaxed) != generation {
+                return;
+            }
+            for e in load_errors {
+                logger.warn(e);
+            }
+            *me.load_progress.lock().unwrap() = None;
+            me.has_changed.set();
+            me.loaded.send_replace(true);
+        });
+    }
 
-        Ok(Self {
-            file_id_map: Arc::new(RwLock::new(file_index)),
-            mod_file_map: Arc::new(RwLock::new(mod_files)),
-            files_sorted: Arc::new(RwLock::new(files_sorted)),
-            has_changed: Arc::new(AtomicBool::new(false)),
+    fn empty(config: &Config, file_lists: FileLists) -> Self {
+        Self {
+            file_id_map: Arc::new(RwLock::new(HashMap::new())),
+            mod_file_map: Arc::new(RwLock::new(HashMap::new())),
+            files_sorted: Arc::new(RwLock::new(vec![])),
+            has_changed: ChangeFlag::new(false),
+            load_progress: Arc::new(Mutex::new(None)),
+            loaded: Arc::new(watch::channel(false).0),
+            generation: Arc::new(AtomicUsize::new(0)),
             file_lists,
+            config: config.clone(),
+        }
+    }
+
+    // The (loaded, total) number of files while they're being loaded in the background, None once they're all loaded
+    pub fn load_progress(&self) -> Option<(usize, usize)> {
+        *self.load_progress.lock().unwrap()
+    }
+
+    pub async fn wait_until_loaded(&self) {
+        let _ = self.loaded.subscribe().wait_for(|loaded| *loaded).await;
+    }
+
+    /* Parses the metadata of the files on a blocking thread. Files that are already in the index are skipped, since a
+     * download may have added them while the index was being loaded in the background. Returns false without adding
+     * anything if the index has been reloaded since the load started. */
+    async fn load_batch(&self, batch: Vec<PathBuf>, load_errors: &mut Vec<String>, generation: usize) -> bool {
+        let parsed = tokio::task::spawn_blocking(move || {
+            batch.iter().map(|path| (metadata_path(path), read_local_file(path))).collect::<Vec<_>>()
         })
+        .await
+        .unwrap_or_default();
+
+        let mut files_lock = self.file_id_map.write().await;
+        let mut mf_lock = self.mod_file_map.write().await;
+        let mut fs_lock = self.files_sorted.write().await;
+        if self.generation.load(Ordering::Relaxed) != generation {
+            return false;
+        }
+        for (json_file, res) in parsed {
+            let lf = match res {
+                Ok(lf) => lf,
+                Err(e) => {
+                    push_load_error(load_errors, &json_file, e);
+                    continue;
+                }
+            };
+            if files_lock.contains_key(&lf.file_id) {
+                continue;
+            }
+            // It's unexpected but possible that FileDetails is missing
+            let file_details = match self.file_lists.filedetails_for(&lf).await {
+                Some(fd) => fd,
+                None => continue,
+            };
+            let file_data = Arc::new(FileData::new(lf.clone(), file_details));
+            files_lock.insert(lf.file_id, file_data.clone());
+            fs_lock.push(file_data.clone());
+            mf_lock.entry((lf.game, lf.mod_id)).or_insert_with(BinaryHeap::new).push(file_data);
+        }
+        self.has_changed.set();
+        true
     }
 
     pub async fn add(&self, lf: LocalFile) {
@@ -102,7 +204,33 @@ impl FileIndex {
             }
         }
         self.files_sorted.write().await.push(fdata);
-        self.has_changed.store(true, Ordering::Relaxed);
+        self.has_changed.set();
+    }
+
+    /* Removes the file from the index and deletes its metadata from disk, leaving the file itself in place. The metadata
+     * is deleted first, so that failing to do so leaves the index intact. */
+    pub async fn remove(&self, file_id: u64) -> Result<Option<LocalFile>, io::Error> {
+        let mut fs_lock = self.files_sorted.write().await;
+        let mut mf_lock = self.mod_file_map.write().await;
+        let mut files_lock = self.file_id_map.write().await;
+        let lf = match files_lock.get(&file_id) {
+            Some(fdata) => fdata.local_file.read().await.clone(),
+            None => return Ok(None),
+        };
+        remove_if_exists(&self.config.path_for(PathType::LocalFile(&lf)).await).await?;
+
+        files_lock.remove(&file_id);
+        fs_lock.retain(|fdata| fdata.file_id != file_id);
+        let key = (lf.game.to_owned(), lf.mod_id);
+        if let Some(heap) = mf_lock.get_mut(&key) {
+            heap.retain(|fdata| fdata.file_id != file_id);
+            if heap.is_empty() {
+                mf_lock.remove(&key);
+            }
+        }
+
+        self.has_changed.set();
+        Ok(Some(lf))
     }
 
     pub async fn get_by_filename(&self, name: &str) -> Option<Arc<FileData>> {
@@ -116,3 +244,39 @@ impl FileIndex {
         None
     }
 }
+
+// The downloaded files in the download directories, oldest first, which have a <file>.json next to them
+fn files_with_metadata(config: &Config) -> Vec<PathBuf> {
+    let mut dir_entries: Vec<_> = config
+        .download_dirs()
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|rd| rd.filter_map(|f| f.ok()))
+        .filter(|f| f.path().is_file() && f.path().extension().and_then(OsStr::to_str) != Some("json"))
+        .collect();
+    // Sort files by creation time
+    dir_entries.sort_by_key(|f| match f.metadata() {
+        Ok(md) => md.created().unwrap_or(UNIX_EPOCH),
+        Err(_) => UNIX_EPOCH,
+    });
+    dir_entries.into_iter().map(|f| f.path()).collect()
+}
+
+fn metadata_path(path: &Path) -> PathBuf {
+    path.with_file_name(format!("{}.json", path.file_name().unwrap_or_default().to_string_lossy()))
+}
+
+/* Files installed before the time was recorded get the modification time of the file, or that of its metadata file if
+ * the file itself can't be read. */
+fn read_local_file(path: &Path) -> io::Result<LocalFile> {
+    let mut lf: LocalFile = serde_json::from_str(&fs::read_to_string(metadata_path(path))?)?;
+    if lf.installed_at.is_none() {
+        lf.installed_at = fs::metadata(path)
+            .or_else(|_| fs::metadata(metadata_path(path)))
+            .and_then(|md| md.modified())
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+    }
+    Ok(lf)
+}
@@ -1,4 +1,4 @@
-use super::{CacheError, Cacheable, LocalFile};
+use super::{push_load_error, CacheError, Cacheable, LocalFile};
 use crate::api::{FileDetails, FileList};
 use crate::config::{paths, Config};
 use std::collections::HashMap;
@@ -15,7 +15,7 @@ pub struct FileLists {
 
 impl FileLists {
     // TODO read file lists from disk only on-demand, so we don't pointlessly deserialize data for other games
-    pub async fn new(config: &Config) -> Result<Self, CacheError> {
+    pub async fn new(config: &Config, load_errors: &mut Vec<String>) -> Result<Self, CacheError> {
         let mut file_lists: HashMap<(String, u32), FileList> = HashMap::new();
 
         fs::create_dir_all(config.cache_dir()).await?;
@@ -31,8 +31,11 @@ impl FileLists {
                     if f.path().is_file() && f.path().extension().and_then(OsStr::to_str) == Some("json") {
                         if let Some(filename) = f.path().file_stem() {
                             if let Ok(mod_id) = str::parse::<u32>(&filename.to_string_lossy()) {
-                                if let Ok(fl) = FileList::load(f.path()).await {
-                                    file_lists.insert((game_name.to_string_lossy().into_owned(), mod_id), fl);
+                                match FileList::load(f.path()).await {
+                                    Ok(fl) => {
+                                        file_lists.insert((game_name.to_string_lossy().into_owned(), mod_id), fl);
+                                    }
+                                    Err(e) => push_load_error(load_errors, &f.path(), e),
                                 }
                             }
                         }
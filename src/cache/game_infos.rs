@@ -0,0 +1,44 @@
+use super::Cacheable;
+use crate::api::GameInfo;
+use crate::config::{Config, PathType};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/* Game info is used for showing the names of the games instead of their domain names. Like mod info, it's read from
+ * disk the first time it's needed, and games without a game info file are remembered as None until it's fetched. */
+#[derive(Clone)]
+pub struct GameInfos {
+    config: Config,
+    map: Arc<RwLock<HashMap<String, Option<GameInfo>>>>,
+}
+
+impl GameInfos {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.clone(),
+            map: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn insert<S: Into<String>>(&self, game: S, value: GameInfo) {
+        self.map.write().await.insert(game.into(), Some(value));
+    }
+
+    pub async fn get(&self, game: &str) -> Option<GameInfo> {
+        if let Some(gi) = self.map.read().await.get(game) {
+            return gi.clone();
+        }
+        let gi = GameInfo::load(self.config.path_for(PathType::GameInfo(game)).await).await.ok();
+        self.map.write().await.insert(game.to_string(), gi.clone());
+        gi
+    }
+
+    // The name of the game, or its domain name if the game info hasn't been fetched
+    pub async fn name(&self, game: &str) -> String {
+        match self.get(game).await {
+            Some(gi) if !gi.name.is_empty() => gi.name,
+            _ => game.to_string(),
+        }
+    }
+}
@@ -1,5 +1,8 @@
 use crate::api::downloads::FileInfo;
+use crate::config::Config;
+use crate::util::opener;
 use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct LocalFile {
@@ -8,6 +11,17 @@ pub struct LocalFile {
     pub mod_id: u32,
     pub file_id: u64,
     pub update_status: UpdateStatus,
+    #[serde(default)]
+    pub verification: VerificationState,
+    #[serde(default)]
+    pub archive_state: ArchiveState,
+    /* Seconds since the Unix epoch. Files from older versions and imported files don't have this, in which case the
+     * modification time of the file is used when the file index is loaded. */
+    #[serde(default)]
+    pub installed_at: Option<u64>,
+    // The files extracted into the game's data directory, relative to it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deployed_files: Vec<String>,
 }
 
 impl LocalFile {
@@ -18,8 +32,21 @@ impl LocalFile {
             mod_id: fi.mod_id,
             file_id: fi.file_id,
             update_status,
+            verification: VerificationState::Unverified,
+            archive_state: ArchiveState::Untested,
+            installed_at: Some(SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())),
+            deployed_files: vec![],
         }
     }
+
+    /* Opens the directory that contains the file, rather than the archive itself, with xdg-open or the platform's own
+     * opener. The file_manager setting can be used to have the file selected in it. */
+    pub async fn open_in_file_manager(&self, config: &Config) -> Result<(), String> {
+        let path = config.file_path(&self.game, &self.file_name).await;
+        let dir = path.parent().unwrap_or(&path);
+        let (file, dir) = (path.to_string_lossy(), dir.to_string_lossy());
+        opener::open_in_file_manager(&file, &dir, config.file_manager.as_deref(), &config.open_command)
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
@@ -37,3 +64,21 @@ impl UpdateStatus {
         }
     }
 }
+
+// Whether the file's md5 sum has been checked against the one the Nexus has for it
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum VerificationState {
+    #[default]
+    Unverified,
+    Verified,
+    Mismatch,
+}
+
+// The result of the archive's own integrity test, which is run before extracting it
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub enum ArchiveState {
+    #[default]
+    Untested,
+    Intact,
+    Corrupt,
+}
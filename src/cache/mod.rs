@@ -1,30 +1,58 @@
 mod cache_error;
+mod cache_stats;
 mod cacheable;
 mod file_data;
 mod file_index;
 mod file_lists;
+mod game_infos;
 mod local_file;
+mod mod_infos;
+mod user_mods;
 pub use cache_error::*;
+pub use cache_stats::{CacheStats, GameUsage};
 pub use cacheable::*;
 pub use file_data::FileData;
 pub use file_index::*;
 pub use file_lists::*;
+pub use game_infos::GameInfos;
 pub use local_file::*;
+pub use mod_infos::*;
+pub use user_mods::*;
 
 //use self::{CacheError, Cacheable, FileIndex, FileListCache, LocalFile};
-use crate::api::{DownloadLink, FileList};
+use crate::api::downloads::DownloadInfo;
+use crate::api::{Client, DownloadLink, FileList, FileUpdate, GameInfo, Importer, ModInfo};
 use crate::config::{Config, PathType};
+use crate::logger::Logger;
+use crate::util;
 
 use tokio::fs;
 use tokio::io;
 
-use std::sync::atomic::Ordering;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::sync::RwLock;
+
+// How long a metadata save can take before its .tmp file counts as left behind by a crash
+const INTERRUPTED_SAVE_AGE: Duration = Duration::from_secs(600);
 
 #[derive(Clone)]
 pub struct Cache {
     pub file_lists: FileLists,
     pub file_index: FileIndex,
+    pub mod_infos: ModInfos,
+    pub game_infos: GameInfos,
+    pub user_mods: UserMods,
     config: Config,
+    stats: Arc<RwLock<Option<(Instant, CacheStats)>>>,
+    // Metadata files that couldn't be read on startup, to be logged once the logger is available
+    pub load_errors: Vec<String>,
 }
 
 impl Cache {
@@ -37,13 +65,40 @@ impl Cache {
      * - file_id        -> FileDetails
      */
     pub async fn new(config: &Config) -> Result<Self, CacheError> {
-        let file_lists = FileLists::new(config).await?;
-        let file_index = FileIndex::new(config, file_lists.clone()).await?;
+        let mut load_errors = vec![];
+        let file_lists = FileLists::new(config, &mut load_errors).await?;
+        let file_index = FileIndex::new(config, file_lists.clone(), &mut load_errors).await?;
+        let user_mods = UserMods::new(config, file_index.has_changed.clone(), &mut load_errors).await;
+
+        Ok(Self {
+            config: config.clone(),
+            file_lists,
+            file_index,
+            mod_infos: ModInfos::new(config),
+            game_infos: GameInfos::new(config),
+            user_mods,
+            stats: Arc::new(RwLock::new(None)),
+            load_errors,
+        })
+    }
+
+    /* Like new(), but the metadata of the downloaded files is loaded in the background, which takes a while with
+     * thousands of files. Used for the UI, which fills in the file table as they're loaded. */
+    pub async fn new_in_background(config: &Config, logger: &Logger) -> Result<Self, CacheError> {
+        let mut load_errors = vec![];
+        let file_lists = FileLists::new(config, &mut load_errors).await?;
+        let file_index = FileIndex::new_in_background(config, file_lists.clone(), logger.clone());
+        let user_mods = UserMods::new(config, file_index.has_changed.clone(), &mut load_errors).await;
 
         Ok(Self {
             config: config.clone(),
             file_lists,
             file_index,
+            mod_infos: ModInfos::new(config),
+            game_infos: GameInfos::new(config),
+            user_mods,
+            stats: Arc::new(RwLock::new(None)),
+            load_errors,
         })
     }
 
@@ -59,58 +114,501 @@ impl Cache {
         mod_id: &u32,
         file_id: &u64,
     ) -> Result<(), CacheError> {
-        let path = self.config.path_for(PathType::DownloadLink(game, mod_id, file_id));
+        let path = self.config.path_for(PathType::DownloadLink(game, mod_id, file_id)).await;
         dl.save(path).await?;
         Ok(())
     }
 
     pub async fn save_file_list(&self, fl: &FileList, game: &str, mod_id: u32) -> Result<(), CacheError> {
-        let path = self.config.path_for(PathType::FileList(game, &mod_id));
+        let path = self.config.path_for(PathType::FileList(game, &mod_id)).await;
         fl.save(path).await?;
         self.file_lists.insert((game, mod_id), fl.clone()).await;
         Ok(())
     }
 
+    pub async fn save_mod_info(&self, mi: &ModInfo, game: &str, mod_id: u32) -> Result<(), CacheError> {
+        // Kept in memory even if saving fails, since it's still usable for this session
+        self.mod_infos.insert((game, mod_id), mi.clone()).await;
+        let path = self.config.path_for(PathType::ModInfo(game, &mod_id)).await;
+        mi.save(path).await?;
+        Ok(())
+    }
+
+    pub async fn save_game_info(&self, gi: &GameInfo, game: &str) -> Result<(), CacheError> {
+        self.game_infos.insert(game, gi.clone()).await;
+        gi.save(self.config.path_for(PathType::GameInfo(game)).await).await?;
+        Ok(())
+    }
+
+    /* Fetches the game and mod info that's missing for the downloaded files, so that the file table can show their names
+     * instead of domain names and mod ids. Nothing is fetched if that would use up the remaining API request quota. */
+    pub async fn fetch_missing_names(&self, client: &Client, logger: &Logger) {
+        self.file_index.wait_until_loaded().await;
+        let mut games = BTreeSet::new();
+        let mut mods = BTreeSet::new();
+        for fdata in self.file_index.files_sorted.read().await.iter() {
+            let lf = fdata.local_file.read().await;
+            games.insert(lf.game.clone());
+            mods.insert((lf.game.clone(), lf.mod_id));
+        }
+        let mut missing_games = vec![];
+        for game in games {
+            if self.game_infos.get(&game).await.is_none() {
+                missing_games.push(game);
+            }
+        }
+        let mut missing_mods = vec![];
+        for (game, mod_id) in mods {
+            if self.mod_infos.get((&game, mod_id)).await.is_none() {
+                missing_mods.push((game, mod_id));
+            }
+        }
+        let requests = missing_games.len() + missing_mods.len();
+        if requests == 0 {
+            return;
+        }
+        if !client.request_counter.allows(requests).await {
+            logger.warn(format!("Not fetching the names of {requests} games and mods, the API request quota is low."));
+            return;
+        }
+
+        for game in missing_games {
+            match client.game_info(&game).await {
+                Ok(gi) => {
+                    if let Err(e) = self.save_game_info(&gi, &game).await {
+                        logger.warn(format!("Unable to save the game info of {game}: {e}"));
+                    }
+                }
+                Err(e) => logger.debug(format!("Unable to fetch the game info of {game}: {e}")),
+            }
+        }
+        // Deleted mods can't be fetched, and are shown by their id
+        for (game, mod_id) in missing_mods {
+            match client.mod_info(&game, mod_id).await {
+                Ok(mi) => {
+                    if let Err(e) = self.save_mod_info(&mi, &game, mod_id).await {
+                        logger.warn(format!("Unable to save mod info for {game} {mod_id}: {e}"));
+                    }
+                }
+                Err(e) => logger.debug(format!("Unable to fetch mod info for {game} {mod_id}: {e}")),
+            }
+        }
+        self.file_index.has_changed.set();
+    }
+
     pub async fn save_local_file(&self, lf: LocalFile) -> Result<(), io::Error> {
-        lf.save(self.config.path_for(PathType::LocalFile(&lf))).await?;
+        lf.save(self.config.path_for(PathType::LocalFile(&lf)).await).await?;
         self.file_index.add(lf).await;
         Ok(())
     }
 
-    // Delete a file and its metadata based on its index in file_index.files_sorted.
-    pub async fn delete_by_index(&self, i: usize) -> Result<(), io::Error> {
-        let mut fs_lock = self.file_index.files_sorted.write().await;
-        let mut mf_lock = self.file_index.mod_file_map.write().await;
-        let mut files_lock = self.file_index.file_id_map.write().await;
-        let fd = fs_lock.get(i).unwrap().clone();
-        let lf_lock = fd.local_file.write().await;
-        let id_to_delete = fs_lock.get(i).unwrap().file_id;
-
-        files_lock.remove(&id_to_delete);
+    /* Removes a file from the list based on its index in file_index.files_sorted. The file itself is only deleted if
+     * delete_archive is set, otherwise it's left in the download directory without metadata. */
+    pub async fn delete_by_index(&self, i: usize, delete_archive: bool) -> Result<(), io::Error> {
+        let fdata = match self.file_index.files_sorted.read().await.get(i) {
+            Some(fdata) => fdata.clone(),
+            None => return Ok(()),
+        };
+        self.delete(&fdata, delete_archive).await
+    }
 
-        fs_lock.remove(i);
+    pub async fn delete_by_file_id(&self, file_id: u64, delete_archive: bool) -> Result<(), io::Error> {
+        let fdata = match self.file_index.file_id_map.read().await.get(&file_id) {
+            Some(fdata) => fdata.clone(),
+            None => return Ok(()),
+        };
+        self.delete(&fdata, delete_archive).await
+    }
 
-        let heap = mf_lock.get_mut(&(lf_lock.game.to_owned(), lf_lock.mod_id)).unwrap();
-        heap.retain(|fdata| fdata.file_id != id_to_delete);
-        if heap.is_empty() {
-            mf_lock.remove(&(lf_lock.game.to_owned(), lf_lock.mod_id));
+    async fn delete(&self, fdata: &FileData, delete_archive: bool) -> Result<(), io::Error> {
+        /* The file itself is deleted first, so that failing to do so leaves the metadata and index intact. A remnant of
+         * an earlier download of the same file is removed along with it, unless it has a .part.json. Then it belongs to
+         * a download that's still in the download list, possibly running, since the .part.json is saved before the
+         * .part file is written to, and it's left for the download to finish or be deleted from the list. */
+        if delete_archive {
+            let lf = fdata.local_file.read().await.clone();
+            let file_name = lf.file_name;
+            let path = self.config.file_path(&lf.game, &file_name).await;
+            remove_if_exists(&path).await?;
+            let part_path = self.config.part_path(&lf.game, &file_name).await;
+            let dl_info_path = part_path.with_file_name(format!("{file_name}.part.json"));
+            if !fs::try_exists(&dl_info_path).await.unwrap_or(true) {
+                remove_if_exists(&part_path).await?;
+            }
         }
 
-        let mut path = self.config.path_for(PathType::LocalFile(&lf_lock));
-        fs::remove_file(&path).await?;
-        path.pop();
-        path.push(&lf_lock.file_name);
-        fs::remove_file(path).await?;
-        self.file_index.has_changed.store(true, Ordering::Relaxed);
+        self.file_index.remove(fdata.file_id).await?;
         Ok(())
     }
+
+    /* Stats about the downloaded files. Computing them reads the metadata of each file on disk, so the result is reused
+     * for a few seconds, which lets the UI call this on every refresh. */
+    pub async fn stats(&self) -> CacheStats {
+        if let Some((computed, stats)) = &*self.stats.read().await {
+            if computed.elapsed() < CacheStats::MAX_AGE {
+                return stats.clone();
+            }
+        }
+
+        let mut stats = CacheStats::default();
+        let mut mods = HashSet::new();
+        let mut games = HashSet::new();
+        for fdata in self.file_index.files_sorted.read().await.iter() {
+            let lf = fdata.local_file.read().await;
+            stats.files += 1;
+            mods.insert((lf.game.clone(), lf.mod_id));
+            games.insert(lf.game.clone());
+            if let Ok(md) = fs::metadata(self.config.file_path(&lf.game, &lf.file_name).await).await {
+                stats.total_size += md.len();
+                if let Ok(modified) = md.modified() {
+                    stats.latest_install = stats.latest_install.max(Some(modified));
+                }
+            }
+        }
+        stats.mods = mods.len();
+        stats.games = games.len();
+
+        *self.stats.write().await = Some((Instant::now(), stats.clone()));
+        stats
+    }
+
+    /* The disk usage of the download directory by game, sorted by game. Only files with metadata are counted, since
+     * files without it can't be attributed to a game. */
+    pub async fn disk_usage(&self) -> Vec<GameUsage> {
+        let superseded: HashSet<u64> =
+            self.superseded_files().await.into_iter().map(|(fdata, _)| fdata.file_id).collect();
+        let mut usage: BTreeMap<String, GameUsage> = BTreeMap::new();
+        for fdata in self.file_index.files_sorted.read().await.iter() {
+            let lf = fdata.local_file.read().await;
+            let size = match fs::metadata(self.config.file_path(&lf.game, &lf.file_name).await).await {
+                Ok(md) => md.len(),
+                Err(_) => continue,
+            };
+            let game = usage.entry(lf.game.clone()).or_insert_with(|| GameUsage {
+                game: lf.game.clone(),
+                ..Default::default()
+            });
+            game.files += 1;
+            game.size += size;
+            if superseded.contains(&fdata.file_id) {
+                game.superseded_size += size;
+            }
+        }
+        usage.into_values().collect()
+    }
+
+    /* Downloaded files that have a newer version in their update chain downloaded as well, along with their size on
+     * disk. Files that are only listed as old versions, or whose update isn't downloaded, are left out, since they're
+     * the only copy of the mod the user has. Sorted by upload time, oldest first, which is the order they're pruned in. */
+    pub async fn superseded_files(&self) -> Vec<(Arc<FileData>, u64)> {
+        let downloaded: HashSet<u64> = self.file_index.file_id_map.read().await.keys().copied().collect();
+        let mut superseded = vec![];
+        for fdata in self.file_index.files_sorted.read().await.iter() {
+            let lf = fdata.local_file.read().await.clone();
+            let has_downloaded_update = match self.file_lists.get((&lf.game, lf.mod_id)).await {
+                Some(fl) => newer_in_chain(fdata.file_id, &fl.file_updates).iter().any(|id| downloaded.contains(id)),
+                None => false,
+            };
+            if !has_downloaded_update {
+                continue;
+            }
+            if let Ok(md) = fs::metadata(self.config.file_path(&lf.game, &lf.file_name).await).await {
+                superseded.push((fdata.clone(), md.len()));
+            }
+        }
+        superseded.sort_by(|(a, _), (b, _)| a.cmp(b));
+        superseded
+    }
+
+    /* Downloaded files that a newer downloaded version of the same file makes redundant, keeping the newest `keep` of
+     * each. Unlike superseded_files() this doesn't depend on the Nexus, only on the versions that are on disk. Returns
+     * the files along with their size on disk, oldest first. */
+    pub async fn old_versions(&self, keep: usize) -> Vec<(Arc<FileData>, u64)> {
+        let files = self.file_index.files_sorted.read().await.clone();
+        let mut versions = vec![];
+        for fdata in &files {
+            let lf = fdata.local_file.read().await;
+            let fd = &fdata.file_details;
+            versions.push((lf.game.clone(), lf.mod_id, fd.name.clone(), fd.version.clone(), fd.uploaded_timestamp));
+        }
+        let versions: Vec<FileVersion> = versions
+            .iter()
+            .map(|(game, mod_id, name, version, uploaded)| {
+                (game.as_str(), *mod_id, name.as_str(), version.as_deref().unwrap_or_default(), *uploaded)
+            })
+            .collect();
+
+        let mut old = vec![];
+        for i in select_old_versions(&versions, keep) {
+            let fdata = &files[i];
+            let lf = fdata.local_file.read().await;
+            if let Ok(md) = fs::metadata(self.config.file_path(&lf.game, &lf.file_name).await).await {
+                old.push((fdata.clone(), md.len()));
+            }
+        }
+        old.sort_by(|(a, _), (b, _)| a.cmp(b));
+        old
+    }
+
+    // The size of the metadata cache, which isn't included in disk_usage()
+    pub fn metadata_size(&self) -> u64 {
+        dir_size(&self.config.cache_dir())
+    }
+
+    /* Scans the download directory for files left behind by failed downloads and deletions:
+     * - <file>.json metadata whose <file> no longer exists
+     * - <file>.part.json download state whose <file>.part no longer exists
+     * - <file>.part files without the <file>.part.json needed to resume them
+     * - <file>.<n>.tmp files from metadata saves that were interrupted, once they're old enough that no save is still
+     *   writing them
+     * Only .json files that dmodman wrote are deleted, so the .json files that come with mods are left alone, as are
+     * mod files without metadata, since they might be the only copy the user has. The <file>.tmp files of downloads
+     * that are being moved into place don't match either. Returns the paths of the deleted files. */
+    pub async fn clean_orphans(&self) -> Result<Vec<PathBuf>, io::Error> {
+        let mut removed = vec![];
+        for f in download_dir_entries(&self.config).await? {
+            let path = f.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = f.file_name().to_string_lossy().into_owned();
+            let is_orphan = if let Some(part_name) = file_name.strip_suffix(".json") {
+                // Covers both <file>.json and <file>.part.json
+                !path.with_file_name(part_name).exists() && is_metadata(&path).await
+            } else if file_name.ends_with(".part") {
+                !path.with_file_name(format!("{}.json", file_name)).exists()
+            } else {
+                is_interrupted_save(&f).await
+            };
+            if is_orphan && remove_if_exists(&path).await? {
+                removed.push(path);
+            }
+        }
+        Ok(removed)
+    }
+
+    /* Recreates the metadata of archives whose metadata is missing or can't be read, by looking up their md5 sums on the
+     * Nexus. The md5 search needs a game, so the default game and the games of the other files are tried in turn.
+     * Returns the result of each archive, which is either a description of the file or why it couldn't be identified. */
+    pub async fn rebuild_index(&self, client: &Client) -> Vec<(PathBuf, Result<String, String>)> {
+        let mut games: Vec<String> = self.config.default_game.iter().cloned().collect();
+        let mut other_games = BTreeSet::new();
+        for fdata in self.file_index.files_sorted.read().await.iter() {
+            other_games.insert(fdata.local_file.read().await.game.clone());
+        }
+        games.extend(other_games.into_iter().filter(|game| Some(game) != self.config.default_game.as_ref()));
+
+        let mut results = vec![];
+        for path in self.unindexed_archives().await {
+            if games.is_empty() {
+                results.push((path, Err("No game to look it up in, set default_game in the config.".to_string())));
+                continue;
+            }
+            let md5 = match Importer::hash(&path).await {
+                Ok(md5) => md5,
+                Err(e) => {
+                    results.push((path, Err(e)));
+                    continue;
+                }
+            };
+            let mut result = Err(String::new());
+            for game in &games {
+                result = Importer::new(self.clone(), client.clone(), game).import_hashed(&path, &md5).await;
+                if result.is_ok() {
+                    break;
+                }
+            }
+            results.push((path, result));
+        }
+        results
+    }
+
+    // Archives in the download directory without metadata, or whose metadata is corrupt
+    async fn unindexed_archives(&self) -> Vec<PathBuf> {
+        let mut archives = vec![];
+        for f in download_dir_entries(&self.config).await.unwrap_or_default() {
+            let path = f.path();
+            if !path.is_file() || !is_archive(&path) {
+                continue;
+            }
+            let json_file = path.with_file_name(format!("{}.json", f.file_name().to_string_lossy()));
+            if LocalFile::load(json_file).await.is_err() {
+                archives.push(path);
+            }
+        }
+        archives.sort();
+        archives
+    }
+
+    /* Lists the mod files in the download directory that have no metadata, such as files downloaded with a browser or
+     * another mod manager. Partial downloads are skipped, since they're handled by the download queue. */
+    pub async fn unmanaged_files(&self) -> Result<Vec<PathBuf>, io::Error> {
+        let mut unmanaged = vec![];
+        for f in download_dir_entries(&self.config).await? {
+            let path = f.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = f.file_name().to_string_lossy().into_owned();
+            if file_name.ends_with(".json") || file_name.ends_with(".part") || file_name.ends_with(".tmp") {
+                continue;
+            }
+            if !path.with_file_name(format!("{}.json", file_name)).exists() {
+                unmanaged.push(path);
+            }
+        }
+        unmanaged.sort();
+        Ok(unmanaged)
+    }
+}
+
+fn dir_size(dir: &Path) -> u64 {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(md) if md.is_dir() => dir_size(&entry.path()),
+            Ok(md) => md.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/* Picks files to delete from the front of the list until their combined size is at least the excess. Returns how many
+ * of them are needed, or all of them if that's not enough. */
+pub fn select_for_pruning(sizes: &[u64], excess: u64) -> usize {
+    let mut freed = 0;
+    for (i, size) in sizes.iter().enumerate() {
+        if freed >= excess {
+            return i;
+        }
+        freed += size;
+    }
+    sizes.len()
+}
+
+// The game, mod id, file name, version and upload time of a downloaded file
+// The ids of the files that replace the file in its mod's update chain, oldest first
+fn newer_in_chain(file_id: u64, file_updates: &BinaryHeap<FileUpdate>) -> Vec<u64> {
+    let mut newer = vec![];
+    let mut current = file_id;
+    // Each update is newer than the previous one, so the chain can't loop back on itself
+    for upd in file_updates.clone().into_sorted_vec() {
+        if upd.old_file_id == current {
+            current = upd.new_file_id;
+            newer.push(current);
+        }
+    }
+    newer
+}
+
+type FileVersion<'a> = (&'a str, u32, &'a str, &'a str, u64);
+
+/* Groups the files by game, mod and file name, and returns the indices of the files that are older than the newest
+ * `keep` files of each group. Versions are often part of the file name, so that is left out of it. Only files with an
+ * older version than a kept file are returned, since nothing has replaced files with the same version, such as
+ * variants of the main file that were uploaded at different times. */
+fn select_old_versions(files: &[FileVersion], keep: usize) -> Vec<usize> {
+    let mut groups: HashMap<(&str, u32, String), Vec<usize>> = HashMap::new();
+    for (i, (game, mod_id, name, version, _)) in files.iter().enumerate() {
+        let name = if version.is_empty() {
+            name.to_string()
+        } else {
+            name.replace(version, "")
+        };
+        groups.entry((game, *mod_id, name.trim().to_lowercase())).or_default().push(i);
+    }
+    let mut old = vec![];
+    for mut group in groups.into_values() {
+        group.sort_by(|&a, &b| util::version::compare(files[b].3, files[a].3).then(files[b].4.cmp(&files[a].4)));
+        if let Some(&newest) = group.first().filter(|_| keep > 0) {
+            let is_older = |&&i: &&usize| util::version::compare(files[newest].3, files[i].3) == Ordering::Greater;
+            old.extend(group.iter().skip(keep).filter(is_older));
+        } else {
+            old.extend(group);
+        }
+    }
+    old.sort();
+    old
+}
+
+/* Missing files are expected, such as mod files without metadata, but anything else means that the file is corrupt or
+ * unreadable and is reported. */
+fn push_load_error(load_errors: &mut Vec<String>, path: &Path, e: std::io::Error) {
+    if e.kind() != ErrorKind::NotFound {
+        load_errors.push(format!("Unable to load {}, skipping it: {e}", path.display()));
+    }
+}
+
+fn is_archive(path: &Path) -> bool {
+    let ext = path.extension().and_then(OsStr::to_str).map(str::to_lowercase);
+    matches!(ext.as_deref(), Some("7z") | Some("zip") | Some("rar"))
+}
+
+/* The entries of the download directory, the per-game directories and the directories of partial downloads in
+ * temp_download_dir. Missing directories are skipped. */
+async fn download_dir_entries(config: &Config) -> Result<Vec<fs::DirEntry>, io::Error> {
+    let mut entries = vec![];
+    for dir in config.part_dirs() {
+        let mut dir_entries = match fs::read_dir(&dir).await {
+            Ok(rd) => rd,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        while let Some(f) = dir_entries.next_entry().await? {
+            entries.push(f);
+        }
+    }
+    Ok(entries)
+}
+
+// Whether the file is the metadata of a downloaded file or the state of a download
+async fn is_metadata(path: &Path) -> bool {
+    let contents = match fs::read_to_string(path).await {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    if path.to_string_lossy().ends_with(".part.json") {
+        serde_json::from_str::<DownloadInfo>(&contents).is_ok()
+    } else {
+        serde_json::from_str::<LocalFile>(&contents).is_ok()
+    }
+}
+
+// A <file>.<n>.tmp file from Cacheable::save() that hasn't been written to for a while
+async fn is_interrupted_save(f: &fs::DirEntry) -> bool {
+    let file_name = f.file_name().to_string_lossy().into_owned();
+    let is_save = match file_name.strip_suffix(".tmp").and_then(|name| name.rsplit_once('.')) {
+        Some((_, n)) => !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()),
+        None => false,
+    };
+    let modified = f.metadata().await.and_then(|md| md.modified()).ok();
+    is_save && modified.and_then(|t| t.elapsed().ok()).is_some_and(|age| age >= INTERRUPTED_SAVE_AGE)
+}
+
+// Returns whether the file existed
+async fn remove_if_exists(path: &Path) -> Result<bool, io::Error> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::Cache;
     use super::CacheError;
+    use super::{LocalFile, UpdateStatus};
+    use crate::api::downloads::{DownloadInfo, FileInfo};
+    use crate::api::FileUpdate;
     use crate::config::ConfigBuilder;
+    use crate::logger::Logger;
+    use std::collections::BinaryHeap;
+    use tokio::fs;
 
     #[tokio::test]
     async fn load_file_details() -> Result<(), CacheError> {
@@ -124,4 +622,178 @@ mod test {
         assert_eq!(fdata.local_file.read().await.game, game);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn load_in_background() -> Result<(), CacheError> {
+        let config = ConfigBuilder::default().profile("morrowind").build().unwrap();
+        let cache = Cache::new(&config).await?;
+        let background = Cache::new_in_background(&config, &Logger::default()).await?;
+        background.file_index.wait_until_loaded().await;
+        assert_eq!(background.file_index.load_progress(), None);
+
+        let file_ids = |cache: Cache| async move {
+            cache.file_index.files_sorted.read().await.iter().map(|fdata| fdata.file_id).collect::<Vec<u64>>()
+        };
+        assert_eq!(file_ids(background.clone()).await, file_ids(cache.clone()).await);
+
+        // Reloading while the first load may still be running loads each file once
+        let background = Cache::new_in_background(&config, &Logger::default()).await?;
+        background.file_index.reload(Logger::default()).await;
+        background.file_index.wait_until_loaded().await;
+        assert_eq!(file_ids(background).await, file_ids(cache).await);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stats() -> Result<(), CacheError> {
+        let config = ConfigBuilder::default().profile("morrowind").build().unwrap();
+        let cache = Cache::new(&config).await?;
+
+        let stats = cache.stats().await;
+        assert_eq!((stats.files, stats.mods, stats.games, stats.total_size), (3, 2, 1, 0));
+        assert!(stats.latest_install.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn disk_usage() -> Result<(), CacheError> {
+        let config = ConfigBuilder::default().profile("morrowind").build().unwrap();
+        let cache = Cache::new(&config).await?;
+
+        let usage = cache.disk_usage().await;
+        assert_eq!(usage.len(), 1);
+        assert_eq!((usage[0].game.as_str(), usage[0].files, usage[0].size), ("morrowind", 3, 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unindexed_archives() -> Result<(), CacheError> {
+        // Every archive in the test directory has valid metadata
+        let config = ConfigBuilder::default().profile("morrowind").build().unwrap();
+        let cache = Cache::new(&config).await?;
+        assert!(cache.unindexed_archives().await.is_empty());
+        assert!(super::is_archive(std::path::Path::new("Mod-1-2-3.ZIP")));
+        assert!(!super::is_archive(std::path::Path::new("Mod-1-2-3.7z.json")));
+        Ok(())
+    }
+
+    #[test]
+    fn select_for_pruning() {
+        assert_eq!(super::select_for_pruning(&[10, 20, 30], 0), 0);
+        assert_eq!(super::select_for_pruning(&[10, 20, 30], 10), 1);
+        assert_eq!(super::select_for_pruning(&[10, 20, 30], 25), 2);
+        assert_eq!(super::select_for_pruning(&[10, 20, 30], 100), 3);
+    }
+
+    #[test]
+    fn newer_in_chain() {
+        let update = |old_file_id, new_file_id, uploaded_timestamp| FileUpdate {
+            old_file_id,
+            new_file_id,
+            old_file_name: String::new(),
+            new_file_name: String::new(),
+            uploaded_timestamp,
+            uploaded_time: String::new(),
+        };
+        let updates = BinaryHeap::from([update(1, 2, 100), update(5, 6, 150), update(2, 3, 200)]);
+        assert_eq!(super::newer_in_chain(1, &updates), vec![2, 3]);
+        assert_eq!(super::newer_in_chain(3, &updates), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn old_versions() {
+        let files = [
+            ("morrowind", 46599, "Better Bodies 1.9", "1.9", 100),
+            ("morrowind", 46599, "Better Bodies 1.10", "1.10", 200),
+            ("morrowind", 46599, "Better Bodies Patch", "1.0", 50),
+            ("morrowind", 46599, "Better Bodies 1.8", "1.8", 300),
+            ("skyrim", 46599, "Better Bodies 1.7", "1.7", 10),
+        ];
+        assert_eq!(super::select_old_versions(&files, 1), vec![0, 3]);
+        assert_eq!(super::select_old_versions(&files, 2), vec![3]);
+        assert!(super::select_old_versions(&files, 3).is_empty());
+
+        // Uploads of the same version don't replace each other, but a newer version replaces them all
+        let files = [
+            ("morrowind", 46599, "Main File", "1.0", 100),
+            ("morrowind", 46599, "Main File", "1.0", 200),
+            ("morrowind", 46599, "Main File", "1.0", 300),
+        ];
+        assert!(super::select_old_versions(&files, 1).is_empty());
+        let files = [
+            ("morrowind", 46599, "Main File", "1.0", 100),
+            ("morrowind", 46599, "Main File", "1.0", 200),
+            ("morrowind", 46599, "Main File", "1.1", 300),
+        ];
+        assert_eq!(super::select_old_versions(&files, 1), vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn clean_orphans() -> Result<(), CacheError> {
+        let config = ConfigBuilder::default().profile("clean_orphans").build().unwrap();
+        let dir = config.download_dir();
+        fs::create_dir_all(&dir).await?;
+        let kept = [
+            "kept.7z",
+            "kept.7z.json",
+            "unmanaged.7z",
+            "resumable.7z.part",
+            "resumable.7z.part.json",
+            "manifest.json",
+            "moving.7z.tmp",
+            "saving.7z.json.4.tmp",
+        ];
+        let orphans = [
+            "crashed.7z.json.3.tmp",
+            "gone.7z.json",
+            "no_data.7z.part.json",
+            "no_state.7z.part",
+        ];
+        for name in kept.iter().chain(orphans.iter()) {
+            fs::write(dir.join(name), "").await?;
+        }
+        // Only the metadata that dmodman writes is removed
+        let fi = FileInfo::new("morrowind".to_string(), 1, 2, "gone.7z".to_string());
+        let lf = LocalFile::new(fi.clone(), UpdateStatus::UpToDate(0));
+        fs::write(dir.join("gone.7z.json"), serde_json::to_string(&lf).unwrap()).await?;
+        let dl_info = DownloadInfo::new(fi, "https://example.com/gone.7z".parse().unwrap());
+        fs::write(dir.join("no_data.7z.part.json"), serde_json::to_string(&dl_info).unwrap()).await?;
+        fs::write(dir.join("manifest.json"), "{\"Name\": \"Mod\"}").await?;
+        let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        std::fs::File::options().write(true).open(dir.join("crashed.7z.json.3.tmp"))?.set_modified(an_hour_ago)?;
+        let cache = Cache::new(&config).await?;
+
+        let mut removed = cache.clean_orphans().await?;
+        removed.sort();
+        let all_kept = kept.iter().all(|name| dir.join(name).exists());
+        fs::remove_dir_all(&dir).await?;
+
+        assert_eq!(removed, orphans.iter().map(|name| dir.join(name)).collect::<Vec<_>>());
+        assert!(all_kept);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unmanaged_files() -> Result<(), CacheError> {
+        let config = ConfigBuilder::default().profile("unmanaged_files").build().unwrap();
+        let dir = config.download_dir();
+        fs::create_dir_all(&dir).await?;
+        for name in [
+            "managed.7z",
+            "managed.7z.json",
+            "partial.7z.part",
+            "partial.7z.part.json",
+            "b.zip",
+            "a.7z",
+        ] {
+            fs::write(dir.join(name), "").await?;
+        }
+        let cache = Cache::new(&config).await?;
+
+        let unmanaged = cache.unmanaged_files().await?;
+        fs::remove_dir_all(&dir).await?;
+
+        assert_eq!(unmanaged, vec![dir.join("a.7z"), dir.join("b.zip")]);
+        Ok(())
+    }
 }
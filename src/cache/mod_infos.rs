@@ -0,0 +1,59 @@
+use super::Cacheable;
+use crate::api::ModInfo;
+use crate::config::{Config, PathType};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/* Mod info is only needed for showing the details of the selected file, so unlike file lists they're read from disk
+ * on demand instead of all at once on startup. Mods without a mod info file are remembered as None until it's fetched,
+ * so that they aren't looked for on disk again every time. */
+#[derive(Clone)]
+pub struct ModInfos {
+    config: Config,
+    #[allow(clippy::type_complexity)]
+    map: Arc<RwLock<HashMap<(String, u32), Option<ModInfo>>>>,
+}
+
+impl ModInfos {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            config: config.clone(),
+            map: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    pub async fn insert<S: Into<String>>(&self, (game, mod_id): (S, u32), value: ModInfo) {
+        self.map.write().await.insert((game.into(), mod_id), Some(value));
+    }
+
+    pub async fn get(&self, (game, mod_id): (&str, u32)) -> Option<ModInfo> {
+        if let Some(mi) = self.map.read().await.get(&(game.to_string(), mod_id)) {
+            return mi.clone();
+        }
+        let mi = ModInfo::load(self.config.path_for(PathType::ModInfo(game, &mod_id)).await).await.ok();
+        self.map.write().await.insert((game.to_string(), mod_id), mi.clone());
+        mi
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModInfos;
+    use crate::config::ConfigBuilder;
+
+    #[tokio::test]
+    async fn load_on_demand() {
+        let config = ConfigBuilder::default().profile("morrowind").build().unwrap();
+        let mod_infos = ModInfos::new(&config);
+
+        let mi = mod_infos.get(("morrowind", 46599)).await.unwrap();
+        assert!(mi.is_available());
+        assert!(mod_infos.map.read().await.contains_key(&("morrowind".to_string(), 46599)));
+        assert!(mod_infos.get(("morrowind", 1)).await.is_none());
+        // A missing mod info is remembered until it's inserted
+        assert!(matches!(mod_infos.map.read().await.get(&("morrowind".to_string(), 1)), Some(None)));
+        mod_infos.insert(("morrowind", 1), mi).await;
+        assert!(mod_infos.get(("morrowind", 1)).await.is_some());
+    }
+}
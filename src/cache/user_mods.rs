@@ -0,0 +1,111 @@
+use super::{push_load_error, Cacheable};
+use crate::api::{Endorsements, TrackedMods};
+use crate::config::{Config, PathType};
+use crate::util::changes::ChangeFlag;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::io;
+use tokio::sync::{RwLock, RwLockReadGuard};
+
+// The (game, mod_id) of each mod the user has endorsed or is tracking on the Nexus
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct UserModLists {
+    pub endorsed: HashSet<(String, u32)>,
+    pub tracked: HashSet<(String, u32)>,
+}
+
+impl UserModLists {
+    pub fn from_responses(endorsements: Endorsements, tracked_mods: TrackedMods) -> Self {
+        Self {
+            endorsed: endorsements
+                .results
+                .into_iter()
+                .filter(|e| e.status == "Endorsed")
+                .map(|e| (e.domain_name, e.mod_id))
+                .collect(),
+            tracked: tracked_mods.results.into_iter().map(|t| (t.domain_name, t.mod_id)).collect(),
+        }
+    }
+}
+
+/* The lists are saved so that they can be shown on startup before they've been refreshed from the Nexus.
+ * Changes are signaled through the file index's has_changed, since they're shown in the file table. */
+#[derive(Clone)]
+pub struct UserMods {
+    config: Config,
+    lists: Arc<RwLock<UserModLists>>,
+    has_changed: ChangeFlag,
+}
+
+impl UserMods {
+    pub async fn new(config: &Config, has_changed: ChangeFlag, load_errors: &mut Vec<String>) -> Self {
+        let path = config.path_for(PathType::UserMods).await;
+        let lists = match UserModLists::load(path.clone()).await {
+            Ok(lists) => lists,
+            Err(e) => {
+                push_load_error(load_errors, &path, e);
+                UserModLists::default()
+            }
+        };
+        Self {
+            config: config.clone(),
+            lists: Arc::new(RwLock::new(lists)),
+            has_changed,
+        }
+    }
+
+    pub async fn read(&self) -> RwLockReadGuard<'_, UserModLists> {
+        self.lists.read().await
+    }
+
+    pub async fn replace(&self, lists: UserModLists) -> Result<(), io::Error> {
+        *self.lists.write().await = lists;
+        self.changed().await
+    }
+
+    pub async fn set_endorsed(&self, (game, mod_id): (&str, u32), endorsed: bool) -> Result<(), io::Error> {
+        set_contains(&mut self.lists.write().await.endorsed, (game.to_string(), mod_id), endorsed);
+        self.changed().await
+    }
+
+    pub async fn set_tracked(&self, (game, mod_id): (&str, u32), tracked: bool) -> Result<(), io::Error> {
+        set_contains(&mut self.lists.write().await.tracked, (game.to_string(), mod_id), tracked);
+        self.changed().await
+    }
+
+    async fn changed(&self) -> Result<(), io::Error> {
+        self.has_changed.set();
+        self.lists.read().await.save(self.config.path_for(PathType::UserMods).await).await
+    }
+}
+
+fn set_contains(set: &mut HashSet<(String, u32)>, key: (String, u32), contains: bool) {
+    if contains {
+        set.insert(key);
+    } else {
+        set.remove(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::UserModLists;
+    use crate::api::{Endorsements, TrackedMods};
+
+    #[test]
+    fn lists_from_responses() {
+        let endorsements: Endorsements = serde_json::from_str(
+            r#"[{"mod_id": 46599, "domain_name": "morrowind", "date": 1556986716, "version": "1.03", "status": "Endorsed"},
+                {"mod_id": 1, "domain_name": "morrowind", "date": 1556986716, "version": "1.0", "status": "Abstained"}]"#,
+        )
+        .unwrap();
+        let tracked: TrackedMods = serde_json::from_str(r#"[{"mod_id": 2, "domain_name": "skyrim"}]"#).unwrap();
+
+        let lists = UserModLists::from_responses(endorsements, tracked);
+        assert!(lists.endorsed.contains(&("morrowind".to_string(), 46599)));
+        assert!(!lists.endorsed.contains(&("morrowind".to_string(), 1)));
+        assert_eq!(lists.tracked.len(), 1);
+        assert!(lists.tracked.contains(&("skyrim".to_string(), 2)));
+    }
+}
@@ -0,0 +1,735 @@
+use std::cmp::Reverse;
+use std::fs;
+use std::io::{self, ErrorKind, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use tokio::time;
+
+use crate::api::{Client, DownloadState, DownloadStatus, Downloads, Importer, NxmUrl, QueueOutcome};
+use crate::cache::{self, Cache};
+use crate::config::Config;
+use crate::logger::LogLevel;
+use crate::nxm_socket::{self, SocketCommand};
+use crate::util;
+use crate::util::format;
+
+/* Command line arguments. dmodman is mostly used interactively or as an nxm:// URL handler, so the arguments are
+ * parsed by hand instead of pulling in an argument parsing library. */
+
+pub const USAGE: &str = "Usage: dmodman [--profile <name>] [-d | nxm://... | --queue nxm://... | --queue-file <file>
+                | --list-downloads [--json] | --import [<game>] | --download-file <game> <mod_id> <file_id>
+                | --disk-usage [size | name | files] | --prune | --stop | --command <command>
+                | --export-queue <file> | --import-queue <file>] [--config <file>] [--log-level <level>]
+
+    nxm://...           Queue the download, in an already running instance if there is one.
+    --queue nxm://...   Queue the download in the running instance and print whether it succeeded.
+    --queue-file <file> Queue the nxm:// URLs in the file, one per line, in the running instance. Waits for
+                        downloads to finish when max_concurrent_downloads are already running.
+    --list-downloads    Print the downloads of the running instance.
+    --json              Print the downloads as JSON.
+    --import [<game>]   Identify the files in the download directory that lack metadata, using the game's
+                        Nexus domain name, e.g. skyrimspecialedition. Defaults to default_game in the config,
+                        and asks for the game if neither is set.
+    --download-file <game> <mod_id> <file_id>
+                        Download the file without the TUI, printing its progress. Requires a premium account,
+                        since others can only download through nxm:// URLs from the Nexus.
+    --disk-usage [size | name | files]
+                        Show how much space the downloaded files of each game take, sorted by size (the
+                        default), game name or number of files.
+    --prune             Offer to delete old versions of mods, oldest first, until the download directory is
+                        under max_download_dir_size, or all of them if it isn't set. Asks before deleting.
+    --stop              Stop the running instance after it has saved the state of its downloads.
+    --command <command> Send a command to the running instance: pause-all, resume-all, check-updates or quit.
+    --export-queue <file>
+                        Save the unfinished downloads of the running instance to the file, e.g. to continue them
+                        on another machine.
+    --import-queue <file>
+                        Queue the downloads saved with --export-queue, in the running instance if there is one.
+    -d                  Run without the TUI, only listening for downloads.
+    --profile <name>    Use the profile's config file and download directory.
+    --config <file>     Read the settings from the file instead of ~/.config/dmodman/config.toml.
+    --log-level <level> Only log messages at or above debug, info, warn or error, overriding the config.
+    -h, --help          Show this message.
+    -V, --version       Show the version and the commit it was built from.";
+
+pub const VERSION: &str =
+    concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"), " (", env!("DMODMAN_GIT_HASH"), ")");
+
+#[derive(Debug, PartialEq)]
+pub enum UsageSort {
+    Size,
+    Name,
+    Files,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub nxm: Option<NxmUrl>,
+    pub queue: Option<NxmUrl>,
+    pub queue_file: Option<PathBuf>,
+    pub list_downloads: bool,
+    pub json: bool,
+    pub import: bool,
+    pub game: Option<String>,
+    pub download_file: Option<(String, u32, u64)>,
+    pub disk_usage: Option<UsageSort>,
+    pub prune: bool,
+    pub stop: bool,
+    pub command: Option<SocketCommand>,
+    pub export_queue: Option<PathBuf>,
+    pub import_queue: Option<PathBuf>,
+    pub is_interactive: bool,
+    pub profile: Option<String>,
+    pub config: Option<PathBuf>,
+    pub log_level: Option<LogLevel>,
+    pub show_help: bool,
+    pub show_version: bool,
+}
+
+pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Args, String> {
+    let mut parsed = Args {
+        is_interactive: true,
+        ..Default::default()
+    };
+
+    let mut args = args.into_iter().peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" => parsed.is_interactive = false,
+            "-h" | "--help" => parsed.show_help = true,
+            "-V" | "--version" => parsed.show_version = true,
+            "--list-downloads" => parsed.list_downloads = true,
+            "--json" => parsed.json = true,
+            "--stop" => parsed.stop = true,
+            "--prune" => parsed.prune = true,
+            "--disk-usage" => {
+                let sort = match args.peek().map(String::as_str) {
+                    Some("size") => Some(UsageSort::Size),
+                    Some("name") => Some(UsageSort::Name),
+                    Some("files") => Some(UsageSort::Files),
+                    _ => None,
+                };
+                if sort.is_some() {
+                    args.next();
+                }
+                parsed.disk_usage = Some(sort.unwrap_or(UsageSort::Size));
+            }
+            "--queue" => match args.next() {
+                Some(nxm_str) if nxm_str.starts_with("nxm://") => parsed.queue = Some(parse_nxm(&nxm_str)?),
+                _ => return Err("--queue requires an nxm:// URL.".to_string()),
+            },
+            "--command" => match args.next().as_deref().and_then(SocketCommand::from_name) {
+                Some(command) => parsed.command = Some(command),
+                None => {
+                    let names: Vec<&str> = SocketCommand::ALL.iter().map(SocketCommand::name).collect();
+                    return Err(format!("--command requires one of {}.", names.join(", ")));
+                }
+            },
+            "--queue-file" => match args.next() {
+                Some(path) if !path.is_empty() && !path.starts_with('-') => parsed.queue_file = Some(path.into()),
+                _ => return Err("--queue-file requires a file.".to_string()),
+            },
+            "--export-queue" => match args.next() {
+                Some(path) if !path.is_empty() && !path.starts_with('-') => parsed.export_queue = Some(path.into()),
+                _ => return Err("--export-queue requires a file.".to_string()),
+            },
+            "--import-queue" => match args.next() {
+                Some(path) if !path.is_empty() && !path.starts_with('-') => parsed.import_queue = Some(path.into()),
+                _ => return Err("--import-queue requires a file.".to_string()),
+            },
+            "--import" => {
+                parsed.import = true;
+                if args.peek().is_some_and(|game| !game.starts_with('-') && !game.starts_with("nxm://")) {
+                    parsed.game = args.next();
+                }
+            }
+            "--download-file" => {
+                let (game, mod_id, file_id) = (args.next(), args.next(), args.next());
+                match (game, mod_id.and_then(|id| id.parse().ok()), file_id.and_then(|id| id.parse().ok())) {
+                    (Some(game), Some(mod_id), Some(file_id)) if is_valid_game_slug(&game) => {
+                        parsed.download_file = Some((game, mod_id, file_id))
+                    }
+                    _ => return Err("--download-file requires a game, a mod id and a file id.".to_string()),
+                }
+            }
+            "--profile" => match args.next() {
+                Some(profile) if !profile.is_empty() && !profile.starts_with('-') => parsed.profile = Some(profile),
+                _ => return Err("--profile requires a profile name.".to_string()),
+            },
+            "--config" => match args.next() {
+                Some(path) if !path.is_empty() && !path.starts_with('-') => parsed.config = Some(path.into()),
+                _ => return Err("--config requires a file.".to_string()),
+            },
+            "--log-level" => match args.next().as_deref().and_then(LogLevel::parse) {
+                Some(level) => parsed.log_level = Some(level),
+                None => return Err("--log-level requires one of debug, info, warn or error.".to_string()),
+            },
+            nxm_str if nxm_str.starts_with("nxm://") => {
+                if parsed.nxm.is_some() {
+                    return Err("Only one nxm:// URL can be given at a time.".to_string());
+                }
+                parsed.nxm = Some(parse_nxm(nxm_str)?);
+            }
+            _ => return Err(format!("Unknown argument: {arg}")),
+        }
+    }
+    if parsed.queue.is_some() && (parsed.nxm.is_some() || !parsed.is_interactive) {
+        return Err("--queue can't be combined with other nxm:// URLs or -d.".to_string());
+    }
+    if parsed.queue_file.is_some() && (parsed.queue.is_some() || parsed.nxm.is_some() || !parsed.is_interactive) {
+        return Err("--queue-file can't be combined with other nxm:// URLs, --queue or -d.".to_string());
+    }
+    if parsed.download_file.is_some() && (parsed.queue.is_some() || parsed.nxm.is_some() || parsed.queue_file.is_some())
+    {
+        return Err("--download-file can't be combined with nxm:// URLs, --queue or --queue-file.".to_string());
+    }
+    if parsed.json && !parsed.list_downloads {
+        return Err("--json is only supported with --list-downloads.".to_string());
+    }
+    Ok(parsed)
+}
+
+// Expired URLs are accepted, so that the running instance can mark their downloads as expired
+fn parse_nxm(nxm_str: &str) -> Result<NxmUrl, String> {
+    NxmUrl::from_str(nxm_str).map_err(|e| format!("Unable to parse {nxm_str}: {e}"))
+}
+
+/* Picks the game from the argument or the configured default, or asks for it when running in a terminal. The game is
+ * checked with the Nexus so that a typo gets a clear error instead of every file failing to import. A game that was
+ * asked for can be saved as the default. */
+pub async fn resolve_game(config: &mut Config, client: &Client, game: Option<&str>) -> Result<String, String> {
+    let mut asked = false;
+    let game = match game.map(str::to_string).or(config.default_game.clone()) {
+        Some(game) => game,
+        None if io::stdin().is_terminal() => {
+            asked = true;
+            prompt("Game (its Nexus domain name, e.g. skyrimspecialedition): ")
+        }
+        None => return Err("No game given, and default_game isn't set in the config.".to_string()),
+    };
+    if !is_valid_game_slug(&game) {
+        return Err(format!(
+            "\"{game}\" isn't a valid game. Use the game's domain name as it appears in its Nexus URL, e.g. \
+             skyrimspecialedition for https://www.nexusmods.com/skyrimspecialedition."
+        ));
+    }
+    match client.game_exists(&game).await {
+        Ok(true) => {}
+        Ok(false) => return Err(format!("The Nexus doesn't have a game called \"{game}\".")),
+        // The game can't be checked without an API connection, and the import reports its own errors
+        Err(e) => println!("Unable to check that {game} exists: {e}"),
+    }
+    if asked && prompt("Save as the default game? [y/N] ").eq_ignore_ascii_case("y") {
+        if let Err(e) = config.save_default_game(&game) {
+            println!("Unable to save the default game: {e}");
+        }
+    }
+    Ok(game)
+}
+
+fn prompt(question: &str) -> String {
+    print!("{question}");
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    let _ = io::stdin().read_line(&mut answer);
+    answer.trim().to_string()
+}
+
+// Nexus domain names are lowercase letters and digits, like "skyrimspecialedition" or "fallout4"
+fn is_valid_game_slug(game: &str) -> bool {
+    !game.is_empty() && game.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+}
+
+/* The following talk to an already running instance through its socket, and return the exit code for the process so
+ * that scripts can tell whether they succeeded. */
+
+pub async fn queue(socket_path: &Path, nxm: &NxmUrl) -> i32 {
+    let reply = match nxm_socket::send_and_receive(socket_path, nxm.url.as_str()).await {
+        Ok(reply) => reply,
+        Err(e) => format!("ERR {}\n", connection_error(e)),
+    };
+    print!("{reply}");
+    if reply.starts_with("OK") {
+        0
+    } else {
+        1
+    }
+}
+
+/* Queues the URLs one at a time, so that the running instance never has more than max_concurrent_downloads in progress.
+ * Lines that are empty or start with # are ignored. */
+pub async fn queue_file(socket_path: &Path, path: &Path, max_concurrent_downloads: usize) -> i32 {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("Unable to read {}: {e}", path.display());
+            return 1;
+        }
+    };
+    let (mut queued, mut skipped, mut invalid, mut failed) = (0, 0, 0, 0);
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        if let Err(e) = NxmUrl::from_str(line).and_then(|nxm| nxm.check_expiration()) {
+            println!("Invalid: {line}: {e}");
+            invalid += 1;
+            continue;
+        }
+        if let Err(e) = wait_for_free_slot(socket_path, max_concurrent_downloads).await {
+            println!("{}", connection_error(e));
+            return 1;
+        }
+        match nxm_socket::send_and_receive(socket_path, line).await {
+            Ok(reply) if reply.starts_with("OK skipped") => skipped += 1,
+            Ok(reply) if reply.starts_with("OK") => queued += 1,
+            Ok(reply) => {
+                println!("Failed: {line}: {}", reply.trim().trim_start_matches("ERR "));
+                failed += 1;
+            }
+            Err(e) => {
+                println!("{}", connection_error(e));
+                return 1;
+            }
+        }
+    }
+    println!("Queued {queued}, skipped {skipped} already downloaded, {invalid} invalid, {failed} failed.");
+    if invalid + failed > 0 {
+        1
+    } else {
+        0
+    }
+}
+
+async fn wait_for_free_slot(socket_path: &Path, max_concurrent_downloads: usize) -> std::io::Result<()> {
+    if max_concurrent_downloads == 0 {
+        return Ok(());
+    }
+    let downloading = DownloadState::Downloading.to_string();
+    loop {
+        let reply = nxm_socket::send_and_receive(socket_path, "LIST").await?;
+        let active = match serde_json::from_str::<Vec<DownloadStatus>>(&reply) {
+            Ok(statuses) => statuses.iter().filter(|s| s.state == downloading).count(),
+            // Not knowing the amount of downloads shouldn't stop queuing them
+            Err(_) => return Ok(()),
+        };
+        if active < max_concurrent_downloads {
+            return Ok(());
+        }
+        time::sleep(Duration::from_secs(2)).await;
+    }
+}
+
+pub async fn list_downloads(socket_path: &Path, json: bool) -> i32 {
+    let reply = match nxm_socket::send_and_receive(socket_path, "LIST").await {
+        Ok(reply) => reply,
+        Err(e) => {
+            println!("{}", connection_error(e));
+            return 1;
+        }
+    };
+    if let Some(e) = reply.strip_prefix("ERR ") {
+        print!("{e}");
+        return 1;
+    }
+    if json {
+        print!("{reply}");
+        return 0;
+    }
+    match serde_json::from_str::<Vec<DownloadStatus>>(&reply) {
+        Ok(statuses) if statuses.is_empty() => println!("No downloads."),
+        Ok(statuses) => {
+            for status in &statuses {
+                println!("{status}");
+            }
+            let count = |prefix: &str| statuses.iter().filter(|s| s.state.starts_with(prefix)).count();
+            let (active, queued, done) = (count("Downloading"), count("Paused"), count("Done"));
+            println!("{}", format::download_counts(active, queued, done));
+        }
+        Err(e) => {
+            println!("Unable to parse the reply of the running instance: {e}");
+            return 1;
+        }
+    }
+    0
+}
+
+/* Downloads a single file without the TUI, printing its progress every second until it's done. Only premium users can
+ * get a download link without a nxm:// URL, so others are told where to download the file on the Nexus instead. A
+ * partially downloaded file is resumed. */
+pub async fn download_file(downloads: &Downloads, game: &str, mod_id: u32, file_id: u64) -> i32 {
+    match downloads.queue_file(game, mod_id, file_id, "").await {
+        Ok(QueueOutcome::Queued) => {}
+        // Already downloaded, which has been logged
+        Ok(QueueOutcome::Skipped) => return 0,
+        Err(e) => {
+            println!("{e}");
+            println!(
+                "Without a premium account, download the file from \
+                 https://www.nexusmods.com/{game}/mods/{mod_id}?tab=files&file_id={file_id}"
+            );
+            return 1;
+        }
+    }
+    let mut interval = time::interval(Duration::from_secs(1));
+    let shutdown = util::wait_for_shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = &mut shutdown => {
+                // Saves the progress so that running the same command again continues the download
+                downloads.shutdown().await;
+                return 1;
+            }
+        }
+        let status = downloads.status_list().await.into_iter().find(|status| status.file_id == file_id);
+        let status = if let Some(status) = status { status } else { return 1 };
+        println!("{status}");
+        match status.state.as_str() {
+            "Downloading" => {}
+            "Done" => return 0,
+            // Failed or paused downloads have logged the reason
+            _ => return 1,
+        }
+    }
+}
+
+/* Imports the files in the download directory that were downloaded without dmodman, and lists the ones that couldn't be
+ * identified so that the user can deal with them manually. */
+pub async fn import(cache: &Cache, client: &Client, game: &str) -> i32 {
+    let files = match cache.unmanaged_files().await {
+        Ok(files) => files,
+        Err(e) => {
+            println!("Unable to read the download directory: {e}");
+            return 1;
+        }
+    };
+    if files.is_empty() {
+        println!("No files without metadata found.");
+        return 0;
+    }
+
+    let importer = Importer::new(cache.clone(), client.clone(), game);
+    let mut failed = vec![];
+    for (i, path) in files.iter().enumerate() {
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        println!("[{}/{}] {file_name}", i + 1, files.len());
+        match importer.import(path).await {
+            Ok(description) => println!("    Imported as {description}"),
+            Err(e) => {
+                println!("    {e}");
+                failed.push(format!("{file_name}: {e}"));
+            }
+        }
+    }
+
+    println!("Imported {} of {} files.", files.len() - failed.len(), files.len());
+    if !failed.is_empty() {
+        println!("The following files couldn't be imported:");
+        for f in failed {
+            println!("    {f}");
+        }
+    }
+    0
+}
+
+// Prints the disk usage of each game, and warns if the total is over max_download_dir_size
+pub async fn disk_usage(cache: &Cache, sort: &UsageSort, max_size_mib: u64) -> i32 {
+    let mut usage = cache.disk_usage().await;
+    match sort {
+        UsageSort::Size => usage.sort_by_key(|game| Reverse(game.size)),
+        UsageSort::Name => usage.sort_by(|a, b| a.game.cmp(&b.game)),
+        UsageSort::Files => usage.sort_by_key(|game| Reverse(game.files)),
+    }
+    println!("{:<32}{:>8}{:>12}{:>12}", "Game", "Files", "Size", "Superseded");
+    for game in &usage {
+        println!(
+            "{:<32}{:>8}{:>12}{:>12}",
+            game.game,
+            game.files,
+            format::human_size(game.size),
+            format::human_size(game.superseded_size)
+        );
+    }
+    let total: u64 = usage.iter().map(|game| game.size).sum();
+    let superseded: u64 = usage.iter().map(|game| game.superseded_size).sum();
+    let files: usize = usage.iter().map(|game| game.files).sum();
+    println!("{:<32}{files:>8}{:>12}{:>12}", "Total", format::human_size(total), format::human_size(superseded));
+    println!("Metadata cache: {}", format::human_size(cache.metadata_size()));
+    if max_size_mib > 0 && total > max_size_mib * 1024 * 1024 {
+        println!(
+            "The downloads are over the limit of {}. Run dmodman --prune to delete old versions.",
+            format::human_size(max_size_mib * 1024 * 1024)
+        );
+    }
+    0
+}
+
+/* Deletes superseded files, oldest first, until the downloads fit in max_download_dir_size. Without a limit all of them
+ * are offered. Nothing is deleted without confirmation, so this only works in a terminal. */
+pub async fn prune(cache: &Cache, max_size_mib: u64) -> i32 {
+    let mut candidates = cache.superseded_files().await;
+    if max_size_mib > 0 {
+        let total: u64 = cache.disk_usage().await.iter().map(|game| game.size).sum();
+        let excess = total.saturating_sub(max_size_mib * 1024 * 1024);
+        if excess == 0 {
+            println!("The downloads are under the limit of {}.", format::human_size(max_size_mib * 1024 * 1024));
+            return 0;
+        }
+        let sizes: Vec<u64> = candidates.iter().map(|(_, size)| *size).collect();
+        candidates.truncate(cache::select_for_pruning(&sizes, excess));
+    }
+    if candidates.is_empty() {
+        println!("No old versions of mods found.");
+        return 0;
+    }
+
+    let mut freed = 0;
+    for (fdata, size) in &candidates {
+        let lf = fdata.local_file.read().await;
+        println!("{:>12}  {}: {}", format::human_size(*size), lf.game, lf.file_name);
+        freed += size;
+    }
+    if !io::stdin().is_terminal() {
+        println!("Run dmodman --prune in a terminal to confirm deleting these files.");
+        return 1;
+    }
+    let question = format!("Delete these {} files, freeing {}? [y/N] ", candidates.len(), format::human_size(freed));
+    if !prompt(&question).eq_ignore_ascii_case("y") {
+        return 0;
+    }
+    for (fdata, _) in &candidates {
+        if let Err(e) = cache.delete_by_file_id(fdata.file_id, true).await {
+            println!("Unable to delete {}: {e}", fdata.local_file.read().await.file_name);
+            return 1;
+        }
+    }
+    println!("Deleted {} files.", candidates.len());
+    0
+}
+
+pub async fn stop(socket_path: &Path) -> i32 {
+    match nxm_socket::send_and_receive(socket_path, "STOP").await {
+        Ok(reply) if reply.trim() == "ACK" => {
+            println!("dmodman stopped.");
+            0
+        }
+        Ok(reply) => {
+            println!("Unexpected reply from dmodman: {}", reply.trim());
+            1
+        }
+        Err(e) => {
+            println!("{}", connection_error(e));
+            1
+        }
+    }
+}
+
+pub async fn send_command(socket_path: &Path, command: SocketCommand) -> i32 {
+    if command == SocketCommand::Quit {
+        return stop(socket_path).await;
+    }
+    match nxm_socket::send_and_receive(socket_path, command.message()).await {
+        Ok(reply) if reply.trim() == "OK" => 0,
+        Ok(reply) => {
+            println!("{}", reply.trim().trim_start_matches("ERR "));
+            1
+        }
+        Err(e) => {
+            println!("{}", connection_error(e));
+            1
+        }
+    }
+}
+
+pub async fn export_queue(socket_path: &Path, path: &Path) -> i32 {
+    match send_queue_file(socket_path, "EXPORT_QUEUE", path).await {
+        Ok(count) => {
+            println!("Saved {count} downloads to {}.", path.display());
+            0
+        }
+        Err(e) => {
+            println!("{e}");
+            1
+        }
+    }
+}
+
+pub async fn import_queue(socket_path: &Path, path: &Path) -> i32 {
+    match send_queue_file(socket_path, "IMPORT_QUEUE", path).await {
+        Ok(count) => {
+            println!("Queued {count} downloads in the running instance. Any that failed are shown in its log.");
+            0
+        }
+        Err(e) => {
+            println!("{e}");
+            1
+        }
+    }
+}
+
+// The running instance may have another working directory, so it's sent the absolute path
+async fn send_queue_file(socket_path: &Path, msg: &str, path: &Path) -> Result<String, String> {
+    let path = std::env::current_dir().map_err(|e| e.to_string())?.join(path);
+    match nxm_socket::send_and_receive(socket_path, &format!("{msg} {}", path.display())).await {
+        Ok(reply) => match reply.trim().strip_prefix("OK ") {
+            Some(count) => Ok(count.to_string()),
+            None => Err(reply.trim().trim_start_matches("ERR ").to_string()),
+        },
+        Err(e) => Err(connection_error(e)),
+    }
+}
+
+fn connection_error(e: std::io::Error) -> String {
+    match e.kind() {
+        ErrorKind::NotFound | ErrorKind::ConnectionRefused => "dmodman is not running.".to_string(),
+        _ => format!("Unable to connect to dmodman: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_valid_game_slug, parse, UsageSort};
+    use crate::logger::LogLevel;
+    use crate::nxm_socket::SocketCommand;
+    use std::path::PathBuf;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_profile() {
+        let parsed = parse(args(&["--profile", "skyrim", "nxm://skyrim/mods/1/files/2"])).unwrap();
+        assert_eq!(parsed.profile.as_deref(), Some("skyrim"));
+        assert_eq!(
+            parsed.nxm.map(|nxm| (nxm.domain_name, nxm.mod_id, nxm.file_id)),
+            Some(("skyrim".to_string(), 1, 2))
+        );
+        assert!(parsed.is_interactive);
+
+        assert!(parse(args(&["--profile"])).is_err());
+        assert!(parse(args(&["--profile", "-d"])).is_err());
+        assert!(parse(args(&["--foo"])).is_err());
+    }
+
+    #[test]
+    fn parse_config() {
+        let parsed = parse(args(&["--config", "/tmp/dmodman.toml", "-d"])).unwrap();
+        assert_eq!(parsed.config, Some(PathBuf::from("/tmp/dmodman.toml")));
+        assert!(!parsed.is_interactive);
+        assert!(parse(args(&["--config"])).is_err());
+        assert!(parse(args(&["--config", "--profile", "skyrim"])).is_err());
+    }
+
+    #[test]
+    fn parse_download_file() {
+        let parsed = parse(args(&["--download-file", "skyrim", "1", "2"])).unwrap();
+        assert_eq!(parsed.download_file, Some(("skyrim".to_string(), 1, 2)));
+
+        assert!(parse(args(&["--download-file", "skyrim", "1"])).is_err());
+        assert!(parse(args(&["--download-file", "skyrim", "mod", "2"])).is_err());
+        assert!(parse(args(&["--download-file", "skyrim", "1", "2", "nxm://skyrim/mods/1/files/2"])).is_err());
+    }
+
+    #[test]
+    fn parse_version() {
+        assert!(parse(args(&["--version"])).unwrap().show_version);
+        assert!(parse(args(&["-V"])).unwrap().show_version);
+        assert!(!parse(args(&["-d"])).unwrap().show_version);
+    }
+
+    #[test]
+    fn parse_queue() {
+        let parsed = parse(args(&["--queue", "nxm://skyrim/mods/1/files/2"])).unwrap();
+        assert_eq!(parsed.queue.map(|nxm| nxm.file_id), Some(2));
+        assert_eq!(parsed.nxm, None);
+
+        assert!(parse(args(&["--queue", "https://nexusmods.com"])).is_err());
+        assert!(parse(args(&["--queue", "nxm://skyrim/mods/1/files/2", "-d"])).is_err());
+        assert!(parse(args(&["--queue", "nxm://skyrim/mods/1"])).is_err());
+    }
+
+    #[test]
+    fn parse_queue_file() {
+        let parsed = parse(args(&["--queue-file", "modlist.txt"])).unwrap();
+        assert_eq!(parsed.queue_file, Some(PathBuf::from("modlist.txt")));
+
+        assert!(parse(args(&["--queue-file"])).is_err());
+        assert!(parse(args(&["--queue-file", "modlist.txt", "-d"])).is_err());
+        assert!(parse(args(&["--queue-file", "modlist.txt", "nxm://skyrim/mods/1/files/2"])).is_err());
+    }
+
+    #[test]
+    fn parse_list_downloads() {
+        let parsed = parse(args(&["--list-downloads", "--json"])).unwrap();
+        assert!(parsed.list_downloads);
+        assert!(parsed.json);
+
+        assert!(parse(args(&["--json"])).is_err());
+    }
+
+    #[test]
+    fn parse_command() {
+        assert_eq!(parse(args(&["--command", "pause-all"])).unwrap().command, Some(SocketCommand::PauseAll));
+        assert!(parse(args(&["--command", "pause"])).is_err());
+        assert!(parse(args(&["--command"])).is_err());
+    }
+
+    #[test]
+    fn parse_queue_export() {
+        let parsed = parse(args(&["--export-queue", "queue.json"])).unwrap();
+        assert_eq!(parsed.export_queue, Some(PathBuf::from("queue.json")));
+        let parsed = parse(args(&["--import-queue", "queue.json", "-d"])).unwrap();
+        assert_eq!(parsed.import_queue, Some(PathBuf::from("queue.json")));
+
+        assert!(parse(args(&["--export-queue"])).is_err());
+        assert!(parse(args(&["--import-queue", "--profile", "skyrim"])).is_err());
+    }
+
+    #[test]
+    fn parse_import() {
+        let parsed = parse(args(&["--import", "morrowind"])).unwrap();
+        assert!(parsed.import);
+        assert_eq!(parsed.game.as_deref(), Some("morrowind"));
+
+        let parsed = parse(args(&["--import", "-d"])).unwrap();
+        assert!(parsed.import);
+        assert_eq!(parsed.game, None);
+        assert!(!parsed.is_interactive);
+    }
+
+    #[test]
+    fn parse_disk_usage() {
+        assert_eq!(parse(args(&["--disk-usage"])).unwrap().disk_usage, Some(UsageSort::Size));
+        assert_eq!(parse(args(&["--disk-usage", "name"])).unwrap().disk_usage, Some(UsageSort::Name));
+        let parsed = parse(args(&["--disk-usage", "--profile", "skyrim"])).unwrap();
+        assert_eq!((parsed.disk_usage, parsed.profile.as_deref()), (Some(UsageSort::Size), Some("skyrim")));
+        assert!(parse(args(&["--prune"])).unwrap().prune);
+    }
+
+    #[test]
+    fn game_slug() {
+        assert!(is_valid_game_slug("skyrimspecialedition"));
+        assert!(is_valid_game_slug("fallout4"));
+        assert!(!is_valid_game_slug(""));
+        assert!(!is_valid_game_slug("Skyrim Special Edition"));
+        assert!(!is_valid_game_slug("../morrowind"));
+    }
+
+    #[test]
+    fn parse_stop() {
+        assert!(parse(args(&["--stop"])).unwrap().stop);
+        assert!(!parse(args(&[])).unwrap().stop);
+    }
+
+    #[test]
+    fn parse_log_level() {
+        let parsed = parse(args(&["-d", "--log-level", "debug"])).unwrap();
+        assert_eq!(parsed.log_level, Some(LogLevel::Debug));
+        assert!(!parsed.is_interactive);
+
+        assert!(parse(args(&["--log-level"])).is_err());
+        assert!(parse(args(&["--log-level", "verbose"])).is_err());
+    }
+}
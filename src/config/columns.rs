@@ -0,0 +1,114 @@
+use serde::Deserialize;
+
+/* The columns that can be shown in the tables, configured with file_table_columns and download_table_columns.
+ * The widths are relative to each other, so hiding a column leaves its space to the remaining ones. */
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum FileColumnId {
+    Name,
+    Game, // hidden while there are fewer games than game_column_threshold
+    ModName,
+    Category,
+    ModId,
+    Flags,
+    Version,
+    Status,
+    Installed,
+    Endorsed,
+    Tracked,
+}
+
+impl FileColumnId {
+    pub const DEFAULT: &'static [Self] = &[
+        Self::Name,
+        Self::Game,
+        Self::ModName,
+        Self::Category,
+        Self::ModId,
+        Self::Flags,
+        Self::Version,
+        Self::Status,
+        Self::Installed,
+        Self::Endorsed,
+        Self::Tracked,
+    ];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Self::Name => "Name",
+            Self::Game => "Game",
+            Self::ModName => "Mod",
+            Self::Category => "Category",
+            Self::ModId => "ModId",
+            Self::Flags => "Flags",
+            Self::Version => "Version",
+            Self::Status => "Status",
+            Self::Installed => "Installed",
+            Self::Endorsed => "Endorsed",
+            Self::Tracked => "Tracked",
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        match self {
+            Self::Name => 6,
+            // Configurable with game_column_width
+            Self::Game => 2,
+            Self::ModName => 4,
+            Self::Category => 2,
+            Self::ModId => 1,
+            Self::Flags => 1,
+            Self::Version => 2,
+            Self::Status => 3,
+            Self::Installed => 2,
+            Self::Endorsed => 1,
+            Self::Tracked => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum DownloadColumnId {
+    Filename,
+    Progress,
+    ProgressBar,
+    Status,
+}
+
+impl DownloadColumnId {
+    pub const DEFAULT: &'static [Self] = &[Self::Filename, Self::Progress, Self::ProgressBar, Self::Status];
+
+    pub fn header(&self) -> &'static str {
+        match self {
+            Self::Filename => "Filename",
+            Self::Progress => "Progress",
+            Self::ProgressBar => "",
+            Self::Status => "Status",
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        match self {
+            Self::Filename => 3,
+            Self::Progress => 1,
+            Self::ProgressBar => 1,
+            Self::Status => 1,
+        }
+    }
+}
+
+/* How the file table groups its rows, configured with file_table_grouping. Grouped rows are shown under collapsible
+ * headers. */
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum FileGrouping {
+    Flat,
+    Game,
+    Mod, // by game, and then by mod within each game
+}
+
+// How the Installed column shows when each file was downloaded, configured with date_format
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum DateFormat {
+    Absolute, // "2024-03-05"
+    Relative, // "2 d ago"
+}
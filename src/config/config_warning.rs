@@ -0,0 +1,38 @@
+use std::fmt;
+use std::path::PathBuf;
+
+// Settings that are likely to cause errors later on. Unlike ConfigError, these don't stop the program from starting.
+#[derive(Debug, PartialEq)]
+pub enum ConfigWarning {
+    DownloadDirNotDirectory { path: PathBuf },
+    DownloadDirNotWritable { path: PathBuf },
+    ApiKeyMalformed,
+    LogDirNotWritable { path: PathBuf },
+    InvalidHeader { name: String },
+    StagingDirMissing,
+}
+
+impl fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigWarning::DownloadDirNotDirectory { path } => {
+                write!(f, "The download directory {} is not a directory.", path.display())
+            }
+            ConfigWarning::DownloadDirNotWritable { path } => {
+                write!(f, "The download directory {} is not writable, downloads will fail.", path.display())
+            }
+            ConfigWarning::ApiKeyMalformed => {
+                write!(f, "The API key doesn't look like a Nexus API key. Check it for typos or extra characters.")
+            }
+            ConfigWarning::LogDirNotWritable { path } => {
+                write!(f, "{} is not writable, messages won't be saved to the log file.", path.display())
+            }
+            ConfigWarning::InvalidHeader { name } => {
+                write!(f, "The extra header \"{name}\" is not a valid HTTP header and won't be sent.")
+            }
+            ConfigWarning::StagingDirMissing => {
+                write!(f, "Some downloads are set to be staged, but staging_dir isn't set. They'll be kept instead.")
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use super::Config;
+
+use serde::Deserialize;
+
+/* What is done with a file once it has been downloaded, configured per game or file category with download_actions,
+ * and with default_download_action for the rest. */
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq)]
+pub enum DownloadAction {
+    Keep,
+    Extract, // into a directory named after the archive, next to it
+    Stage,   // moved to <staging_dir>/<game>, along with its metadata
+}
+
+impl Config {
+    /* The most specific rule in download_actions decides: "<game>/<category>", then "<game>", then "<category>". The
+     * categories are those of the Nexus, e.g. main or optional. Keys are case insensitive. */
+    pub fn download_action(&self, game: &str, category: Option<&str>) -> DownloadAction {
+        let mut keys = vec![];
+        if let Some(category) = category {
+            keys.push(format!("{game}/{category}"));
+        }
+        keys.push(game.to_string());
+        keys.extend(category.map(str::to_string));
+
+        keys.iter()
+            .find_map(|key| {
+                self.download_actions.iter().find(|(rule, _)| rule.eq_ignore_ascii_case(key)).map(|(_, action)| *action)
+            })
+            .unwrap_or(self.default_download_action)
+    }
+}
@@ -0,0 +1,151 @@
+use super::ConfigBuilder;
+
+use serde::Deserialize;
+use toml::Value;
+
+/* Migrations of the config file from each schema version to the next, where the first one migrates version 1 to 2.
+ * Config files without a schema_version are version 1. A migration only has to handle the settings that it changes. */
+const MIGRATIONS: &[(&str, Migration)] = &[("v1 to v2", migrate_v1_to_v2)];
+
+type Migration = fn(&mut Value);
+
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+// Brings a parsed config file up to SCHEMA_VERSION by applying the migrations after its own version in order
+pub struct ConfigMigrator {
+    pub version: u32,
+}
+
+impl ConfigMigrator {
+    pub fn new(value: &Value) -> Result<Self, String> {
+        let version = match value.get("schema_version") {
+            None => 1,
+            Some(version) => match version.as_integer().and_then(|v| u32::try_from(v).ok()) {
+                Some(version) if version > 0 => version,
+                _ => return Err(format!("schema_version must be a positive number, not {version}")),
+            },
+        };
+        Ok(Self { version })
+    }
+
+    pub fn needs_migration(&self) -> bool {
+        self.version < SCHEMA_VERSION
+    }
+
+    // Written by a newer version of dmodman, which this one can't migrate back
+    pub fn is_newer(&self) -> bool {
+        self.version > SCHEMA_VERSION
+    }
+
+    /* Returns the names of the migrations that were applied. Fails if the migrated config can't be loaded, in which
+     * case the value is left half-migrated. */
+    pub fn migrate(&self, value: &mut Value) -> Result<Vec<&'static str>, String> {
+        let table = value.as_table_mut().ok_or("The config isn't a table of settings.")?;
+        table.remove("schema_version");
+        let mut applied = vec![];
+        for (name, migration) in MIGRATIONS.iter().skip(self.version as usize - 1) {
+            migration(value);
+            applied.push(*name);
+        }
+        if let Some(table) = value.as_table_mut() {
+            table.insert("schema_version".to_string(), Value::Integer(SCHEMA_VERSION.into()));
+        }
+        ConfigBuilder::deserialize(value.clone()).map_err(|e| e.to_string())?;
+        Ok(applied)
+    }
+}
+
+// Adds the current schema_version to the contents of a config file, see insert_setting()
+pub fn insert_schema_version(contents: &str) -> Option<String> {
+    insert_setting(contents, "schema_version", Value::Integer(SCHEMA_VERSION.into()))
+}
+
+/* Adds a top-level setting to the contents of a config file, without touching the rest of it. The setting goes before
+ * the first table header, since a key after it would belong to that table. Returns None if the setting doesn't end up
+ * at the top level, e.g. because a line in a multi-line string looks like a table header, or if it's already set. */
+pub fn insert_setting(contents: &str, key: &str, value: Value) -> Option<String> {
+    let line = format!("{key} = {value}\n");
+    let mut offset = 0;
+    let mut header = None;
+    for l in contents.split_inclusive('\n') {
+        if l.trim_start().starts_with('[') && !l.contains('=') {
+            header = Some(offset);
+            break;
+        }
+        offset += l.len();
+    }
+    let inserted = match header {
+        Some(offset) => format!("{}{line}\n{}", &contents[..offset], &contents[offset..]),
+        None if contents.is_empty() || contents.ends_with('\n') => format!("{contents}\n{line}"),
+        None => format!("{contents}\n\n{line}"),
+    };
+    let parsed: Value = toml::from_str(&inserted).ok()?;
+    (parsed.get(key)? == &value).then_some(inserted)
+}
+
+// The ModName column was added, and is shown after the Game column in configs that list their columns
+fn migrate_v1_to_v2(toml: &mut Value) {
+    let columns = match toml.get_mut("file_table_columns").and_then(Value::as_array_mut) {
+        Some(columns) => columns,
+        None => return,
+    };
+    if columns.iter().any(|c| c.as_str() == Some("ModName")) {
+        return;
+    }
+    if let Some(i) = columns.iter().position(|c| c.as_str() == Some("Game")) {
+        columns.insert(i + 1, Value::String("ModName".to_string()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{insert_schema_version, insert_setting, ConfigMigrator, SCHEMA_VERSION};
+    use toml::Value;
+
+    #[test]
+    fn migrate_columns() {
+        let mut value: Value = toml::from_str("file_table_columns = [\"Name\", \"Game\", \"Version\"]").unwrap();
+        let migrator = ConfigMigrator::new(&value).unwrap();
+        assert_eq!(migrator.version, 1);
+        assert_eq!(migrator.migrate(&mut value).unwrap(), vec!["v1 to v2"]);
+        let columns: Vec<&str> =
+            value["file_table_columns"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+        assert_eq!(columns, vec!["Name", "Game", "ModName", "Version"]);
+        assert_eq!(value["schema_version"].as_integer(), Some(SCHEMA_VERSION.into()));
+        assert!(!ConfigMigrator::new(&value).unwrap().needs_migration());
+    }
+
+    #[test]
+    fn schema_version_before_tables() {
+        let contents = "apikey = \"1234\"\n\n# Headers\n[extra_headers]\nX-Test = \"1\"\n";
+        let inserted = insert_schema_version(contents).unwrap();
+        let value: Value = toml::from_str(&inserted).unwrap();
+        assert_eq!(value["schema_version"].as_integer(), Some(SCHEMA_VERSION.into()));
+        assert!(value["extra_headers"].get("schema_version").is_none());
+        assert!(inserted.ends_with("[extra_headers]\nX-Test = \"1\"\n"));
+        assert!(!ConfigMigrator::new(&value).unwrap().needs_migration());
+
+        let inserted = insert_schema_version("apikey = \"1234\"").unwrap();
+        assert_eq!(inserted, format!("apikey = \"1234\"\n\nschema_version = {SCHEMA_VERSION}\n"));
+
+        let game = || Value::String("skyrim \"se\"".to_string());
+        let inserted = insert_setting("[download_actions]\nmorrowind = \"Keep\"\n", "default_game", game()).unwrap();
+        let value: Value = toml::from_str(&inserted).unwrap();
+        assert_eq!(value["default_game"], game());
+        // A setting that's already there would be a duplicate key
+        assert!(insert_setting(&inserted, "default_game", game()).is_none());
+    }
+
+    #[test]
+    fn failed_migration() {
+        let value: Value = toml::from_str("schema_version = \"two\"").unwrap();
+        assert!(ConfigMigrator::new(&value).is_err());
+
+        // The migrated config has to load, which an invalid column name doesn't
+        let mut value: Value = toml::from_str("file_table_columns = [\"Game\", \"Nonexistent\"]").unwrap();
+        assert!(ConfigMigrator::new(&value).unwrap().migrate(&mut value).is_err());
+
+        let value: Value = toml::from_str("schema_version = 1000").unwrap();
+        assert!(ConfigMigrator::new(&value).unwrap().is_newer());
+    }
+}
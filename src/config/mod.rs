@@ -1,44 +1,130 @@
+pub mod columns;
 pub mod config_error;
+pub mod config_warning;
+pub mod download_action;
+pub mod migrate;
 pub mod paths;
 
+pub use columns::{DateFormat, DownloadColumnId, FileColumnId, FileGrouping};
 pub use config_error::ConfigError;
+pub use config_warning::ConfigWarning;
+pub use download_action::DownloadAction;
+use migrate::{insert_schema_version, insert_setting};
+pub use migrate::{ConfigMigrator, SCHEMA_VERSION};
 pub use paths::PathType;
 
+use crate::logger::{LogLevel, Logger};
 use crate::util;
 
+use std::collections::HashMap;
 use std::env;
 use std::io::prelude::Write;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use std::{fs, fs::File};
 
 use serde::Deserialize;
 
-/* The ConfigBuilder is loaded based on the config file, or initialized with empty values. It's used for deserializing
- * and setting config values that might be missing. We then turn it into a proper Config, which let's us avoid wrapping
- * most settings inside an Option. */
-#[derive(Deserialize)]
-pub struct ConfigBuilder {
+/* Defines the ConfigBuilder from its settings, each of which is optional, along with merge(), so that a new setting
+ * only has to be added here and to Config. */
+macro_rules! config_builder {
+    ($($(#[$attr:meta])* $field:ident: $type:ty,)+) => {
+        /* The ConfigBuilder is loaded based on the config file, or initialized with empty values. It's used for
+         * deserializing and setting config values that might be missing. We then turn it into a proper Config, which
+         * let's us avoid wrapping most settings inside an Option. */
+        #[derive(Default, Deserialize)]
+        pub struct ConfigBuilder {
+            $($(#[$attr])* pub $field: Option<$type>,)+
+            // Not a setting, but the file the config was loaded from, so that it's reloaded from the same file
+            #[serde(skip)]
+            pub config_path: Option<PathBuf>,
+        }
+
+        impl ConfigBuilder {
+            // The settings that are set in the overrides replace the current ones
+            fn merge(mut self, overrides: ConfigBuilder) -> Self {
+                $(self.$field = overrides.$field.or(self.$field);)+
+                self
+            }
+        }
+    };
+}
+
+config_builder! {
     // API key can be stored in either config or separate file (when generated for user). Config takes precedence.
-    pub apikey: Option<String>,
-    pub profile: Option<String>,
-    pub download_dir: Option<String>,
+    apikey: String,
+    profile: String,
+    download_dir: String,
+    file_manager: String,
+    browser_command: String,
+    file_table_columns: Vec<FileColumnId>,
+    download_table_columns: Vec<DownloadColumnId>,
+    file_table_grouping: FileGrouping,
+    date_format: DateFormat,
+    desktop_notifications: bool,
+    proxy_url: String,
+    proxy_username: String,
+    proxy_password: String,
+    connect_timeout: u64,
+    read_timeout: u64,
+    api_timeout: u64,
+    api_url: String,
+    log_level: LogLevel,
+    max_log_size: u64,
+    socket_path: PathBuf,
+    reserved_disk_space: u64,
+    max_concurrent_downloads: usize,
+    default_game: String,
+    api_game_slug: String,
+    verify_resume: bool,
+    update_check_interval: u64,
+    post_download_command: String,
+    extra_headers: HashMap<String, String>,
+    max_download_dir_size: u64,
+    keep_versions: usize,
+    game_column_threshold: usize,
+    game_column_width: u32,
+    game_download_dirs: HashMap<String, String>,
+    game_data_dirs: HashMap<String, String>,
+    status_endpoint: bool,
+    open_command: Vec<String>,
+    temp_download_dir: String,
+    download_actions: HashMap<String, DownloadAction>,
+    default_download_action: DownloadAction,
+    staging_dir: String,
 }
 
 impl ConfigBuilder {
-    pub fn default() -> Self {
-        Self {
-            apikey: None,
-            profile: None,
-            download_dir: None,
-        }
+    // Only used by unit tests, the program itself loads the file that may have been given with --config
+    #[allow(dead_code)]
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from_path(&config_file())
     }
 
-    pub fn load() -> Result<Self, ConfigError> {
+    // config_file() by default, or the file given with --config, for example to keep separate setups apart
+    pub fn load_from_path(path: &Path) -> Result<Self, ConfigError> {
         let mut contents = String::new();
-        let mut f = File::open(config_file())?;
+        let mut f = File::open(path)?;
         f.read_to_string(&mut contents)?;
-        Ok(toml::from_str(&contents)?)
+        let mut builder: ConfigBuilder = toml::from_str(&contents)?;
+        builder.config_path = Some(path.to_path_buf());
+        Ok(builder)
+    }
+
+    /* Profiles are configured in <config dir>/profiles/<profile>.toml, and their settings override those in config.toml.
+     * A profile doesn't need a config file. Each profile has its own subdirectory in the download directory. */
+    pub fn load_profile(mut self, profile: &str) -> Result<Self, ConfigError> {
+        match fs::read_to_string(profile_file(profile)) {
+            Ok(contents) => {
+                let overrides: ConfigBuilder = toml::from_str(&contents)?;
+                self = self.merge(overrides);
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.profile = Some(profile.to_string());
+        Ok(self)
     }
 
     /* This isn't used anymore, but demonstrates how the builder pattern could be used if it were.
@@ -69,7 +155,68 @@ impl ConfigBuilder {
 pub struct Config {
     pub apikey: Option<String>,
     pub profile: Option<String>,
-    pub download_dir: String,
+    // config.toml in the config directory, unless another file was given with --config
+    pub config_path: PathBuf,
+    // Shared by all clones, so that a reloaded download_dir is used everywhere at once, see download_dir()
+    download_dir: Arc<RwLock<String>>,
+    pub file_manager: Option<String>,
+    // Opens URLs instead of xdg-open, with {url} as a placeholder
+    pub browser_command: Option<String>,
+    pub file_table_columns: Vec<FileColumnId>,
+    pub download_table_columns: Vec<DownloadColumnId>,
+    pub file_table_grouping: FileGrouping,
+    pub date_format: DateFormat,
+    pub desktop_notifications: bool,
+    pub proxy_url: Option<String>,
+    pub proxy_username: Option<String>,
+    pub proxy_password: Option<String>,
+    // In seconds
+    pub connect_timeout: u64,
+    pub read_timeout: u64,
+    // The whole API request, including the response body
+    pub api_timeout: u64,
+    pub api_url: Option<String>,
+    pub log_level: LogLevel,
+    // In KiB
+    pub max_log_size: u64,
+    pub socket_path: Option<PathBuf>,
+    // In MiB
+    pub reserved_disk_space: u64,
+    // 0 means no limit
+    pub max_concurrent_downloads: usize,
+    // Used by commands that need a game when none is given
+    pub default_game: Option<String>,
+    // Replaces the game in API requests, for games whose nxm:// name differs from their domain name
+    pub api_game_slug: Option<String>,
+    pub verify_resume: bool,
+    // In minutes, 0 disables checking updates on a timer
+    pub update_check_interval: u64,
+    // Run after each finished download, with placeholders for the file
+    pub post_download_command: Option<String>,
+    // Sent with every request, both to the API and for downloads
+    pub extra_headers: HashMap<String, String>,
+    // In MiB, 0 means no limit
+    pub max_download_dir_size: u64,
+    // How many downloaded versions of each file are kept when deleting old versions, at least 1
+    pub keep_versions: usize,
+    // The Game column is only shown when the files are from at least this many games
+    pub game_column_threshold: usize,
+    pub game_column_width: u32,
+    // Overrides the default <download_dir>/<game> for each game
+    pub game_download_dirs: HashMap<String, String>,
+    // Where extracted archives of each game are deployed, instead of next to the archive
+    pub game_data_dirs: HashMap<String, String>,
+    // Whether the socket answers status queries with the state of the downloads and updates
+    pub status_endpoint: bool,
+    // The program and arguments that open URLs and directories, with the target appended
+    pub open_command: Vec<String>,
+    // Where partial downloads are kept until they're complete, instead of the download directory
+    pub temp_download_dir: Option<String>,
+    // What is done with finished downloads, by game, category or both
+    pub download_actions: HashMap<String, DownloadAction>,
+    pub default_download_action: DownloadAction,
+    // Where downloads with the Stage action are moved to
+    pub staging_dir: Option<String>,
 }
 
 impl Config {
@@ -85,11 +232,147 @@ impl Config {
             }
         };
 
+        // A table without columns isn't useful, so an empty list also falls back to the defaults
+        let file_table_columns = match config.file_table_columns {
+            Some(columns) if !columns.is_empty() => columns,
+            _ => FileColumnId::DEFAULT.to_vec(),
+        };
+        let download_table_columns = match config.download_table_columns {
+            Some(columns) if !columns.is_empty() => columns,
+            _ => DownloadColumnId::DEFAULT.to_vec(),
+        };
+
         Self {
             apikey: config.apikey,
             profile: config.profile,
-            download_dir,
+            config_path: config.config_path.unwrap_or_else(config_file),
+            download_dir: Arc::new(RwLock::new(download_dir)),
+            file_manager: config.file_manager,
+            browser_command: config.browser_command,
+            file_table_columns,
+            download_table_columns,
+            file_table_grouping: config.file_table_grouping.unwrap_or(FileGrouping::Flat),
+            date_format: config.date_format.unwrap_or(DateFormat::Absolute),
+            desktop_notifications: config.desktop_notifications.unwrap_or(false),
+            proxy_url: config.proxy_url,
+            proxy_username: config.proxy_username,
+            proxy_password: config.proxy_password,
+            connect_timeout: config.connect_timeout.unwrap_or(15),
+            read_timeout: config.read_timeout.unwrap_or(30),
+            api_timeout: config.api_timeout.unwrap_or(60),
+            api_url: config.api_url,
+            log_level: config.log_level.unwrap_or(LogLevel::Info),
+            max_log_size: config.max_log_size.unwrap_or(1024),
+            socket_path: config.socket_path,
+            reserved_disk_space: config.reserved_disk_space.unwrap_or(0),
+            max_concurrent_downloads: config.max_concurrent_downloads.unwrap_or(0),
+            default_game: config.default_game,
+            api_game_slug: config.api_game_slug,
+            verify_resume: config.verify_resume.unwrap_or(false),
+            update_check_interval: config.update_check_interval.unwrap_or(0),
+            post_download_command: config.post_download_command,
+            extra_headers: config.extra_headers.unwrap_or_default(),
+            max_download_dir_size: config.max_download_dir_size.unwrap_or(0),
+            keep_versions: config.keep_versions.unwrap_or(1).max(1),
+            game_column_threshold: config.game_column_threshold.unwrap_or(2),
+            game_column_width: config.game_column_width.unwrap_or(FileColumnId::Game.width()),
+            game_download_dirs: config.game_download_dirs.unwrap_or_default(),
+            game_data_dirs: config.game_data_dirs.unwrap_or_default(),
+            status_endpoint: config.status_endpoint.unwrap_or(false),
+            open_command: config.open_command.unwrap_or_else(|| vec!["xdg-open".to_string()]),
+            temp_download_dir: config.temp_download_dir,
+            download_actions: config.download_actions.unwrap_or_default(),
+            default_download_action: config.default_download_action.unwrap_or(DownloadAction::Keep),
+            staging_dir: config.staging_dir,
+        }
+    }
+
+    /* Reads the config files again so that settings can be changed without restarting. The config file and profile
+     * chosen on startup are kept. Returns the new config, along with the names of changed settings that only take
+     * effect after a restart. */
+    pub fn reload(&self) -> Result<(Config, Vec<&'static str>), ConfigError> {
+        let mut builder = match ConfigBuilder::load_from_path(&self.config_path) {
+            Ok(cb) => cb,
+            Err(ConfigError::IOError { source }) if source.kind() == ErrorKind::NotFound => ConfigBuilder::default(),
+            Err(e) => return Err(e),
+        };
+        builder.config_path = Some(self.config_path.clone());
+        if let Some(profile) = &self.profile {
+            builder = builder.load_profile(profile)?;
+        }
+        Ok(self.merge_reloaded(builder.build()?))
+    }
+
+    /* A new download_dir applies immediately, since all clones of the config share it, and the caller reloads the file
+     * index. The settings in the keep_until_restart! list below keep their current values until restart, because
+     * they're only read when the parts that use them are set up, like the HTTP client, the tables and the socket. */
+    fn merge_reloaded(&self, mut reloaded: Config) -> (Config, Vec<&'static str>) {
+        let mut needs_restart = vec![];
+        let download_dir = reloaded.download_dir.read().unwrap().clone();
+        *self.download_dir.write().unwrap() = download_dir;
+        reloaded.download_dir = self.download_dir.clone();
+        // Keeps the current values of the settings, and reports them under the name if the reloaded config changed them
+        macro_rules! keep_until_restart {
+            ($name:literal: $($field:ident),+) => {
+                if ($(&reloaded.$field,)+) != ($(&self.$field,)+) {
+                    needs_restart.push($name);
+                    $(reloaded.$field = self.$field.clone();)+
+                }
+            };
+        }
+        keep_until_restart!("game_download_dirs": game_download_dirs);
+        keep_until_restart!("game_data_dirs": game_data_dirs);
+        keep_until_restart!("temp_download_dir": temp_download_dir);
+        keep_until_restart!("status_endpoint": status_endpoint);
+        keep_until_restart!("file_table_columns": file_table_columns);
+        keep_until_restart!("download_table_columns": download_table_columns);
+        keep_until_restart!("game_column": game_column_threshold, game_column_width);
+        keep_until_restart!("file_table_grouping": file_table_grouping);
+        keep_until_restart!("date_format": date_format);
+        keep_until_restart!("proxy": proxy_url, proxy_username, proxy_password);
+        keep_until_restart!("timeouts": connect_timeout, read_timeout, api_timeout);
+        keep_until_restart!("api_url": api_url);
+        keep_until_restart!("logging": log_level, max_log_size);
+        keep_until_restart!("socket_path": socket_path);
+        keep_until_restart!("update_check_interval": update_check_interval);
+        keep_until_restart!("extra_headers": extra_headers);
+        keep_until_restart!("download_actions": download_actions, default_download_action, staging_dir);
+        (reloaded, needs_restart)
+    }
+
+    /* Checks for settings that would only cause errors later, such as a download directory that can't be written to.
+     * Directories that don't exist yet are created when needed, so their closest existing parent is checked instead. */
+    pub fn validate(&self) -> Vec<ConfigWarning> {
+        let mut warnings = vec![];
+        let download_dir = self.download_dir();
+        if download_dir.exists() && !download_dir.is_dir() {
+            warnings.push(ConfigWarning::DownloadDirNotDirectory { path: download_dir });
+        } else if !is_writable(&download_dir) {
+            warnings.push(ConfigWarning::DownloadDirNotWritable { path: download_dir });
+        }
+        if self.apikey.as_deref().is_some_and(|apikey| !is_valid_apikey(apikey)) {
+            warnings.push(ConfigWarning::ApiKeyMalformed);
         }
+        let log_dir = config_dir();
+        if !is_writable(&log_dir) {
+            warnings.push(ConfigWarning::LogDirNotWritable { path: log_dir });
+        }
+        for (name, value) in &self.extra_headers {
+            if !is_valid_header(name, value) {
+                warnings.push(ConfigWarning::InvalidHeader { name: name.clone() });
+            }
+        }
+        let stages =
+            self.download_actions.values().chain([&self.default_download_action]).any(|a| *a == DownloadAction::Stage);
+        if stages && self.staging_dir.is_none() {
+            warnings.push(ConfigWarning::StagingDirMissing);
+        }
+        warnings
+    }
+
+    // An Authorization header can be used instead of an API key
+    pub fn has_credentials(&self) -> bool {
+        self.apikey.is_some() || self.extra_headers.keys().any(|name| name.eq_ignore_ascii_case("authorization"))
     }
 
     pub fn cache_dir(&self) -> PathBuf {
@@ -104,19 +387,152 @@ impl Config {
     }
 
     pub fn download_dir(&self) -> PathBuf {
-        let mut path = PathBuf::from(&self.download_dir);
+        let mut path = PathBuf::from(&*self.download_dir.read().unwrap());
         if let Some(profile) = &self.profile {
             path.push(profile);
         }
         path
     }
 
+    // The Unix socket that the running instance listens on for nxm:// URLs and commands
+    pub fn socket_path(&self) -> PathBuf {
+        match &self.socket_path {
+            Some(path) => path.clone(),
+            None => default_socket_path(env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from)),
+        }
+    }
+
+    /* Migrates the config file to the current schema_version before it's loaded. If only the version number changes,
+     * it's inserted before the first table, which keeps the comments in the file. Otherwise the original is kept as
+     * <file>.bak and the migrated settings are written without comments. If the migration fails, the file is backed up
+     * and replaced with the example config. Files that aren't valid TOML are left alone, since loading them reports the
+     * error. */
+    pub fn migrate(path: &Path, logger: &Logger) {
+        let contents = if let Ok(contents) = fs::read_to_string(path) {
+            contents
+        } else {
+            return;
+        };
+        let mut value: toml::Value = if let Ok(value) = toml::from_str(&contents) {
+            value
+        } else {
+            return;
+        };
+        let file = path.display();
+        let backup = path.with_extension("toml.bak");
+
+        let migrator = ConfigMigrator::new(&value);
+        if let Ok(migrator) = &migrator {
+            if migrator.is_newer() {
+                logger.warn(format!(
+                    "{file} has schema_version {}, which is newer than this version of dmodman supports ({}).",
+                    migrator.version, SCHEMA_VERSION
+                ));
+            }
+            if !migrator.needs_migration() {
+                return;
+            }
+        }
+        let mut original = value.clone();
+        match migrator.and_then(|migrator| migrator.migrate(&mut value)) {
+            Ok(applied) => {
+                let mut migrated = value.clone();
+                for v in [&mut original, &mut migrated] {
+                    if let Some(table) = v.as_table_mut() {
+                        table.remove("schema_version");
+                    }
+                }
+                let result = match insert_schema_version(&contents) {
+                    Some(inserted) if original == migrated => fs::write(path, inserted),
+                    _ => fs::copy(path, &backup)
+                        .and_then(|_| fs::write(path, toml::to_string_pretty(&value).map_err(std::io::Error::other)?)),
+                };
+                match result {
+                    Ok(()) => logger.log(format!("Migrated {file} with the migrations {}.", applied.join(", "))),
+                    Err(e) => logger.warn(format!("Unable to save the migrated {file}: {e}")),
+                }
+            }
+            Err(e) => {
+                let result = fs::copy(path, &backup).and_then(|_| fs::write(path, DEFAULT_CONFIG));
+                match result {
+                    Ok(()) => logger.error(format!(
+                        "Unable to migrate {file}, so it was replaced with the defaults. The original is in {}: {e}",
+                        backup.display()
+                    )),
+                    Err(write_error) => logger.error(format!("Unable to migrate {file}: {e} ({write_error})")),
+                }
+            }
+        }
+    }
+
     pub fn save_apikey(&self) -> Result<(), std::io::Error> {
         fs::create_dir_all(config_dir())?;
         let mut f = File::create(apikey_file())?;
         f.write_all(self.apikey.as_ref().unwrap().as_bytes())?;
         f.flush()
     }
+
+    // Added to config.toml before its first table, so that the user's comments in it are kept
+    pub fn save_default_game(&mut self, game: &str) -> Result<(), std::io::Error> {
+        if let Some(dir) = self.config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = match fs::read_to_string(&self.config_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let contents = insert_setting(&contents, "default_game", toml::Value::String(game.to_string()))
+            .ok_or_else(|| std::io::Error::new(ErrorKind::InvalidData, "unable to add default_game to the config"))?;
+        fs::write(&self.config_path, contents)?;
+        self.default_game = Some(game.to_string());
+        Ok(())
+    }
+}
+
+// The example config, in which every setting is commented out
+const DEFAULT_CONFIG: &str = include_str!("../../config.toml");
+
+// Tries to create a file in the directory, which is more reliable than looking at the permission bits
+fn is_writable(dir: &Path) -> bool {
+    let existing = match dir.ancestors().find(|p| p.exists()) {
+        Some(p) => p,
+        None => return false,
+    };
+    let test_file = existing.join(format!(".dmodman-write-test-{}", std::process::id()));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&test_file) {
+        Ok(_) => fs::remove_file(test_file).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/* Nexus API keys are long base64 strings, with "--" between their parts. Copy-pasting sometimes adds quotes or cuts off
+ * the end, which the API only reports as an invalid key. */
+fn is_valid_apikey(apikey: &str) -> bool {
+    apikey.len() >= 32 && apikey.chars().all(|c| c.is_ascii_alphanumeric() || "+/=-_".contains(c))
+}
+
+/* Header names are tokens as defined in RFC 9110, and values can't contain control characters other than tabs. Invalid
+ * headers are left out of requests. */
+fn is_valid_header(name: &str, value: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c))
+        && value.chars().all(|c| c == '\t' || !c.is_control())
+}
+
+/* The runtime directory is private to the user, which avoids conflicts between users. Without it, the user id keeps
+ * the sockets of different users apart in /tmp. */
+fn default_socket_path(runtime_dir: Option<PathBuf>) -> PathBuf {
+    match runtime_dir {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join("dmodman.sock"),
+        _ => {
+            extern "C" {
+                fn getuid() -> u32;
+            }
+            let uid = unsafe { getuid() };
+            PathBuf::from(format!("/tmp/dmodman-{uid}.sock"))
+        }
+    }
 }
 
 pub fn config_dir() -> PathBuf {
@@ -137,6 +553,27 @@ pub fn config_file() -> PathBuf {
     path
 }
 
+pub fn profile_file(profile: &str) -> PathBuf {
+    let mut path = config_dir();
+    path.push("profiles");
+    path.push(format!("{profile}.toml"));
+    path
+}
+
+// The profiles that have a config file, whose directories are in the download directory of the default profile
+pub fn profile_names() -> Vec<String> {
+    let mut path = config_dir();
+    path.push("profiles");
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_string_lossy().strip_suffix(".toml").map(str::to_string))
+        .collect()
+}
+
 pub fn apikey_file() -> PathBuf {
     let mut path = config_dir();
     path.push("apikey");
@@ -152,7 +589,11 @@ pub fn try_read_apikey() -> Result<String, std::io::Error> {
 
 #[cfg(test)]
 mod tests {
-    use crate::config::{ConfigBuilder, ConfigError};
+    use super::{default_socket_path, is_valid_apikey, is_valid_header};
+    use crate::config::{
+        ConfigBuilder, ConfigError, ConfigWarning, DownloadAction, DownloadColumnId, FileColumnId, FileGrouping,
+    };
+    use std::path::PathBuf;
 
     #[test]
     fn read_apikey() -> Result<(), ConfigError> {
@@ -172,4 +613,169 @@ mod tests {
         assert!(path.exists());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn game_download_dirs() -> Result<(), ConfigError> {
+        let modfile = "Graphic Herbalism MWSE - OpenMW-46599-1-03-1556986083.7z";
+        let config = ConfigBuilder::default().profile("morrowind").build()?;
+        assert_eq!(config.download_dir_for_game("morrowind"), config.download_dir().join("morrowind"));
+        // Files from before games had their own directories are still found in the download directory
+        assert_eq!(config.file_path("morrowind", modfile).await, config.download_dir().join(modfile));
+        assert_eq!(config.file_path("morrowind", "new.7z").await, config.download_dir().join("morrowind/new.7z"));
+
+        let builder: ConfigBuilder = toml::from_str("game_download_dirs = { skyrim = \"/mnt/skyrim\" }")?;
+        let config = builder.build()?;
+        assert_eq!(config.file_path("skyrim", "a.7z").await, PathBuf::from("/mnt/skyrim/a.7z"));
+        assert_eq!(config.part_path("skyrim", "a.7z").await, PathBuf::from("/mnt/skyrim/a.7z.part"));
+
+        let builder: ConfigBuilder = toml::from_str("temp_download_dir = \"/var/tmp/dmodman\"")?;
+        let config = builder.build()?;
+        assert_eq!(config.part_path("skyrim", "a.7z").await, PathBuf::from("/var/tmp/dmodman/skyrim/a.7z.part"));
+
+        let builder: ConfigBuilder = toml::from_str("game_data_dirs = { morrowind = \"/mnt/morrowind/Data Files\" }")?;
+        let config = builder.build()?;
+        assert_eq!(config.data_dir_for_game("morrowind"), Some(PathBuf::from("/mnt/morrowind/Data Files")));
+        assert_eq!(config.data_dir_for_game("skyrim"), None);
+
+        /* The games' directories are those of games in the cache, and those with the metadata of the game's files. The
+         * directories of profiles, extracted archives and hidden directories are skipped. */
+        let dir = std::env::temp_dir().join(format!("dmodman-download-dirs-{}", std::process::id()));
+        for subdir in ["morrowind", "skyrim", "testprofile", "Mod", ".Mod.fomod"] {
+            std::fs::create_dir_all(dir.join(subdir)).unwrap();
+        }
+        std::fs::write(dir.join("Mod.7z"), b"").unwrap();
+        std::fs::write(dir.join("Mod/manifest.json"), b"{\"Name\": \"Mod\"}").unwrap();
+        let metadata = "{\"game\": \"skyrim\", \"file_name\": \"a.7z\", \"mod_id\": 1, \"file_id\": 2, \
+                        \"update_status\": {\"UpToDate\": 0}}";
+        std::fs::write(dir.join("skyrim/a.7z.json"), metadata).unwrap();
+        std::fs::write(dir.join("testprofile/a.7z.json"), metadata).unwrap();
+        let builder: ConfigBuilder = toml::from_str(&format!("download_dir = {:?}", dir.to_string_lossy()))?;
+        let download_dirs = builder.build()?.download_dirs();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(download_dirs, vec![dir.clone(), dir.join("morrowind"), dir.join("skyrim")]);
+        Ok(())
+    }
+
+    #[test]
+    fn download_actions() -> Result<(), ConfigError> {
+        let builder: ConfigBuilder = toml::from_str(
+            "download_actions = { skyrim = \"Extract\", \"skyrim/OPTIONAL\" = \"Keep\", main = \"Stage\" }
+            default_download_action = \"Keep\"",
+        )?;
+        let config = builder.build()?;
+        assert_eq!(config.download_action("skyrim", Some("MAIN")), DownloadAction::Extract);
+        assert_eq!(config.download_action("skyrim", Some("OPTIONAL")), DownloadAction::Keep);
+        assert_eq!(config.download_action("morrowind", Some("MAIN")), DownloadAction::Stage);
+        assert_eq!(config.download_action("morrowind", None), DownloadAction::Keep);
+        assert!(config.validate().contains(&ConfigWarning::StagingDirMissing));
+        Ok(())
+    }
+
+    #[test]
+    fn validate() -> Result<(), ConfigError> {
+        // The test API key is a placeholder, and the test directories exist and are writable
+        let config = ConfigBuilder::default().profile("morrowind").build()?;
+        assert_eq!(config.validate(), vec![ConfigWarning::ApiKeyMalformed]);
+        assert!(is_valid_apikey("cG9ydGFsLWtleS1mb3ItdGVzdGluZw==--QWJjZGVmZ2hpams=--0123456789abcdef"));
+        assert!(!is_valid_apikey("\"cG9ydGFsLWtleS1mb3ItdGVzdGluZw==--QWJjZGVmZ2hpams=\""));
+        Ok(())
+    }
+
+    #[test]
+    fn extra_headers() -> Result<(), ConfigError> {
+        let builder: ConfigBuilder =
+            toml::from_str("extra_headers = { Authorization = \"Bearer abc\", \"X Bad\" = \"1\" }")?;
+        let config = builder.build()?;
+        assert!(config.has_credentials());
+        let invalid: Vec<ConfigWarning> =
+            config.validate().into_iter().filter(|w| matches!(w, ConfigWarning::InvalidHeader { .. })).collect();
+        assert_eq!(
+            invalid,
+            vec![ConfigWarning::InvalidHeader {
+                name: "X Bad".to_string()
+            }]
+        );
+
+        assert!(is_valid_header("X-Forwarded-For", "10.0.0.1"));
+        assert!(!is_valid_header("", "value"));
+        assert!(!is_valid_header("X-Test", "line\nbreak"));
+        Ok(())
+    }
+
+    #[test]
+    fn socket_path() -> Result<(), ConfigError> {
+        let builder: ConfigBuilder = toml::from_str("socket_path = \"/srv/dmodman.sock\"")?;
+        assert_eq!(builder.build()?.socket_path(), PathBuf::from("/srv/dmodman.sock"));
+
+        let runtime_dir = Some(PathBuf::from("/run/user/1000"));
+        assert_eq!(default_socket_path(runtime_dir), PathBuf::from("/run/user/1000/dmodman.sock"));
+        let fallback = default_socket_path(None);
+        assert!(fallback.starts_with("/tmp") && fallback.to_string_lossy().ends_with(".sock"));
+        Ok(())
+    }
+
+    #[test]
+    fn table_columns() -> Result<(), ConfigError> {
+        let config = ConfigBuilder::default().build()?;
+        assert_eq!(config.file_table_columns, FileColumnId::DEFAULT);
+        assert_eq!(config.download_table_columns, DownloadColumnId::DEFAULT);
+
+        let builder: ConfigBuilder = toml::from_str("file_table_columns = [\"Name\", \"Flags\"]")?;
+        let config = builder.build()?;
+        assert_eq!(config.file_table_columns, vec![FileColumnId::Name, FileColumnId::Flags]);
+        Ok(())
+    }
+
+    #[test]
+    fn merge_reloaded() -> Result<(), ConfigError> {
+        let config = ConfigBuilder::default().build()?;
+        let reloaded: ConfigBuilder = toml::from_str(
+            "file_manager = \"thunar\"\n\
+            download_dir = \"/tmp/elsewhere\"\n\
+            file_table_grouping = \"Game\"",
+        )?;
+        let (merged, needs_restart) = config.merge_reloaded(reloaded.build()?);
+        assert_eq!(merged.file_manager.as_deref(), Some("thunar"));
+        assert_eq!(merged.file_table_grouping, FileGrouping::Flat);
+        assert_eq!(needs_restart, vec!["file_table_grouping"]);
+        // The clones of the old config move to the new download directory as well
+        assert_eq!(merged.download_dir(), PathBuf::from("/tmp/elsewhere"));
+        assert_eq!(config.download_dir(), merged.download_dir());
+        Ok(())
+    }
+
+    #[test]
+    fn load_from_path() -> Result<(), ConfigError> {
+        let path = std::env::temp_dir().join(format!("dmodman-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "file_manager = \"dolphin\"")?;
+        let config = ConfigBuilder::load_from_path(&path)?.build()?;
+        std::fs::remove_file(&path)?;
+        assert_eq!(config.file_manager.as_deref(), Some("dolphin"));
+        assert_eq!(config.config_path, path);
+        assert!(ConfigBuilder::load_from_path(&path).is_err());
+        assert_eq!(ConfigBuilder::default().build()?.config_path, super::config_file());
+        Ok(())
+    }
+
+    #[test]
+    fn example_config() -> Result<(), ConfigError> {
+        // Written when a migration fails, so it has to be current
+        let value: toml::Value = toml::from_str(super::DEFAULT_CONFIG)?;
+        assert!(!super::ConfigMigrator::new(&value).unwrap().needs_migration());
+        Ok(())
+    }
+
+    #[test]
+    fn load_profile() -> Result<(), ConfigError> {
+        let config = ConfigBuilder::default().load_profile("testprofile")?.build()?;
+        assert_eq!(config.profile.as_deref(), Some("testprofile"));
+        assert_eq!(config.file_manager.as_deref(), Some("thunar"));
+        assert!(config.download_dir().ends_with("testprofile"));
+
+        // Profiles without a config file only change the download directory
+        let config = ConfigBuilder::default().load_profile("morrowind")?.build()?;
+        assert_eq!(config.file_manager, None);
+        assert!(config.download_dir().ends_with("morrowind"));
+        Ok(())
+    }
 }
@@ -2,7 +2,9 @@ use super::Config;
 
 use crate::api::downloads::DownloadInfo;
 use crate::cache::LocalFile;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 pub const DL_LINKS: &str = "download_links";
 pub const FILE_LISTS: &str = "file_lists";
@@ -17,6 +19,8 @@ pub enum PathType<'a> {
     GameInfo(&'a str),                       // game
     Md5Search(&'a str, &'a u32, &'a u64),    // game, mod_id, file_id
     ModInfo(&'a str, &'a u32),               // game, mod_id
+    UserMods,                                // endorsed and tracked mods of all games
+    DownloadOrder,                           // the order of the download list, by file id
 
     // Local formats
     LocalFile(&'a LocalFile),
@@ -24,7 +28,9 @@ pub enum PathType<'a> {
 }
 
 impl Config {
-    pub fn path_for(&self, path_type: PathType) -> PathBuf {
+    /* The paths of local files depend on where the file already is, which is checked without blocking, so this is async
+     * even though the paths in the cache are only computed. */
+    pub async fn path_for(&self, path_type: PathType<'_>) -> PathBuf {
         let mut path;
 
         match path_type {
@@ -57,15 +63,146 @@ impl Config {
                 path.push(MOD_INFO);
                 path.push(format!("{}.json", mod_id));
             }
+            PathType::UserMods => {
+                path = self.cache_dir();
+                path.push("user_mods.json");
+            }
+            // Each profile has its own downloads
+            PathType::DownloadOrder => {
+                path = self.cache_dir();
+                match &self.profile {
+                    Some(profile) => path.push(format!("download_order-{profile}.json")),
+                    None => path.push("download_order.json"),
+                }
+            }
+            // Metadata is kept next to the file it belongs to
             PathType::LocalFile(lf) => {
-                path = self.download_dir();
-                path.push(format!("{}.json", lf.file_name));
+                path = self.file_path(&lf.game, &lf.file_name).await.with_file_name(format!("{}.json", lf.file_name));
             }
             PathType::DownloadInfo(di) => {
-                path = self.download_dir();
-                path.push(format!("{}.part.json", di.file_info.file_name));
+                let fi = &di.file_info;
+                path =
+                    self.part_path(&fi.game, &fi.file_name).await.with_file_name(format!("{}.part.json", fi.file_name));
             }
         }
         path
     }
+
+    // Each game's files are kept in game_download_dirs if it's set for the game, otherwise in <download_dir>/<game>
+    pub fn download_dir_for_game(&self, game: &str) -> PathBuf {
+        match self.game_download_dirs.get(game) {
+            Some(dir) => PathBuf::from(dir),
+            None => self.download_dir().join(game),
+        }
+    }
+
+    // Archives of games that have a data directory in game_data_dirs are extracted there, see Archives::test_and_extract
+    pub fn data_dir_for_game(&self, game: &str) -> Option<PathBuf> {
+        self.game_data_dirs.get(game).map(PathBuf::from)
+    }
+
+    /* The path of a file of the game. Files downloaded before games had their own directories are directly in the
+     * download directory, and are found there as long as the game's directory doesn't have a file with the same name.
+     * New files go in the game's directory. */
+    pub async fn file_path(&self, game: &str, file_name: &str) -> PathBuf {
+        let path = self.download_dir_for_game(game).join(file_name);
+        if !exists(&path).await {
+            let legacy_path = self.download_dir().join(file_name);
+            if exists(&legacy_path).await {
+                return legacy_path;
+            }
+        }
+        path
+    }
+
+    /* Where the partial file of a download is written. If temp_download_dir is set, that's <temp_download_dir>/<game>,
+     * and the file is moved to the game's download directory once it's complete. Partial files that are already in the
+     * download directory are still resumed from there. */
+    pub async fn part_path(&self, game: &str, file_name: &str) -> PathBuf {
+        let part_name = format!("{file_name}.part");
+        let path = self.file_path(game, &part_name).await;
+        match &self.temp_download_dir {
+            Some(temp_dir) if !exists(&path).await => PathBuf::from(temp_dir).join(game).join(part_name),
+            _ => path,
+        }
+    }
+
+    // The directories that can contain partial downloads, which include the per-game directories in temp_download_dir
+    pub fn part_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = self.download_dirs();
+        if let Some(temp_dir) = &self.temp_download_dir {
+            if let Ok(entries) = fs::read_dir(temp_dir) {
+                dirs.extend(entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).filter(|p| p.is_dir()));
+            }
+        }
+        dirs
+    }
+
+    /* The directories that can contain downloaded files: the download directory itself, the directories of the games in
+     * it, and the directories in game_download_dirs. A subdirectory is only a game's directory if the game is known
+     * from the cache or game_download_dirs, or if the directory has the metadata of a file of that game. This leaves
+     * out the directories of profiles, those that archives were extracted into, and hidden ones such as the temporary
+     * directories of FOMOD installs. */
+    pub fn download_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = vec![self.download_dir()];
+        let known_games = self.known_games();
+        let profiles = super::profile_names();
+        if let Ok(entries) = fs::read_dir(self.download_dir()) {
+            let mut subdirs: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.is_dir())
+                .filter(|path| {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    !name.starts_with('.')
+                        && !profiles.iter().any(|profile| *profile == name)
+                        && (known_games.contains(name.as_ref()) || has_metadata_of_game(path, &name))
+                })
+                .collect();
+            subdirs.sort();
+            dirs.extend(subdirs);
+        }
+        let mut game_dirs: Vec<PathBuf> = self.game_download_dirs.values().map(PathBuf::from).collect();
+        game_dirs.sort();
+        for dir in game_dirs {
+            if dir.is_dir() && !dirs.contains(&dir) {
+                dirs.push(dir);
+            }
+        }
+        dirs
+    }
+
+    /* The games that have a directory in the cache, for their file lists and download links, or a directory in
+     * game_download_dirs. The other files in the cache, like user_mods.json, aren't games. */
+    fn known_games(&self) -> HashSet<String> {
+        let mut games: HashSet<String> = self.game_download_dirs.keys().cloned().collect();
+        if let Ok(entries) = fs::read_dir(self.cache_dir()) {
+            games.extend(
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| entry.path().is_dir())
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned()),
+            );
+        }
+        games
+    }
+}
+
+// Whether the directory has the metadata of a downloaded file of the game, which is enough to tell it's the game's
+fn has_metadata_of_game(dir: &Path, game: &str) -> bool {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return false,
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json") && path.is_file())
+        .filter_map(|path| serde_json::from_str::<LocalFile>(&fs::read_to_string(path).ok()?).ok())
+        .any(|lf| lf.game == game)
+}
+
+// Errors, such as missing permissions, count as the file not existing, as with Path::exists()
+async fn exists(path: &Path) -> bool {
+    tokio::fs::try_exists(path).await.unwrap_or(false)
 }
@@ -1,58 +1,250 @@
 use crate::config;
-use std::fmt::{Debug, Display};
-use std::sync::atomic::{AtomicBool, Ordering};
+use crate::util::changes::ChangeFlag;
+use crate::util::format;
+use std::fmt::{self, Debug, Display};
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
 use std::sync::Arc;
+use std::time::SystemTime;
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::Write;
+use std::path::PathBuf;
 use std::sync::RwLock;
 
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "debug" => Some(Self::Debug),
+            "info" => Some(Self::Info),
+            "warn" => Some(Self::Warn),
+            "error" => Some(Self::Error),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Debug => "DEBUG",
+            Self::Info => "INFO",
+            Self::Warn => "WARN",
+            Self::Error => "ERROR",
+        }
+    }
+}
+
+// A message shown in the UI
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub number: usize,
+    pub level: LogLevel,
+    pub text: String,
+    // How many times the message was logged in a row, such as the same error during a flaky connection
+    pub count: u32,
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.number, self.text)?;
+        if self.count > 1 {
+            write!(f, " (×{})", self.count)?;
+        }
+        Ok(())
+    }
+}
+
+/* Messages are shown in the UI and appended to dmodman.log in the config directory, each line prefixed with the time
+ * and level. Once the log file grows past max_file_size it's moved to dmodman.log.1, replacing the previous one. */
 #[derive(Clone, Default)]
 pub struct Logger {
-    pub messages: Arc<RwLock<Vec<String>>>,
-    pub has_changed: Arc<AtomicBool>, // used by UI to ask if error list needs to be redrawn
+    pub messages: Arc<RwLock<Vec<Message>>>,
+    pub has_changed: ChangeFlag, // used by UI to ask if error list needs to be redrawn
     is_interactive: bool,
+    writes_file: bool,
+    level: Arc<AtomicU8>,
+    max_file_size: Arc<AtomicU64>, // in bytes, 0 means the file is never rotated
 }
 
 impl Logger {
     pub fn new(is_interactive: bool) -> Self {
-        Self {
+        let logger = Self {
             is_interactive,
+            writes_file: true,
             ..Default::default()
-        }
+        };
+        logger.set_file_options(LogLevel::Info, 0);
+        logger
+    }
+
+    // The options are only known after the config has been loaded, which happens after the logger is created
+    pub fn set_file_options(&self, level: LogLevel, max_file_size: u64) {
+        self.level.store(level as u8, Ordering::Relaxed);
+        self.max_file_size.store(max_file_size, Ordering::Relaxed);
     }
 
-    // TODO allow optionally logging to file (maybe with log levels?)
     pub fn log<S: Into<String> + Debug + Display>(&self, msg: S) {
+        self.log_at(LogLevel::Info, msg);
+    }
+
+    pub fn debug<S: Into<String> + Debug + Display>(&self, msg: S) {
+        self.log_at(LogLevel::Debug, msg);
+    }
+
+    pub fn warn<S: Into<String> + Debug + Display>(&self, msg: S) {
+        self.log_at(LogLevel::Warn, msg);
+    }
+
+    pub fn error<S: Into<String> + Debug + Display>(&self, msg: S) {
+        self.log_at(LogLevel::Error, msg);
+    }
+
+    pub fn log_at<S: Into<String> + Debug + Display>(&self, level: LogLevel, msg: S) {
+        if (level as u8) < self.level.load(Ordering::Relaxed) {
+            return;
+        }
+        if self.writes_file {
+            // There's nowhere else to report a failure to write the log, and it shouldn't stop the program
+            let _ = self.write_to_file(level, &msg);
+        }
+
         if !self.is_interactive {
             println!("{:?}", msg);
             return;
         }
 
         let mut lock = self.messages.write().unwrap();
-        let len = lock.len();
-
-        let mut path = config::config_dir();
-        path.push("dmodman.log");
-        let mut logfile = File::options().create(true).append(true).open(path).unwrap();
-        logfile.write(format!("{}\n", msg).as_bytes()).unwrap();
-
-        // TODO timestamp instead of number messages, but might require external crate to be sane
-        lock.push(format!("{:?}: {}", len, msg.into()));
-        self.has_changed.store(true, Ordering::Relaxed);
+        let text: String = msg.into();
+        // A repeat of the latest message only increases its count, so that it doesn't push the others out of view
+        if let Some(last) = lock.last_mut().filter(|last| last.level == level && last.text == text) {
+            last.count += 1;
+        } else {
+            // TODO timestamp instead of number messages, but might require external crate to be sane
+            let number = lock.len();
+            lock.push(Message {
+                number,
+                level,
+                text,
+                count: 1,
+            });
+        }
+        self.has_changed.set();
     }
 
     // Useful for testing UI code without causing re-rendering
     #[allow(dead_code)]
     pub fn log_to_file<S: Into<String> + Debug + Display>(&self, msg: S) {
-        let mut path = config::config_dir();
-        path.push("dmodman.log");
-        let mut logfile = File::options().create(true).append(true).open(path).unwrap();
-        logfile.write(format!("{}\n", msg).as_bytes()).unwrap();
+        self.write_to_file(LogLevel::Info, &msg).unwrap();
+    }
+
+    fn write_to_file(&self, level: LogLevel, msg: &dyn Display) -> Result<(), std::io::Error> {
+        let path = log_file();
+        let max_file_size = self.max_file_size.load(Ordering::Relaxed);
+        if max_file_size > 0 && fs::metadata(&path).is_ok_and(|md| md.len() >= max_file_size) {
+            fs::rename(&path, path.with_extension("log.1"))?;
+        }
+        let mut logfile = File::options().create(true).append(true).open(path)?;
+        logfile.write_all(format_line(SystemTime::now(), level, msg).as_bytes())
     }
 
     pub async fn remove(&self, i: usize) {
         self.messages.write().unwrap().remove(i);
-        self.has_changed.store(true, Ordering::Relaxed);
+        self.has_changed.set();
+    }
+
+    // Keeps the messages for which the predicate returns true. Returns the number of removed messages.
+    pub fn retain<F: FnMut(&Message) -> bool>(&self, predicate: F) -> usize {
+        let mut lock = self.messages.write().unwrap();
+        let len = lock.len();
+        lock.retain(predicate);
+        self.has_changed.set();
+        len - lock.len()
+    }
+
+    // Only the messages shown in the UI are cleared, the log file is left as it is
+    pub fn clear_all(&self) {
+        let cleared = self.retain(|_| false);
+        self.log(format!("Cleared {cleared} messages."));
+    }
+}
+
+fn log_file() -> PathBuf {
+    let mut path = config::config_dir();
+    path.push("dmodman.log");
+    path
+}
+
+fn format_line(time: SystemTime, level: LogLevel, msg: &dyn Display) -> String {
+    format!("{} {:<5} {}\n", format::utc_timestamp(time), level.as_str(), msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_line, LogLevel, Logger};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn line_format() {
+        let time = UNIX_EPOCH + Duration::from_secs(1700000000);
+        assert_eq!(format_line(time, LogLevel::Warn, &"Disk full"), "2023-11-14 22:13:20 WARN  Disk full\n");
+        assert_eq!(format_line(time, LogLevel::Error, &"Disk full"), "2023-11-14 22:13:20 ERROR Disk full\n");
+    }
+
+    #[test]
+    fn parse_level() {
+        assert!(LogLevel::Info < LogLevel::Warn);
+        assert_eq!(LogLevel::parse("ERROR"), Some(LogLevel::Error));
+        assert_eq!(LogLevel::parse("verbose"), None);
+    }
+
+    #[test]
+    fn retain_messages() {
+        let logger = Logger {
+            is_interactive: true,
+            ..Default::default()
+        };
+        logger.log("Download finished");
+        logger.error("Download failed");
+        logger.log("Update available");
+        assert_eq!(logger.retain(|msg| !msg.text.contains("Download")), 2);
+        assert_eq!(shown(&logger), vec!["2: Update available"]);
+
+        logger.clear_all();
+        assert_eq!(shown(&logger), vec!["0: Cleared 1 messages."]);
+    }
+
+    #[test]
+    fn repeated_messages() {
+        let logger = Logger {
+            is_interactive: true,
+            ..Default::default()
+        };
+        for _ in 0..42 {
+            logger.error("IO error when writing bytes");
+        }
+        // The same text at a different level is a different message
+        logger.warn("IO error when writing bytes");
+        logger.error("IO error when writing bytes");
+        assert_eq!(
+            shown(&logger),
+            vec![
+                "0: IO error when writing bytes (×42)",
+                "1: IO error when writing bytes",
+                "2: IO error when writing bytes"
+            ]
+        );
+    }
+
+    fn shown(logger: &Logger) -> Vec<String> {
+        logger.messages.read().unwrap().iter().map(|msg| msg.to_string()).collect()
     }
 }
@@ -1,6 +1,7 @@
 mod api;
 mod archives;
 mod cache;
+mod cmd;
 mod config;
 mod logger;
 mod nxm_socket;
@@ -11,11 +12,17 @@ use std::env::args;
 use std::error::Error;
 use std::io::ErrorKind;
 
-use api::{Client, Downloads};
+use reqwest::StatusCode;
+use signal_hook::consts::signal::SIGHUP;
+use signal_hook_tokio::Signals;
+use tokio_stream::StreamExt;
+
+use api::{ApiError, Client, Downloads, UpdateChecker};
 use archives::Archives;
 use cache::Cache;
 use config::{Config, ConfigBuilder};
 use logger::Logger;
+use nxm_socket::StopRequest;
 
 /* dmodman acts as an url handler for nxm:// links in order for the "download with mod manager" button to work on
  * NexusMods.
@@ -26,35 +33,79 @@ use logger::Logger;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let mut nxm_str_opt: Option<&str> = None;
-    let mut is_interactive = true;
-
-    let args: Vec<String> = args().collect();
-    if args.len() > 2 {
-        println!("Too many arguments. Invoke dmodman without arguments or with an nxm:// URL.");
-        return Ok(());
-    } else if let Some(first_arg) = args.get(1) {
-        if first_arg.starts_with("nxm://") {
-            nxm_str_opt = Some(first_arg);
-        } else if first_arg == "-d" {
-            is_interactive = false;
-        } else {
-            println!("Arguments are expected only when acting as an nxm:// URL handler.");
+    let args = match cmd::parse(args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            println!("{e}\n\n{}", cmd::USAGE);
             return Ok(());
         }
+    };
+    if args.show_help {
+        println!("{}", cmd::USAGE);
+        return Ok(());
     }
+    if args.show_version {
+        println!("{}", cmd::VERSION);
+        return Ok(());
+    }
+    let nxm_opt = args.nxm.as_ref();
+    // Downloading a single file prints its progress instead of showing the TUI
+    let is_interactive = args.is_interactive && args.download_file.is_none();
 
     /* We can't println in the TUI. Instead we use Logger which can log to a file and show messages in the TUI.
      * It calls println!() instead when running as a daemon. */
     let logger = Logger::new(is_interactive);
 
     // TODO config is cloned needlessly in a few places
-    let mut config = match ConfigBuilder::load() {
+    let config_path = args.config.clone().unwrap_or_else(config::config_file);
+    Config::migrate(&config_path, &logger);
+    let mut config_builder = match ConfigBuilder::load_from_path(&config_path) {
         Ok(cb) => cb,
+        // A config file given with --config has to exist
+        Err(e) if args.config.is_some() => {
+            println!("Unable to load the config from {}: {e}", config_path.display());
+            std::process::exit(1);
+        }
         Err(_) => ConfigBuilder::default(),
+    };
+    config_builder.config_path = Some(config_path);
+    // The profile has to be known before the cache is loaded from its download directory
+    if let Some(profile) = args.profile.clone().or(config_builder.profile.clone()) {
+        Config::migrate(&config::profile_file(&profile), &logger);
+        config_builder = config_builder.load_profile(&profile)?;
+    }
+    let mut config = config_builder.build()?;
+    logger.set_file_options(args.log_level.unwrap_or(config.log_level), config.max_log_size * 1024);
+    // The socket path can be configured, so these need the config even though they only talk to the running instance
+    if let Some(nxm) = &args.queue {
+        std::process::exit(cmd::queue(&config.socket_path(), nxm).await);
+    }
+    if let Some(path) = &args.queue_file {
+        std::process::exit(cmd::queue_file(&config.socket_path(), path, config.max_concurrent_downloads).await);
+    }
+    if args.list_downloads {
+        std::process::exit(cmd::list_downloads(&config.socket_path(), args.json).await);
     }
-    .build()?;
-    if config.apikey.is_none() {
+    if args.stop {
+        std::process::exit(cmd::stop(&config.socket_path()).await);
+    }
+    if let Some(command) = args.command {
+        std::process::exit(cmd::send_command(&config.socket_path(), command).await);
+    }
+    if let Some(path) = &args.export_queue {
+        std::process::exit(cmd::export_queue(&config.socket_path(), path).await);
+    }
+    // These only look at the download directory, so they don't need an API key
+    if let Some(sort) = &args.disk_usage {
+        let cache = Cache::new(&config).await?;
+        std::process::exit(cmd::disk_usage(&cache, sort, config.max_download_dir_size).await);
+    }
+    if args.prune {
+        let cache = Cache::new(&config).await?;
+        std::process::exit(cmd::prune(&cache, config.max_download_dir_size).await);
+    }
+    // The SSO flow reads the answers from stdin, so it's skipped when running as a daemon
+    if !config.has_credentials() && args.is_interactive {
         if let Some(apikey) = ui::sso::start_apikey_flow().await {
             config.apikey = Some(apikey);
             config.save_apikey()?;
@@ -63,22 +114,61 @@ async fn main() -> Result<(), Box<dyn Error>> {
             logger.log("No API key configured. API connections are disabled.");
         }
     }
+    for warning in config.validate() {
+        logger.warn(warning.to_string());
+    }
 
-    let cache = Cache::new(&config).await?;
-    let client = Client::new(&config).await;
+    // The UI is shown while the files are loaded, but importing needs all of them to know which ones lack metadata
+    let cache = if is_interactive && !args.import {
+        Cache::new_in_background(&config, &logger).await?
+    } else {
+        Cache::new(&config).await?
+    };
+    for e in &cache.load_errors {
+        logger.warn(e);
+    }
+    if config.max_download_dir_size > 0 {
+        let (cache, logger, max_size) = (cache.clone(), logger.clone(), config.max_download_dir_size);
+        tokio::task::spawn(async move {
+            cache.file_index.wait_until_loaded().await;
+            let total: u64 = cache.disk_usage().await.iter().map(|game| game.size).sum();
+            if total > max_size * 1024 * 1024 {
+                logger.warn(format!(
+                    "The downloads take {}, over max_download_dir_size. Run dmodman --prune to delete old versions.",
+                    util::format::human_size(total)
+                ));
+            }
+        });
+    }
+    let client = Client::new(&config).await?;
+    if args.import {
+        match cmd::resolve_game(&mut config, &client, args.game.as_deref()).await {
+            Ok(game) => std::process::exit(cmd::import(&cache, &client, &game).await),
+            Err(e) => {
+                println!("{e}");
+                std::process::exit(1);
+            }
+        }
+    }
     let downloads = Downloads::new(&cache, &client, &config, &logger).await;
+    if let Some((game, mod_id, file_id)) = &args.download_file {
+        std::process::exit(cmd::download_file(&downloads, game, *mod_id, *file_id).await);
+    }
 
-    // Try bind to /run/user/$uid. If it already exists then send nexus download links there and quit.
+    // Try bind to the socket. If it already exists then send nexus download links there and quit.
     let nxm_socket;
-    match nxm_socket::try_bind().await {
+    match nxm_socket::try_bind(&config.socket_path()).await {
         Ok(sock) => {
             nxm_socket = sock;
         }
         Err(e) if e.kind() == ErrorKind::AddrInUse => {
             println!("Another instance of dmodman is already running.");
-            if let Some(nxm_str) = nxm_str_opt {
+            if let Some(nxm) = nxm_opt {
                 println!("Sending download to already running instance.");
-                nxm_socket::send_msg(nxm_str).await.unwrap();
+                nxm_socket::send_msg(&config.socket_path(), nxm.url.as_str()).await.unwrap();
+            }
+            if let Some(path) = &args.import_queue {
+                std::process::exit(cmd::import_queue(&config.socket_path(), path).await);
             }
             return Err(e.into());
         }
@@ -88,28 +178,134 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     };
 
+    /* Only the instance that serves the socket validates the API key, so that forwarding an nxm:// link to it doesn't
+     * make a request. It's done in the background, since the UI doesn't need to wait for it. */
+    if config.has_credentials() {
+        let (client, logger) = (client.clone(), logger.clone());
+        tokio::task::spawn(async move { validate_apikey(&client, &logger).await });
+    }
     downloads.resume_on_startup().await;
 
-    if let Some(nxm_str) = nxm_str_opt {
-        downloads.try_queue(nxm_str).await;
+    if let Some(nxm) = nxm_opt {
+        // Errors are shown in the UI
+        let _ = downloads.try_queue(nxm).await;
+    }
+    if let Some(path) = &args.import_queue {
+        if let Err(e) = downloads.import_queue(path).await {
+            logger.error(e);
+        }
     }
 
-    /* Only start the UI if running interactively. Otherwise we block the main thread with the listen loop so the
-     * program doesn't exit. */
+    /* Only start the UI if running interactively. Otherwise we block the main thread until the program is told to
+     * quit, while the listen loop runs in the background. */
+    let stop_request = StopRequest::default();
+    // Shared by the UI and the socket, so that their update checks don't overlap
+    let updater = UpdateChecker::new(cache.clone(), client.clone(), config.clone(), logger.clone());
     if is_interactive {
         {
             let downloads = downloads.clone();
+            let updater = updater.clone();
             let msgs = logger.clone();
+            let stop_request = stop_request.clone();
+            let status_endpoint = config.status_endpoint;
             tokio::task::spawn(async move {
-                nxm_socket::listen_for_downloads(nxm_socket, downloads, msgs).await;
+                nxm_socket::listen_for_downloads(nxm_socket, downloads, updater, msgs, stop_request, status_endpoint)
+                    .await;
             });
         }
 
-        let archive = Archives::new(config.clone(), logger.clone());
-        ui::MainUI::new(cache, client, config, downloads, logger, archive).await.run().await;
+        if config.has_credentials() {
+            let (cache, client, logger) = (cache.clone(), client.clone(), logger.clone());
+            tokio::task::spawn(async move { cache.fetch_missing_names(&client, &logger).await });
+        }
+
+        let archive = Archives::new(config.clone(), cache.clone(), client.clone(), logger.clone());
+        ui::MainUI::new(cache, client, config, downloads.clone(), updater, logger, archive)
+            .await
+            .run(stop_request.clone())
+            .await;
     } else {
-        nxm_socket::listen_for_downloads(nxm_socket, downloads, logger).await;
+        nxm_socket::listen_for_downloads(
+            nxm_socket,
+            downloads.clone(),
+            updater,
+            logger.clone(),
+            stop_request.clone(),
+            config.status_endpoint,
+        )
+        .await;
+        tokio::select! {
+            _ = util::wait_for_shutdown_signal() => {}
+            _ = stop_request.wait() => {}
+            _ = reload_on_sighup(config, cache, client, downloads.clone(), logger) => {}
+        }
     }
 
+    // Makes sure the partially downloaded files can be resumed on the next startup
+    downloads.shutdown().await;
+    stop_request.acknowledge().await;
+
     Ok(())
 }
+
+/* Checks the API key with the Nexus, so that an expired or revoked key is noticed right away instead of on the first
+ * request that needs it. The SSO flow can't run alongside the UI or in a daemon, so a rejected key is only reported,
+ * along with how to create a new one. Other errors are only logged, since the key may still be fine once the Nexus can
+ * be reached. */
+async fn validate_apikey(client: &Client, logger: &Logger) {
+    match client.validate_apikey().await {
+        Ok(account) => {
+            let kind = if account.is_premium { "premium" } else { "free" };
+            logger.log(format!("Logged in to the Nexus as {} ({kind} account).", account.name));
+        }
+        Err(ApiError::ConnectionError { source }) if source.status() == Some(StatusCode::UNAUTHORIZED) => {
+            logger.error(format!(
+                "The Nexus rejected the API key, it may have expired or been revoked. Requests to the Nexus will fail \
+                until it's replaced. Set a new apikey in config.toml, or delete {} and restart dmodman to create one.",
+                config::apikey_file().display()
+            ));
+        }
+        Err(e) => logger.warn(format!("Unable to validate the API key: {e}")),
+    }
+}
+
+/* Reloads the config when asked to. Settings that can't be changed at runtime keep their current values, and the user
+ * is told to restart for them to take effect. Returns None if the config couldn't be read. */
+pub async fn reload_config(
+    config: &Config,
+    cache: &Cache,
+    client: &Client,
+    downloads: &Downloads,
+    logger: &Logger,
+) -> Option<Config> {
+    let download_dir = config.download_dir();
+    match config.reload() {
+        Ok((config, needs_restart)) => {
+            client.apply_config(&config);
+            downloads.apply_config(&config);
+            if config.download_dir() != download_dir {
+                logger.log(format!("Loading the files in {:?}...", config.download_dir()));
+                cache.file_index.reload(logger.clone()).await;
+            }
+            if needs_restart.is_empty() {
+                logger.log("Reloaded config.");
+            } else {
+                logger.log(format!("Reloaded config. Restart dmodman to apply: {}", needs_restart.join(", ")));
+            }
+            Some(config)
+        }
+        Err(e) => {
+            logger.error(format!("Unable to reload config: {e}"));
+            None
+        }
+    }
+}
+
+async fn reload_on_sighup(mut config: Config, cache: Cache, client: Client, downloads: Downloads, logger: Logger) {
+    let mut signals = Signals::new([SIGHUP]).unwrap();
+    while signals.next().await.is_some() {
+        if let Some(reloaded) = reload_config(&config, &cache, &client, &downloads, &logger).await {
+            config = reloaded;
+        }
+    }
+}
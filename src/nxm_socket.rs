@@ -1,38 +1,110 @@
 use std::io::{Error, ErrorKind};
-use std::str;
+use std::path::{Path, PathBuf};
+use std::str::{self, FromStr};
+use std::sync::Arc;
 
-use tokio::io::Interest;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
 use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{Mutex, Notify};
 use tokio::task;
 
-use crate::api::Downloads;
+use crate::api::{Downloads, NxmUrl, QueueOutcome, UpdateChecker};
 use crate::Logger;
 
 // Listens for nxm:// urls to queue as downloads
 pub struct NxmSocketListener {
     listener: UnixListener, // Wrapped into a struct so we can impl Drop on it
+    path: PathBuf,
 }
 
 impl NxmSocketListener {
-    fn bind() -> Result<Self, Error> {
+    fn bind(path: &Path) -> Result<Self, Error> {
         Ok(Self {
-            listener: UnixListener::bind(get_socket_path())?,
+            listener: UnixListener::bind(path)?,
+            path: path.to_path_buf(),
         })
     }
 }
 
 impl Drop for NxmSocketListener {
     fn drop(&mut self) {
-        remove_socket().unwrap()
+        std::fs::remove_file(&self.path).unwrap()
     }
 }
 
-pub async fn try_bind() -> Result<NxmSocketListener, Error> {
-    match NxmSocketListener::bind() {
+/* A STOP message asks the running instance to shut down. The connection is kept open until the downloads have been
+ * saved, so that the stopping process can wait for the ACK before exiting. */
+#[derive(Clone, Default)]
+pub struct StopRequest {
+    notify: Arc<Notify>,
+    streams: Arc<Mutex<Vec<UnixStream>>>,
+}
+
+impl StopRequest {
+    pub async fn wait(&self) {
+        self.notify.notified().await;
+    }
+
+    async fn request(&self, stream: UnixStream) {
+        self.streams.lock().await.push(stream);
+        // Stores a permit if the program isn't waiting yet, so the request isn't lost
+        self.notify.notify_one();
+    }
+
+    pub async fn acknowledge(&self) {
+        for mut stream in self.streams.lock().await.drain(..) {
+            let _ = stream.write_all(b"ACK\n").await;
+        }
+    }
+}
+
+// Commands that control the running instance, sent with "dmodman --command <name>"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SocketCommand {
+    PauseAll,
+    ResumeAll,
+    CheckUpdates,
+    Quit,
+}
+
+impl SocketCommand {
+    pub const ALL: [Self; 4] = [Self::PauseAll, Self::ResumeAll, Self::CheckUpdates, Self::Quit];
+
+    // The name given on the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PauseAll => "pause-all",
+            Self::ResumeAll => "resume-all",
+            Self::CheckUpdates => "check-updates",
+            Self::Quit => "quit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.name() == name)
+    }
+
+    // The message sent over the socket. Quitting is the same as the STOP message that --stop sends.
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::PauseAll => "PAUSE_ALL",
+            Self::ResumeAll => "RESUME_ALL",
+            Self::CheckUpdates => "CHECK_UPDATES",
+            Self::Quit => "STOP",
+        }
+    }
+
+    fn from_message(msg: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|command| command.message() == msg)
+    }
+}
+
+pub async fn try_bind(path: &Path) -> Result<NxmSocketListener, Error> {
+    match NxmSocketListener::bind(path) {
         Ok(listener) => Ok(listener),
         Err(ref e) if e.kind() == ErrorKind::AddrInUse => {
             // Even if the socket address is in use, we can't know if it's responding without trying to connect
-            match connect().await {
+            match UnixStream::connect(path).await {
                 // Another running instance is accepting connections
                 Ok(_stream) => Err(ErrorKind::AddrInUse.into()),
                 // Socket probably hasn't been cleanly removed. Remove it and bind to it.
@@ -40,11 +112,11 @@ pub async fn try_bind() -> Result<NxmSocketListener, Error> {
                     println!(
                         "Previous socket {} exists but is refusing connections. \
                         dmodman might not have shut down cleanly. Removing it...",
-                        get_socket_path()
+                        path.display()
                     );
-                    remove_socket()?;
+                    std::fs::remove_file(path)?;
                     // Retry bind() and return whatever the result is
-                    NxmSocketListener::bind()
+                    NxmSocketListener::bind(path)
                 }
                 /* Catch-all for unanticipated ways in which the socket can break.
                  * Hitting this case should be unlikely. */
@@ -55,65 +127,155 @@ pub async fn try_bind() -> Result<NxmSocketListener, Error> {
     }
 }
 
-pub async fn listen_for_downloads(nxm_sock: NxmSocketListener, downloads: Downloads, logger: Logger) {
+pub async fn listen_for_downloads(
+    nxm_sock: NxmSocketListener,
+    downloads: Downloads,
+    updater: UpdateChecker,
+    logger: Logger,
+    stop_request: StopRequest,
+    status_endpoint: bool,
+) {
     task::spawn(async move {
         loop {
             match nxm_sock.listener.accept().await {
                 Ok((stream, _addr)) => {
                     if let Ok(ready) = stream.ready(Interest::READABLE).await {
                         if ready.is_readable() {
-                            handle_incoming_stream(stream, &downloads, &logger).await;
+                            handle_incoming_stream(
+                                stream,
+                                &downloads,
+                                &updater,
+                                &logger,
+                                &stop_request,
+                                status_endpoint,
+                            )
+                            .await;
                         }
                     } // It doesn't seem like the two else {} paths here require dealing with
                 }
                 Err(e) => {
-                    logger.log(format!("nxm socket was unable to accept connection: {}", e));
+                    logger.error(format!("nxm socket was unable to accept connection: {}", e));
                 }
             }
         }
     });
 }
 
-async fn handle_incoming_stream(stream: UnixStream, downloads: &Downloads, logger: &Logger) {
+/* Messages are either nxm:// URLs to queue, which are answered with "OK\n", "OK skipped\n" if the file is already
+ * downloaded or downloading, or "ERR <message>\n", "LIST", which is answered with the state of the downloads as JSON,
+ * or "STOP", which is answered with "ACK\n" once the program is about to exit. The other SocketCommands are answered
+ * with "OK\n" once they've been carried out, except for checking updates, which continues in the background. Clients
+ * are free to ignore the reply.
+ * "EXPORT_QUEUE <path>" saves the unfinished downloads to the file, and "IMPORT_QUEUE <path>" queues the downloads saved
+ * in it. Both are answered with "OK <number of downloads>\n" or "ERR <message>\n". The paths need to be absolute.
+ * If the status endpoint is enabled, "STATUS" is answered with a StatusSnapshot as JSON, and so is an HTTP request for
+ * /status, e.g. from "curl --unix-socket <socket_path> http://localhost/status". Neither changes any state. */
+async fn handle_incoming_stream(
+    mut stream: UnixStream,
+    downloads: &Downloads,
+    updater: &UpdateChecker,
+    logger: &Logger,
+    stop_request: &StopRequest,
+    status_endpoint: bool,
+) {
     let mut data = vec![0; 1024];
     match stream.try_read(&mut data) {
-        Ok(_bytes) => match str::from_utf8(&data) {
+        Ok(bytes) => match str::from_utf8(&data[..bytes]) {
             Ok(msg) => {
-                if msg.starts_with("nxm://") {
-                    downloads.try_queue(msg).await;
-                }
+                let msg = msg.trim();
+                let reply = if let Some(command) = SocketCommand::from_message(msg) {
+                    match command {
+                        SocketCommand::Quit => {
+                            logger.log("Received a request to stop, shutting down.");
+                            stop_request.request(stream).await;
+                            return;
+                        }
+                        SocketCommand::PauseAll => downloads.pause_all().await,
+                        SocketCommand::ResumeAll => downloads.resume_all().await,
+                        SocketCommand::CheckUpdates => updater.update_all().await,
+                    }
+                    logger.log(format!("Received the {} command.", command.name()));
+                    "OK\n".to_string()
+                } else if msg.starts_with("nxm://") {
+                    let queued = match NxmUrl::from_str(msg) {
+                        Ok(nxm) => downloads.try_queue(&nxm).await,
+                        Err(e) => {
+                            let msg = format!("Unable to parse string \"{msg}\" as nxm url: {e}");
+                            logger.error(&msg);
+                            Err(msg)
+                        }
+                    };
+                    match queued {
+                        Ok(QueueOutcome::Queued) => "OK\n".to_string(),
+                        Ok(QueueOutcome::Skipped) => "OK skipped\n".to_string(),
+                        Err(e) => format!("ERR {}\n", e.replace('\n', " ")),
+                    }
+                } else if msg == "STATUS" || msg.starts_with("GET ") {
+                    let snapshot = if status_endpoint {
+                        Some(serde_json::to_string(&downloads.status_snapshot().await).map_err(|e| e.to_string()))
+                    } else {
+                        None
+                    };
+                    match msg.strip_prefix("GET ") {
+                        Some(request) => http_reply(request, snapshot),
+                        None => match snapshot {
+                            Some(Ok(json)) => format!("{json}\n"),
+                            Some(Err(e)) => format!("ERR {e}\n"),
+                            None => format!("ERR {STATUS_DISABLED}\n"),
+                        },
+                    }
+                } else if let Some(path) = msg.strip_prefix("EXPORT_QUEUE ") {
+                    match downloads.export_queue(Path::new(path)).await {
+                        Ok(count) => format!("OK {count}\n"),
+                        Err(e) => format!("ERR Unable to save the queue to {path}: {e}\n"),
+                    }
+                } else if let Some(path) = msg.strip_prefix("IMPORT_QUEUE ") {
+                    match downloads.import_queue(Path::new(path)).await {
+                        Ok(count) => format!("OK {count}\n"),
+                        Err(e) => format!("ERR {e}\n"),
+                    }
+                } else if msg == "LIST" {
+                    match serde_json::to_string(&downloads.status_list().await) {
+                        Ok(json) => format!("{json}\n"),
+                        Err(e) => format!("ERR {e}\n"),
+                    }
+                } else {
+                    format!("ERR Unknown command: {msg}\n")
+                };
+                let _ = stream.write_all(reply.as_bytes()).await;
             }
             Err(e) => {
-                logger.log(format!("nxm socket received invalid UTF-8 sequence: {}", e));
+                logger.error(format!("nxm socket received invalid UTF-8 sequence: {}", e));
             }
         },
         // is_readable returned a false positive
         Err(ref e) if e.kind() == ErrorKind::WouldBlock => {}
         Err(e) => {
-            logger.log(format!("nxm socket encountered error: {}", e));
+            logger.error(format!("nxm socket encountered error: {}", e));
         }
     }
 }
 
-fn get_socket_path() -> String {
-    extern "C" {
-        fn getuid() -> u32;
-    }
-    let uid;
-    unsafe { uid = getuid() }
-    format!("/run/user/{}/dmodman.socket", uid)
-}
-
-fn remove_socket() -> Result<(), Error> {
-    std::fs::remove_file(get_socket_path())
-}
+const STATUS_DISABLED: &str = "The status endpoint is disabled. Set status_endpoint = true in the config to enable it.";
 
-async fn connect() -> Result<UnixStream, Error> {
-    UnixStream::connect(get_socket_path()).await
+/* Answers an HTTP GET request, given without the method. Only /status exists. The snapshot is None if the status
+ * endpoint is disabled. The connection is closed after the reply, so HTTP/1.1 clients don't wait for more. */
+fn http_reply(request: &str, snapshot: Option<Result<String, String>>) -> String {
+    let path = request.split_whitespace().next().unwrap_or_default();
+    let (status, content_type, body) = match (path, snapshot) {
+        ("/status", Some(Ok(json))) => ("200 OK", "application/json", json),
+        ("/status", Some(Err(e))) => ("500 Internal Server Error", "text/plain", e),
+        ("/status", None) => ("403 Forbidden", "text/plain", STATUS_DISABLED.to_string()),
+        _ => ("404 Not Found", "text/plain", format!("Not found: {path}")),
+    };
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
 }
 
-pub async fn send_msg(msg: &str) -> Result<(), Error> {
-    let stream = connect().await?;
+pub async fn send_msg(path: &Path, msg: &str) -> Result<(), Error> {
+    let stream = UnixStream::connect(path).await?;
     loop {
         let ready = stream.ready(Interest::WRITABLE).await?;
         if ready.is_writable() {
@@ -131,3 +293,38 @@ pub async fn send_msg(msg: &str) -> Result<(), Error> {
         }
     }
 }
+
+// Sends the message and returns the reply of the running instance
+pub async fn send_and_receive(path: &Path, msg: &str) -> Result<String, Error> {
+    let mut stream = UnixStream::connect(path).await?;
+    stream.write_all(msg.as_bytes()).await?;
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).await?;
+    Ok(reply)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{http_reply, SocketCommand};
+
+    #[test]
+    fn socket_command() {
+        for command in SocketCommand::ALL {
+            assert_eq!(SocketCommand::from_name(command.name()), Some(command));
+            assert_eq!(SocketCommand::from_message(command.message()), Some(command));
+        }
+        assert_eq!(SocketCommand::from_message("STOP"), Some(SocketCommand::Quit));
+        assert_eq!(SocketCommand::from_name("PAUSE_ALL"), None);
+    }
+
+    #[test]
+    fn http_status() {
+        let reply = http_reply("/status HTTP/1.1\r\nHost: localhost", Some(Ok("{}".to_string())));
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reply.contains("Content-Length: 2\r\n"));
+        assert!(reply.ends_with("\r\n\r\n{}"));
+
+        assert!(http_reply("/status HTTP/1.1", None).starts_with("HTTP/1.1 403 Forbidden"));
+        assert!(http_reply("/ HTTP/1.1", Some(Ok("{}".to_string()))).starts_with("HTTP/1.1 404 Not Found"));
+    }
+}
@@ -1,17 +1,46 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
+use crate::archives::{ArchiveEntry, ModStructureType};
 use crate::Archives;
 use ratatui::layout::Constraint;
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::widgets::{Block, Borders, Cell, Row, Table, TableState};
 use tokio_stream::StreamExt;
 
 use crate::util;
+use crate::util::changes::ChangeFlag;
+
+// A file or directory shown under an expanded archive. Directories that only appear in the paths of files are included.
+#[derive(Clone, Debug, PartialEq)]
+struct TreeNode {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+    size: u64,
+}
+
+// Each row of the table is either an archive, as an index into Archives::files, or an entry inside one
+#[derive(Debug, PartialEq)]
+enum ArchiveRow {
+    Archive(usize),
+    Entry(usize, TreeNode),
+}
 
 pub struct ArchiveTable<'a> {
     headers: Row<'a>,
-    widths: [Constraint; 2],
+    widths: [Constraint; 3],
+    // Detected in the background, since it reads every archive. Archives that aren't done yet have no type.
+    mod_types: Arc<Mutex<HashMap<PathBuf, ModStructureType>>>,
+    mod_types_changed: ChangeFlag,
+    // The contents of the archives that have been expanded, by archive path
+    contents: HashMap<PathBuf, Vec<ArchiveEntry>>,
+    // The expanded archives and the expanded directories within each of them
+    expanded: HashMap<PathBuf, HashSet<PathBuf>>,
+    rows: Vec<ArchiveRow>,
+    tree_changed: bool,
     pub block: Block<'a>,
     pub highlight_style: Style,
     pub state: TableState,
@@ -24,13 +53,24 @@ pub struct ArchiveTable<'a> {
 impl<'a> ArchiveTable<'a> {
     pub fn new(redraw_terminal: Arc<AtomicBool>) -> Self {
         let block = Block::default().borders(Borders::ALL).title("Archives");
-        let headers = Row::new(["Name", "Size"].iter().map(|h| Cell::from(*h).style(Style::default().fg(Color::Red))));
-        let widths = [Constraint::Ratio(4, 5), Constraint::Ratio(1, 5)];
+        let headers =
+            Row::new(["Name", "Type", "Size"].iter().map(|h| Cell::from(*h).style(Style::default().fg(Color::Red))));
+        let widths = [
+            Constraint::Ratio(7, 10),
+            Constraint::Ratio(3, 20),
+            Constraint::Ratio(3, 20),
+        ];
 
         Self {
             block,
             headers,
             widths,
+            mod_types: Arc::new(Mutex::new(HashMap::new())),
+            mod_types_changed: ChangeFlag::default(),
+            contents: HashMap::new(),
+            expanded: HashMap::new(),
+            rows: vec![],
+            tree_changed: false,
             highlight_style: Style::default(),
             state: TableState::default(),
             widget: Table::default().widths(widths),
@@ -40,19 +80,179 @@ impl<'a> ArchiveTable<'a> {
         }
     }
 
+    // The index in Archives::files of the selected archive, or of the archive that contains the selected entry
+    pub fn selected_archive_index(&self) -> Option<usize> {
+        match self.state.selected().and_then(|i| self.rows.get(i)) {
+            Some(ArchiveRow::Archive(i)) | Some(ArchiveRow::Entry(i, _)) => Some(*i),
+            None => None,
+        }
+    }
+
+    /* Expands the selected archive or directory. The contents of an archive are read the first time it's expanded,
+     * which can take a while for large archives. */
+    pub async fn expand(&mut self, archives: &Archives) -> compress_tools::Result<()> {
+        match self.state.selected().and_then(|i| self.rows.get(i)) {
+            Some(ArchiveRow::Archive(i)) => {
+                let path = archives.files[*i].path();
+                if !self.contents.contains_key(&path) {
+                    let entries = archives.list_contents(path.clone()).await?;
+                    self.contents.insert(path.clone(), entries);
+                }
+                self.expanded.entry(path).or_default();
+            }
+            Some(ArchiveRow::Entry(i, node)) if node.is_dir => {
+                let path = archives.files[*i].path();
+                self.expanded.entry(path).or_default().insert(node.path.clone());
+            }
+            _ => return Ok(()),
+        }
+        self.tree_changed = true;
+        Ok(())
+    }
+
+    /* Collapses the selected archive or directory. If it's a file or already collapsed, collapses the directory or
+     * archive that contains it instead and selects that. */
+    pub fn collapse(&mut self, archives: &Archives) {
+        let selected = if let Some(i) = self.state.selected() { i } else { return };
+        let (archive, node) = match self.rows.get(selected) {
+            Some(ArchiveRow::Archive(i)) => (*i, None),
+            Some(ArchiveRow::Entry(i, node)) => (*i, Some(node)),
+            None => return,
+        };
+        let path = archives.files[archive].path();
+        let dirs = if let Some(dirs) = self.expanded.get_mut(&path) {
+            dirs
+        } else {
+            return;
+        };
+        let parent = match node {
+            None => None,
+            Some(node) if node.is_dir && dirs.remove(&node.path) => {
+                self.tree_changed = true;
+                return;
+            }
+            Some(node) => node.path.parent().filter(|parent| !parent.as_os_str().is_empty()),
+        };
+        match parent {
+            Some(parent) => {
+                dirs.remove(parent);
+            }
+            None => {
+                self.expanded.remove(&path);
+            }
+        }
+        // The rows above the collapsed one don't change, so its index stays the same
+        let collapsed_row = self.rows[..=selected].iter().rposition(|row| match (row, parent) {
+            (ArchiveRow::Archive(i), None) => *i == archive,
+            (ArchiveRow::Entry(i, node), Some(parent)) => *i == archive && node.path == parent,
+            _ => false,
+        });
+        self.state.select(collapsed_row);
+        self.tree_changed = true;
+    }
+
+    pub async fn toggle_expanded(&mut self, archives: &Archives) -> compress_tools::Result<()> {
+        let is_expanded = match self.state.selected().and_then(|i| self.rows.get(i)) {
+            Some(ArchiveRow::Archive(i)) => self.expanded.contains_key(&archives.files[*i].path()),
+            Some(ArchiveRow::Entry(i, node)) if node.is_dir => {
+                self.expanded.get(&archives.files[*i].path()).is_some_and(|dirs| dirs.contains(&node.path))
+            }
+            _ => return Ok(()),
+        };
+        if is_expanded {
+            self.collapse(archives);
+            Ok(())
+        } else {
+            self.expand(archives).await
+        }
+    }
+
+    // Detects the types of the archives that don't have one yet, one at a time so that the disk isn't thrashed
+    fn detect_mod_types(&self, paths: HashSet<PathBuf>) {
+        let mod_types = self.mod_types.clone();
+        let changed = self.mod_types_changed.clone();
+        let new_paths: Vec<PathBuf> = {
+            let known = mod_types.lock().unwrap();
+            paths.into_iter().filter(|path| !known.contains_key(path)).collect()
+        };
+        tokio::task::spawn(async move {
+            for path in new_paths {
+                let (path, mod_type) = match tokio::task::spawn_blocking(move || {
+                    let mod_type = Archives::detect_mod_type(&path);
+                    (path, mod_type)
+                })
+                .await
+                {
+                    Ok(detected) => detected,
+                    Err(_) => continue,
+                };
+                mod_types.lock().unwrap().insert(path, mod_type);
+                changed.set();
+            }
+        });
+    }
+
     // TODO use inotify to refresh the directory state only when needed
     pub async fn refresh(&mut self, archives: &mut Archives) {
-        if archives.swap_has_changed() {
-            let arch_list = archives.list().await;
-            let mut stream = tokio_stream::iter(arch_list.iter());
+        let dir_changed = archives.swap_has_changed();
+        if dir_changed {
+            archives.list().await;
+            // Forget archives that are gone, since a new archive with the same name may have different contents
+            let paths: HashSet<PathBuf> = archives.files.iter().map(|f| f.path()).collect();
+            self.contents.retain(|path, _| paths.contains(path));
+            self.expanded.retain(|path, _| paths.contains(path));
+            self.mod_types.lock().unwrap().retain(|path, _| paths.contains(path));
+            self.detect_mod_types(paths);
+        }
+        if self.mod_types_changed.take() {
+            self.tree_changed = true;
+        }
+        if dir_changed || self.tree_changed {
+            self.tree_changed = false;
+            self.rows.clear();
+            let mut stream = tokio_stream::iter(archives.files.iter().enumerate());
             let mut rows: Vec<Row> = vec![];
-            while let Some(direntry) = stream.next().await {
+            while let Some((i, direntry)) = stream.next().await {
+                let path = direntry.path();
+                let arrow = if self.expanded.contains_key(&path) {
+                    "▾"
+                } else {
+                    "▸"
+                };
+                let mod_type = self.mod_types.lock().unwrap().get(&path).map_or("", ModStructureType::label);
                 rows.push(Row::new(vec![
-                    direntry.file_name().into_string().unwrap(),
-                    util::format::human_readable(direntry.metadata().await.unwrap().len()).0,
-                ]))
+                    format!("{arrow} {}", direntry.file_name().into_string().unwrap()),
+                    mod_type.to_string(),
+                    util::format::human_size(direntry.metadata().await.unwrap().len()),
+                ]));
+                self.rows.push(ArchiveRow::Archive(i));
+
+                let (entries, dirs) = match (self.contents.get(&path), self.expanded.get(&path)) {
+                    (Some(entries), Some(dirs)) => (entries, dirs),
+                    _ => continue,
+                };
+                for node in build_tree(entries, dirs) {
+                    let indent = "  ".repeat(node.depth + 1);
+                    let name = node.path.file_name().unwrap_or_default().to_string_lossy();
+                    let row = if node.is_dir {
+                        let arrow = if dirs.contains(&node.path) { "▾" } else { "▸" };
+                        Row::new(vec![format!("{indent}{arrow} {name}/"), String::new(), String::new()])
+                            .style(Style::default().add_modifier(Modifier::BOLD))
+                    } else {
+                        Row::new(vec![
+                            format!("{indent}  {name}"),
+                            String::new(),
+                            util::format::human_size(node.size),
+                        ])
+                    };
+                    rows.push(row);
+                    self.rows.push(ArchiveRow::Entry(i, node));
+                }
             }
             self.len = rows.len();
+            if self.state.selected().is_some_and(|i| i >= self.len) {
+                self.state.select(self.len.checked_sub(1));
+            }
             self.widget = Table::new(rows, self.widths)
                 .header(self.headers.to_owned())
                 .block(self.block.to_owned())
@@ -66,3 +266,93 @@ impl<'a> ArchiveTable<'a> {
         }
     }
 }
+
+/* Arranges the entries of an archive as a tree, sorted by path so that each directory is followed by its contents.
+ * Archives don't always list the directories themselves, so they're derived from the paths of the files. Only the
+ * contents of expanded directories are included. */
+fn build_tree(entries: &[ArchiveEntry], expanded: &HashSet<PathBuf>) -> Vec<TreeNode> {
+    let mut nodes: BTreeMap<&Path, (bool, u64)> = BTreeMap::new();
+    for entry in entries {
+        nodes.insert(&entry.path, (entry.is_dir, entry.size));
+        for ancestor in entry.path.ancestors().skip(1).filter(|a| !a.as_os_str().is_empty()) {
+            nodes.insert(ancestor, (true, 0));
+        }
+    }
+    nodes
+        .into_iter()
+        .filter(|(path, _)| {
+            path.ancestors().skip(1).filter(|a| !a.as_os_str().is_empty()).all(|dir| expanded.contains(dir))
+        })
+        .map(|(path, (is_dir, size))| TreeNode {
+            path: path.to_path_buf(),
+            depth: path.components().count() - 1,
+            is_dir,
+            size,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_tree, TreeNode};
+    use crate::archives::ArchiveEntry;
+    use std::collections::HashSet;
+    use std::path::PathBuf;
+
+    fn entry(path: &str, is_dir: bool, size: u64) -> ArchiveEntry {
+        ArchiveEntry {
+            path: PathBuf::from(path),
+            size,
+            is_dir,
+            crc: None,
+            password: None,
+        }
+    }
+
+    fn node(path: &str, depth: usize, is_dir: bool, size: u64) -> TreeNode {
+        TreeNode {
+            path: PathBuf::from(path),
+            depth,
+            is_dir,
+            size,
+        }
+    }
+
+    #[test]
+    fn archive_tree() {
+        // Only Data/ is listed as a directory, the others come from the file paths
+        let entries = vec![
+            entry("readme.txt", false, 10),
+            entry("Data/", true, 0),
+            entry("Data/Textures/a.dds", false, 200),
+            entry("Data/plugin.esp", false, 30),
+            entry("fomod/ModuleConfig.xml", false, 40),
+        ];
+        let mut expanded = HashSet::new();
+        assert_eq!(
+            build_tree(&entries, &expanded),
+            vec![
+                node("Data", 0, true, 0),
+                node("fomod", 0, true, 0),
+                node("readme.txt", 0, false, 10)
+            ]
+        );
+
+        expanded.insert(PathBuf::from("Data"));
+        assert_eq!(
+            build_tree(&entries, &expanded),
+            vec![
+                node("Data", 0, true, 0),
+                node("Data/Textures", 1, true, 0),
+                node("Data/plugin.esp", 1, false, 30),
+                node("fomod", 0, true, 0),
+                node("readme.txt", 0, false, 10),
+            ]
+        );
+
+        // Expanded directories stay hidden while their parent is collapsed
+        expanded.insert(PathBuf::from("Data/Textures"));
+        expanded.remove(&PathBuf::from("Data"));
+        assert_eq!(build_tree(&entries, &expanded).len(), 3);
+    }
+}
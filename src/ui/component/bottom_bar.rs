@@ -1,32 +1,208 @@
-use crate::api::RequestCounter;
+use crate::api::query::Account;
+use crate::api::{Client, DownloadTotals, Downloads, RequestCounter};
+use crate::cache::{Cache, CacheStats};
+use crate::util::format;
 use ratatui::layout::Alignment;
+use ratatui::style::{Color, Style};
+use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 pub struct BottomBar<'a> {
+    cache: Cache,
+    client: Client,
     request_counter: RequestCounter,
+    downloads: Downloads,
+    // The bytes read by the active downloads when the speed was last measured
+    last_sample: Option<(Instant, u64)>,
+    speed: u64, // in bytes per second
+    download_totals: String,
+    stats: String,
+    rate_limit: Span<'static>,
+    // Shown once the API key has been validated
+    account: Vec<Span<'static>>,
     pub widget: Paragraph<'a>,
     pub needs_redraw: AtomicBool,
     redraw_terminal: Arc<AtomicBool>,
 }
 
 impl<'a> BottomBar<'a> {
-    pub fn new(redraw_terminal: Arc<AtomicBool>, request_counter: RequestCounter) -> Self {
-        let widget = Paragraph::new("Remaining | hourly: NA | daily: NA").alignment(Alignment::Right);
-        request_counter.has_changed.store(true, Ordering::Relaxed);
+    pub fn new(redraw_terminal: Arc<AtomicBool>, cache: Cache, client: &Client, downloads: Downloads) -> Self {
+        let widget = Paragraph::new(format_rate_limit(None, None)).alignment(Alignment::Right);
+        client.request_counter.has_changed.set();
         Self {
+            cache,
+            client: client.clone(),
             widget,
-            request_counter: request_counter.clone(),
+            request_counter: client.request_counter.clone(),
+            downloads,
+            last_sample: None,
+            speed: 0,
+            download_totals: String::new(),
+            stats: String::new(),
+            rate_limit: format_rate_limit(None, None),
+            account: format_account(client.account().as_ref()),
             needs_redraw: AtomicBool::new(true),
             redraw_terminal,
         }
     }
 
     pub async fn refresh(&mut self) {
-        if self.request_counter.has_changed.swap(false, Ordering::Relaxed) {
-            self.widget = Paragraph::new(self.request_counter.format().await).alignment(Alignment::Right);
+        let mut has_changed = false;
+        if self.client.account_changed.take() {
+            self.account = format_account(self.client.account().as_ref());
+            has_changed = true;
+        }
+        if self.request_counter.has_changed.take() {
+            let (hourly, daily) = self.request_counter.remaining().await;
+            self.rate_limit = format_rate_limit(hourly, daily);
+            has_changed = true;
+        }
+        // Cache::stats() reuses its result for a few seconds, so this is cheap to call on every refresh
+        let stats = format_stats(&self.cache.stats().await, self.cache.file_index.load_progress());
+        if stats != self.stats {
+            self.stats = stats;
+            has_changed = true;
+        }
+        let totals = self.downloads.totals().await;
+        self.measure_speed(&totals);
+        let download_totals = format_totals(&totals, self.speed);
+        if download_totals != self.download_totals {
+            self.download_totals = download_totals;
+            has_changed = true;
+        }
+        if has_changed {
+            let mut spans = self.account.clone();
+            spans.push(Span::raw(format!("{} | {} | ", self.download_totals, self.stats)));
+            spans.push(self.rate_limit.clone());
+            let line = Line::from(spans);
+            self.widget = Paragraph::new(line).alignment(Alignment::Right);
             self.redraw_terminal.store(true, Ordering::Relaxed);
         }
     }
 }
+
+impl BottomBar<'_> {
+    /* The speed is averaged over at least a second, since the UI refreshes more often than that. Finished downloads
+     * drop out of the byte count, so a decrease is treated as no progress. */
+    fn measure_speed(&mut self, totals: &DownloadTotals) {
+        let now = Instant::now();
+        match self.last_sample {
+            Some((time, bytes_read)) => {
+                let elapsed = now.duration_since(time);
+                if elapsed >= Duration::from_secs(1) {
+                    self.speed = (totals.bytes_read.saturating_sub(bytes_read) as f64 / elapsed.as_secs_f64()) as u64;
+                    self.last_sample = Some((now, totals.bytes_read));
+                }
+            }
+            None => self.last_sample = Some((now, totals.bytes_read)),
+        }
+    }
+}
+
+fn format_totals(totals: &DownloadTotals, speed: u64) -> String {
+    if totals.active + totals.queued + totals.done == 0 {
+        return "Idle".to_string();
+    }
+    let mut text = format::download_counts(totals.active, totals.queued, totals.done);
+    if totals.active == 0 {
+        return text;
+    }
+    if let Some(fraction) = totals.fraction {
+        text.push_str(&format!(", {:.0}%", fraction * 100.0));
+    }
+    text.push_str(&format!(", {}/s", format::human_size(speed)));
+    text
+}
+
+// While the files are loaded in the background, shows how many of them have been loaded instead
+fn format_stats(stats: &CacheStats, load_progress: Option<(usize, usize)>) -> String {
+    if let Some((loaded, total)) = load_progress {
+        return format!("Loaded {loaded}/{total} files");
+    }
+    let mut text = format!("{} mods in {} games, {}", stats.mods, stats.games, format::human_size(stats.total_size));
+    if let Some(elapsed) = stats.latest_install.and_then(|t| SystemTime::now().duration_since(t).ok()) {
+        text.push_str(&format!(", latest {}", format::time_ago(elapsed)));
+    }
+    text
+}
+
+// The name of the account, with a badge that shows whether it's premium, or nothing if the API key wasn't validated
+fn format_account(account: Option<&Account>) -> Vec<Span<'static>> {
+    match account {
+        Some(account) => {
+            let badge = if account.is_premium {
+                Span::styled("[Premium]", Style::default().fg(Color::Yellow))
+            } else {
+                Span::raw("[Free]")
+            };
+            vec![Span::raw(format!("{} ", account.name)), badge, Span::raw(" | ")]
+        }
+        None => vec![],
+    }
+}
+
+/* The remaining API requests, which turn yellow when the hourly quota is running low and red once it has run out, so
+ * that rate limited requests don't come as a surprise. */
+fn format_rate_limit(hourly: Option<u16>, daily: Option<u16>) -> Span<'static> {
+    let na = |n: Option<u16>| n.map_or_else(|| "NA".to_string(), |n| n.to_string());
+    let text = format!("RL: H:{} D:{}", na(hourly), na(daily));
+    match hourly {
+        Some(0) => Span::styled(text, Style::default().fg(Color::Red)),
+        Some(n) if n < 10 => Span::styled(text, Style::default().fg(Color::Yellow)),
+        _ => Span::raw(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_account, format_rate_limit, format_totals};
+    use crate::api::query::Account;
+    use crate::api::DownloadTotals;
+    use ratatui::style::{Color, Style};
+    use ratatui::text::Span;
+
+    #[test]
+    fn rate_limit() {
+        assert_eq!(format_rate_limit(None, None), Span::raw("RL: H:NA D:NA"));
+        assert_eq!(format_rate_limit(Some(100), Some(19000)), Span::raw("RL: H:100 D:19000"));
+        assert_eq!(format_rate_limit(Some(9), Some(0)).style, Style::default().fg(Color::Yellow));
+        assert_eq!(format_rate_limit(Some(0), Some(0)).style, Style::default().fg(Color::Red));
+    }
+
+    #[test]
+    fn account() {
+        assert!(format_account(None).is_empty());
+        let mut account = Account {
+            user_id: 1234,
+            name: "dmodman".to_string(),
+            is_premium: false,
+            is_supporter: false,
+        };
+        assert_eq!(format_account(Some(&account))[1], Span::raw("[Free]"));
+        account.is_premium = true;
+        assert_eq!(format_account(Some(&account))[1].style, Style::default().fg(Color::Yellow));
+    }
+
+    #[test]
+    fn download_totals() {
+        assert_eq!(format_totals(&DownloadTotals::default(), 0), "Idle");
+        let totals = DownloadTotals {
+            active: 2,
+            queued: 1,
+            done: 3,
+            bytes_read: 3072,
+            fraction: Some(0.25),
+        };
+        assert_eq!(format_totals(&totals, 2048), "↓ 2 active, 1 queued, 3 done, 25%, 2.00 KiB/s");
+        let totals = DownloadTotals {
+            fraction: None,
+            ..totals
+        };
+        assert_eq!(format_totals(&totals, 100), "↓ 2 active, 1 queued, 3 done, 100 B/s");
+        let totals = DownloadTotals { active: 0, ..totals };
+        assert_eq!(format_totals(&totals, 0), "↓ 0 active, 1 queued, 3 done");
+    }
+}
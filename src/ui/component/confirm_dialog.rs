@@ -0,0 +1,52 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+
+// What to do once the user has answered
+pub enum ConfirmAction {
+    // Whether the file itself is deleted along with its metadata depends on the answer
+    DeleteFile { file_id: u64 },
+    ClearMessages,
+    // A different file with the same name as the download is in the way
+    OverwriteFile { file_id: u64 },
+    // The message is the error that made the download fail
+    RetryDownload { file_id: u64 },
+    RebuildIndex,
+    // The archive failed its integrity test, so it's deleted and downloaded again
+    RedownloadCorrupt { file_id: u64 },
+    // Newer versions of these files have been downloaded
+    DeleteOldVersions { file_ids: Vec<u64> },
+}
+
+/* Asks a yes/no question before an action that can't be undone. The answer keys are shown in the hotkey bar, and the
+ * action is taken by whoever handles them. */
+pub struct ConfirmDialog<'a> {
+    pub action: Option<ConfirmAction>,
+    pub widget: Paragraph<'a>,
+    redraw_terminal: Arc<AtomicBool>,
+}
+
+impl ConfirmDialog<'_> {
+    pub fn new(redraw_terminal: Arc<AtomicBool>) -> Self {
+        Self {
+            action: None,
+            widget: Paragraph::default(),
+            redraw_terminal,
+        }
+    }
+
+    pub fn show(&mut self, action: ConfirmAction, question: &str, message: String) {
+        let border_style = Style::default().fg(Color::Yellow).bg(Color::Black);
+        let block = Block::default().borders(Borders::ALL).title(question.to_string()).border_style(border_style);
+        self.widget = Paragraph::new(message).wrap(Wrap { trim: false }).block(block);
+        self.action = Some(action);
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    pub fn hide(&mut self) -> Option<ConfirmAction> {
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+        self.action.take()
+    }
+}
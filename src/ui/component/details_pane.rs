@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+use reqwest::StatusCode;
+use tokio::sync::RwLock;
+
+use crate::api::{ApiError, Client};
+use crate::cache::{Cache, FileData};
+use crate::util::changes::ChangeFlag;
+use crate::util::format;
+use crate::Logger;
+
+enum FetchState {
+    Pending,
+    Done,
+    NotFound,
+    Failed,
+}
+
+/* Shows the details of the file selected in the file table. Mod info is fetched from the Nexus the first time a file
+ * from that mod is shown, and cached after that. */
+pub struct DetailsPane<'a> {
+    cache: Cache,
+    client: Client,
+    logger: Logger,
+    pub visible: bool,
+    pub widget: Paragraph<'a>,
+    // Mod info requests made during this session, so that failed requests aren't repeated
+    fetches: Arc<RwLock<HashMap<(String, u32), FetchState>>>,
+    fetch_done: ChangeFlag,
+    shown_file_id: Option<u64>,
+    redraw_terminal: Arc<AtomicBool>,
+}
+
+impl<'a> DetailsPane<'a> {
+    pub fn new(redraw_terminal: Arc<AtomicBool>, cache: Cache, client: Client, logger: Logger) -> Self {
+        Self {
+            cache,
+            client,
+            logger,
+            visible: false,
+            widget: Paragraph::default(),
+            fetches: Arc::new(RwLock::new(HashMap::new())),
+            fetch_done: ChangeFlag::default(),
+            shown_file_id: None,
+            redraw_terminal,
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        self.shown_file_id = None;
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    pub async fn refresh(&mut self, selected: Option<Arc<FileData>>) {
+        if !self.visible {
+            return;
+        }
+        let file_id = selected.as_ref().map(|fd| fd.file_id);
+        if !self.fetch_done.take() && file_id == self.shown_file_id && file_id.is_some() {
+            return;
+        }
+        self.shown_file_id = file_id;
+
+        let block = Block::default().borders(Borders::ALL).title("Details");
+        let fdata = match selected {
+            Some(fdata) => fdata,
+            None => {
+                self.widget = Paragraph::new("No file selected.").block(block);
+                self.redraw_terminal.store(true, Ordering::Relaxed);
+                return;
+            }
+        };
+        let (game, mod_id, installed_at) = {
+            let lf = fdata.local_file.read().await;
+            (lf.game.clone(), lf.mod_id, lf.installed_at)
+        };
+        let mod_info = self.cache.mod_infos.get((&game, mod_id)).await;
+        if mod_info.is_none() {
+            self.fetch_mod_info(&game, mod_id).await;
+        }
+
+        let fd = &fdata.file_details;
+        let mut lines = vec![];
+        match &mod_info {
+            Some(mi) if mi.is_available() => {
+                lines.push(field("Mod", mi.name.clone().unwrap_or_default()));
+                lines.push(field("Author", mi.author.clone()));
+                lines.push(field("Mod version", mi.version.clone()));
+            }
+            Some(mi) => {
+                lines.push(field("Mod", format!("No longer available on the Nexus ({})", mi.status)));
+            }
+            None => {
+                let status = match self.fetches.read().await.get(&(game.clone(), mod_id)) {
+                    Some(FetchState::NotFound) => "Not found on the Nexus, it may have been deleted",
+                    Some(FetchState::Failed) => "Unable to fetch mod info",
+                    _ => "Loading...",
+                };
+                lines.push(field("Mod", status.to_string()));
+            }
+        }
+        lines.push(field("File", fd.name.clone()));
+        lines.push(field("Version", fd.version.clone().unwrap_or_default()));
+        lines.push(field("Category", fd.category_name.clone().unwrap_or_default()));
+        lines.push(field("Uploaded", fd.uploaded_time.clone()));
+        lines.push(field("Size", format::human_size(fd.size_kb * 1024)));
+        if let Some(secs) = installed_at {
+            lines.push(field("Installed", format::utc_timestamp(UNIX_EPOCH + Duration::from_secs(secs))));
+        }
+        lines.push(Line::default());
+        lines.push(Line::from(fd.description.clone()));
+        if let Some(summary) = mod_info.and_then(|mi| mi.summary) {
+            lines.push(Line::default());
+            lines.push(Line::from(summary));
+        }
+
+        self.widget = Paragraph::new(lines).wrap(Wrap { trim: false }).block(block);
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    async fn fetch_mod_info(&self, game: &str, mod_id: u32) {
+        let key = (game.to_string(), mod_id);
+        {
+            let mut fetches = self.fetches.write().await;
+            if fetches.contains_key(&key) {
+                return;
+            }
+            fetches.insert(key.clone(), FetchState::Pending);
+        }
+
+        let cache = self.cache.clone();
+        let client = self.client.clone();
+        let logger = self.logger.clone();
+        let fetches = self.fetches.clone();
+        let fetch_done = self.fetch_done.clone();
+        tokio::task::spawn(async move {
+            let (game, mod_id) = &key;
+            let state = match client.mod_info(game, *mod_id).await {
+                Ok(mi) => {
+                    if let Err(e) = cache.save_mod_info(&mi, game, *mod_id).await {
+                        logger.error(format!("Unable to save mod info for {game} {mod_id}: {e}"));
+                    }
+                    FetchState::Done
+                }
+                Err(ApiError::ConnectionError { source }) if source.status() == Some(StatusCode::NOT_FOUND) => {
+                    FetchState::NotFound
+                }
+                Err(e) => {
+                    logger.warn(format!("Unable to fetch mod info for {game} {mod_id}: {e}"));
+                    FetchState::Failed
+                }
+            };
+            fetches.write().await.insert(key.clone(), state);
+            fetch_done.set();
+        });
+    }
+}
+
+fn field(name: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{name}: "), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw(value),
+    ])
+}
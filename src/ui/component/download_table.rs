@@ -1,18 +1,31 @@
-use crate::api::Downloads;
+use crate::api::{DownloadInfo, DownloadState, Downloads};
+use crate::config::DownloadColumnId;
+use crate::util::format;
 use ratatui::layout::Constraint;
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Span;
+use ratatui::widgets::block::{Position, Title};
 use ratatui::widgets::{Block, Borders, Cell, Row, Table, TableState};
+use std::collections::BTreeSet;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio_stream::StreamExt;
 
 pub struct DownloadTable<'a> {
     pub state: TableState,
     pub downloads: Downloads,
     pub block: Block<'a>,
+    columns: Vec<DownloadColumnId>,
     headers: Row<'a>,
-    widths: [Constraint; 3],
+    widths: Vec<Constraint>,
+    // Width of the progress bar column in characters, known once the layout has been calculated
+    bar_width: usize,
     pub highlight_style: Style,
+    // Rows selected for bulk actions. These are separate from the focused row, which is tracked by the TableState.
+    pub multi_selected: BTreeSet<usize>,
+    // Why each download failed, if it did, so that the error of the selected one can be shown
+    errors: Vec<Option<String>>,
     pub widget: Table<'a>,
     pub needs_redraw: AtomicBool,
     redraw_terminal: Arc<AtomicBool>,
@@ -20,27 +33,26 @@ pub struct DownloadTable<'a> {
 }
 
 impl<'a> DownloadTable<'a> {
-    pub fn new(redraw_terminal: Arc<AtomicBool>, downloads: Downloads) -> Self {
+    pub fn new(redraw_terminal: Arc<AtomicBool>, downloads: Downloads, columns: Vec<DownloadColumnId>) -> Self {
         let block = Block::default().borders(Borders::ALL).title("Downloads");
 
-        let headers = Row::new(
-            ["Filename", "Progress", "Status"].iter().map(|h| Cell::from(*h).style(Style::default().fg(Color::Red))),
-        );
+        let headers = Row::new(columns.iter().map(|c| Cell::from(c.header()).style(Style::default().fg(Color::Red))));
 
-        downloads.has_changed.store(true, Ordering::Relaxed);
-        let widths = [
-            Constraint::Percentage(60),
-            Constraint::Percentage(20),
-            Constraint::Percentage(20),
-        ];
+        downloads.has_changed.set();
+        let total_width: u32 = columns.iter().map(|c| c.width()).sum();
+        let widths: Vec<Constraint> = columns.iter().map(|c| Constraint::Ratio(c.width(), total_width)).collect();
 
         Self {
             state: TableState::default(),
             downloads,
+            columns,
             block,
             headers,
             widths,
+            bar_width: 0,
             highlight_style: Style::default(),
+            multi_selected: BTreeSet::new(),
+            errors: vec![],
             widget: Table::default(),
             needs_redraw: AtomicBool::new(false),
             redraw_terminal,
@@ -53,29 +65,109 @@ impl<'a> DownloadTable<'a> {
     where
         'b: 'a,
     {
-        if self.downloads.has_changed.swap(false, Ordering::Relaxed) {
+        if self.downloads.has_changed.take() {
             let tasks = self.downloads.tasks.read().await;
             let mut stream = tokio_stream::iter(tasks.values());
             let mut rows: Vec<Row> = vec![];
+            self.errors.clear();
+            let spinner = format::SPINNER[(SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() / 250
+                % format::SPINNER.len() as u128) as usize];
+            let mut i = 0;
             while let Some(task) = stream.next().await {
-                rows.push(Row::new(vec![
-                    task.dl_info.file_info.file_name.to_owned(),
-                    task.dl_info.progress.to_string(),
-                    task.dl_info.get_state().to_string(),
-                ]))
+                let mut row = Row::new(self.columns.iter().map(|column| match column {
+                    DownloadColumnId::Filename => task.dl_info.file_info.file_name.to_owned(),
+                    DownloadColumnId::Progress => task.dl_info.progress.to_string(),
+                    DownloadColumnId::ProgressBar => self.progress_bar(&task.dl_info, spinner),
+                    DownloadColumnId::Status => task.dl_info.get_state().to_string(),
+                }));
+                if self.multi_selected.contains(&i) {
+                    row = row.style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD));
+                }
+                rows.push(row);
+                self.errors.push(match task.dl_info.get_state() {
+                    DownloadState::Error => task.dl_info.error(),
+                    _ => None,
+                });
+                i += 1;
             }
+            // Forget selections of downloads that no longer exist
+            self.multi_selected.retain(|i| *i < rows.len());
 
             self.len = rows.len();
-            self.widget = Table::new(rows, self.widths)
+            self.widget = Table::new(rows, self.widths.clone())
                 .header(self.headers.to_owned())
-                .block(self.block.to_owned())
+                .block(self.block_with_error())
                 .highlight_style(self.highlight_style);
 
             self.needs_redraw.store(false, Ordering::Relaxed);
             self.redraw_terminal.store(true, Ordering::Relaxed);
         } else if self.needs_redraw.swap(false, Ordering::Relaxed) {
-            self.widget = self.widget.clone().block(self.block.to_owned()).highlight_style(self.highlight_style);
+            self.widget = self.widget.clone().block(self.block_with_error()).highlight_style(self.highlight_style);
             self.redraw_terminal.store(true, Ordering::Relaxed);
         }
     }
+
+    // Shows why the selected download failed at the bottom of the table
+    fn block_with_error(&self) -> Block<'a> {
+        match self.state.selected().and_then(|i| self.errors.get(i)).and_then(Option::as_ref) {
+            Some(error) => self.block.clone().title(
+                Title::from(Span::styled(
+                    format!(" Error: {} ", error.replace('\n', " ")),
+                    Style::default().fg(Color::Red),
+                ))
+                .position(Position::Bottom),
+            ),
+            None => self.block.to_owned(),
+        }
+    }
+
+    pub fn toggle_multi_select(&mut self, i: usize) {
+        if !self.multi_selected.remove(&i) {
+            self.multi_selected.insert(i);
+        }
+        // The rows need to be rebuilt to change their style
+        self.downloads.has_changed.set();
+    }
+
+    // Keeps the selections pointing to the same downloads after the download at index i has been removed
+    pub fn shift_multi_selected(&mut self, removed: usize) {
+        self.multi_selected = self
+            .multi_selected
+            .iter()
+            .filter(|i| **i != removed)
+            .map(|i| if *i > removed { i - 1 } else { *i })
+            .collect();
+    }
+
+    // Keeps the selections pointing to the same downloads after the downloads at indices i and j have been swapped
+    pub fn swap_multi_selected(&mut self, i: usize, j: usize) {
+        let (has_i, has_j) = (self.multi_selected.remove(&i), self.multi_selected.remove(&j));
+        if has_i {
+            self.multi_selected.insert(j);
+        }
+        if has_j {
+            self.multi_selected.insert(i);
+        }
+    }
+
+    // Called when the terminal is resized, since the progress bars are as wide as their column
+    pub fn resize(&mut self, table_width: u16) {
+        let total_width: u32 = self.columns.iter().map(|c| c.width()).sum();
+        let bar_width = self.columns.iter().find(|c| **c == DownloadColumnId::ProgressBar).map(|c| c.width());
+        if let Some(bar_width) = bar_width {
+            // Borders on both sides and one space between columns
+            let available = table_width.saturating_sub(2 + self.columns.len() as u16 - 1) as u32;
+            self.bar_width = (available * bar_width / total_width) as usize;
+            self.downloads.has_changed.set();
+        }
+    }
+
+    fn progress_bar(&self, dl_info: &DownloadInfo, spinner: &str) -> String {
+        match (dl_info.progress.fraction(), dl_info.get_state()) {
+            (_, DownloadState::Done) => format::progress_bar(1.0, self.bar_width),
+            (Some(fraction), _) => format::progress_bar(fraction, self.bar_width),
+            (None, DownloadState::Downloading) => spinner.to_string(),
+            (None, _) => format::progress_bar(0.0, self.bar_width),
+        }
+    }
 }
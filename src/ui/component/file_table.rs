@@ -1,51 +1,105 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use ratatui::layout::Constraint;
-use ratatui::style::{Color, Style};
+use ratatui::style::{Color, Modifier, Style};
 use ratatui::widgets::{Block, Borders, Cell, Row, Table, TableState};
 use tokio_stream::StreamExt;
 
-use crate::cache::{FileIndex, UpdateStatus};
+use crate::api::UpdateChecker;
+use crate::cache::{ArchiveState, FileIndex, GameInfos, ModInfos, UpdateStatus, UserMods, VerificationState};
+use crate::config::{Config, DateFormat, FileColumnId, FileGrouping};
+use crate::util::changes::ChangeFlag;
+use crate::util::format;
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+enum GroupKey {
+    Game(String),
+    Mod(String, u32),
+}
+
+// Each row of the table is either a group header or an index into FileIndex::files_sorted
+#[derive(Debug, PartialEq)]
+enum FileRow {
+    Header(GroupKey, usize), // the amount of files in the group
+    File(usize),
+}
 
 pub struct FileTable<'a> {
     pub file_index: FileIndex,
+    // For showing the names of games and mods, which fall back to the domain name and mod id
+    mod_infos: ModInfos,
+    game_infos: GameInfos,
+    user_mods: UserMods,
+    updater: UpdateChecker,
+    // The configured columns, and the ones currently shown
+    all_columns: Vec<FileColumnId>,
+    columns: Vec<FileColumnId>,
+    game_column_threshold: usize,
+    game_column_width: u32,
+    grouping: FileGrouping,
+    date_format: DateFormat,
+    // Kept across refreshes, so that new files don't expand the groups again
+    collapsed: HashSet<GroupKey>,
+    show_ignored: bool,
+    // Only files with an update available are shown
+    only_updates: bool,
+    // Newest first, instead of the order in which the files were loaded
+    sort_by_installed: bool,
+    rows: Vec<FileRow>,
     headers: Row<'a>,
-    widths: [Constraint; 5],
+    widths: Vec<Constraint>,
     pub block: Block<'a>,
     pub highlight_style: Style,
     pub state: TableState,
     pub widget: Table<'a>,
     pub needs_redraw: AtomicBool,
-    has_data_changed: Arc<AtomicBool>,
+    has_data_changed: ChangeFlag,
     redraw_terminal: Arc<AtomicBool>,
     pub len: usize,
 }
 
 impl<'a> FileTable<'a> {
-    pub fn new(redraw_terminal: Arc<AtomicBool>, file_index: FileIndex) -> Self {
+    pub fn new(
+        redraw_terminal: Arc<AtomicBool>,
+        file_index: FileIndex,
+        mod_infos: ModInfos,
+        game_infos: GameInfos,
+        user_mods: UserMods,
+        updater: UpdateChecker,
+        config: &Config,
+    ) -> Self {
         let block = Block::default().borders(Borders::ALL).title("Files");
-        let headers = Row::new(
-            ["Name", "Category", "ModId", "Flags", "Version"]
-                .iter()
-                .map(|h| Cell::from(*h).style(Style::default().fg(Color::Red))),
-        );
-        let widths = [
-            Constraint::Ratio(6, 12),
-            Constraint::Ratio(2, 12),
-            Constraint::Ratio(1, 12),
-            Constraint::Ratio(1, 12),
-            Constraint::Ratio(2, 12),
-        ];
 
         let has_data_changed = file_index.has_changed.clone();
-        has_data_changed.store(true, Ordering::Relaxed);
+        has_data_changed.set();
+
+        let columns = visible_columns(&config.file_table_columns, 0, config.game_column_threshold);
+        let (headers, widths) = column_layout(&columns, config.game_column_width);
 
         Self {
             file_index: file_index.clone(),
+            mod_infos,
+            game_infos,
+            user_mods,
+            updater,
+            all_columns: config.file_table_columns.clone(),
+            columns,
+            game_column_threshold: config.game_column_threshold,
+            game_column_width: config.game_column_width,
+            grouping: config.file_table_grouping,
+            date_format: config.date_format,
+            collapsed: HashSet::new(),
+            show_ignored: true,
+            only_updates: false,
+            sort_by_installed: false,
+            rows: vec![],
             block,
             headers,
-            widths,
+            widths: widths.clone(),
             highlight_style: Style::default(),
             state: TableState::default(),
             widget: Table::default().widths(widths),
@@ -56,37 +110,164 @@ impl<'a> FileTable<'a> {
         }
     }
 
+    // The index in FileIndex::files_sorted of the selected file, or None if a group header is selected
+    pub fn selected_file_index(&self) -> Option<usize> {
+        match self.state.selected().and_then(|i| self.rows.get(i)) {
+            Some(FileRow::File(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    // Collapses or expands the group if a header is selected. Returns false otherwise.
+    pub fn toggle_collapsed(&mut self) -> bool {
+        if let Some(FileRow::Header(key, _)) = self.state.selected().and_then(|i| self.rows.get(i)) {
+            if !self.collapsed.remove(key) {
+                self.collapsed.insert(key.clone());
+            }
+            self.has_data_changed.set();
+            return true;
+        }
+        false
+    }
+
+    pub fn toggle_show_ignored(&mut self) {
+        self.show_ignored = !self.show_ignored;
+        self.update_title();
+    }
+
+    pub fn toggle_only_updates(&mut self) {
+        self.only_updates = !self.only_updates;
+        self.update_title();
+    }
+
+    fn update_title(&mut self) {
+        let title = match (self.only_updates, self.show_ignored) {
+            (true, _) => "Files (updates only)",
+            (false, true) => "Files",
+            (false, false) => "Files (ignored hidden)",
+        };
+        self.block = Block::default().borders(Borders::ALL).title(title);
+        self.has_data_changed.set();
+    }
+
+    pub fn toggle_sort_by_installed(&mut self) {
+        self.sort_by_installed = !self.sort_by_installed;
+        self.has_data_changed.set();
+    }
+
     pub async fn refresh<'b>(&mut self)
     where
         'b: 'a,
     {
-        if self.has_data_changed.swap(false, Ordering::Relaxed) {
+        if self.has_data_changed.take() {
             let files = self.file_index.files_sorted.read().await;
-            let mut stream = tokio_stream::iter(files.iter());
+            let user_mods = self.user_mods.read().await;
+            let mut mods: Vec<(String, u32)> = vec![];
+            let mut visible: Vec<(usize, Option<u64>)> = vec![];
+            let mut stream = tokio_stream::iter(files.iter().enumerate());
+            while let Some((i, fdata)) = stream.next().await {
+                let lf = fdata.local_file.read().await;
+                mods.push((lf.game.clone(), lf.mod_id));
+                let is_visible = match lf.update_status {
+                    UpdateStatus::OutOfDate(_) => true,
+                    _ if self.only_updates => false,
+                    UpdateStatus::IgnoredUntil(_) => self.show_ignored,
+                    _ => true,
+                };
+                if is_visible {
+                    visible.push((i, lf.installed_at));
+                }
+            }
+            if self.sort_by_installed {
+                visible.sort_by_key(|(_, installed_at)| Reverse(*installed_at));
+            }
+            let visible: Vec<usize> = visible.into_iter().map(|(i, _)| i).collect();
+            self.rows = build_tree(&mods, &visible, self.grouping, &self.collapsed);
+
+            let games: HashSet<&str> = mods.iter().map(|(game, _)| game.as_str()).collect();
+            let columns = visible_columns(&self.all_columns, games.len(), self.game_column_threshold);
+            if columns != self.columns {
+                (self.headers, self.widths) = column_layout(&columns, self.game_column_width);
+                self.columns = columns;
+            }
+
+            let indent = match self.grouping {
+                FileGrouping::Flat => "",
+                FileGrouping::Game => "  ",
+                FileGrouping::Mod => "    ",
+            };
             let mut rows: Vec<Row> = vec![];
-            while let Some(fdata) = stream.next().await {
+            for row in &self.rows {
+                let i = match row {
+                    FileRow::Header(key, count) => {
+                        let arrow = if self.collapsed.contains(key) { "▸" } else { "▾" };
+                        let label = match key {
+                            GroupKey::Game(game) => format!("{arrow} {} ({count})", self.game_infos.name(game).await),
+                            GroupKey::Mod(game, mod_id) => {
+                                format!("  {arrow} {} ({count})", self.mod_name(game, *mod_id).await)
+                            }
+                        };
+                        rows.push(Row::new(vec![label]).style(Style::default().add_modifier(Modifier::BOLD)));
+                        continue;
+                    }
+                    FileRow::File(i) => *i,
+                };
+                let fdata = &files[i];
                 let lf = &fdata.local_file.read().await;
                 let fd = &fdata.file_details;
-                rows.push(Row::new(vec![
-                    fd.name.to_string(),
-                    match &fd.category_name {
-                        Some(cat) => cat.to_string(),
-                        None => fd.category_id.to_string(),
-                    },
-                    lf.mod_id.to_string(),
-                    match &lf.update_status {
-                        UpdateStatus::OutOfDate(_) => "!".to_string(),
-                        UpdateStatus::UpToDate(_) => "".to_string(),
-                        UpdateStatus::IgnoredUntil(_) => "".to_string(),
-                        UpdateStatus::HasNewFile(_) => "?".to_string(),
-                    },
-                    fd.version.clone().map_or("".to_string(), |v| v),
-                ]))
+                // The status, the flags and the color of the row all show the same update status
+                let update_status = &lf.update_status;
+                let status = self.status_cell(update_status, &lf.game, lf.mod_id, fdata.file_id).await;
+                // The names are only looked up for the columns that are shown
+                let game_name = if self.columns.contains(&FileColumnId::Game) {
+                    self.game_infos.name(&lf.game).await
+                } else {
+                    String::new()
+                };
+                let mod_name = if self.columns.contains(&FileColumnId::ModName) {
+                    self.mod_name(&lf.game, lf.mod_id).await
+                } else {
+                    String::new()
+                };
+                let row =
+                    Row::new(self.columns.iter().map(|column| match column {
+                        FileColumnId::Name => Cell::from(format!("{indent}{}", fd.name)),
+                        FileColumnId::Game => Cell::from(game_name.clone()),
+                        FileColumnId::ModName => Cell::from(mod_name.clone()),
+                        FileColumnId::Category => Cell::from(match &fd.category_name {
+                            Some(cat) => cat.to_string(),
+                            None => fd.category_id.to_string(),
+                        }),
+                        FileColumnId::ModId => Cell::from(lf.mod_id.to_string()),
+                        FileColumnId::Flags => Cell::from(match update_status {
+                            UpdateStatus::OutOfDate(_) => "!",
+                            UpdateStatus::UpToDate(_) => "",
+                            UpdateStatus::IgnoredUntil(_) => "I",
+                            UpdateStatus::HasNewFile(_) => "?",
+                        }),
+                        FileColumnId::Version => Cell::from(fd.version.clone().unwrap_or_default()),
+                        FileColumnId::Status => status.clone(),
+                        FileColumnId::Installed => {
+                            Cell::from(lf.installed_at.map_or(String::new(), |secs| {
+                                installed_date(secs, self.date_format, SystemTime::now())
+                            }))
+                        }
+                        FileColumnId::Endorsed => {
+                            Cell::from(check_mark(user_mods.endorsed.contains(&(lf.game.clone(), lf.mod_id))))
+                        }
+                        FileColumnId::Tracked => {
+                            Cell::from(check_mark(user_mods.tracked.contains(&(lf.game.clone(), lf.mod_id))))
+                        }
+                    }));
+                rows.push(row.style(row_style(update_status, lf.archive_state, lf.verification)));
             }
 
             self.len = rows.len();
+            if self.state.selected().is_some_and(|i| i >= self.len) {
+                self.state.select(self.len.checked_sub(1));
+            }
 
-            self.widget = Table::new(rows, self.widths)
+            self.widget = Table::new(rows, self.widths.clone())
                 .header(self.headers.to_owned())
                 .block(self.block.to_owned())
                 .highlight_style(self.highlight_style.to_owned());
@@ -99,3 +280,215 @@ impl<'a> FileTable<'a> {
         }
     }
 }
+
+impl FileTable<'_> {
+    // Hidden and removed mods have no name in their mod info
+    async fn mod_name(&self, game: &str, mod_id: u32) -> String {
+        match self.mod_infos.get((game, mod_id)).await.and_then(|mi| mi.name) {
+            Some(name) => name,
+            None => format!("Mod {mod_id}"),
+        }
+    }
+
+    /* The status comes from the last finished update check, so it can be out of date while a check is running. Files
+     * that have some other new file in their mod are shown as unknown, since it might or might not be an update. */
+    async fn status_cell(&self, status: &UpdateStatus, game: &str, mod_id: u32, file_id: u64) -> Cell<'static> {
+        match status {
+            UpdateStatus::UpToDate(_) => Cell::from("Up to date").style(Style::default().fg(Color::Green)),
+            UpdateStatus::OutOfDate(_) => {
+                let text = match self.updater.newest_version(game, mod_id, file_id).await {
+                    Some(version) if !version.is_empty() => format!("Update available ({version})"),
+                    _ => "Update available".to_string(),
+                };
+                Cell::from(text).style(Style::default().fg(Color::Yellow))
+            }
+            UpdateStatus::IgnoredUntil(_) => Cell::from("Ignored").style(Style::default().fg(Color::DarkGray)),
+            UpdateStatus::HasNewFile(_) => Cell::from("Unknown").style(Style::default().add_modifier(Modifier::DIM)),
+        }
+    }
+}
+
+// The game column only takes up space when there's more than one game to tell apart
+fn visible_columns(columns: &[FileColumnId], game_count: usize, game_column_threshold: usize) -> Vec<FileColumnId> {
+    columns.iter().copied().filter(|c| *c != FileColumnId::Game || game_count >= game_column_threshold).collect()
+}
+
+fn column_layout<'a>(columns: &[FileColumnId], game_column_width: u32) -> (Row<'a>, Vec<Constraint>) {
+    let width = |c: &FileColumnId| match c {
+        FileColumnId::Game => game_column_width,
+        _ => c.width(),
+    };
+    let total_width: u32 = columns.iter().map(width).sum();
+    let headers = Row::new(columns.iter().map(|c| Cell::from(c.header()).style(Style::default().fg(Color::Red))));
+    (headers, columns.iter().map(|c| Constraint::Ratio(width(c), total_width)).collect())
+}
+
+/* Colors the files by their status, so that they stand out without the status column: red for archives that are
+ * corrupt or don't match the Nexus, gray for ignored updates, yellow for updates and green for files that are up to
+ * date. Files whose status is unknown keep the default color. */
+fn row_style(status: &UpdateStatus, archive_state: ArchiveState, verification: VerificationState) -> Style {
+    if archive_state == ArchiveState::Corrupt || verification == VerificationState::Mismatch {
+        return Style::default().fg(Color::Red);
+    }
+    match status {
+        UpdateStatus::IgnoredUntil(_) => Style::default().fg(Color::DarkGray),
+        UpdateStatus::OutOfDate(_) => Style::default().fg(Color::Yellow),
+        UpdateStatus::UpToDate(_) => Style::default().fg(Color::Green),
+        UpdateStatus::HasNewFile(_) => Style::default(),
+    }
+}
+
+// Only the date, since the details pane shows the time as well
+fn installed_date(secs: u64, date_format: DateFormat, now: SystemTime) -> String {
+    let installed_at = UNIX_EPOCH + Duration::from_secs(secs);
+    match date_format {
+        DateFormat::Absolute => format::utc_timestamp(installed_at)[..10].to_string(),
+        DateFormat::Relative => format::time_ago(now.duration_since(installed_at).unwrap_or_default()),
+    }
+}
+
+fn check_mark(is_set: bool) -> String {
+    if is_set { "✓" } else { "" }.to_string()
+}
+
+/* Builds the rows of the table from the (game, mod_id) of each file. Only the files in `visible` are shown, in that
+ * order. Groups are sorted by name and id, while the files within a group keep their order. The children of collapsed
+ * groups are left out, so that navigating the table skips over them. */
+fn build_tree(
+    mods: &[(String, u32)],
+    visible: &[usize],
+    grouping: FileGrouping,
+    collapsed: &HashSet<GroupKey>,
+) -> Vec<FileRow> {
+    // The positions in `visible`, so that sorting them keeps the order of the files
+    let mut games: BTreeMap<&str, BTreeMap<u32, Vec<usize>>> = BTreeMap::new();
+    for (pos, &i) in visible.iter().enumerate() {
+        let (game, mod_id) = &mods[i];
+        games.entry(game).or_default().entry(*mod_id).or_default().push(pos);
+    }
+
+    let mut rows = vec![];
+    match grouping {
+        FileGrouping::Flat => rows.extend(visible.iter().map(|&i| FileRow::File(i))),
+        FileGrouping::Game => {
+            for (game, mod_files) in games {
+                let key = GroupKey::Game(game.to_string());
+                let mut files: Vec<usize> = mod_files.into_values().flatten().collect();
+                files.sort();
+                let is_collapsed = collapsed.contains(&key);
+                rows.push(FileRow::Header(key, files.len()));
+                if !is_collapsed {
+                    rows.extend(files.into_iter().map(|pos| FileRow::File(visible[pos])));
+                }
+            }
+        }
+        FileGrouping::Mod => {
+            for (game, mod_files) in games {
+                let key = GroupKey::Game(game.to_string());
+                let is_collapsed = collapsed.contains(&key);
+                rows.push(FileRow::Header(key, mod_files.values().map(|files| files.len()).sum()));
+                if is_collapsed {
+                    continue;
+                }
+                for (mod_id, files) in mod_files {
+                    let key = GroupKey::Mod(game.to_string(), mod_id);
+                    let is_collapsed = collapsed.contains(&key);
+                    rows.push(FileRow::Header(key, files.len()));
+                    if !is_collapsed {
+                        rows.extend(files.into_iter().map(|pos| FileRow::File(visible[pos])));
+                    }
+                }
+            }
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_tree, installed_date, row_style, visible_columns, FileRow, GroupKey};
+    use crate::cache::{ArchiveState, UpdateStatus, VerificationState};
+    use crate::config::{DateFormat, FileColumnId, FileGrouping};
+    use ratatui::style::{Color, Style};
+    use std::collections::HashSet;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn row_colors() {
+        let (untested, unverified) = (ArchiveState::Untested, VerificationState::Unverified);
+        assert_eq!(row_style(&UpdateStatus::UpToDate(0), untested, unverified), Style::default().fg(Color::Green));
+        assert_eq!(row_style(&UpdateStatus::OutOfDate(0), untested, unverified), Style::default().fg(Color::Yellow));
+        assert_eq!(
+            row_style(&UpdateStatus::IgnoredUntil(0), untested, unverified),
+            Style::default().fg(Color::DarkGray)
+        );
+        assert_eq!(row_style(&UpdateStatus::HasNewFile(0), untested, unverified), Style::default());
+        // A broken archive matters more than its update status
+        let red = Style::default().fg(Color::Red);
+        assert_eq!(row_style(&UpdateStatus::OutOfDate(0), ArchiveState::Corrupt, unverified), red);
+        assert_eq!(row_style(&UpdateStatus::UpToDate(0), untested, VerificationState::Mismatch), red);
+    }
+
+    #[test]
+    fn date_format() {
+        let now = UNIX_EPOCH + Duration::from_secs(1700000000 + 2 * 86400);
+        assert_eq!(installed_date(1700000000, DateFormat::Absolute, now), "2023-11-14");
+        assert_eq!(installed_date(1700000000, DateFormat::Relative, now), "2 d ago");
+        // Clocks that went backwards show the file as just downloaded
+        assert_eq!(installed_date(1800000000, DateFormat::Relative, now), "just now");
+    }
+
+    #[test]
+    fn file_tree() {
+        let mods = vec![
+            ("skyrim".to_string(), 2),
+            ("morrowind".to_string(), 1),
+            ("skyrim".to_string(), 1),
+        ];
+        let game = |g: &str| GroupKey::Game(g.to_string());
+        let mut collapsed = HashSet::new();
+        let all = [0, 1, 2];
+
+        assert_eq!(
+            build_tree(&mods, &all, FileGrouping::Flat, &collapsed),
+            vec![FileRow::File(0), FileRow::File(1), FileRow::File(2)]
+        );
+        assert_eq!(
+            build_tree(&mods, &all, FileGrouping::Game, &collapsed),
+            vec![
+                FileRow::Header(game("morrowind"), 1),
+                FileRow::File(1),
+                FileRow::Header(game("skyrim"), 2),
+                FileRow::File(0),
+                FileRow::File(2),
+            ]
+        );
+
+        collapsed.insert(game("morrowind"));
+        collapsed.insert(GroupKey::Mod("skyrim".to_string(), 1));
+        assert_eq!(
+            build_tree(&mods, &all, FileGrouping::Mod, &collapsed),
+            vec![
+                FileRow::Header(game("morrowind"), 1),
+                FileRow::Header(game("skyrim"), 2),
+                FileRow::Header(GroupKey::Mod("skyrim".to_string(), 1), 1),
+                FileRow::Header(GroupKey::Mod("skyrim".to_string(), 2), 1),
+                FileRow::File(0),
+            ]
+        );
+
+        // Hidden files are left out, and the order of the visible files is kept within groups
+        assert_eq!(
+            build_tree(&mods, &[2, 0], FileGrouping::Game, &HashSet::new()),
+            vec![FileRow::Header(game("skyrim"), 2), FileRow::File(2), FileRow::File(0)]
+        );
+    }
+
+    #[test]
+    fn game_column() {
+        let columns = [FileColumnId::Name, FileColumnId::Game, FileColumnId::Version];
+        assert_eq!(visible_columns(&columns, 1, 2), vec![FileColumnId::Name, FileColumnId::Version]);
+        assert_eq!(visible_columns(&columns, 2, 2), columns);
+        assert_eq!(visible_columns(&columns, 1, 0), columns);
+    }
+}
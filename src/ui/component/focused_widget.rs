@@ -48,6 +48,40 @@ impl MainUI<'_> {
         self.focused_widget().needs_redraw();
     }
 
+    pub fn select_first(&mut self) {
+        self.focused_widget().jump_to_top();
+        self.focused_widget().needs_redraw();
+    }
+
+    pub fn select_last(&mut self) {
+        self.focused_widget().jump_to_bottom();
+        self.focused_widget().needs_redraw();
+    }
+
+    // Paging moves the selection by a screenful, keeping one row of the previous page visible
+    pub fn select_next_page(&mut self) {
+        let rows = self.visible_rows().saturating_sub(1).max(1);
+        self.focused_widget().next_page(rows);
+        self.focused_widget().needs_redraw();
+    }
+
+    pub fn select_previous_page(&mut self) {
+        let rows = self.visible_rows().saturating_sub(1).max(1);
+        self.focused_widget().previous_page(rows);
+        self.focused_widget().needs_redraw();
+    }
+
+    // The height of the focused widget's area minus the borders and table header
+    fn visible_rows(&self) -> usize {
+        let (area, header_height) = match self.focused {
+            FocusedWidget::ArchiveTable => (self.rectangles.main_vertical[2], 1),
+            FocusedWidget::DownloadTable => (self.rectangles.main_horizontal[1], 1),
+            FocusedWidget::FileTable => (self.rectangles.main_horizontal[0], 1),
+            FocusedWidget::LogList => (self.rectangles.main_vertical[3], 0),
+        };
+        area.height.saturating_sub(2 + header_height) as usize
+    }
+
     pub fn selected_index(&mut self) -> Option<usize> {
         self.focused_widget().selected()
     }
@@ -0,0 +1,177 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+
+use crate::archives::{FomodInstaller, GroupType, PluginType};
+use crate::ui::component::traits::Select;
+
+/* Shows the options of the current FOMOD install step. Each group is listed with a header row followed by its
+ * options, and the description of the selected option is shown next to the list. */
+pub struct FomodDialog<'a> {
+    pub installer: Option<FomodInstaller>,
+    pub root: String,
+    pub dest_dir: String,
+    pub block: Block<'a>,
+    pub highlight_style: Style,
+    pub state: ListState,
+    pub widget: List<'a>,
+    pub description: Paragraph<'a>,
+    pub needs_redraw: AtomicBool,
+    // (group, plugin) for each row, None for group headers
+    rows: Vec<Option<(usize, usize)>>,
+    redraw_terminal: Arc<AtomicBool>,
+    pub len: usize,
+}
+
+impl<'a> FomodDialog<'a> {
+    pub fn new(redraw_terminal: Arc<AtomicBool>) -> Self {
+        Self {
+            installer: None,
+            root: String::new(),
+            dest_dir: String::new(),
+            block: Block::default().borders(Borders::ALL).title("Installer"),
+            highlight_style: Style::default(),
+            state: ListState::default(),
+            widget: List::default(),
+            description: Paragraph::default(),
+            needs_redraw: AtomicBool::new(false),
+            rows: vec![],
+            redraw_terminal,
+            len: 0,
+        }
+    }
+
+    pub fn show(&mut self, installer: FomodInstaller, root: String, dest_dir: String) {
+        self.installer = Some(installer);
+        self.root = root;
+        self.dest_dir = dest_dir;
+        self.build_rows();
+    }
+
+    pub fn hide(&mut self) -> Option<FomodInstaller> {
+        self.rows.clear();
+        self.len = 0;
+        self.installer.take()
+    }
+
+    pub fn select_next_option(&mut self) {
+        for _ in 0..self.len {
+            self.next();
+            if self.selected_option().is_some() {
+                break;
+            }
+        }
+        self.needs_redraw.store(true, Ordering::Relaxed);
+    }
+
+    pub fn select_previous_option(&mut self) {
+        for _ in 0..self.len {
+            self.previous();
+            if self.selected_option().is_some() {
+                break;
+            }
+        }
+        self.needs_redraw.store(true, Ordering::Relaxed);
+    }
+
+    pub fn toggle_selected(&mut self) {
+        if let (Some((group, plugin)), Some(installer)) = (self.selected_option(), self.installer.as_mut()) {
+            installer.toggle(group, plugin);
+            self.needs_redraw.store(true, Ordering::Relaxed);
+        }
+    }
+
+    // Returns false if there are no more steps, meaning that the files can be installed
+    pub fn next_step(&mut self) -> bool {
+        let has_next = self.installer.as_mut().is_some_and(|installer| installer.next());
+        if has_next {
+            self.build_rows();
+        }
+        has_next
+    }
+
+    pub fn previous_step(&mut self) {
+        if self.installer.as_mut().is_some_and(|installer| installer.previous()) {
+            self.build_rows();
+        }
+    }
+
+    fn selected_option(&self) -> Option<(usize, usize)> {
+        self.state.selected().and_then(|i| self.rows.get(i).copied().flatten())
+    }
+
+    fn build_rows(&mut self) {
+        self.rows.clear();
+        if let Some(step) = self.installer.as_ref().and_then(|installer| installer.current_step()) {
+            for (g, group) in step.groups.iter().enumerate() {
+                self.rows.push(None);
+                self.rows.extend((0..group.plugins.len()).map(|p| Some((g, p))));
+            }
+        }
+        self.len = self.rows.len();
+        self.state.select(self.rows.iter().position(|row| row.is_some()));
+        self.needs_redraw.store(true, Ordering::Relaxed);
+    }
+
+    pub fn refresh(&mut self) {
+        if !self.needs_redraw.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let installer = if let Some(installer) = &self.installer {
+            installer
+        } else {
+            return;
+        };
+        let step = if let Some(step) = installer.current_step() {
+            step
+        } else {
+            return;
+        };
+
+        let mut items: Vec<ListItem> = vec![];
+        for (g, group) in step.groups.iter().enumerate() {
+            let hint = match group.group_type {
+                GroupType::AtLeastOne => "select at least one",
+                GroupType::AtMostOne => "select at most one",
+                GroupType::ExactlyOne => "select one",
+                GroupType::All => "all required",
+                GroupType::Any => "select any",
+            };
+            items.push(
+                ListItem::new(Line::from(format!("{} ({hint})", group.name)))
+                    .style(Style::default().add_modifier(Modifier::BOLD)),
+            );
+            let single = matches!(group.group_type, GroupType::ExactlyOne | GroupType::AtMostOne);
+            for (p, plugin) in group.plugins.iter().enumerate() {
+                let checkbox = match (single, installer.is_selected(g, p)) {
+                    (true, true) => "(*)",
+                    (true, false) => "( )",
+                    (false, true) => "[x]",
+                    (false, false) => "[ ]",
+                };
+                let mut item = ListItem::new(Line::from(format!("  {checkbox} {}", plugin.name)));
+                if plugin.plugin_type == PluginType::NotUsable {
+                    item = item.style(Style::default().add_modifier(Modifier::DIM));
+                }
+                items.push(item);
+            }
+        }
+
+        let steps = &installer.config.steps;
+        let title =
+            format!("{} - {} ({}/{})", installer.config.module_name, step.name, installer.step + 1, steps.len());
+        self.widget = List::new(items).block(self.block.clone().title(title)).highlight_style(self.highlight_style);
+
+        let description = match self.selected_option() {
+            Some((g, p)) => step.groups[g].plugins[p].description.clone(),
+            None => String::new(),
+        };
+        self.description = Paragraph::new(description)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("Description"));
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+}
@@ -1,5 +1,4 @@
-use super::FocusedWidget;
-use crate::ui::hotkeys::*;
+use crate::ui::hotkeys::KeyBinding;
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
 use ratatui::widgets::Paragraph;
@@ -7,33 +6,32 @@ use std::sync::atomic::{AtomicBool, Ordering};
 
 pub struct HotkeyBar<'a> {
     pub widget: Paragraph<'a>,
-    focused: FocusedWidget,
+    key_bindings: &'static [KeyBinding],
     pub needs_redraw: AtomicBool,
 }
 
 impl<'a> HotkeyBar<'a> {
-    pub fn new(focused: FocusedWidget) -> Self {
+    pub fn new(key_bindings: &'static [KeyBinding]) -> Self {
         let widget = Paragraph::new(Line::from(vec![]));
         Self {
             widget,
-            focused,
+            key_bindings,
             needs_redraw: AtomicBool::new(true),
         }
     }
 
-    pub async fn refresh(&mut self, focused: &FocusedWidget) {
-        if self.needs_redraw.swap(false, Ordering::Relaxed) || !self.focused.eq(focused) {
-            let keys = match focused {
-                FocusedWidget::ArchiveTable => ARCHIVES_KEYS,
-                FocusedWidget::FileTable => FILES_KEYS,
-                FocusedWidget::LogList => LOG_KEYS,
-                FocusedWidget::DownloadTable => DOWNLOADS_KEYS,
-            };
+    // Renders the bindings as "[p] Pause  [Del] Delete  [v] View on web"
+    pub async fn refresh(&mut self, key_bindings: &'static [KeyBinding]) {
+        if self.needs_redraw.swap(false, Ordering::Relaxed) || !std::ptr::eq(self.key_bindings, key_bindings) {
+            self.key_bindings = key_bindings;
 
             let mut text = vec![];
-            for (key, action) in keys {
-                text.push(Span::styled(*key, Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-                text.push(Span::raw(*action));
+            for binding in key_bindings {
+                text.push(Span::styled(
+                    format!("[{}]", binding.key),
+                    Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+                ));
+                text.push(Span::raw(format!(" {}  ", binding.action)));
             }
 
             self.widget = Paragraph::new(Line::from(text));
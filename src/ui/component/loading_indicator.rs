@@ -0,0 +1,53 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ratatui::style::{Color, Style};
+use ratatui::widgets::Paragraph;
+
+use crate::api::LoadingCounter;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const FRAME_DURATION: Duration = Duration::from_millis(100);
+
+/* A spinner in the top right corner, shown while API requests or update checks are in flight. The UI is only redrawn
+ * on ticks and input, so the animation advances at most that often. */
+pub struct LoadingIndicator<'a> {
+    loading: LoadingCounter,
+    started: Instant,
+    frame: Option<usize>,
+    pub widget: Paragraph<'a>,
+    redraw_terminal: Arc<AtomicBool>,
+}
+
+impl<'a> LoadingIndicator<'a> {
+    pub fn new(redraw_terminal: Arc<AtomicBool>, loading: LoadingCounter) -> Self {
+        Self {
+            loading,
+            started: Instant::now(),
+            frame: None,
+            widget: Paragraph::default(),
+            redraw_terminal,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.frame.is_some()
+    }
+
+    pub fn refresh(&mut self) {
+        let frame = if self.loading.is_loading() {
+            Some((self.started.elapsed().as_millis() / FRAME_DURATION.as_millis()) as usize % FRAMES.len())
+        } else {
+            None
+        };
+        if frame == self.frame {
+            return;
+        }
+        self.frame = frame;
+        if let Some(frame) = frame {
+            self.widget = Paragraph::new(FRAMES[frame].to_string()).style(Style::default().fg(Color::Yellow));
+        }
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+}
@@ -1,10 +1,11 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use ratatui::style::Style;
+use ratatui::style::{Color, Style};
 use ratatui::text::Line;
 use ratatui::widgets::{Block, Borders, List, ListItem, ListState};
 
+use crate::logger::LogLevel;
 use crate::Logger;
 
 pub struct LogList<'a> {
@@ -15,6 +16,10 @@ pub struct LogList<'a> {
     pub widget: List<'a>,
     pub needs_redraw: AtomicBool,
     list_items: Vec<ListItem<'a>>,
+    // Only warnings and errors are shown
+    only_problems: bool,
+    // The index in Logger::messages of each shown message
+    shown: Vec<usize>,
     redraw_terminal: Arc<AtomicBool>,
     pub len: usize,
 }
@@ -25,7 +30,7 @@ impl<'a> LogList<'a> {
         let highlight_style = Style::default();
 
         // Render on first loop
-        logger.has_changed.store(true, Ordering::Relaxed);
+        logger.has_changed.set();
 
         Self {
             block,
@@ -35,30 +40,55 @@ impl<'a> LogList<'a> {
             widget: List::default(),
             needs_redraw: AtomicBool::new(false),
             list_items: vec![],
+            only_problems: false,
+            shown: vec![],
             redraw_terminal,
             len: 0,
         }
     }
 
+    pub fn toggle_only_problems(&mut self) {
+        self.only_problems = !self.only_problems;
+        let title = if self.only_problems {
+            "Log (warnings and errors)"
+        } else {
+            "Log"
+        };
+        self.block = Block::default().borders(Borders::ALL).title(title);
+        self.logger.has_changed.set();
+    }
+
+    // The index in Logger::messages of the message shown at this index
+    pub fn message_index(&self, i: usize) -> Option<usize> {
+        self.shown.get(i).copied()
+    }
+
     /* TODO there is an open issue for ratatui for word wrapping list items. Until then we can't properly show
      * long error messages: https://github.com/ratatui-org/ratatui/issues/128 */
     pub async fn refresh<'b>(&mut self)
     where
         'b: 'a,
     {
-        if self.logger.has_changed.swap(false, Ordering::Relaxed) {
-            let new_len;
-            let mut items: Vec<ListItem<'a>> = {
+        if self.logger.has_changed.take() {
+            /* All the items are rebuilt, since the count of the latest message changes when it's repeated, and messages
+             * can be removed. */
+            self.list_items = {
                 let msgs_lock = self.logger.messages.read().unwrap();
-                new_len = msgs_lock.len();
-                if new_len > 0 {
-                    let msgs: &[String] = &msgs_lock[self.len..msgs_lock.len()];
-                    msgs.iter().map(|msg| ListItem::new(Line::from(msg.to_owned()))).collect()
-                } else {
-                    vec![]
-                }
+                self.shown = msgs_lock
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, msg)| !self.only_problems || msg.level >= LogLevel::Warn)
+                    .map(|(i, _)| i)
+                    .collect();
+                self.shown
+                    .iter()
+                    .map(|&i| {
+                        let msg = &msgs_lock[i];
+                        ListItem::new(Line::from(msg.to_string())).style(level_style(msg.level))
+                    })
+                    .collect()
             };
-            self.list_items.append(&mut items);
+            let new_len = self.list_items.len();
 
             if self.state.selected() == None && new_len != 0 || self.state.selected() == self.len.checked_sub(1) {
                 self.state.select(Some(new_len));
@@ -78,3 +108,12 @@ impl<'a> LogList<'a> {
         }
     }
 }
+
+fn level_style(level: LogLevel) -> Style {
+    match level {
+        LogLevel::Debug => Style::default().fg(Color::DarkGray),
+        LogLevel::Info => Style::default(),
+        LogLevel::Warn => Style::default().fg(Color::Yellow),
+        LogLevel::Error => Style::default().fg(Color::Red),
+    }
+}
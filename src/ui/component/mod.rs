@@ -1,20 +1,34 @@
 mod archive_table;
 mod bottom_bar;
+mod confirm_dialog;
+mod details_pane;
 mod download_table;
 mod file_table;
 mod focused_widget;
+mod fomod_dialog;
 mod hotkey_bar;
+mod loading_indicator;
 mod log_list;
+mod mod_details_panel;
 mod popup_dialog;
+mod scrollbar;
 mod tabbar;
 pub mod traits;
+mod version_picker;
 
 pub use archive_table::ArchiveTable;
 pub use bottom_bar::BottomBar;
+pub use confirm_dialog::*;
+pub use details_pane::DetailsPane;
 pub use download_table::DownloadTable;
 pub use file_table::FileTable;
 pub use focused_widget::*;
+pub use fomod_dialog::FomodDialog;
 pub use hotkey_bar::HotkeyBar;
+pub use loading_indicator::LoadingIndicator;
 pub use log_list::LogList;
-pub use popup_dialog::PopupDialog;
+pub use mod_details_panel::ModDetailsPanel;
+pub use popup_dialog::{PopupDialog, ReadLineAction};
+pub use scrollbar::render_scrollbar;
 pub use tabbar::TabBar;
+pub use version_picker::VersionPicker;
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
+
+use crate::api::ModInfo;
+
+// Mod info that was fetched for the panel is shown again without a new request for this long
+pub const MOD_INFO_TTL: Duration = Duration::from_secs(5 * 60);
+
+/* Shows the description of a mod along with its other details, opened with Enter in the file table. The description
+ * is formatted with BBCode on the Nexus, which is stripped here. */
+pub struct ModDetailsPanel<'a> {
+    pub widget: Paragraph<'a>,
+    fetched: HashMap<(String, u32), (Instant, ModInfo)>,
+    redraw_terminal: Arc<AtomicBool>,
+}
+
+impl<'a> ModDetailsPanel<'a> {
+    pub fn new(redraw_terminal: Arc<AtomicBool>) -> Self {
+        Self {
+            widget: Paragraph::default(),
+            fetched: HashMap::new(),
+            redraw_terminal,
+        }
+    }
+
+    // The mod info fetched for the panel within the last MOD_INFO_TTL
+    pub fn cached(&self, game: &str, mod_id: u32) -> Option<ModInfo> {
+        match self.fetched.get(&(game.to_string(), mod_id)) {
+            Some((fetched_at, mi)) if fetched_at.elapsed() < MOD_INFO_TTL => Some(mi.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn show(&mut self, game: String, mod_id: u32, mi: ModInfo) {
+        let name = mi.name.clone().unwrap_or_else(|| format!("Mod {mod_id}"));
+        let block = Block::default().borders(Borders::ALL).title(format!("{name} ({game})"));
+        let mut lines = vec![
+            field("Name", name),
+            field("Summary", mi.summary.as_deref().map(strip_markup).unwrap_or_default()),
+            field("Version", mi.version.clone()),
+            field("Author", mi.author.clone()),
+            field("Category", mi.category_id.to_string()),
+            field("Endorsements", mi.endorsement_count.to_string()),
+            Line::default(),
+        ];
+        match mi.description.as_deref() {
+            Some(description) => lines.extend(strip_markup(description).lines().map(|l| Line::from(l.to_string()))),
+            None => lines.push(Line::from("No description.")),
+        }
+        self.widget = Paragraph::new(lines).wrap(Wrap { trim: false }).block(block);
+        self.fetched.insert((game, mod_id), (Instant::now(), mi));
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    pub fn hide(&mut self) {
+        self.widget = Paragraph::default();
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+}
+
+fn field(name: &str, value: String) -> Line<'static> {
+    Line::from(vec![
+        Span::styled(format!("{name}: "), Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
+        Span::raw(value),
+    ])
+}
+
+/* Removes BBCode tags such as [b] and [url=...], and turns the <br /> tags that the Nexus inserts into line breaks.
+ * Brackets that don't look like a tag are kept. */
+fn strip_markup(text: &str) -> String {
+    let text = text.replace("<br />", "\n").replace("<br/>", "\n").replace("<br>", "\n").replace("\r\n", "\n");
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text.as_str();
+    while let Some(start) = rest.find('[') {
+        stripped.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find(']') {
+            Some(end) if is_tag(&after[..end]) => rest = &after[end + 1..],
+            _ => {
+                stripped.push('[');
+                rest = after;
+            }
+        }
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+fn is_tag(tag: &str) -> bool {
+    let name = tag.strip_prefix('/').unwrap_or(tag);
+    let name = name.split('=').next().unwrap_or_default();
+    !name.is_empty() && (name == "*" || name.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_markup;
+
+    #[test]
+    fn markup() {
+        assert_eq!(
+            strip_markup("[center][b]Better Bodies[/b][/center]<br /><br />See [url=https://example.com]here[/url]."),
+            "Better Bodies\n\nSee here."
+        );
+        assert_eq!(strip_markup("[size=4][color=#ff0000]Red[/color][/size]"), "Red");
+        assert_eq!(strip_markup("Version [1.2] (see [list][*]a[/list])"), "Version [1.2] (see a)");
+    }
+}
@@ -1,11 +1,21 @@
 use ratatui::style::{Color, Style};
 use ratatui::widgets::Widget;
 use ratatui::widgets::{Block, Borders};
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tui_textarea::{CursorMove, TextArea};
 
+// What the entered line is used for
+pub enum ReadLineAction {
+    // The name of the directory that the selected archive is installed into
+    InstallTo,
+    // The password of an encrypted archive, which is extracted with it. The input is masked.
+    ArchivePassword { path: PathBuf },
+}
+
 pub struct PopupDialog<'a> {
+    pub action: Option<ReadLineAction>,
     pub textarea: TextArea<'a>,
     pub needs_redraw: AtomicBool,
     redraw_terminal: Arc<AtomicBool>,
@@ -16,6 +26,7 @@ impl PopupDialog<'_> {
         let mut textarea = TextArea::default();
         textarea.set_block(Block::default().borders(Borders::ALL).title("Target directory"));
         Self {
+            action: None,
             textarea,
             needs_redraw: AtomicBool::new(false),
             redraw_terminal,
@@ -30,7 +41,7 @@ impl PopupDialog<'_> {
         self.textarea.lines()[0].clone()
     }
 
-    pub fn show(&mut self, suggested_value: &str, title: String) {
+    pub fn show(&mut self, action: ReadLineAction, suggested_value: &str, title: String) {
         let input_style = Style::default().fg(Color::Black).bg(Color::White);
         let border_style = Style::default().fg(Color::Yellow).bg(Color::Black);
         self.textarea = TextArea::from([suggested_value]);
@@ -38,5 +49,13 @@ impl PopupDialog<'_> {
         self.textarea.set_cursor_line_style(input_style);
         self.textarea.move_cursor(CursorMove::End);
         self.textarea.set_placeholder_text(suggested_value);
+        if let ReadLineAction::ArchivePassword { .. } = action {
+            self.textarea.set_mask_char('*');
+        }
+        self.action = Some(action);
+    }
+
+    pub fn hide(&mut self) -> Option<ReadLineAction> {
+        self.action.take()
     }
 }
@@ -0,0 +1,20 @@
+use ratatui::layout::{Margin, Rect};
+use ratatui::widgets::{Scrollbar, ScrollbarOrientation, ScrollbarState};
+use ratatui::Frame;
+
+/* Draws a scrollbar over the right border of a bordered table or list, but only if its contents don't fit in the
+ * pane. header_height is the amount of rows taken by a table's header, which aren't available for the items. */
+pub fn render_scrollbar(frame: &mut Frame, area: Rect, len: usize, offset: usize, header_height: u16) {
+    let visible_rows = area.height.saturating_sub(2 + header_height) as usize;
+    if len <= visible_rows {
+        return;
+    }
+
+    // The thumb reaches the bottom of the track when the last item is visible, not when it's at the top of the pane
+    let mut state = ScrollbarState::new(len - visible_rows + 1).position(offset).viewport_content_length(visible_rows);
+    let track_area = area.inner(&Margin {
+        vertical: 1,
+        horizontal: 0,
+    });
+    frame.render_stateful_widget(Scrollbar::new(ScrollbarOrientation::VerticalRight), track_area, &mut state);
+}
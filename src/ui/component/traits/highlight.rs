@@ -3,7 +3,7 @@ use std::sync::atomic::Ordering;
 use async_trait::async_trait;
 use ratatui::style::{Color, Modifier, Style};
 
-use crate::ui::component::{ArchiveTable, DownloadTable, FileTable, LogList};
+use crate::ui::component::{ArchiveTable, DownloadTable, FileTable, FomodDialog, LogList, VersionPicker};
 
 macro_rules! impl_highlight {
     ($T:ty) => {
@@ -32,7 +32,9 @@ macro_rules! impl_highlight {
 impl_highlight!(ArchiveTable<'_>);
 impl_highlight!(DownloadTable<'_>);
 impl_highlight!(FileTable<'_>);
+impl_highlight!(FomodDialog<'_>);
 impl_highlight!(LogList<'_>);
+impl_highlight!(VersionPicker<'_>);
 
 #[async_trait]
 pub trait Highlight {
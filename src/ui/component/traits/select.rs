@@ -1,6 +1,6 @@
 use std::sync::atomic::Ordering;
 
-use crate::ui::component::{ArchiveTable, DownloadTable, FileTable, LogList, TabBar};
+use crate::ui::component::{ArchiveTable, DownloadTable, FileTable, FomodDialog, LogList, TabBar, VersionPicker};
 
 impl Select for TabBar<'_> {
     fn len(&self) -> usize {
@@ -40,7 +40,9 @@ macro_rules! impl_stateful {
 impl_stateful!(ArchiveTable<'_>);
 impl_stateful!(DownloadTable<'_>);
 impl_stateful!(FileTable<'_>);
+impl_stateful!(FomodDialog<'_>);
 impl_stateful!(LogList<'_>);
+impl_stateful!(VersionPicker<'_>);
 
 pub trait Select {
     fn len(&self) -> usize;
@@ -88,4 +90,41 @@ pub trait Select {
         };
         self.select(Some(i));
     }
+
+    fn jump_to_top(&mut self) {
+        if self.len() > 0 {
+            self.select(Some(0));
+        }
+    }
+
+    fn jump_to_bottom(&mut self) {
+        let len = self.len();
+        if len > 0 {
+            self.select(Some(len - 1));
+        }
+    }
+
+    // Unlike next() and previous(), paging stops at the ends of the list instead of wrapping around
+    fn next_page(&mut self, rows: usize) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.selected() {
+            Some(i) => (i + rows).min(len - 1),
+            None => 0,
+        };
+        self.select(Some(i));
+    }
+
+    fn previous_page(&mut self, rows: usize) {
+        if self.len() == 0 {
+            return;
+        }
+        let i = match self.selected() {
+            Some(i) => i.saturating_sub(rows),
+            None => 0,
+        };
+        self.select(Some(i));
+    }
 }
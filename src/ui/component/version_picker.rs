@@ -0,0 +1,103 @@
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState};
+
+use crate::api::{FileDetails, FileList};
+use crate::util::format;
+
+/* Lists all the files of a mod, newest first, so that a specific version can be downloaded instead of the latest one.
+ * Files that have already been downloaded are marked. */
+pub struct VersionPicker<'a> {
+    pub game: String,
+    pub mod_id: u32,
+    files: Vec<FileDetails>,
+    downloaded: HashSet<u64>,
+    pub block: Block<'a>,
+    pub highlight_style: Style,
+    pub state: ListState,
+    pub widget: List<'a>,
+    pub needs_redraw: AtomicBool,
+    redraw_terminal: Arc<AtomicBool>,
+    pub len: usize,
+}
+
+impl<'a> VersionPicker<'a> {
+    pub fn new(redraw_terminal: Arc<AtomicBool>) -> Self {
+        Self {
+            game: String::new(),
+            mod_id: 0,
+            files: vec![],
+            downloaded: HashSet::new(),
+            block: Block::default().borders(Borders::ALL),
+            highlight_style: Style::default(),
+            state: ListState::default(),
+            widget: List::default(),
+            needs_redraw: AtomicBool::new(false),
+            redraw_terminal,
+            len: 0,
+        }
+    }
+
+    pub fn show(&mut self, game: String, mod_id: u32, file_list: FileList, downloaded: HashSet<u64>) {
+        let mut files = file_list.files;
+        files.sort_by_key(|fd| Reverse(fd.uploaded_timestamp));
+        self.block = self.block.clone().title(format!("Files of {game} mod {mod_id}"));
+        self.game = game;
+        self.mod_id = mod_id;
+        self.len = files.len();
+        self.files = files;
+        self.downloaded = downloaded;
+        self.state.select(if self.len > 0 { Some(0) } else { None });
+        self.needs_redraw.store(true, Ordering::Relaxed);
+    }
+
+    pub fn hide(&mut self) {
+        self.files.clear();
+        self.len = 0;
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    pub fn selected_file(&self) -> Option<&FileDetails> {
+        self.state.selected().and_then(|i| self.files.get(i))
+    }
+
+    pub fn refresh(&mut self) {
+        if !self.needs_redraw.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        let items: Vec<ListItem> = self
+            .files
+            .iter()
+            .map(|fd| {
+                // The Nexus categories are MAIN, UPDATE, OPTIONAL, OLD_VERSION, MISCELLANEOUS and ARCHIVED
+                let category = match &fd.category_name {
+                    Some(name) => name.to_lowercase().replace('_', " "),
+                    None => fd.category_id.to_string(),
+                };
+                let mut text = format!(
+                    "{category:<14}{:<14}{} ({}, {})",
+                    fd.version.as_deref().unwrap_or_default(),
+                    fd.name,
+                    format::human_size(fd.size_kb * 1024),
+                    fd.uploaded_time.get(..10).unwrap_or(&fd.uploaded_time),
+                );
+                let mut style = Style::default();
+                if self.downloaded.contains(&fd.file_id) {
+                    text.push_str(" [downloaded]");
+                    style = style.add_modifier(Modifier::DIM);
+                }
+                if fd.category_name.as_deref() == Some("MAIN") {
+                    style = style.add_modifier(Modifier::BOLD);
+                }
+                ListItem::new(Line::from(text)).style(style)
+            })
+            .collect();
+        self.widget = List::new(items).block(self.block.clone()).highlight_style(self.highlight_style);
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+}
@@ -4,45 +4,29 @@ use termion::event::Event;
 use termion::input::TermRead;
 use tokio::sync::mpsc;
 
-use std::time::Duration;
-
-pub enum TickEvent {
-    Input(Event),
-    Tick,
-}
-
+/* Terminal input, read on its own thread since termion blocks. The UI loop waits for this and for changes to the data
+ * it shows, see util::changes, so there's no tick to poll on. */
 pub struct Events {
-    rx: mpsc::UnboundedReceiver<TickEvent>,
+    rx: mpsc::UnboundedReceiver<Event>,
 }
 
 impl Events {
     pub fn new() -> Events {
-        let tick_rate = Duration::from_millis(250);
-
         let (tx, rx) = mpsc::unbounded_channel();
         let _input_handle = {
-            let tx = tx.clone();
             thread::spawn(move || {
                 let stdin = io::stdin();
                 for event in stdin.events().flatten() {
-                    if tx.send(TickEvent::Input(event)).is_err() {
+                    if tx.send(event).is_err() {
                         return;
                     }
                 }
             })
         };
-        let _tick_handle = {
-            thread::spawn(move || loop {
-                if tx.send(TickEvent::Tick).is_err() {
-                    break;
-                }
-                thread::sleep(tick_rate);
-            })
-        };
         Events { rx }
     }
 
-    pub async fn next(&mut self) -> Option<TickEvent> {
+    pub async fn next(&mut self) -> Option<Event> {
         self.rx.recv().await
     }
 }
@@ -1,5 +1,3 @@
-use std::process::Command;
-
 use std::sync::atomic::Ordering;
 use termion::event::{Event, Key, MouseButton, MouseEvent};
 
@@ -8,28 +6,141 @@ use termion::event::{Event, Key, MouseButton, MouseEvent};
 use super::component::traits::*;
 use super::component::*;
 use super::main_ui::*;
+use crate::api::DownloadState;
+use crate::archives::FomodInstaller;
+use crate::util::{format, opener};
+
+pub struct KeyBinding {
+    pub key: &'static str,
+    pub action: &'static str,
+}
+
+impl KeyBinding {
+    const fn new(key: &'static str, action: &'static str) -> Self {
+        Self { key, action }
+    }
+}
+
+// Escape sequences sent by xterm-compatible terminals
+const SHIFT_UP: &[u8] = b"\x1b[1;2A";
+const SHIFT_DOWN: &[u8] = b"\x1b[1;2B";
 
-pub const ARCHIVES_KEYS: &[(&str, &str)] = &[("<i>", "install "), ("<Del>", "delete "), ("<q>", "quit ")];
-pub const DOWNLOADS_KEYS: &[(&str, &str)] = &[("<p>", "pause/resume "), ("<Del>", "delete "), ("<q>", "quit ")];
-pub const FILES_KEYS: &[(&str, &str)] = &[
-    ("<u>", "update all "),
-    ("<U>", "update selected "),
-    ("<i>", "ignore update "),
-    ("<v>", "visit on Nexus "),
-    ("<Del>", "delete "),
-    ("<q>", "quit "),
+pub const ARCHIVES_KEYS: &[KeyBinding] = &[
+    KeyBinding::new("Enter", "Expand/collapse"),
+    KeyBinding::new("i", "Install"),
+    KeyBinding::new("x", "Extract"),
+    KeyBinding::new("Del", "Delete"),
+    KeyBinding::new("1-4", "Files/Downloads/Log/Archives"),
+    KeyBinding::new("q", "Quit"),
+];
+pub const DOWNLOADS_KEYS: &[KeyBinding] = &[
+    KeyBinding::new("p", "Pause/resume"),
+    KeyBinding::new("r", "Retry failed"),
+    KeyBinding::new("Space", "Select"),
+    KeyBinding::new("P", "Pause/resume selected, or all"),
+    KeyBinding::new("D", "Delete selected"),
+    KeyBinding::new("a", "Pause all"),
+    KeyBinding::new("A/R", "Resume all"),
+    KeyBinding::new("v", "View on web"),
+    KeyBinding::new("S-Up/K", "Move up"),
+    KeyBinding::new("S-Down/J", "Move down"),
+    KeyBinding::new("Del", "Delete"),
+    KeyBinding::new("1-4", "Files/Downloads/Log/Archives"),
+    KeyBinding::new("q", "Quit"),
+];
+pub const FILES_KEYS: &[KeyBinding] = &[
+    KeyBinding::new("u", "Check all updates"),
+    KeyBinding::new("U", "Check selected"),
+    KeyBinding::new("a", "Download all updates"),
+    KeyBinding::new("L", "Download latest"),
+    KeyBinding::new("V", "Pick version"),
+    KeyBinding::new("i", "Ignore/unignore update"),
+    KeyBinding::new("I", "Show/hide ignored"),
+    KeyBinding::new("f", "Show only updates"),
+    KeyBinding::new("s", "Sort by install time"),
+    KeyBinding::new("e", "Endorse/abstain"),
+    KeyBinding::new("t", "Track/untrack"),
+    KeyBinding::new("R", "Refresh endorsements"),
+    KeyBinding::new("v", "View on web"),
+    KeyBinding::new("y", "Copy URL"),
+    KeyBinding::new("Y", "Copy file name"),
+    KeyBinding::new("o", "Open directory"),
+    KeyBinding::new("d", "Details"),
+    KeyBinding::new("C", "Clean orphans"),
+    KeyBinding::new("O", "Delete old versions"),
+    KeyBinding::new("r", "Rebuild index"),
+    KeyBinding::new("Enter", "Mod details, or collapse/expand"),
+    KeyBinding::new("Del", "Delete"),
+    KeyBinding::new("1-4", "Files/Downloads/Log/Archives"),
+    KeyBinding::new("q", "Quit"),
+];
+pub const LOG_KEYS: &[KeyBinding] = &[
+    KeyBinding::new("Del", "Delete"),
+    KeyBinding::new("c", "Clear all"),
+    KeyBinding::new("w", "Warnings only"),
+    KeyBinding::new("1-4", "Files/Downloads/Log/Archives"),
+    KeyBinding::new("q", "Quit"),
+];
+pub const FOMOD_KEYS: &[KeyBinding] = &[
+    KeyBinding::new("Space", "Toggle option"),
+    KeyBinding::new("Enter", "Next step"),
+    KeyBinding::new("Backspace", "Previous step"),
+    KeyBinding::new("Esc", "Cancel"),
+];
+pub const VERSION_PICKER_KEYS: &[KeyBinding] =
+    &[KeyBinding::new("Enter", "Download"), KeyBinding::new("Esc", "Cancel")];
+pub const MOD_DETAILS_KEYS: &[KeyBinding] = &[KeyBinding::new("Esc", "Close")];
+pub const CONFIRM_KEYS: &[KeyBinding] = &[
+    KeyBinding::new("y", "Yes"),
+    KeyBinding::new("n", "No"),
+    KeyBinding::new("Esc", "Cancel"),
 ];
-pub const LOG_KEYS: &[(&str, &str)] = &[("<Del>", "delete "), ("<q>", "quit ")];
 
 impl MainUI<'_> {
+    // The keys shown in the hotkey bar depend on which widget has focus
+    pub fn key_bindings(&self) -> &'static [KeyBinding] {
+        match self.input_mode {
+            InputMode::Fomod => return FOMOD_KEYS,
+            InputMode::Confirm => return CONFIRM_KEYS,
+            InputMode::VersionPicker => return VERSION_PICKER_KEYS,
+            InputMode::ModDetails => return MOD_DETAILS_KEYS,
+            _ => {}
+        }
+        match self.focused {
+            FocusedWidget::ArchiveTable => ARCHIVES_KEYS,
+            FocusedWidget::DownloadTable => DOWNLOADS_KEYS,
+            FocusedWidget::FileTable => FILES_KEYS,
+            FocusedWidget::LogList => LOG_KEYS,
+        }
+    }
+
     pub async fn handle_events(&mut self, event: Event) {
         //MouseEvent::Press(mouse_event, x, y) => {
         //self.logger.log(format!("click! {mouse_event:?}, x: {x}, y: {y}"));
         //Event::Unsupported(u) => {
         //self.logger.log(format!("Unsupported: {u:?}"));
-        if let InputMode::ReadLine = self.input_mode {
-            self.read_input_line(event).await;
-            return;
+        match self.input_mode {
+            InputMode::ReadLine => {
+                self.read_input_line(event).await;
+                return;
+            }
+            InputMode::Fomod => {
+                self.handle_fomod_keys(event).await;
+                return;
+            }
+            InputMode::Confirm => {
+                self.handle_confirm_keys(event).await;
+                return;
+            }
+            InputMode::VersionPicker => {
+                self.handle_version_picker_keys(event).await;
+                return;
+            }
+            InputMode::ModDetails => {
+                self.handle_mod_details_keys(event).await;
+                return;
+            }
+            InputMode::Normal => {}
         }
 
         if let Event::Key(Key::Char('q')) | Event::Key(Key::Ctrl('c')) = event {
@@ -37,16 +148,36 @@ impl MainUI<'_> {
             return;
         }
 
+        /* The navigation keys are only handled here, so that the focused widget doesn't also act on them. Left and right
+         * are passed on, since the archive table uses them to expand and collapse archives. */
         match event {
             Event::Key(Key::Down)
             | Event::Key(Key::Char('j'))
             | Event::Mouse(MouseEvent::Press(MouseButton::WheelDown, _, _)) => {
                 self.select_next();
+                return;
             }
             Event::Key(Key::Up)
             | Event::Key(Key::Char('k'))
             | Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, _, _)) => {
                 self.select_previous();
+                return;
+            }
+            Event::Key(Key::Home) | Event::Key(Key::Char('g')) => {
+                self.select_first();
+                return;
+            }
+            Event::Key(Key::End) | Event::Key(Key::Char('G')) => {
+                self.select_last();
+                return;
+            }
+            Event::Key(Key::PageDown) | Event::Key(Key::Ctrl('f')) => {
+                self.select_next_page();
+                return;
+            }
+            Event::Key(Key::PageUp) | Event::Key(Key::Ctrl('b')) => {
+                self.select_previous_page();
+                return;
             }
             Event::Key(Key::Left) | Event::Key(Key::Char('h')) => match self.focused {
                 FocusedWidget::LogList | FocusedWidget::DownloadTable => {
@@ -66,13 +197,19 @@ impl MainUI<'_> {
                 }
                 _ => {}
             },
+            Event::Key(Key::Char('1')) | Event::Key(Key::F(1)) => return self.jump_to(FocusedWidget::FileTable),
+            Event::Key(Key::Char('2')) | Event::Key(Key::F(2)) => return self.jump_to(FocusedWidget::DownloadTable),
+            Event::Key(Key::Char('3')) | Event::Key(Key::F(3)) => return self.jump_to(FocusedWidget::LogList),
+            Event::Key(Key::Char('4')) | Event::Key(Key::F(4)) => return self.jump_to(FocusedWidget::ArchiveTable),
             Event::Key(Key::Char('\t')) => {
                 self.tab_bar.next_tab();
                 self.change_focused_tab().await;
+                return;
             }
             Event::Key(Key::BackTab) => {
                 self.tab_bar.prev_tab();
                 self.change_focused_tab().await;
+                return;
             }
             _ => {
                 // Uncomment to log keypresses
@@ -101,16 +238,25 @@ impl MainUI<'_> {
         match key {
             Key::Char('i') => {
                 if let FocusedWidget::FileTable = self.focused {
-                    if let Some(i) = self.selected_index() {
-                        self.updater.ignore_file(i).await;
+                    if let Some(i) = self.files_view.selected_file_index() {
+                        self.updater.toggle_ignored(i).await;
                     }
                 }
             }
+            Key::Char('I') => {
+                self.files_view.toggle_show_ignored();
+            }
+            Key::Char('f') => {
+                self.files_view.toggle_only_updates();
+            }
+            Key::Char('s') => {
+                self.files_view.toggle_sort_by_installed();
+            }
             Key::Char('U') => {
                 let game: String;
                 let mod_id: u32;
                 {
-                    if let Some(i) = self.selected_index() {
+                    if let Some(i) = self.files_view.selected_file_index() {
                         let files_lock = self.files_view.file_index.files_sorted.read().await;
                         let fdata = files_lock.get(i).unwrap();
                         let lf_lock = fdata.local_file.read().await;
@@ -125,27 +271,119 @@ impl MainUI<'_> {
             Key::Char('u') => {
                 self.updater.update_all().await;
             }
+            Key::Char('a') => {
+                self.updater.update_all_with_downloads(self.downloads.clone()).await;
+            }
+            Key::Char('L') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    self.download_latest(i).await;
+                }
+            }
+            Key::Char('V') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    self.show_version_picker(i).await;
+                }
+            }
+            Key::Char('e') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    let files_lock = self.files_view.file_index.files_sorted.read().await;
+                    let fdata = files_lock.get(i).unwrap();
+                    let lf_lock = fdata.local_file.read().await;
+                    let version = fdata.file_details.version.clone().unwrap_or_default();
+                    self.user_mod_sync.toggle_endorsed(lf_lock.game.clone(), lf_lock.mod_id, version);
+                }
+            }
+            Key::Char('t') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    let files_lock = self.files_view.file_index.files_sorted.read().await;
+                    let fdata = files_lock.get(i).unwrap();
+                    let lf_lock = fdata.local_file.read().await;
+                    self.user_mod_sync.toggle_tracked(lf_lock.game.clone(), lf_lock.mod_id);
+                }
+            }
+            Key::Char('R') => {
+                self.user_mod_sync.refresh();
+            }
             Key::Char('v') => {
-                if let Some(i) = self.selected_index() {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    let files_lock = self.files_view.file_index.files_sorted.read().await;
+                    let fdata = files_lock.get(i).unwrap();
+                    let lf_lock = fdata.local_file.read().await;
+                    self.open_externally(&nexus_mod_url(&lf_lock.game, lf_lock.mod_id));
+                }
+            }
+            Key::Char('y') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    let files_lock = self.files_view.file_index.files_sorted.read().await;
+                    let lf = files_lock.get(i).unwrap().local_file.read().await.clone();
+                    drop(files_lock);
+                    self.copy_to_clipboard(nexus_mod_url(&lf.game, lf.mod_id), "mod URL");
+                }
+            }
+            Key::Char('Y') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    let files_lock = self.files_view.file_index.files_sorted.read().await;
+                    let file_name = files_lock.get(i).unwrap().local_file.read().await.file_name.clone();
+                    drop(files_lock);
+                    self.copy_to_clipboard(file_name, "file name");
+                }
+            }
+            Key::Char('o') => {
+                if let Some(i) = self.files_view.selected_file_index() {
                     let files_lock = self.files_view.file_index.files_sorted.read().await;
                     let fdata = files_lock.get(i).unwrap();
                     let lf_lock = fdata.local_file.read().await;
-                    let url = format!("https://www.nexusmods.com/{}/mods/{}", &lf_lock.game, &lf_lock.mod_id);
-                    if Command::new("xdg-open").arg(url).status().is_err() {
-                        self.logger.log("xdg-open is needed to open URLs in browser.".to_string());
+                    if let Err(e) = lf_lock.open_in_file_manager(&self.config).await {
+                        self.logger.log(e);
                     }
                 }
             }
-            Key::Delete => {
-                if let Some(i) = self.selected_index() {
-                    if let Err(e) = self.cache.delete_by_index(i).await {
-                        self.logger.log(format!("Unable to delete file: {}", e));
-                    } else {
-                        if i == 0 {
-                            self.select_widget_index(None);
-                        }
-                        self.select_previous();
+            Key::Char('d') => {
+                self.details_pane.toggle();
+            }
+            Key::Char('C') => match self.cache.clean_orphans().await {
+                Ok(removed) => {
+                    for path in &removed {
+                        self.logger.log(format!("Removed orphaned file {:?}", path));
                     }
+                    self.logger.log(format!("Cleaned up {} orphaned file(s).", removed.len()));
+                }
+                Err(e) => {
+                    self.logger.error(format!("Error when cleaning up orphaned files: {}", e));
+                }
+            },
+            Key::Char('O') => {
+                self.confirm_delete_old_versions().await;
+            }
+            Key::Char('r') => {
+                self.confirm_dialog.show(
+                    ConfirmAction::RebuildIndex,
+                    "Rebuild the index?",
+                    "Archives with missing or corrupt metadata are identified on the Nexus by their md5 sum, which \
+                     takes a while for large files."
+                        .to_string(),
+                );
+                self.input_mode = InputMode::Confirm;
+            }
+            Key::Char('\n') => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    self.show_mod_details(i).await;
+                } else {
+                    self.files_view.toggle_collapsed();
+                }
+            }
+            Key::Char(' ') => {
+                self.files_view.toggle_collapsed();
+            }
+            Key::Delete => {
+                if let Some(i) = self.files_view.selected_file_index() {
+                    let files_lock = self.files_view.file_index.files_sorted.read().await;
+                    let fdata = files_lock.get(i).unwrap();
+                    let file_name = fdata.local_file.read().await.file_name.clone();
+                    let action = ConfirmAction::DeleteFile { file_id: fdata.file_id };
+                    drop(files_lock);
+                    self.confirm_dialog.show(action, "Also delete the file from disk?", file_name);
+                    self.input_mode = InputMode::Confirm;
                 }
             }
             _ => {}
@@ -153,7 +391,13 @@ impl MainUI<'_> {
     }
 
     async fn handle_downloads_keys(&mut self, event: Event) {
-        let key = if let Event::Key(key) = event { key } else { return };
+        // termion 2 doesn't recognize Shift+arrow keys, so they arrive as unparsed escape sequences
+        let key = match event {
+            Event::Key(key) => key,
+            Event::Unsupported(bytes) if bytes == SHIFT_UP => Key::Char('K'),
+            Event::Unsupported(bytes) if bytes == SHIFT_DOWN => Key::Char('J'),
+            _ => return,
+        };
 
         match key {
             Key::Char('p') => {
@@ -163,9 +407,86 @@ impl MainUI<'_> {
                     }
                 }
             }
+            Key::Char(' ') => {
+                if let Some(i) = self.selected_index() {
+                    self.downloads_view.toggle_multi_select(i);
+                    self.select_next();
+                }
+            }
+            // Without a selection, pauses everything, like when the network connection has been lost
+            Key::Char('P') => {
+                if self.downloads_view.multi_selected.is_empty() {
+                    self.downloads.pause_all().await;
+                }
+                for i in self.downloads_view.multi_selected.clone() {
+                    self.downloads.toggle_pause_for(i).await;
+                }
+            }
+            Key::Char('D') => {
+                let selected = std::mem::take(&mut self.downloads_view.multi_selected);
+                // Delete from the end so that the remaining indices stay valid
+                for i in selected.into_iter().rev() {
+                    self.downloads.delete(i).await;
+                }
+                let len = self.downloads.tasks.read().await.len();
+                if len == 0 {
+                    self.select_widget_index(None);
+                } else if self.selected_index().is_some_and(|i| i >= len) {
+                    self.select_widget_index(Some(len - 1));
+                }
+            }
+            Key::Char('a') => {
+                self.downloads.pause_all().await;
+            }
+            Key::Char('A') | Key::Char('R') => {
+                self.downloads.resume_all().await;
+            }
+            Key::Char('v') => {
+                if let Some(i) = self.selected_index() {
+                    let tasks_lock = self.downloads.tasks.read().await;
+                    let (_, task) = tasks_lock.get_index(i).unwrap();
+                    let fi = &task.dl_info.file_info;
+                    self.open_externally(&nexus_mod_url(&fi.game, fi.mod_id));
+                }
+            }
+            Key::Char('r') => {
+                if let Some(i) = self.selected_index() {
+                    let tasks_lock = self.downloads.tasks.read().await;
+                    let (file_id, task) = tasks_lock.get_index(i).unwrap();
+                    let file_id = *file_id;
+                    if !matches!(task.dl_info.get_state(), DownloadState::Error) {
+                        self.logger.log(format!(
+                            "{} hasn't failed, so it can't be retried.",
+                            task.dl_info.file_info.file_name
+                        ));
+                        return;
+                    }
+                    let error = task.dl_info.error().unwrap_or_else(|| "The reason wasn't recorded.".to_string());
+                    drop(tasks_lock);
+                    self.confirm_dialog.show(ConfirmAction::RetryDownload { file_id }, "Retry the download?", error);
+                    self.input_mode = InputMode::Confirm;
+                }
+            }
+            Key::Char('K') => {
+                if let Some(i) = self.selected_index() {
+                    if self.downloads.move_up(i).await {
+                        self.downloads_view.swap_multi_selected(i, i - 1);
+                        self.select_widget_index(Some(i - 1));
+                    }
+                }
+            }
+            Key::Char('J') => {
+                if let Some(i) = self.selected_index() {
+                    if self.downloads.move_down(i).await {
+                        self.downloads_view.swap_multi_selected(i, i + 1);
+                        self.select_widget_index(Some(i + 1));
+                    }
+                }
+            }
             Key::Delete => {
                 if let Some(i) = self.selected_index() {
                     self.downloads_view.downloads.delete(i).await;
+                    self.downloads_view.shift_multi_selected(i);
                     if i == 0 {
                         self.select_widget_index(None);
                     }
@@ -180,27 +501,39 @@ impl MainUI<'_> {
         let key = if let Event::Key(key) = event { key } else { return };
 
         match key {
+            Key::Char('\n') => {
+                if let Err(e) = self.archives_view.toggle_expanded(&self.archives).await {
+                    self.logger.error(format!("Unable to list the contents of the archive: {e:?}"));
+                }
+            }
+            Key::Right => {
+                if let Err(e) = self.archives_view.expand(&self.archives).await {
+                    self.logger.error(format!("Unable to list the contents of the archive: {e:?}"));
+                }
+            }
+            Key::Left => {
+                self.archives_view.collapse(&self.archives);
+            }
             Key::Char('i') => {
-                if let Some(i) = self.selected_index() {
+                if let Some(i) = self.archives_view.selected_archive_index() {
                     let path = self.archives.files.get(i).unwrap().path();
-                    match self.archives.list_contents(path.clone()).await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            self.logger.log(format!("{:?}", e));
-                        }
-                    }
                     let file_name = path.file_name().unwrap().to_string_lossy();
                     let dialog_title = "Target directory".to_string();
                     if let Some(fd) = self.cache.file_index.get_by_filename(&file_name).await {
-                        self.popup_dialog.show(&fd.file_details.name, dialog_title);
+                        self.popup_dialog.show(ReadLineAction::InstallTo, &fd.file_details.name, dialog_title);
                     } else {
                         self.logger.log("Warn: mod for {file_name} doesn't exist in db");
-                        self.popup_dialog.show(&file_name, dialog_title);
+                        self.popup_dialog.show(ReadLineAction::InstallTo, &file_name, dialog_title);
                     }
                     self.input_mode = InputMode::ReadLine;
                     self.redraw_terminal.store(true, Ordering::Relaxed);
                 }
             }
+            Key::Char('x') => {
+                if let Some(i) = self.archives_view.selected_archive_index() {
+                    self.archives.extract_selected(i);
+                }
+            }
             Key::Delete => {
                 self.logger.log("Not implemented.");
             }
@@ -214,17 +547,66 @@ impl MainUI<'_> {
         match key {
             Key::Delete => {
                 if let Some(i) = self.selected_index() {
-                    self.log_view.logger.remove(i).await;
+                    if let Some(message) = self.log_view.message_index(i) {
+                        self.log_view.logger.remove(message).await;
+                    }
                     if i == 0 {
                         self.select_widget_index(None);
                     }
                     self.select_previous();
                 }
             }
+            Key::Char('c') => {
+                let count = self.log_view.logger.messages.read().unwrap().len();
+                self.confirm_dialog.show(
+                    ConfirmAction::ClearMessages,
+                    "Clear all messages?",
+                    format!("{count} messages"),
+                );
+                self.input_mode = InputMode::Confirm;
+            }
+            Key::Char('w') => self.log_view.toggle_only_problems(),
             _ => {}
         }
     }
 
+    // Failures are logged, since the UI has no other way of showing them
+    fn open_externally(&self, target: &str) {
+        if let Err(e) = opener::open_url(target, self.config.browser_command.as_deref(), &self.config.open_command) {
+            self.logger.log(e);
+        }
+    }
+
+    /* Copies the text to the system clipboard. The clipboard is kept open for the rest of the session, since on X11 its
+     * contents are lost once the program that set them closes it. */
+    fn copy_to_clipboard(&mut self, text: String, description: &str) {
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(e) => {
+                    self.logger.log(format!("The clipboard isn't available: {e}"));
+                    return;
+                }
+            }
+        }
+        match self.clipboard.as_mut().unwrap().set_text(text) {
+            Ok(()) => self.logger.log(format!("Copied {description} to clipboard.")),
+            Err(e) => self.logger.warn(format!("Unable to copy {description} to clipboard: {e}")),
+        }
+    }
+
+    // Focuses the pane directly, switching to its tab if it's on the other one
+    fn jump_to(&mut self, target: FocusedWidget) {
+        let tab = match target {
+            FocusedWidget::ArchiveTable => 1,
+            _ => 0,
+        };
+        if self.tab_bar.selected() != Some(tab) {
+            self.tab_bar.select(Some(tab));
+        }
+        self.change_focus_to(target);
+    }
+
     async fn change_focused_tab(&mut self) {
         match self.tab_bar.selected() {
             Some(0) => {
@@ -243,12 +625,19 @@ impl MainUI<'_> {
         if let Event::Key(key) = event {
             match key {
                 Key::Ctrl('c') | Key::Esc => {
+                    self.popup_dialog.hide();
                     self.input_mode = InputMode::Normal;
                 }
                 Key::Char('\n') => {
-                    let dest_dir = self.popup_dialog.get_contents();
-                    self.archives.extract(self.archives_view.selected().unwrap(), dest_dir).await;
+                    let contents = self.popup_dialog.get_contents();
                     self.input_mode = InputMode::Normal;
+                    match self.popup_dialog.hide() {
+                        Some(ReadLineAction::InstallTo) => self.install_selected(contents).await,
+                        Some(ReadLineAction::ArchivePassword { path }) => {
+                            self.archives.extract_with_password(path, contents);
+                        }
+                        None => {}
+                    }
                     self.redraw_terminal.store(true, Ordering::Relaxed);
                 }
                 // disable tab character
@@ -260,4 +649,288 @@ impl MainUI<'_> {
             self.redraw_terminal.store(true, Ordering::Relaxed);
         }
     }
+
+    // Installs the selected archive into dest_dir, through its FOMOD installer if it has one
+    async fn install_selected(&mut self, dest_dir: String) {
+        let i = match self.archives_view.selected_archive_index() {
+            Some(i) => i,
+            None => return,
+        };
+        // Archives without a FOMOD installer are extracted as is
+        match self.archives.read_fomod(i).await {
+            Some((root, module_config)) => {
+                let installer = FomodInstaller::new(module_config);
+                if installer.has_steps() {
+                    self.fomod_dialog.show(installer, root, dest_dir);
+                    self.input_mode = InputMode::Fomod;
+                } else {
+                    self.archives.install_fomod(i, dest_dir, root, installer.files()).await;
+                }
+            }
+            None => self.archives.uncompress(i, dest_dir).await,
+        }
+    }
+
+    async fn handle_fomod_keys(&mut self, event: Event) {
+        let key = if let Event::Key(key) = event { key } else { return };
+
+        match key {
+            Key::Ctrl('c') | Key::Esc => {
+                self.fomod_dialog.hide();
+                self.input_mode = InputMode::Normal;
+                self.logger.log("Installation cancelled.");
+            }
+            Key::Down | Key::Char('j') => self.fomod_dialog.select_next_option(),
+            Key::Up | Key::Char('k') => self.fomod_dialog.select_previous_option(),
+            Key::Char(' ') => self.fomod_dialog.toggle_selected(),
+            Key::Backspace => self.fomod_dialog.previous_step(),
+            Key::Char('\n') => {
+                let has_next_step = self.fomod_dialog.next_step();
+                if !has_next_step {
+                    if let Some(installer) = self.fomod_dialog.hide() {
+                        let i = self.archives_view.selected_archive_index().unwrap();
+                        let dest_dir = std::mem::take(&mut self.fomod_dialog.dest_dir);
+                        let root = std::mem::take(&mut self.fomod_dialog.root);
+                        self.archives.install_fomod(i, dest_dir, root, installer.files()).await;
+                    }
+                    self.input_mode = InputMode::Normal;
+                }
+            }
+            _ => {}
+        }
+        self.hotkey_bar.needs_redraw.store(true, Ordering::Relaxed);
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    /* Only premium users can get download links without a nxm:// URL, so if that fails, the file is opened on the Nexus
+     * for downloading it there. */
+    async fn download_latest(&self, i: usize) {
+        let (game, mod_id, newest) = match self.updater.newest_file_for(i).await {
+            Some(newest) => newest,
+            None => {
+                self.logger.log("No newer version of the file is known. Checking updates may find one.");
+                return;
+            }
+        };
+        if self.cache.file_index.file_id_map.read().await.contains_key(&newest.new_file_id) {
+            self.logger.log(format!("The latest version, {}, has already been downloaded.", newest.new_file_name));
+            return;
+        }
+        self.download_or_open(&game, mod_id, newest.new_file_id, &newest.new_file_name).await;
+    }
+
+    async fn redownload(&mut self, file_id: u64) {
+        let (game, mod_id, file_name) = match self.cache.file_index.file_id_map.read().await.get(&file_id) {
+            Some(fdata) => {
+                let lf = fdata.local_file.read().await;
+                (lf.game.clone(), lf.mod_id, lf.file_name.clone())
+            }
+            None => return,
+        };
+        // The corrupt file would otherwise be in the way of the new download
+        self.delete_file(file_id, true).await;
+        self.download_or_open(&game, mod_id, file_id, &file_name).await;
+    }
+
+    async fn download_or_open(&self, game: &str, mod_id: u32, file_id: u64, file_name: &str) {
+        // A download link isn't even requested for free accounts, since it would only fail
+        let is_free = self.client.is_premium() == Some(false);
+        if is_free || self.downloads.queue_file(game, mod_id, file_id, "").await.is_err() {
+            self.logger.log(format!("Opening {file_name} on the Nexus instead."));
+            let url = format!("{}?tab=files&file_id={file_id}", nexus_mod_url(game, mod_id));
+            self.open_externally(&url);
+        }
+    }
+
+    // The file list is fetched again, since the cached one may not have the latest files
+    async fn show_version_picker(&mut self, i: usize) {
+        let (game, mod_id) = {
+            let files_lock = self.files_view.file_index.files_sorted.read().await;
+            let lf = files_lock.get(i).unwrap().local_file.read().await;
+            (lf.game.clone(), lf.mod_id)
+        };
+        let file_list = match self.client.mod_files(&game, mod_id).await {
+            Ok(file_list) => file_list,
+            Err(e) => {
+                self.logger.warn(format!("Unable to get the files of {game} mod {mod_id}: {e}"));
+                return;
+            }
+        };
+        if let Err(e) = self.cache.save_file_list(&file_list, &game, mod_id).await {
+            self.logger.error(format!("Unable to save the file list of {game} mod {mod_id}: {e}"));
+        }
+        let downloaded = self.cache.file_index.file_id_map.read().await.keys().copied().collect();
+        self.version_picker.show(game, mod_id, file_list, downloaded);
+        self.input_mode = InputMode::VersionPicker;
+    }
+
+    async fn handle_version_picker_keys(&mut self, event: Event) {
+        let key = if let Event::Key(key) = event { key } else { return };
+
+        match key {
+            Key::Ctrl('c') | Key::Esc => {
+                self.version_picker.hide();
+                self.input_mode = InputMode::Normal;
+            }
+            Key::Down | Key::Char('j') => self.version_picker.next(),
+            Key::Up | Key::Char('k') => self.version_picker.previous(),
+            Key::Char('\n') => {
+                if let Some(fd) = self.version_picker.selected_file().cloned() {
+                    self.version_picker.hide();
+                    self.input_mode = InputMode::Normal;
+                    let (game, mod_id) = (self.version_picker.game.clone(), self.version_picker.mod_id);
+                    self.download_or_open(&game, mod_id, fd.file_id, &fd.file_name).await;
+                }
+            }
+            _ => {}
+        }
+        self.version_picker.needs_redraw();
+        self.hotkey_bar.needs_redraw.store(true, Ordering::Relaxed);
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    // Mod info is fetched again if the panel's copy is more than MOD_INFO_TTL old, since the description may have changed
+    async fn show_mod_details(&mut self, i: usize) {
+        let (game, mod_id) = {
+            let files_lock = self.files_view.file_index.files_sorted.read().await;
+            let lf = files_lock.get(i).unwrap().local_file.read().await;
+            (lf.game.clone(), lf.mod_id)
+        };
+        let mod_info = match self.mod_details.cached(&game, mod_id) {
+            Some(mi) => mi,
+            None => match self.client.mod_info(&game, mod_id).await {
+                Ok(mi) => {
+                    if let Err(e) = self.cache.save_mod_info(&mi, &game, mod_id).await {
+                        self.logger.error(format!("Unable to save mod info for {game} {mod_id}: {e}"));
+                    }
+                    mi
+                }
+                Err(e) => {
+                    self.logger.warn(format!("Unable to fetch mod info for {game} {mod_id}: {e}"));
+                    return;
+                }
+            },
+        };
+        self.mod_details.show(game, mod_id, mod_info);
+        self.input_mode = InputMode::ModDetails;
+    }
+
+    // The file table isn't touched, so the same row is still selected after the panel is closed
+    async fn handle_mod_details_keys(&mut self, event: Event) {
+        let key = if let Event::Key(key) = event { key } else { return };
+
+        if let Key::Ctrl('c') | Key::Esc = key {
+            self.mod_details.hide();
+            self.input_mode = InputMode::Normal;
+        }
+        self.hotkey_bar.needs_redraw.store(true, Ordering::Relaxed);
+        self.redraw_terminal.store(true, Ordering::Relaxed);
+    }
+
+    async fn handle_confirm_keys(&mut self, event: Event) {
+        let key = if let Event::Key(key) = event { key } else { return };
+
+        let confirmed = match key {
+            Key::Char('y') => true,
+            Key::Char('n') => false,
+            // The download stays paused, and can be resumed to be asked again
+            Key::Ctrl('c') | Key::Esc => {
+                self.confirm_dialog.hide();
+                self.input_mode = InputMode::Normal;
+                return;
+            }
+            _ => return,
+        };
+        self.input_mode = InputMode::Normal;
+        match self.confirm_dialog.hide() {
+            Some(ConfirmAction::DeleteFile { file_id }) => self.delete_file(file_id, confirmed).await,
+            Some(ConfirmAction::OverwriteFile { file_id }) => {
+                self.downloads.resolve_file_conflict(file_id, confirmed).await;
+            }
+            Some(ConfirmAction::RetryDownload { file_id }) if confirmed => self.downloads.retry(file_id).await,
+            Some(ConfirmAction::RebuildIndex) if confirmed => self.rebuild_index(),
+            Some(ConfirmAction::RedownloadCorrupt { file_id }) if confirmed => self.redownload(file_id).await,
+            Some(ConfirmAction::DeleteOldVersions { file_ids }) if confirmed => {
+                self.delete_old_versions(file_ids).await;
+            }
+            Some(ConfirmAction::ClearMessages) if confirmed => {
+                self.log_view.logger.clear_all();
+                self.select_widget_index(None);
+            }
+            _ => {}
+        }
+    }
+
+    // Runs in the background, since hashing the archives can take a while. New files show up as they're identified.
+    fn rebuild_index(&self) {
+        let (cache, client, logger) = (self.cache.clone(), self.client.clone(), self.logger.clone());
+        self.logger.log("Rebuilding the index...");
+        tokio::task::spawn(async move {
+            let results = cache.rebuild_index(&client).await;
+            let mut rebuilt = 0;
+            for (path, result) in &results {
+                let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+                match result {
+                    Ok(description) => {
+                        rebuilt += 1;
+                        logger.log(format!("Identified {file_name} as {description}"));
+                    }
+                    Err(e) => logger.warn(format!("Unable to identify {file_name}: {e}")),
+                }
+            }
+            logger.log(format!("Rebuilt the metadata of {rebuilt} of {} archive(s).", results.len()));
+        });
+    }
+
+    async fn confirm_delete_old_versions(&mut self) {
+        let old = self.cache.old_versions(self.config.keep_versions).await;
+        if old.is_empty() {
+            self.logger.log("No old versions of mods found.");
+            return;
+        }
+        let mut file_names = vec![];
+        for (fdata, size) in &old {
+            file_names.push(format!("{} ({})", fdata.local_file.read().await.file_name, format::human_size(*size)));
+        }
+        let freed: u64 = old.iter().map(|(_, size)| size).sum();
+        self.confirm_dialog.show(
+            ConfirmAction::DeleteOldVersions {
+                file_ids: old.iter().map(|(fdata, _)| fdata.file_id).collect(),
+            },
+            &format!("Delete {} old version(s), freeing {}?", old.len(), format::human_size(freed)),
+            file_names.join("\n"),
+        );
+        self.input_mode = InputMode::Confirm;
+    }
+
+    // The archives are deleted along with their metadata, and partial files that no download in the list is using
+    async fn delete_old_versions(&mut self, file_ids: Vec<u64>) {
+        let mut deleted = 0;
+        for file_id in file_ids {
+            match self.cache.delete_by_file_id(file_id, true).await {
+                Ok(()) => deleted += 1,
+                Err(e) => self.logger.warn(format!("Unable to delete file {file_id}: {e}")),
+            }
+        }
+        self.logger.log(format!("Deleted {deleted} old version(s)."));
+        self.select_widget_index(None);
+    }
+
+    // The file is looked up again, since the list may have changed while the dialog was open
+    async fn delete_file(&mut self, file_id: u64, delete_archive: bool) {
+        let i = self.files_view.file_index.files_sorted.read().await.iter().position(|fd| fd.file_id == file_id);
+        let i = if let Some(i) = i { i } else { return };
+        if let Err(e) = self.cache.delete_by_index(i, delete_archive).await {
+            self.logger.error(format!("Unable to delete file: {}", e));
+            return;
+        }
+        if self.selected_index() == Some(0) {
+            self.select_widget_index(None);
+        }
+        self.select_previous();
+    }
+}
+
+fn nexus_mod_url(game: &str, mod_id: u32) -> String {
+    format!("https://www.nexusmods.com/{game}/mods/{mod_id}")
 }
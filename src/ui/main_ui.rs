@@ -1,40 +1,66 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
 use ratatui::widgets::Clear;
 use tokio::task;
+use tokio::time::{self, Instant};
 
 use super::component::traits::*;
 use super::component::*;
-use super::event::{Events, TickEvent};
-use crate::api::{Client, Downloads, UpdateChecker};
+use super::event::Events;
+use super::hotkeys::FILES_KEYS;
+use crate::api::{Client, Downloads, UpdateChecker, UserModSync};
 use crate::archives::Archives;
 use crate::cache::Cache;
 use crate::config::Config;
+use crate::nxm_socket::StopRequest;
 use crate::ui::rectangles::{Layouts, Rectangles};
 use crate::ui::*;
+use crate::util::{self, changes};
 use crate::Logger;
 
+// The UI is redrawn at most this often for changes that aren't caused by input
+const FRAME_INTERVAL: Duration = Duration::from_millis(250);
+// Without any changes, the UI still refreshes this often to keep things like the elapsed times up to date
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
 pub enum InputMode {
     Normal,
     ReadLine,
+    Fomod,
+    Confirm,
+    VersionPicker,
+    ModDetails,
 }
 
 pub struct MainUI<'a> {
     pub archives: Archives,
     pub cache: Cache,
+    pub client: Client,
+    pub config: Config,
     pub downloads: Downloads,
     pub logger: Logger,
     pub updater: UpdateChecker,
+    pub user_mod_sync: UserModSync,
+    // Opened on first use
+    pub clipboard: Option<arboard::Clipboard>,
     pub focused: FocusedWidget,
     pub tab_bar: TabBar<'a>,
     pub hotkey_bar: HotkeyBar<'a>,
     pub bottom_bar: BottomBar<'a>,
+    pub loading_indicator: LoadingIndicator<'a>,
+    pub details_pane: DetailsPane<'a>,
     pub archives_view: ArchiveTable<'a>,
     pub files_view: FileTable<'a>,
     pub downloads_view: DownloadTable<'a>,
     pub log_view: LogList<'a>,
     pub popup_dialog: PopupDialog<'a>,
+    pub fomod_dialog: FomodDialog<'a>,
+    pub confirm_dialog: ConfirmDialog<'a>,
+    pub version_picker: VersionPicker<'a>,
+    pub mod_details: ModDetailsPanel<'a>,
+    pub rectangles: Rectangles,
     pub input_mode: InputMode,
     pub redraw_terminal: Arc<AtomicBool>,
     pub should_run: bool,
@@ -46,27 +72,47 @@ impl MainUI<'_> {
         client: Client,
         config: Config,
         downloads: Downloads,
+        updater: UpdateChecker,
         logger: Logger,
         archives: Archives,
     ) -> Self {
-        let updater = UpdateChecker::new(cache.clone(), client.clone(), config, logger.clone());
+        let user_mod_sync = UserModSync::new(cache.clone(), client.clone(), logger.clone());
 
         let redraw_terminal = Arc::new(AtomicBool::new(true));
 
         let focused = FocusedWidget::FileTable;
 
         let tab_bar = TabBar::new(redraw_terminal.clone());
-        let hotkey_bar = HotkeyBar::new(focused.clone());
-        let bottom_bar = BottomBar::new(redraw_terminal.clone(), client.request_counter);
+        let hotkey_bar = HotkeyBar::new(FILES_KEYS);
+        let bottom_bar = BottomBar::new(redraw_terminal.clone(), cache.clone(), &client, downloads.clone());
+        let loading_indicator = LoadingIndicator::new(redraw_terminal.clone(), client.loading.clone());
+        let details_pane = DetailsPane::new(redraw_terminal.clone(), cache.clone(), client.clone(), logger.clone());
         let archives_view = ArchiveTable::new(redraw_terminal.clone());
-        let files_view = FileTable::new(redraw_terminal.clone(), cache.file_index.clone());
-        let downloads_view = DownloadTable::new(redraw_terminal.clone(), downloads.clone());
+        let files_view = FileTable::new(
+            redraw_terminal.clone(),
+            cache.file_index.clone(),
+            cache.mod_infos.clone(),
+            cache.game_infos.clone(),
+            cache.user_mods.clone(),
+            updater.clone(),
+            &config,
+        );
+        let downloads_view =
+            DownloadTable::new(redraw_terminal.clone(), downloads.clone(), config.download_table_columns.clone());
         let log_view = LogList::new(redraw_terminal.clone(), logger.clone());
         let popup_dialog = PopupDialog::new(redraw_terminal.clone());
+        let mut fomod_dialog = FomodDialog::new(redraw_terminal.clone());
+        fomod_dialog.focus();
+        let confirm_dialog = ConfirmDialog::new(redraw_terminal.clone());
+        let mut version_picker = VersionPicker::new(redraw_terminal.clone());
+        version_picker.focus();
+        let mod_details = ModDetailsPanel::new(redraw_terminal.clone());
 
         Self {
             archives,
             cache,
+            client,
+            config,
             downloads,
             focused,
             tab_bar,
@@ -76,10 +122,19 @@ impl MainUI<'_> {
             downloads_view,
             log_view,
             bottom_bar,
+            loading_indicator,
+            details_pane,
             popup_dialog,
+            fomod_dialog,
+            confirm_dialog,
+            version_picker,
+            mod_details,
+            rectangles: Rectangles::default(),
             input_mode: InputMode::Normal,
             redraw_terminal,
             updater,
+            user_mod_sync,
+            clipboard: None,
             logger,
             should_run: true,
         }
@@ -87,13 +142,34 @@ impl MainUI<'_> {
 
     /* This is the main UI loop.
      * Redrawing the terminal is CPU intensive - locks and atomics are used to ensure it's done only when necessary. */
-    pub async fn run(mut self) {
+    pub async fn run(mut self, stop_request: StopRequest) {
         let mut events = Events::new();
         self.files_view.focus();
+        // Without an API key this would only fail, which has already been logged
+        if self.config.has_credentials() {
+            self.user_mod_sync.refresh();
+            if self.config.update_check_interval > 0 {
+                self.updater.start_timer(Duration::from_secs(self.config.update_check_interval * 60));
+            }
+        }
         // X11 (and maybe Wayland?) sends SIGWINCH when the window is resized
         // Set to true so rectangles are calculated on first loop
         let got_sigwinch = Arc::new(AtomicBool::new(true));
         let _sigwinch_task = task::spawn(handle_sigwinch(got_sigwinch.clone()));
+        let got_sighup = Arc::new(AtomicBool::new(false));
+        let _sighup_task = task::spawn(handle_sighup(got_sighup.clone()));
+        let got_shutdown_signal = Arc::new(AtomicBool::new(false));
+        let _shutdown_signal_task = {
+            let got_shutdown_signal = got_shutdown_signal.clone();
+            task::spawn(async move {
+                tokio::select! {
+                    _ = util::wait_for_shutdown_signal() => {}
+                    _ = stop_request.wait() => {}
+                }
+                got_shutdown_signal.store(true, Ordering::Relaxed);
+                changes::notify();
+            })
+        };
         let mut terminal = match term_setup() {
             Ok(term) => term,
             Err(e) => {
@@ -103,16 +179,33 @@ impl MainUI<'_> {
         };
 
         let layouts = Layouts::new();
-        let mut rectangles = Rectangles::default();
 
         while self.should_run {
             self.files_view.refresh().await;
             self.downloads_view.refresh().await;
             self.log_view.refresh().await;
             self.archives_view.refresh(&mut self.archives).await;
-            self.hotkey_bar.refresh(&self.focused).await;
+            let key_bindings = self.key_bindings();
+            self.hotkey_bar.refresh(key_bindings).await;
             self.tab_bar.refresh().await;
             self.bottom_bar.refresh().await;
+            self.loading_indicator.refresh();
+            self.fomod_dialog.refresh();
+            self.version_picker.refresh();
+            if let InputMode::Normal = self.input_mode {
+                self.ask_about_file_conflict().await;
+            }
+            if let InputMode::Normal = self.input_mode {
+                self.ask_about_corrupt_archive().await;
+            }
+            if let InputMode::Normal = self.input_mode {
+                self.ask_about_archive_password();
+            }
+            let selected_file = match self.files_view.selected_file_index() {
+                Some(i) => self.files_view.file_index.files_sorted.read().await.get(i).cloned(),
+                None => None,
+            };
+            self.details_pane.refresh(selected_file).await;
 
             let recalculate_rects = got_sigwinch.swap(false, Ordering::Relaxed);
 
@@ -120,48 +213,198 @@ impl MainUI<'_> {
                 terminal
                     .draw(|frame| {
                         if recalculate_rects {
-                            rectangles.recalculate(&layouts, frame.size());
+                            self.rectangles.recalculate(&layouts, frame.size());
                         }
                         if self.tab_bar.selected().unwrap() == 0 {
                             frame.render_stateful_widget(
                                 &self.files_view.widget,
-                                rectangles.main_horizontal[0],
+                                self.rectangles.main_horizontal[0],
                                 &mut self.files_view.state,
                             );
-                            frame.render_stateful_widget(
-                                &self.downloads_view.widget,
-                                rectangles.main_horizontal[1],
-                                &mut self.downloads_view.state,
+                            render_scrollbar(
+                                frame,
+                                self.rectangles.main_horizontal[0],
+                                self.files_view.len,
+                                self.files_view.state.offset(),
+                                1,
                             );
+                            if self.details_pane.visible {
+                                frame.render_widget(&self.details_pane.widget, self.rectangles.main_horizontal[1]);
+                            } else {
+                                frame.render_stateful_widget(
+                                    &self.downloads_view.widget,
+                                    self.rectangles.main_horizontal[1],
+                                    &mut self.downloads_view.state,
+                                );
+                                render_scrollbar(
+                                    frame,
+                                    self.rectangles.main_horizontal[1],
+                                    self.downloads_view.len,
+                                    self.downloads_view.state.offset(),
+                                    1,
+                                );
+                            }
                         } else if self.tab_bar.selected().unwrap() == 1 {
                             frame.render_stateful_widget(
                                 &self.archives_view.widget,
-                                rectangles.main_vertical[2],
+                                self.rectangles.main_vertical[2],
                                 &mut self.archives_view.state,
                             );
+                            render_scrollbar(
+                                frame,
+                                self.rectangles.main_vertical[2],
+                                self.archives_view.len,
+                                self.archives_view.state.offset(),
+                                1,
+                            );
                         }
                         frame.render_stateful_widget(
                             &self.log_view.widget,
-                            rectangles.main_vertical[3],
+                            self.rectangles.main_vertical[3],
                             &mut self.log_view.state,
                         );
+                        render_scrollbar(
+                            frame,
+                            self.rectangles.main_vertical[3],
+                            self.log_view.len,
+                            self.log_view.state.offset(),
+                            0,
+                        );
 
-                        frame.render_widget(&self.tab_bar.widget, rectangles.main_vertical[0]);
-                        frame.render_widget(&self.hotkey_bar.widget, rectangles.main_vertical[1]);
-                        frame.render_widget(&self.bottom_bar.widget, rectangles.statcounter[0]);
+                        frame.render_widget(&self.tab_bar.widget, self.rectangles.main_vertical[0]);
+                        frame.render_widget(&self.hotkey_bar.widget, self.rectangles.main_vertical[1]);
+                        frame.render_widget(&self.bottom_bar.widget, self.rectangles.statcounter[0]);
+                        if self.loading_indicator.is_visible() {
+                            frame.render_widget(&self.loading_indicator.widget, self.rectangles.loading_indicator[0]);
+                        }
 
-                        if let InputMode::ReadLine = self.input_mode {
-                            // Clear the area so we can render on top of it
-                            frame.render_widget(Clear, rectangles.dialogpopup[0]);
-                            frame.render_widget(self.popup_dialog.widget(), rectangles.dialogpopup[0]);
+                        match self.input_mode {
+                            InputMode::ReadLine => {
+                                // Clear the area so we can render on top of it
+                                frame.render_widget(Clear, self.rectangles.dialogpopup[0]);
+                                frame.render_widget(self.popup_dialog.widget(), self.rectangles.dialogpopup[0]);
+                            }
+                            InputMode::Fomod => {
+                                frame.render_widget(Clear, self.rectangles.main_vertical[2]);
+                                frame.render_stateful_widget(
+                                    &self.fomod_dialog.widget,
+                                    self.rectangles.main_horizontal[0],
+                                    &mut self.fomod_dialog.state,
+                                );
+                                render_scrollbar(
+                                    frame,
+                                    self.rectangles.main_horizontal[0],
+                                    self.fomod_dialog.len,
+                                    self.fomod_dialog.state.offset(),
+                                    0,
+                                );
+                                frame.render_widget(&self.fomod_dialog.description, self.rectangles.main_horizontal[1]);
+                            }
+                            InputMode::VersionPicker => {
+                                frame.render_widget(Clear, self.rectangles.main_horizontal[0]);
+                                frame.render_stateful_widget(
+                                    &self.version_picker.widget,
+                                    self.rectangles.main_horizontal[0],
+                                    &mut self.version_picker.state,
+                                );
+                                render_scrollbar(
+                                    frame,
+                                    self.rectangles.main_horizontal[0],
+                                    self.version_picker.len,
+                                    self.version_picker.state.offset(),
+                                    0,
+                                );
+                            }
+                            InputMode::ModDetails => {
+                                frame.render_widget(Clear, self.rectangles.main_horizontal[0]);
+                                frame.render_widget(&self.mod_details.widget, self.rectangles.main_horizontal[0]);
+                            }
+                            InputMode::Confirm => {
+                                frame.render_widget(Clear, self.rectangles.dialogpopup[0]);
+                                frame.render_widget(&self.confirm_dialog.widget, self.rectangles.dialogpopup[0]);
+                            }
+                            InputMode::Normal => {}
                         }
                     })
                     .unwrap();
+                if recalculate_rects {
+                    self.downloads_view.resize(self.rectangles.main_horizontal[1].width);
+                }
             }
 
-            if let Some(TickEvent::Input(event)) = events.next().await {
-                self.handle_events(event).await;
+            /* Changes from downloads and other background tasks are batched into one redraw per FRAME_INTERVAL, so that
+             * a running download doesn't redraw for every chunk it receives. Input is handled right away. */
+            let next_frame = Instant::now() + FRAME_INTERVAL;
+            let tick = if self.loading_indicator.is_visible() {
+                FRAME_INTERVAL
+            } else {
+                IDLE_INTERVAL
+            };
+            tokio::select! {
+                // Once stdin is closed, this branch is skipped instead of returning None over and over
+                Some(event) = events.next() => self.handle_events(event).await,
+                _ = async {
+                    changes::changed().await;
+                    time::sleep_until(next_frame).await;
+                } => {}
+                _ = time::sleep(tick) => {}
+            }
+            if got_shutdown_signal.load(Ordering::Relaxed) {
+                self.should_run = false;
+            }
+            if got_sighup.swap(false, Ordering::Relaxed) {
+                if let Some(config) =
+                    crate::reload_config(&self.config, &self.cache, &self.client, &self.downloads, &self.logger).await
+                {
+                    self.config = config;
+                }
             }
         }
     }
+
+    // Asks whether to replace an existing file that differs from the one being downloaded
+    async fn ask_about_file_conflict(&mut self) {
+        let file_id = if let Some(file_id) = self.downloads.next_file_conflict() {
+            file_id
+        } else {
+            return;
+        };
+        let file_name = match self.downloads.tasks.read().await.get(&file_id) {
+            Some(task) => task.dl_info.file_info.file_name.clone(),
+            None => return,
+        };
+        self.confirm_dialog.show(ConfirmAction::OverwriteFile { file_id }, "Replace the existing file?", file_name);
+        self.input_mode = InputMode::Confirm;
+    }
+
+    // Offers to download an archive again after it failed its integrity test
+    async fn ask_about_corrupt_archive(&mut self) {
+        let file_id = if let Some(file_id) = self.archives.next_corrupt_archive() {
+            file_id
+        } else {
+            return;
+        };
+        let file_name = match self.cache.file_index.file_id_map.read().await.get(&file_id) {
+            Some(fdata) => fdata.local_file.read().await.file_name.clone(),
+            None => return,
+        };
+        self.confirm_dialog.show(
+            ConfirmAction::RedownloadCorrupt { file_id },
+            "The archive is corrupt. Delete it and download it again?",
+            file_name,
+        );
+        self.input_mode = InputMode::Confirm;
+    }
+
+    // Asks for the password of an encrypted archive that the user tried to extract
+    fn ask_about_archive_password(&mut self) {
+        let path = if let Some(path) = self.archives.next_password_request() {
+            path
+        } else {
+            return;
+        };
+        let title = format!("Password for {}", path.file_name().unwrap_or_default().to_string_lossy());
+        self.popup_dialog.show(ReadLineAction::ArchivePassword { path }, "", title);
+        self.input_mode = InputMode::ReadLine;
+    }
 }
@@ -18,6 +18,7 @@ use termion::input::MouseTerminal;
 use termion::raw::IntoRawMode;
 use termion::screen::IntoAlternateScreen;
 use tokio_stream::StreamExt;
+use crate::util::changes;
 
 pub fn term_setup() -> Result<Terminal<impl Backend>, Box<dyn Error>> {
     let stdout = std::io::stdout().into_raw_mode()?;
@@ -31,9 +32,19 @@ pub fn term_setup() -> Result<Terminal<impl Backend>, Box<dyn Error>> {
     Ok(terminal)
 }
 
+// SIGHUP asks the program to reload its config
+pub async fn handle_sighup(got_sighup: Arc<AtomicBool>) {
+    let mut signals = Signals::new([SIGHUP]).unwrap();
+    while signals.next().await.is_some() {
+        got_sighup.store(true, Ordering::Relaxed);
+        changes::notify();
+    }
+}
+
 pub async fn handle_sigwinch(is_window_resized: Arc<AtomicBool>) {
     let mut signals = Signals::new([SIGWINCH]).unwrap();
     while signals.next().await.is_some() {
         is_window_resized.store(true, Ordering::Relaxed);
+        changes::notify();
     }
 }
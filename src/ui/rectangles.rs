@@ -6,6 +6,7 @@ pub struct Layouts {
     main_vertical: Layout,
     tables: Layout,
     statcounter: Layout,
+    loading_indicator: Layout,
     dialog_horizontal: Layout,
     dialog_vertical: Layout,
 }
@@ -14,6 +15,7 @@ pub struct Rectangles {
     pub main_horizontal: Rc<[Rect]>,
     pub main_vertical: Rc<[Rect]>,
     pub statcounter: Rc<[Rect]>,
+    pub loading_indicator: Rc<[Rect]>,
     pub dialogpopup: Rc<[Rect]>,
 }
 
@@ -22,6 +24,7 @@ impl Default for Rectangles {
         Self {
             main_vertical: [Rect { ..Default::default() }].into(),
             statcounter: [Rect { ..Default::default() }].into(),
+            loading_indicator: [Rect { ..Default::default() }].into(),
             main_horizontal: [Rect { ..Default::default() }].into(),
             dialogpopup: [Rect { ..Default::default() }].into(),
         }
@@ -44,6 +47,10 @@ impl Layouts {
         let statcounter =
             Layout::default().direction(Direction::Vertical).constraints([Constraint::Length(1)]).flex(Flex::End);
 
+        // The right end of the tab bar
+        let loading_indicator =
+            Layout::default().direction(Direction::Horizontal).constraints([Constraint::Length(2)]).flex(Flex::End);
+
         let dialog_horizontal =
             Layout::default().direction(Direction::Vertical).constraints([Constraint::Length(3)]).flex(Flex::Center);
 
@@ -54,6 +61,7 @@ impl Layouts {
             main_vertical,
             tables,
             statcounter,
+            loading_indicator,
             dialog_horizontal,
             dialog_vertical,
         }
@@ -65,6 +73,7 @@ impl Rectangles {
         self.main_vertical = layout.main_vertical.split(window_size);
         self.main_horizontal = layout.tables.split(self.main_vertical[2]);
         self.statcounter = layout.statcounter.split(window_size);
+        self.loading_indicator = layout.loading_indicator.split(self.main_vertical[0]);
         self.dialogpopup = layout.dialog_vertical.split(layout.dialog_horizontal.split(window_size)[0]);
     }
 }
@@ -0,0 +1,59 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/* Lets the UI wait for something to change instead of polling. Downloads, the logger and the cache each set their
+ * ChangeFlag when their data changes, which tells the component that shows the data to rebuild it. Setting any flag
+ * also wakes the UI loop, which otherwise sleeps until input arrives. */
+static CHANGED: Notify = Notify::const_new();
+
+#[derive(Clone, Default)]
+pub struct ChangeFlag {
+    is_set: Arc<AtomicBool>,
+}
+
+impl ChangeFlag {
+    pub fn new(is_set: bool) -> Self {
+        Self {
+            is_set: Arc::new(AtomicBool::new(is_set)),
+        }
+    }
+
+    pub fn set(&self) {
+        self.is_set.store(true, Ordering::Relaxed);
+        notify();
+    }
+
+    // Clears the flag, and returns whether it was set
+    pub fn take(&self) -> bool {
+        self.is_set.swap(false, Ordering::Relaxed)
+    }
+}
+
+// Wakes the UI without marking any data as changed, e.g. for signals and queued questions to the user
+pub fn notify() {
+    CHANGED.notify_one();
+}
+
+/* Returns once something has changed since the last time this returned. Changes made while nobody is waiting are
+ * remembered, so a change made during a redraw wakes the next wait right away. */
+pub async fn changed() {
+    CHANGED.notified().await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{changed, ChangeFlag};
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn change_flag() {
+        let flag = ChangeFlag::default();
+        assert!(!flag.take());
+        flag.clone().set();
+        assert!(timeout(Duration::from_secs(1), changed()).await.is_ok());
+        assert!(flag.take());
+        assert!(!flag.take());
+    }
+}
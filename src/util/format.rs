@@ -1,3 +1,5 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 pub fn vec_with_format_string(format_string: &str, params: Vec<&str>) -> String {
     let parts: Vec<&str> = format_string.split("{}").collect();
 
@@ -13,6 +15,9 @@ pub fn vec_with_format_string(format_string: &str, params: Vec<&str>) -> String
     ret
 }
 
+const UNITS: [&str; 9] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+
+// Formats the bytes in the given unit without the unit's name, such as the "12.5" in "12.5/204.1 MiB"
 pub fn bytes_as_unit(bytes: u64, unit: usize) -> String {
     let mut bytes: f64 = bytes as f64;
     let mut i = 0;
@@ -20,26 +25,95 @@ pub fn bytes_as_unit(bytes: u64, unit: usize) -> String {
         bytes /= 1024.0;
         i += 1;
     }
-    format!("{:.*}", 1, bytes)
+    with_decimals(bytes, unit)
 }
 
+// Returns the size in the largest fitting unit, and the index of that unit
 pub fn human_readable(bytes: u64) -> (String, usize) {
     let mut bytes: f64 = bytes as f64;
-    let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
     let mut i = 0;
-    while (bytes * 10.0).round() / 10.0 >= 1024.0 && i < units.len() - 1 {
+    while (bytes * 10.0).round() / 10.0 >= 1024.0 && i < UNITS.len() - 1 {
         bytes /= 1024.0;
         i += 1;
     }
-    if i == 0 {
-        return (format!("{} {}", bytes as u64, units[i]), 0);
+    (format!("{} {}", with_decimals(bytes, i), UNITS[i]), i)
+}
+
+// The size with its unit, such as "1.25 GiB"
+pub fn human_size(bytes: u64) -> String {
+    human_readable(bytes).0
+}
+
+// Bytes are whole numbers, and small values get two decimals so that they don't look stuck while downloading
+fn with_decimals(value: f64, unit: usize) -> String {
+    match unit {
+        0 => format!("{}", value as u64),
+        _ if value < 10.0 => format!("{value:.2}"),
+        _ => format!("{value:.1}"),
     }
-    (format!("{:.*} {}", 1, bytes, units[i]), i)
 }
 
+// Formats the time since something happened as the largest fitting unit, such as "5 min ago"
+pub fn time_ago(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    match secs {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", secs / 60),
+        3600..=86399 => format!("{} h ago", secs / 3600),
+        _ => format!("{} d ago", secs / 86400),
+    }
+}
+
+// Formats the time as "YYYY-MM-DD HH:MM:SS" in UTC, using the civil_from_days algorithm by Howard Hinnant
+pub fn utc_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = ((secs / 86400) as i64, secs % 86400);
+
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}", rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// Renders a bar such as "████░░░░" that is the given amount of characters wide
+pub fn progress_bar(fraction: f64, width: usize) -> String {
+    let filled = ((fraction.clamp(0.0, 1.0) * width as f64).round() as usize).min(width);
+    format!("{}{}", "█".repeat(filled), "░".repeat(width - filled))
+}
+
+// Summarizes the downloads, as shown in the bottom bar and by --list-downloads
+pub fn download_counts(active: usize, queued: usize, done: usize) -> String {
+    format!("↓ {active} active, {queued} queued, {done} done")
+}
+
+// Shown instead of a progress bar while downloading a file with unknown size
+pub const SPINNER: [&str; 4] = ["|", "/", "-", "\\"];
+
 #[cfg(test)]
 mod tests {
     use crate::util::format;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn time_ago() {
+        assert_eq!(format::time_ago(Duration::from_secs(30)), "just now");
+        assert_eq!(format::time_ago(Duration::from_secs(150)), "2 min ago");
+        assert_eq!(format::time_ago(Duration::from_secs(3 * 86400 + 5)), "3 d ago");
+    }
+
+    #[test]
+    fn utc_timestamp() {
+        assert_eq!(format::utc_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00");
+        assert_eq!(format::utc_timestamp(UNIX_EPOCH + Duration::from_secs(951782400)), "2000-02-29 00:00:00");
+        assert_eq!(format::utc_timestamp(UNIX_EPOCH + Duration::from_secs(1700000000)), "2023-11-14 22:13:20");
+    }
 
     #[test]
     fn endpoint_format() {
@@ -57,4 +131,22 @@ mod tests {
         assert_eq!("936.7 MiB", format::human_readable(982232812).0);
         assert_eq!("19.9 GiB", format::human_readable(21402232812).0);
     }
+
+    #[test]
+    fn human_size() {
+        assert_eq!("0 B", format::human_size(0));
+        assert_eq!("1023 B", format::human_size(1023));
+        assert_eq!("1.00 KiB", format::human_size(1024));
+        assert_eq!("1.25 GiB", format::human_size(1342177280));
+        assert_eq!("16.0 EiB", format::human_size(u64::MAX));
+        assert_eq!("0.50", format::bytes_as_unit(512 * 1024, 2));
+    }
+
+    #[test]
+    fn progress_bar() {
+        assert_eq!("░░░░", format::progress_bar(0.0, 4));
+        assert_eq!("██░░", format::progress_bar(0.5, 4));
+        assert_eq!("████", format::progress_bar(1.0, 4));
+        assert_eq!("", format::progress_bar(0.5, 0));
+    }
 }
@@ -1,8 +1,14 @@
+pub mod changes;
 pub mod format;
+pub mod opener;
+pub mod version;
 
 use md5::{Digest, Md5};
-use std::path::PathBuf;
-use tokio::task;
+use signal_hook::consts::signal::{SIGINT, SIGTERM};
+use signal_hook_tokio::Signals;
+use std::path::{Path, PathBuf};
+use tokio::{fs, io, task};
+use tokio_stream::StreamExt;
 use url::Url;
 
 pub fn file_name_from_url(url: &Url) -> String {
@@ -28,6 +34,73 @@ pub async fn md5sum(path: PathBuf) -> Result<String, std::io::Error> {
     .await?
 }
 
+/* Moves the file, also to another filesystem, where renaming fails. The file is then copied next to the destination and
+ * renamed into place, so that the destination never has a partially copied file. */
+pub async fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).await.is_ok() {
+        return Ok(());
+    }
+    let tmp = to.with_file_name(format!("{}.tmp", to.file_name().unwrap_or_default().to_string_lossy()));
+    if let Err(e) = fs::copy(from, &tmp).await {
+        let _ = fs::remove_file(&tmp).await;
+        return Err(e);
+    }
+    fs::rename(&tmp, to).await?;
+    fs::remove_file(from).await
+}
+
+// The CRC-32 checksum used by zip and 7z, computed incrementally: start with 0 and feed each chunk of the file
+pub fn crc32_update(crc: u32, bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 == 1 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    let mut crc = !crc;
+    for byte in bytes {
+        crc = TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+// The space available to the user on the filesystem that contains the path. Works on both Unix and Windows.
+pub async fn available_space(path: PathBuf) -> Result<u64, std::io::Error> {
+    task::spawn_blocking(move || fs4::available_space(path)).await?
+}
+
+/* Shows a desktop notification with notify-send. Notifications are optional, so failures (including notify-send not
+ * being installed) are ignored. */
+pub async fn notify_desktop(title: &str, body: &str) {
+    if !cfg!(target_os = "linux") {
+        return;
+    }
+    let (title, body) = (title.to_string(), body.to_string());
+    let _ = task::spawn_blocking(move || {
+        std::process::Command::new("notify-send")
+            .args(["--app-name", env!("CARGO_CRATE_NAME"), &title, &body])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+    })
+    .await;
+}
+
+// Returns once the program is asked to quit with SIGTERM or SIGINT, so that it can shut down cleanly
+pub async fn wait_for_shutdown_signal() {
+    let mut signals = Signals::new([SIGTERM, SIGINT]).unwrap();
+    signals.next().await;
+}
+
 pub fn trim_newline(mut string: String) -> String {
     // We're probably only going to run into Unix line endings, but let's deal with both cases to be sure
     if string.ends_with('\n') {
@@ -38,3 +111,33 @@ pub fn trim_newline(mut string: String) -> String {
     }
     string
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32_update, move_file};
+
+    #[test]
+    fn crc32() {
+        assert_eq!(crc32_update(0, b""), 0);
+        assert_eq!(crc32_update(0, b"123456789"), 0xCBF43926);
+        assert_eq!(crc32_update(crc32_update(0, b"1234"), b"56789"), 0xCBF43926);
+    }
+
+    #[tokio::test]
+    async fn move_between_dirs() {
+        let dir = std::env::temp_dir().join(format!("dmodman-move-{}", std::process::id()));
+        tokio::fs::create_dir_all(dir.join("temp")).await.unwrap();
+        let (from, to) = (dir.join("temp/Mod.7z.part"), dir.join("Mod.7z"));
+        tokio::fs::write(&from, b"abc").await.unwrap();
+
+        move_file(&from, &to).await.unwrap();
+        let moved = tokio::fs::read(&to).await.unwrap();
+        let from_exists = from.exists();
+        let missing = move_file(&from, &to).await;
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(moved, b"abc");
+        assert!(!from_exists);
+        assert!(missing.is_err());
+    }
+}
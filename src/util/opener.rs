@@ -0,0 +1,133 @@
+use std::env;
+use std::io::ErrorKind;
+use std::process::Command;
+
+/* Opens URLs and directories in other programs. A configured command is tried first, then the open_command from the
+ * config (xdg-open by default), and then the platform's own opener on macOS and Windows. Only an opener that isn't
+ * installed makes the next one be tried, so that a failing browser doesn't open another one. */
+
+// Opens the target with the command if one is given, with "{url}" replaced by the target
+pub fn open_url(target: &str, command: Option<&str>, open_command: &[String]) -> Result<(), String> {
+    let mut commands = vec![];
+    if let Some(command) = command {
+        commands.push(command_from_template(command, &[("{url}", target)], target).ok_or(EMPTY_COMMAND)?);
+    }
+    commands.extend(default_openers(target, open_command));
+    run_first_installed(target, commands, "browser_command")
+}
+
+/* Shows the file in the file manager from the command if one is given, with "{file}" and "{dir}" replaced by the
+ * paths of the file and its directory. Otherwise the directory is opened with the default opener. */
+pub fn open_in_file_manager(
+    file: &str,
+    dir: &str,
+    command: Option<&str>,
+    open_command: &[String],
+) -> Result<(), String> {
+    match command {
+        Some(command) => {
+            let command = command_from_template(command, &[("{file}", file), ("{dir}", dir)], dir);
+            run_first_installed(dir, vec![command.ok_or(EMPTY_COMMAND)?], "file_manager")
+        }
+        None => run_first_installed(dir, default_openers(dir, open_command), "file_manager"),
+    }
+}
+
+const EMPTY_COMMAND: &str = "The command in the config is empty.";
+
+/* Splits the command on whitespace and replaces the placeholders in each argument, so that paths with spaces stay whole.
+ * If the command has none of the placeholders, the target is appended. Returns None for an empty command. */
+fn command_from_template(template: &str, placeholders: &[(&str, &str)], target: &str) -> Option<(String, Vec<String>)> {
+    let has_placeholder = placeholders.iter().any(|(placeholder, _)| template.contains(placeholder));
+    let mut args: Vec<String> = template
+        .split_whitespace()
+        .map(|arg| {
+            placeholders.iter().fold(arg.to_string(), |arg, (placeholder, value)| arg.replace(placeholder, value))
+        })
+        .collect();
+    if args.is_empty() {
+        return None;
+    }
+    let program = args.remove(0);
+    if !has_placeholder {
+        args.push(target.to_string());
+    }
+    Some((program, args))
+}
+
+/* The open_command with the target as its last argument, e.g. "flatpak-spawn --host xdg-open <target>" in a Flatpak,
+ * followed by the platform's own opener. An empty open_command is skipped. */
+fn default_openers(target: &str, open_command: &[String]) -> Vec<(String, Vec<String>)> {
+    let mut commands = vec![];
+    if let Some((program, args)) = open_command.split_first() {
+        let mut args = args.to_vec();
+        args.push(target.to_string());
+        commands.push((program.clone(), args));
+    }
+    let platform_opener = match env::consts::OS {
+        "macos" => Some(("open", vec![target])),
+        // start is a cmd builtin. The empty argument is the window title, which would otherwise be the URL.
+        "windows" => Some(("cmd", vec!["/C", "start", "", target])),
+        _ => None,
+    };
+    if let Some((program, args)) = platform_opener {
+        commands.push((program.to_string(), args.into_iter().map(str::to_string).collect()));
+    }
+    commands
+}
+
+// Errors include the exact command line, so that missing dependencies are easy to debug
+fn run_first_installed(target: &str, commands: Vec<(String, Vec<String>)>, setting: &str) -> Result<(), String> {
+    let mut not_found = vec![];
+    for (program, args) in commands {
+        let cmdline = format!("{} {}", program, args.join(" "));
+        match Command::new(&program).args(&args).status() {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => return Err(format!("\"{cmdline}\" exited with {status}.")),
+            Err(e) if e.kind() == ErrorKind::NotFound => not_found.push(program),
+            Err(e) => return Err(format!("Unable to run \"{cmdline}\": {e}")),
+        }
+    }
+    Err(format!(
+        "Unable to open {target}, since {} isn't installed. Install it, or set open_command or {setting} in the config.",
+        not_found.join(" or ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{command_from_template, default_openers};
+
+    #[test]
+    fn template() {
+        let url = "https://www.nexusmods.com/morrowind/mods/46599";
+        assert_eq!(
+            command_from_template("firefox --new-tab {url}", &[("{url}", url)], url),
+            Some(("firefox".to_string(), vec!["--new-tab".to_string(), url.to_string()]))
+        );
+        assert_eq!(
+            command_from_template("firefox", &[("{url}", url)], url),
+            Some(("firefox".to_string(), vec![url.to_string()]))
+        );
+
+        let placeholders = [("{file}", "/dl/My Mod.7z"), ("{dir}", "/dl")];
+        assert_eq!(
+            command_from_template("dolphin --select {file}", &placeholders, "/dl"),
+            Some(("dolphin".to_string(), vec!["--select".to_string(), "/dl/My Mod.7z".to_string()]))
+        );
+        assert_eq!(command_from_template("  ", &placeholders, "/dl"), None);
+    }
+
+    #[test]
+    fn open_command() {
+        let open_command = [
+            "flatpak-spawn".to_string(),
+            "--host".to_string(),
+            "xdg-open".to_string(),
+        ];
+        let (program, args) = default_openers("/dl", &open_command).remove(0);
+        assert_eq!(program, "flatpak-spawn");
+        assert_eq!(args, vec!["--host", "xdg-open", "/dl"]);
+        assert!(default_openers("/dl", &[]).iter().all(|(program, _)| program != "xdg-open"));
+    }
+}
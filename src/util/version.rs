@@ -0,0 +1,59 @@
+use std::cmp::Ordering;
+
+/* Compares mod versions as the Nexus shows them, which are free-form but mostly numbers separated by dots, sometimes
+ * with a leading "v" or a suffix like "1.2a". Numeric parts are compared as numbers, so that 1.10 is newer than 1.9,
+ * and a version with more parts is newer than its prefix, e.g. 1.0.1 is newer than 1.0. */
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (parts(a), parts(b));
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            // Numbers sort before letters, so that 1.2 is older than 1.2a but newer than 1.beta
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Err(_), Err(_)) => x.to_lowercase().cmp(&y.to_lowercase()),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+// Splits "v1.2.3a" into ["1", "2", "3", "a"]
+fn parts(version: &str) -> Vec<String> {
+    let version = version.trim();
+    let version = version.strip_prefix(['v', 'V']).unwrap_or(version);
+    let mut parts: Vec<String> = vec![];
+    let mut last_is_digit = None;
+    for c in version.chars() {
+        if !c.is_alphanumeric() {
+            last_is_digit = None;
+            continue;
+        }
+        let is_digit = c.is_ascii_digit();
+        match parts.last_mut() {
+            Some(part) if last_is_digit == Some(is_digit) => part.push(c),
+            _ => parts.push(c.to_string()),
+        }
+        last_is_digit = Some(is_digit);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compare;
+    use std::cmp::Ordering;
+
+    #[test]
+    fn compare_versions() {
+        assert_eq!(compare("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(compare("v1.2", "1.2"), Ordering::Equal);
+        assert_eq!(compare("1.0", "1.0.1"), Ordering::Less);
+        assert_eq!(compare("1.2a", "1.2"), Ordering::Greater);
+        assert_eq!(compare("1.2a", "1.2b"), Ordering::Less);
+        assert_eq!(compare("2.0-beta", "1.9"), Ordering::Greater);
+        assert_eq!(compare("", "0.1"), Ordering::Less);
+    }
+}